@@ -114,14 +114,14 @@ install_compiler() {
     echo ""
     echo -e "${BLUE}Building self-hosted compiler...${NC}"
     cd "$SCRIPT_DIR"
-    ./athon-boot self-hosted/compiler.at > /tmp/athon-compiler.c
+    ./athon-boot --legacy-io self-hosted/compiler.at > /tmp/athon-compiler.c
     gcc /tmp/athon-compiler.c -o athon
     check_status "Self-hosted compiler built"
     
     # Build formatter
     echo ""
     echo -e "${BLUE}Building formatter...${NC}"
-    ./athon-boot cli/athon-fmt.at > /tmp/athon-fmt.c
+    ./athon-boot --legacy-io cli/athon-fmt.at > /tmp/athon-fmt.c
     gcc /tmp/athon-fmt.c -o athon-fmt
     check_status "Formatter built"
     
@@ -148,12 +148,12 @@ quick_update() {
     fi
     
     echo "Rebuilding self-hosted compiler..."
-    ./athon-boot self-hosted/compiler.at > /tmp/athon-compiler.c
+    ./athon-boot --legacy-io self-hosted/compiler.at > /tmp/athon-compiler.c
     gcc /tmp/athon-compiler.c -o athon
     check_status "Compiler updated"
     
     echo "Rebuilding formatter..."
-    ./athon-boot cli/athon-fmt.at > /tmp/athon-fmt.c
+    ./athon-boot --legacy-io cli/athon-fmt.at > /tmp/athon-fmt.c
     gcc /tmp/athon-fmt.c -o athon-fmt
     check_status "Formatter updated"
     
@@ -192,7 +192,7 @@ test_self_compilation() {
     fi
     
     echo "Stage 1: Compiling with bootstrap..."
-    ./athon-boot self-hosted/compiler.at > /tmp/athon-stage1.c
+    ./athon-boot --legacy-io self-hosted/compiler.at > /tmp/athon-stage1.c
     gcc /tmp/athon-stage1.c -o /tmp/athon-stage1
     check_status "Stage 1 complete"
     
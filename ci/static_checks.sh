@@ -100,7 +100,8 @@ echo "Found $example_count example files"
 if [ -f "./athon-boot" ]; then
     compile_errors=0
     for example in examples/*.at; do
-        if ! ./athon-boot "$example" > /dev/null 2>&1; then
+        # Examples predate capabilities, so they may do I/O from any function
+        if ! ./athon-boot --legacy-io "$example" > /dev/null 2>&1; then
             echo -e "${RED}✗ Failed to compile: $example${NC}"
             compile_errors=$((compile_errors + 1))
         fi
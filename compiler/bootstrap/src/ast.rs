@@ -1,13 +1,43 @@
 // AST (Abstract Syntax Tree) definitions for Athōn bootstrap compiler
 
+pub use crate::source_map::Span;
+use std::fmt;
+
+/// Stands for the implementing type in trait method signatures, and is the
+/// type of a bare `self` parameter
+pub const SELF_TYPE: &str = "Self";
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
 #[derive(Debug, Clone)]
-pub enum Expr {
-    Number(i32),
+pub enum ExprKind {
+    // Integer literal; `ty` is the type given by a suffix like `10u8`
+    Number {
+        value: u64,
+        ty: Option<IntType>,
+    },
+    // Floating-point literal; unsuffixed literals are f64
+    Float {
+        value: f64,
+        ty: Option<FloatType>,
+    },
     Boolean(bool),
     Char(char),
     String(String),
     Variable(String),
     ArrayLiteral(Vec<Expr>),
+    // `(a, b)`; its elements are read as fields `.0`, `.1`, ...
+    Tuple(Vec<Expr>),
     ArrayIndex {
         array: Box<Expr>,
         index: Box<Expr>,
@@ -24,6 +54,12 @@ pub enum Expr {
         enum_name: String,
         variant: String,
     },
+    // Value of a union type: `Some(5)`, `None` or `Result::Ok(1)`
+    UnionVariant {
+        union_name: String,
+        variant: String,
+        value: Option<Box<Expr>>,
+    },
     Binary {
         left: Box<Expr>,
         op: BinOp,
@@ -37,6 +73,376 @@ pub enum Expr {
         name: String,
         args: Vec<Expr>,
     },
+    // `receiver.method(args)`: an inherent method of the receiver's type,
+    // or else a method of a trait it implements
+    MethodCall {
+        receiver: Box<Expr>,
+        method: String,
+        args: Vec<Expr>,
+    },
+    // Interpolated string such as "x = {x}"; evaluates to a new string
+    Format(Vec<FormatPart>),
+    // `if c { 1 } else { 2 }`; each branch's value is its trailing
+    // expression, so an `if` used as a value always has an `else`
+    If {
+        condition: Box<Expr>,
+        then_block: Vec<Statement>,
+        else_block: Vec<Statement>,
+    },
+    // `match v { 0 => 1, _ => 2 }`; each arm's value is its trailing
+    // expression
+    Match {
+        value: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    // `{ let a = 1; a + 1 }`
+    Block(Vec<Statement>),
+}
+
+impl ExprKind {
+    /// Whether this is an `if`, `match` or block, whose value comes from
+    /// the statements inside it
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            ExprKind::If { .. } | ExprKind::Match { .. } | ExprKind::Block(_)
+        )
+    }
+}
+
+impl Expr {
+    /// Expressions directly inside this one, leaving out those in the
+    /// statements of an `if`, `match` or block
+    pub fn children(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::ArrayLiteral(elements) | ExprKind::Tuple(elements) => {
+                elements.iter().collect()
+            }
+            ExprKind::ArrayIndex { array, index } => vec![&**array, &**index],
+            ExprKind::StructLiteral { fields, .. } => fields.iter().map(|(_, e)| e).collect(),
+            ExprKind::MemberAccess { object, .. } => vec![&**object],
+            ExprKind::UnionVariant { value, .. } => value.iter().map(|v| &**v).collect(),
+            ExprKind::Binary { left, right, .. } => vec![&**left, &**right],
+            ExprKind::Unary { operand, .. } => vec![&**operand],
+            ExprKind::Call { args, .. } => args.iter().collect(),
+            ExprKind::MethodCall { receiver, args, .. } => {
+                std::iter::once(&**receiver).chain(args).collect()
+            }
+            ExprKind::Format(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    FormatPart::Value(value, _) => Some(value),
+                    _ => None,
+                })
+                .collect(),
+            ExprKind::If { condition, .. } => vec![&**condition],
+            ExprKind::Match { value, .. } => vec![&**value],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether an `if`, `match` or block appears in this expression
+    pub fn contains_block(&self) -> bool {
+        self.kind.is_block_like() || self.children().iter().any(|e| e.contains_block())
+    }
+
+    /// Whether this is an unsuffixed numeric literal, or a value built only
+    /// from them such as `-1`, `2 * 3` or `if c { 1 } else { 2 }`, which
+    /// takes the numeric type it is used as
+    pub fn unsuffixed_literal(&self) -> Option<Literal> {
+        match &self.kind {
+            ExprKind::Number { ty: None, .. } => Some(Literal::Int),
+            ExprKind::Float { ty: None, .. } => Some(Literal::Float),
+            ExprKind::Unary {
+                op: UnaryOp::Neg, ..
+            }
+            | ExprKind::Binary {
+                op: BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod,
+                ..
+            }
+            | ExprKind::If { .. }
+            | ExprKind::Match { .. }
+            | ExprKind::Block(_) => {
+                let kinds: Option<Vec<Literal>> = self
+                    .literal_parts()
+                    .into_iter()
+                    .map(Expr::unsuffixed_literal)
+                    .collect();
+                match kinds? {
+                    kinds if kinds.is_empty() => None,
+                    kinds if kinds.contains(&Literal::Float) => Some(Literal::Float),
+                    _ => Some(Literal::Int),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The values an operation works on, or that the branches of an `if`,
+    /// `match` or block end in
+    pub fn literal_parts(&self) -> Vec<&Expr> {
+        match &self.kind {
+            ExprKind::Unary { operand, .. } => vec![&**operand],
+            ExprKind::Binary { left, right, .. } => vec![&**left, &**right],
+            ExprKind::If {
+                then_block,
+                else_block,
+                ..
+            } => [then_block, else_block]
+                .into_iter()
+                .filter_map(|block| Statement::tail(block))
+                .collect(),
+            ExprKind::Match { arms, .. } => arms
+                .iter()
+                .filter_map(|arm| Statement::tail(&arm.body))
+                .collect(),
+            ExprKind::Block(stmts) => Statement::tail(stmts).into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Kind of an unsuffixed numeric literal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Literal {
+    Int,
+    Float,
+}
+
+#[derive(Debug, Clone)]
+pub enum FormatPart {
+    Literal(String),
+    Value(Expr, FormatSpec),
+    // `{}` placeholder; the parser fills these in from print's arguments
+    Positional(FormatSpec),
+}
+
+// The `:spec` of a placeholder such as `{x:>8}` or `{:.3}`, following
+// `[[fill]align][+][#][0][width][.precision][style]`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FormatSpec {
+    // Padding character; `None` pads with spaces
+    pub fill: Option<char>,
+    pub align: Option<Align>,
+    pub sign: bool,
+    pub alternate: bool,
+    pub zero_pad: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+    pub style: FormatStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FormatStyle {
+    #[default]
+    Display,
+    LowerHex,
+    UpperHex,
+    Octal,
+    Binary,
+    LowerExp,
+    UpperExp,
+}
+
+impl FormatSpec {
+    pub fn parse(text: &str) -> Result<FormatSpec, String> {
+        let mut spec = FormatSpec::default();
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+
+        let align = |c: char| match c {
+            '<' => Some(Align::Left),
+            '>' => Some(Align::Right),
+            '^' => Some(Align::Center),
+            _ => None,
+        };
+        if let Some(a) = chars.get(1).and_then(|&c| align(c)) {
+            spec.fill = Some(chars[0]);
+            spec.align = Some(a);
+            i = 2;
+        } else if let Some(a) = chars.first().and_then(|&c| align(c)) {
+            spec.align = Some(a);
+            i = 1;
+        }
+
+        if chars.get(i) == Some(&'+') {
+            spec.sign = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'#') {
+            spec.alternate = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            spec.zero_pad = true;
+            i += 1;
+        }
+
+        let number = |i: &mut usize| {
+            let digits: String = chars[*i..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            *i += digits.len();
+            digits.parse::<usize>().ok()
+        };
+        spec.width = number(&mut i);
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            spec.precision = number(&mut i);
+            if spec.precision.is_none() {
+                return Err("expected a precision after '.'".to_string());
+            }
+        }
+
+        spec.style = match chars.get(i) {
+            None => FormatStyle::Display,
+            Some('x') => FormatStyle::LowerHex,
+            Some('X') => FormatStyle::UpperHex,
+            Some('o') => FormatStyle::Octal,
+            Some('b') => FormatStyle::Binary,
+            Some('e') => FormatStyle::LowerExp,
+            Some('E') => FormatStyle::UpperExp,
+            Some(c) => return Err(format!("unknown format style '{}'", c)),
+        };
+        if chars.len() > i + 1 {
+            return Err(format!("unexpected '{}'", chars[i + 1]));
+        }
+        Ok(spec)
+    }
+}
+
+// Sized integer types (`int` is the same type as `i32`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+}
+
+impl IntType {
+    pub fn from_name(name: &str) -> Option<IntType> {
+        match name {
+            "i8" => Some(IntType::I8),
+            "i16" => Some(IntType::I16),
+            "i32" | "int" => Some(IntType::I32),
+            "i64" => Some(IntType::I64),
+            "u8" => Some(IntType::U8),
+            "u16" => Some(IntType::U16),
+            "u32" => Some(IntType::U32),
+            "u64" => Some(IntType::U64),
+            "usize" => Some(IntType::Usize),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IntType::I8 => "i8",
+            IntType::I16 => "i16",
+            IntType::I32 => "i32",
+            IntType::I64 => "i64",
+            IntType::U8 => "u8",
+            IntType::U16 => "u16",
+            IntType::U32 => "u32",
+            IntType::U64 => "u64",
+            IntType::Usize => "usize",
+        }
+    }
+
+    // Matching <stdint.h>/<stddef.h> type for the C backend
+    pub fn c_type(self) -> &'static str {
+        match self {
+            IntType::I8 => "int8_t",
+            IntType::I16 => "int16_t",
+            IntType::I32 => "int32_t",
+            IntType::I64 => "int64_t",
+            IntType::U8 => "uint8_t",
+            IntType::U16 => "uint16_t",
+            IntType::U32 => "uint32_t",
+            IntType::U64 => "uint64_t",
+            IntType::Usize => "size_t",
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(
+            self,
+            IntType::I8 | IntType::I16 | IntType::I32 | IntType::I64
+        )
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            IntType::I8 | IntType::U8 => 8,
+            IntType::I16 | IntType::U16 => 16,
+            IntType::I32 | IntType::U32 => 32,
+            IntType::I64 | IntType::U64 | IntType::Usize => 64,
+        }
+    }
+
+    pub fn max_value(self) -> u64 {
+        if self.is_signed() {
+            (1u64 << (self.bits() - 1)) - 1
+        } else {
+            u64::MAX >> (64 - self.bits())
+        }
+    }
+
+    // Type of an unsuffixed literal: `int` when it fits, otherwise the
+    // first of i64/u64 that can hold it (the same promotion C uses)
+    pub fn for_unsuffixed(value: u64) -> IntType {
+        if value <= IntType::I32.max_value() {
+            IntType::I32
+        } else if value <= IntType::I64.max_value() {
+            IntType::I64
+        } else {
+            IntType::U64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatType {
+    F32,
+    F64,
+}
+
+impl FloatType {
+    pub fn from_name(name: &str) -> Option<FloatType> {
+        match name {
+            "f32" => Some(FloatType::F32),
+            "f64" => Some(FloatType::F64),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FloatType::F32 => "f32",
+            FloatType::F64 => "f64",
+        }
+    }
+
+    pub fn c_type(self) -> &'static str {
+        match self {
+            FloatType::F32 => "float",
+            FloatType::F64 => "double",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -45,6 +451,12 @@ pub enum BinOp {
     Sub,
     Mul,
     Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     Eq,
     NotEq,
     Lt,
@@ -59,30 +471,155 @@ pub enum BinOp {
 pub enum UnaryOp {
     Not,
     Neg,
+    BitNot,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+impl Pattern {
+    /// Names the pattern binds, in the order they appear
+    pub fn bindings(&self) -> Vec<&str> {
+        match &self.kind {
+            PatternKind::Binding(name) => vec![name.as_str()],
+            PatternKind::UnionVariant {
+                binding: Some(binding),
+                ..
+            } if binding != "_" => vec![binding.as_str()],
+            PatternKind::Struct { fields, .. } => fields
+                .iter()
+                .flat_map(|(_, field)| field.bindings())
+                .collect(),
+            PatternKind::Or(alternatives) => {
+                alternatives.iter().flat_map(|alt| alt.bindings()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
-pub enum Pattern {
+pub enum PatternKind {
     Wildcard,
-    Number(i32),
+    Number(i64),
     Boolean(bool),
-    EnumVariant { enum_name: String, variant: String },
+    String(String),
+    // `0..=9`, including both ends
+    Range {
+        start: i64,
+        end: i64,
+    },
+    // A name matches anything and binds it
+    Binding(String),
+    EnumVariant {
+        enum_name: String,
+        variant: String,
+    },
+    // `Some(x)` binds the payload to `x`, `Some(_)` ignores it and `None`
+    // has none
+    UnionVariant {
+        name: String,
+        binding: Option<String>,
+    },
+    // `Point { x, y: 0 }`; a field written alone binds it to its own name
+    Struct {
+        name: String,
+        fields: Vec<(String, Pattern)>,
+    },
+    // `1 | 2 | 3`
+    Or(Vec<Pattern>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MatchArm {
     pub pattern: Pattern,
+    // `n if n > 10 =>` only takes the arm when the guard holds
+    pub guard: Option<Expr>,
     pub body: Vec<Statement>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
-pub enum Statement {
+// One element of a destructuring `let`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LetPattern {
+    // `name` or `mut name`
+    Name { name: String, mutable: bool },
+    // `_` skips its element
+    Wildcard,
+    // `(a, b)` destructures a nested tuple
+    Tuple(Vec<LetPattern>),
+}
+
+impl LetPattern {
+    /// Every name the elements bind, whether it is mutable and the path of
+    /// tuple field indices leading to its element
+    pub fn bindings(elements: &[LetPattern]) -> Vec<(&str, bool, Vec<usize>)> {
+        let mut out = Vec::new();
+        Self::collect(elements, &mut Vec::new(), &mut out);
+        out
+    }
+
+    fn collect<'p>(
+        elements: &'p [LetPattern],
+        path: &mut Vec<usize>,
+        out: &mut Vec<(&'p str, bool, Vec<usize>)>,
+    ) {
+        for (i, element) in elements.iter().enumerate() {
+            path.push(i);
+            match element {
+                LetPattern::Name { name, mutable } => out.push((name, *mutable, path.clone())),
+                LetPattern::Wildcard => {}
+                LetPattern::Tuple(inner) => Self::collect(inner, path, out),
+            }
+            path.pop();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// The trailing expression that gives a block its value, if it has one
+    pub fn tail(stmts: &[Statement]) -> Option<&Expr> {
+        match &stmts.last()?.kind {
+            StatementKind::Tail(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    // `let [mut] name[: type_name] = value;`
     Let {
         name: String,
+        mutable: bool,
+        type_name: Option<TypeExpr>,
+        value: Expr,
+    },
+    // `let [mut] (a, mut b, (c, _))[: type_name] = value;` binds each
+    // element of a tuple; `let mut` makes every name in it mutable
+    LetTuple {
+        elements: Vec<LetPattern>,
+        type_name: Option<TypeExpr>,
         value: Expr,
     },
+    // `target = value`, or `target op= value` when `op` is set. The
+    // target is a place: a variable, field or array element.
     Assign {
-        name: String,
+        target: Expr,
+        op: Option<BinOp>,
         value: Expr,
     },
     If {
@@ -110,85 +647,233 @@ pub enum Statement {
     Break,
     Continue,
     Expr(Expr),
+    // The expression ending a block without a `;`, which is the block's
+    // value when the block is used as one
+    Tail(Expr),
+}
+
+// A type as written in source, and as the checker, mono and codegen work
+// with it: `Pair<int, T>`, `*u8`, `[]fn(T)`, `[u8; 16]`,
+// `fn(int, int) -> bool`, `(int, string)`, `dyn Shape`. Its Display form
+// is how types appear in messages.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeExpr {
+    // A primitive, declared or parameter type, with any type arguments
+    Named {
+        name: String,
+        args: Vec<TypeExpr>,
+    },
+    // `*T`
+    Pointer(Box<TypeExpr>),
+    // `[]T`
+    Slice(Box<TypeExpr>),
+    // `[T; N]`
+    Array(Box<TypeExpr>, u64),
+    // `fn(A, B) -> R`; no return type means void
+    Function {
+        params: Vec<TypeExpr>,
+        return_type: Option<Box<TypeExpr>>,
+    },
+    // `(A, B)`
+    Tuple(Vec<TypeExpr>),
+    // `dyn Trait`
+    Dyn(String),
+}
+
+impl TypeExpr {
+    /// A named type without type arguments
+    pub fn named(name: &str) -> Self {
+        TypeExpr::Named {
+            name: name.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// The name of a named type without type arguments: `int`, `Point`
+    /// or a type parameter
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            TypeExpr::Named { name, args } if args.is_empty() => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The elements of a tuple type
+    pub fn tuple_elements(&self) -> Option<&[TypeExpr]> {
+        match self {
+            TypeExpr::Tuple(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// The trait of a trait object type: `Shape` for `dyn Shape`
+    pub fn dyn_trait(&self) -> Option<&str> {
+        match self {
+            TypeExpr::Dyn(trait_name) => Some(trait_name),
+            _ => None,
+        }
+    }
+
+    /// The element type of an array or slice
+    pub fn element(&self) -> Option<&TypeExpr> {
+        match self {
+            TypeExpr::Array(element, _) | TypeExpr::Slice(element) => Some(element),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TypeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |types: &[TypeExpr]| {
+            types
+                .iter()
+                .map(|ty| ty.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            TypeExpr::Named { name, args } if args.is_empty() => write!(f, "{}", name),
+            TypeExpr::Named { name, args } => write!(f, "{}<{}>", name, list(args)),
+            TypeExpr::Pointer(target) => write!(f, "*{}", target),
+            TypeExpr::Slice(element) => write!(f, "[]{}", element),
+            TypeExpr::Array(element, len) => write!(f, "[{}; {}]", element, len),
+            TypeExpr::Function {
+                params,
+                return_type,
+            } => {
+                write!(f, "fn({})", list(params))?;
+                match return_type {
+                    Some(ty) => write!(f, " -> {}", ty),
+                    None => Ok(()),
+                }
+            }
+            TypeExpr::Tuple(elements) => write!(f, "({})", list(elements)),
+            TypeExpr::Dyn(trait_name) => write!(f, "dyn {}", trait_name),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub name: String,
-    pub type_name: String,
+    pub type_name: TypeExpr,
+    pub span: Span,
+}
+
+// A trait bound on a type parameter, written inline (`<T: Display + Eq>`)
+// or in a `where` clause
+#[derive(Debug, Clone)]
+pub struct TypeBound {
+    pub param: String,
+    pub traits: Vec<String>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Function {
     pub name: String,
     pub type_params: Vec<String>, // Generic type parameters: <T, U>
+    pub bounds: Vec<TypeBound>,
     pub params: Vec<Parameter>,
-    pub return_type: Option<String>,
+    pub return_type: Option<TypeExpr>,
     pub body: Vec<Statement>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct StructField {
     pub name: String,
-    pub type_name: String,
+    pub type_name: TypeExpr,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StructDef {
     pub name: String,
     pub type_params: Vec<String>, // Generic type parameters: <T>
+    pub bounds: Vec<TypeBound>,
     pub fields: Vec<StructField>,
+    pub linearity: Linearity,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+// How many times a value of a type must be used. `linear struct` and
+// `affine struct` declare capabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Linearity {
+    #[default]
+    Unrestricted,
+    // At most once
+    Affine,
+    // Exactly once
+    Linear,
+}
+
+#[derive(Debug, Clone)]
 pub struct EnumDef {
     pub name: String,
     pub variants: Vec<String>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct TypeAlias {
     pub name: String,
-    pub target_type: String,
+    pub target_type: TypeExpr,
+    pub span: Span,
 }
 
 // Trait definition
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TraitDef {
     pub name: String,
     pub methods: Vec<TraitMethod>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TraitMethod {
     pub name: String,
     pub params: Vec<Parameter>,
-    pub return_type: Option<String>,
+    pub return_type: Option<TypeExpr>,
+    pub span: Span,
 }
 
 // Trait implementation
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TraitImpl {
     pub trait_name: String,
-    pub type_name: String,
+    pub type_name: TypeExpr,
     pub methods: Vec<Function>,
+    pub span: Span,
+}
+
+// Inherent implementation: methods of a type outside any trait
+#[derive(Debug, Clone)]
+pub struct InherentImpl {
+    pub type_name: TypeExpr,
+    pub methods: Vec<Function>,
+    pub span: Span,
 }
 
 // Union type variant
 #[derive(Debug, Clone)]
 pub struct UnionVariant {
     pub name: String,
-    pub associated_type: Option<String>, // Some(T) has associated type, None doesn't
+    pub associated_type: Option<TypeExpr>, // Some(T) has associated type, None doesn't
+    pub span: Span,
 }
 
 // Union type definition
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UnionType {
     pub name: String,
     pub variants: Vec<UnionVariant>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct Program {
     pub type_aliases: Vec<TypeAlias>,
     pub structs: Vec<StructDef>,
@@ -196,5 +881,6 @@ pub struct Program {
     pub unions: Vec<UnionType>,
     pub traits: Vec<TraitDef>,
     pub impls: Vec<TraitImpl>,
+    pub inherent_impls: Vec<InherentImpl>,
     pub functions: Vec<Function>,
 }
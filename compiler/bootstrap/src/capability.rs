@@ -0,0 +1,119 @@
+// Built-in capabilities for Athōn bootstrap compiler
+// A capability is a value whose possession grants authority over an effect.
+// I/O builtins require one held by the caller; `main` receives the root
+// `SystemCap`, and every other capability is derived from it by splitting
+// or attenuation. Capabilities form a tree: each one grants its own
+// authority and everything below it.
+
+use crate::ast::{Expr, ExprKind};
+
+/// The root capability handed to `main`
+pub const ROOT_CAPABILITY: &str = "SystemCap";
+
+/// Enum naming a capability in `cap_split` and `cap_attenuate`:
+/// `CapType::FileRead` is `FileReadCap`
+pub const CAP_KIND_ENUM: &str = "CapType";
+
+// Built-in capability types and the capability each is derived from.
+// They carry no data at runtime.
+const LATTICE: &[(&str, Option<&str>)] = &[
+    (ROOT_CAPABILITY, None),
+    ("FileCap", Some(ROOT_CAPABILITY)),
+    ("FileReadCap", Some("FileCap")),
+    ("FileWriteCap", Some("FileCap")),
+    ("StdoutCap", Some(ROOT_CAPABILITY)),
+    ("NetworkCap", Some(ROOT_CAPABILITY)),
+    ("NetworkSendCap", Some("NetworkCap")),
+    ("NetworkRecvCap", Some("NetworkCap")),
+];
+
+// Capability required by each effectful builtin
+const BUILTIN_EFFECTS: &[(&str, &str)] = &[
+    ("print", "StdoutCap"),
+    ("file_read", "FileReadCap"),
+    ("file_exists", "FileReadCap"),
+    ("file_write", "FileWriteCap"),
+    ("file_append", "FileWriteCap"),
+];
+
+/// Builtins that derive or destroy capabilities
+pub const CAPABILITY_BUILTINS: &[&str] = &["cap_split", "cap_attenuate", "cap_revoke"];
+
+pub fn is_capability(type_name: &str) -> bool {
+    LATTICE.iter().any(|(name, _)| *name == type_name)
+}
+
+fn parent(type_name: &str) -> Option<&'static str> {
+    LATTICE
+        .iter()
+        .find(|(name, _)| *name == type_name)
+        .and_then(|&(_, parent)| parent)
+}
+
+/// The capability a builtin needs, or `None` if it is pure
+pub fn required_capability(builtin: &str) -> Option<&'static str> {
+    BUILTIN_EFFECTS
+        .iter()
+        .find(|(name, _)| *name == builtin)
+        .map(|&(_, cap)| cap)
+}
+
+/// Whether holding `held` grants the authority of `required`, that is
+/// whether `required` is `held` or derived from it
+pub fn grants(held: &str, required: &str) -> bool {
+    if !is_capability(held) {
+        return false;
+    }
+    let mut current = Some(required);
+    while let Some(cap) = current {
+        if cap == held {
+            return true;
+        }
+        current = parent(cap);
+    }
+    false
+}
+
+/// Whether two capabilities share any authority
+pub fn overlaps(a: &str, b: &str) -> bool {
+    grants(a, b) || grants(b, a)
+}
+
+/// The capability named by a `CapType::Kind` expression
+pub fn named_kind(expr: &Expr) -> Option<&'static str> {
+    let ExprKind::EnumVariant { enum_name, variant } = &expr.kind else {
+        return None;
+    };
+    if enum_name != CAP_KIND_ENUM {
+        return None;
+    }
+    LATTICE
+        .iter()
+        .map(|&(name, _)| name)
+        .find(|name| name.strip_suffix("Cap") == Some(variant.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_effects() {
+        assert_eq!(required_capability("print"), Some("StdoutCap"));
+        assert_eq!(required_capability("file_write"), Some("FileWriteCap"));
+        assert_eq!(required_capability("length"), None);
+    }
+
+    #[test]
+    fn test_derivation_lattice() {
+        assert!(grants("SystemCap", "FileReadCap"));
+        assert!(grants("FileCap", "FileReadCap"));
+        assert!(grants("FileCap", "FileCap"));
+        assert!(!grants("FileReadCap", "FileCap"));
+        assert!(!grants("FileCap", "StdoutCap"));
+        assert!(!grants("int", "int"));
+
+        assert!(overlaps("FileReadCap", "FileCap"));
+        assert!(!overlaps("FileReadCap", "FileWriteCap"));
+    }
+}
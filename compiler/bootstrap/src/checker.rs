@@ -0,0 +1,3820 @@
+// Semantic checks for Athōn bootstrap compiler
+// Runs over the parsed program before codegen and reports problems as
+// diagnostics: undefined names and types, use before definition, call
+// arity, type mismatches, assignment to immutable bindings, unused
+// bindings, matches that miss values or have unreachable arms, misuse of
+// linear and affine capabilities along every control-flow path, and I/O
+// performed without the capability for it.
+// Types are the parser's `TypeExpr`, with generic instances such as
+// `Pair<int, string>` written out; an expression whose type cannot be
+// worked out is never reported.
+
+use crate::ast::*;
+use crate::capability::{self, ROOT_CAPABILITY};
+use crate::coverage::{Coverage, Covers};
+use crate::diagnostics::{
+    Diagnostic, E_ARITY_MISMATCH, E_ASSIGN_IMMUTABLE, E_BREAK_OUTSIDE_LOOP, E_CANNOT_FORMAT,
+    E_CANNOT_INFER, E_IMPL_MISMATCH, E_INVALID_LITERAL, E_INVALID_MAIN, E_INVALID_PATTERN,
+    E_LINEAR_LEAK, E_MISSING_CAPABILITY, E_MISSING_FIELD, E_MOVE_IN_LOOP, E_NON_EXHAUSTIVE,
+    E_TYPE_MISMATCH, E_UNDEFINED_NAME, E_UNDEFINED_TYPE, E_UNKNOWN_MEMBER, E_UNSATISFIED_BOUND,
+    E_USE_AFTER_MOVE, E_USE_BEFORE_DEFINITION, W_UNREACHABLE_ARM, W_UNUSED_BINDING,
+};
+use crate::generics::{self, Bindings};
+use crate::lexer;
+use crate::scope::{ResolveError, ScopeKind, ScopeStack};
+use std::collections::{HashMap, HashSet};
+
+// Built-in functions and their parameter types. "number" accepts any
+// integer or float type and "" accepts anything.
+const BUILTINS: &[(&str, &[&str])] = &[
+    ("length", &["string"]),
+    ("concat", &["string", "string"]),
+    ("compare", &["string", "string"]),
+    ("array_length", &[""]),
+    ("substring", &["string", "int", "int"]),
+    ("abs", &["number"]),
+    ("min", &["number", "number"]),
+    ("max", &["number", "number"]),
+    ("pow", &["number", "number"]),
+    ("sqrt", &["number"]),
+    ("mod", &["number", "number"]),
+    ("file_read", &["string"]),
+    ("file_write", &["string", "string"]),
+    ("file_append", &["string", "string"]),
+    ("file_exists", &["string"]),
+    ("drop", &[""]),
+];
+
+const PRIMITIVE_TYPES: &[&str] = &["int", "bool", "string", "char", "void"];
+
+// Traits that exist without a declaration. Primitive types and enums
+// implement them as the generated C supports: `Display` for anything
+// `print` can format, `Eq` for anything `==` compares by value (strings
+// through `strcmp`, pointers by address) and `Ord` for anything `<`
+// compares by value. Of these, only `Display` can be implemented for
+// other types.
+const BUILTIN_TRAITS: &[&str] = &["Display", "Eq", "Ord"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum BindingKind {
+    Let,
+    LetMut,
+    Parameter,
+    LoopVariable,
+    PatternBinding,
+}
+
+struct Binding {
+    kind: BindingKind,
+    // `None` when the type is unknown
+    ty: Option<TypeExpr>,
+    linearity: Linearity,
+    // Where a capability was moved out of this binding
+    moved: Option<Span>,
+    // The move was `cap_revoke`
+    revoked: bool,
+    // Capabilities split off from this one, whose authority it no longer
+    // has, and where they were split off
+    split_off: Vec<(&'static str, Span)>,
+    span: Span,
+}
+
+// Move and split state of every visible binding at the end of a branch
+struct Flow {
+    moves: Vec<Option<Span>>,
+    splits: Vec<Vec<(&'static str, Span)>>,
+    diverged: bool,
+}
+
+// A branch of an `if` or `match` used as a value
+enum Branch<'e> {
+    // The type of its trailing expression, and the expression
+    Value(Option<TypeExpr>, &'e Expr),
+    // Ends without a trailing expression
+    Missing,
+    // Returns, breaks or continues, so never produces a value
+    Diverged,
+}
+
+/// Options that relax checks for code written before a language change
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheckOptions {
+    /// Allow I/O builtins to be called without holding a capability
+    pub legacy_io: bool,
+}
+
+struct Checker<'a> {
+    options: CheckOptions,
+    functions: HashMap<&'a str, &'a Function>,
+    structs: HashMap<&'a str, &'a StructDef>,
+    enums: HashMap<&'a str, &'a EnumDef>,
+    unions: HashMap<&'a str, &'a UnionType>,
+    traits: HashMap<&'a str, &'a TraitDef>,
+    type_aliases: HashMap<&'a str, &'a TypeExpr>,
+    impls: &'a [TraitImpl],
+    // Trait impl methods by method name
+    impl_methods: HashMap<&'a str, Vec<(&'a TraitImpl, &'a Function)>>,
+    // Inherent methods by method name, with the type they belong to
+    inherent_methods: HashMap<&'a str, Vec<(&'a TypeExpr, &'a Function)>>,
+    // Generic parameters of the item being checked, and the traits they
+    // are bound to implement
+    type_params: &'a [String],
+    bounds: &'a [TypeBound],
+    // Name and declared return type of the function being checked
+    function_name: &'a str,
+    return_type: Option<TypeExpr>,
+    // The current path has returned, broken out or continued
+    diverged: bool,
+    scopes: ScopeStack<Binding>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+pub fn check_program(program: &Program, options: CheckOptions) -> Vec<Diagnostic> {
+    let mut checker = Checker::new(program, options);
+    checker.check_items(program);
+    checker.diagnostics
+}
+
+impl<'a> Checker<'a> {
+    fn new(program: &'a Program, options: CheckOptions) -> Self {
+        let mut impl_methods: HashMap<&str, Vec<_>> = HashMap::new();
+        for impl_block in &program.impls {
+            for method in &impl_block.methods {
+                impl_methods
+                    .entry(method.name.as_str())
+                    .or_default()
+                    .push((impl_block, method));
+            }
+        }
+        let mut inherent_methods: HashMap<&str, Vec<_>> = HashMap::new();
+        for impl_block in &program.inherent_impls {
+            for method in &impl_block.methods {
+                inherent_methods
+                    .entry(method.name.as_str())
+                    .or_default()
+                    .push((&impl_block.type_name, method));
+            }
+        }
+
+        Self {
+            options,
+            functions: program
+                .functions
+                .iter()
+                .map(|f| (f.name.as_str(), f))
+                .collect(),
+            structs: program
+                .structs
+                .iter()
+                .map(|s| (s.name.as_str(), s))
+                .collect(),
+            enums: program.enums.iter().map(|e| (e.name.as_str(), e)).collect(),
+            unions: program
+                .unions
+                .iter()
+                .map(|u| (u.name.as_str(), u))
+                .collect(),
+            traits: program
+                .traits
+                .iter()
+                .map(|t| (t.name.as_str(), t))
+                .collect(),
+            type_aliases: program
+                .type_aliases
+                .iter()
+                .map(|ta| (ta.name.as_str(), &ta.target_type))
+                .collect(),
+            impls: &program.impls,
+            impl_methods,
+            inherent_methods,
+            type_params: &[],
+            bounds: &[],
+            function_name: "",
+            return_type: None,
+            diverged: false,
+            scopes: ScopeStack::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, code: &'static str, message: String, span: Span) {
+        self.diagnostics
+            .push(Diagnostic::error(code, message, span));
+    }
+
+    // --- Items ---
+
+    fn check_items(&mut self, program: &'a Program) {
+        for alias in &program.type_aliases {
+            self.check_type(&alias.target_type, alias.span);
+        }
+        for struct_def in &program.structs {
+            self.type_params = &struct_def.type_params;
+            self.check_bound_names(&struct_def.bounds);
+            for field in &struct_def.fields {
+                self.check_type(&field.type_name, field.span);
+            }
+        }
+        self.type_params = &[];
+        for union_type in &program.unions {
+            for variant in &union_type.variants {
+                if let Some(ty) = &variant.associated_type {
+                    self.check_type(ty, variant.span);
+                }
+            }
+        }
+        let self_type = [SELF_TYPE.to_string()];
+        for trait_def in &program.traits {
+            for method in &trait_def.methods {
+                for param in &method.params {
+                    if !generics::mentions(&param.type_name, &self_type) {
+                        self.check_type(&param.type_name, param.span);
+                    }
+                }
+                if let Some(ty) = &method.return_type {
+                    if !generics::mentions(ty, &self_type) {
+                        self.check_type(ty, method.span);
+                    }
+                }
+            }
+        }
+        for impl_block in &program.impls {
+            // Display is built in, so it may be implemented without a declaration
+            match self.traits.get(impl_block.trait_name.as_str()).copied() {
+                Some(trait_def) => self.check_impl(impl_block, &trait_def.methods),
+                None if impl_block.trait_name == "Display" => {
+                    let to_string = TraitMethod {
+                        name: "to_string".to_string(),
+                        params: vec![Parameter {
+                            name: "self".to_string(),
+                            type_name: TypeExpr::named(SELF_TYPE),
+                            span: impl_block.span,
+                        }],
+                        return_type: Some(TypeExpr::named("string")),
+                        span: impl_block.span,
+                    };
+                    self.check_impl(impl_block, &[to_string]);
+                }
+                None => {
+                    let message = format!("Undefined trait '{}'", impl_block.trait_name);
+                    self.error(E_UNDEFINED_TYPE, message, impl_block.span);
+                }
+            }
+            self.check_type(&impl_block.type_name, impl_block.span);
+            for method in &impl_block.methods {
+                self.check_function(method);
+            }
+        }
+        let mut methods = HashSet::new();
+        for impl_block in &program.inherent_impls {
+            self.check_inherent_impl(impl_block, &mut methods);
+        }
+        for func in &program.functions {
+            if func.name == "main" {
+                self.check_main(func);
+            }
+            self.check_function(func);
+        }
+    }
+
+    // Inherent methods are called on a value, so each takes it as `self`.
+    // `methods` holds the type and name of those seen so far.
+    fn check_inherent_impl(
+        &mut self,
+        impl_block: &'a InherentImpl,
+        methods: &mut HashSet<(TypeExpr, &'a str)>,
+    ) {
+        if !self.check_type(&impl_block.type_name, impl_block.span) {
+            return;
+        }
+        let type_name = self.resolve_type(&impl_block.type_name, 0);
+        for method in &impl_block.methods {
+            if method.params.first().is_none_or(|p| p.name != "self") {
+                let message = format!(
+                    "Method '{}' of '{}' must take 'self' as its first parameter",
+                    method.name, impl_block.type_name
+                );
+                self.error(E_IMPL_MISMATCH, message, method.span);
+            }
+            if !methods.insert((type_name.clone(), &method.name)) {
+                let message = format!(
+                    "Duplicate method '{}' for type '{}'",
+                    method.name, impl_block.type_name
+                );
+                self.error(E_IMPL_MISMATCH, message, method.span);
+            }
+            self.check_function(method);
+        }
+    }
+
+    // `main` takes nothing, or the root capability that every other
+    // capability is derived from
+    fn check_main(&mut self, func: &Function) {
+        let valid = match func.params.as_slice() {
+            [] => true,
+            [param] => self.is_named(&param.type_name, ROOT_CAPABILITY),
+            _ => false,
+        };
+        if !valid {
+            let message = format!(
+                "'main' must take no parameters or a single '{}'",
+                ROOT_CAPABILITY
+            );
+            self.error(E_INVALID_MAIN, message, func.span);
+        }
+    }
+
+    // An impl has exactly the trait's methods, with its signatures
+    fn check_impl(&mut self, impl_block: &TraitImpl, methods: &[TraitMethod]) {
+        let trait_name = &impl_block.trait_name;
+        let bindings = Bindings::from([(SELF_TYPE.to_string(), impl_block.type_name.clone())]);
+        for method in methods {
+            let Some(func) = impl_block.methods.iter().find(|f| f.name == method.name) else {
+                let message = format!(
+                    "Missing method '{}' in impl of '{}' for '{}'",
+                    method.name, trait_name, impl_block.type_name
+                );
+                self.error(E_IMPL_MISMATCH, message, impl_block.span);
+                continue;
+            };
+            if func.params.len() != method.params.len() {
+                let message = format!(
+                    "Method '{}' takes {} parameter{} in trait '{}' but {} in the impl",
+                    method.name,
+                    method.params.len(),
+                    if method.params.len() == 1 { "" } else { "s" },
+                    trait_name,
+                    func.params.len()
+                );
+                self.error(E_IMPL_MISMATCH, message, func.span);
+                continue;
+            }
+            for (i, (expected, param)) in method.params.iter().zip(&func.params).enumerate() {
+                let expected = generics::substitute(&expected.type_name, &bindings);
+                if !self.same_type(&expected, &param.type_name) {
+                    let message = format!(
+                        "Mismatched types in parameter {} of '{}': trait '{}' expects '{}', found '{}'",
+                        i + 1,
+                        method.name,
+                        trait_name,
+                        expected,
+                        param.type_name
+                    );
+                    self.error(E_IMPL_MISMATCH, message, param.span);
+                }
+            }
+            let void = TypeExpr::named("void");
+            let expected = method
+                .return_type
+                .as_ref()
+                .map_or(void.clone(), |ty| generics::substitute(ty, &bindings));
+            let actual = func.return_type.clone().unwrap_or(void);
+            if !self.same_type(&expected, &actual) {
+                let message = format!(
+                    "Mismatched return type of '{}': trait '{}' expects '{}', found '{}'",
+                    method.name, trait_name, expected, actual
+                );
+                self.error(E_IMPL_MISMATCH, message, func.span);
+            }
+        }
+        for func in &impl_block.methods {
+            if !methods.iter().any(|m| m.name == func.name) {
+                let message = format!(
+                    "Method '{}' is not a member of trait '{}'",
+                    func.name, trait_name
+                );
+                self.error(E_IMPL_MISMATCH, message, func.span);
+            }
+        }
+    }
+
+    fn check_function(&mut self, func: &'a Function) {
+        self.type_params = &func.type_params;
+        self.bounds = &func.bounds;
+        self.check_bound_names(&func.bounds);
+        self.function_name = &func.name;
+        self.return_type = func.return_type.clone();
+        self.scopes = ScopeStack::new();
+        self.diverged = false;
+        for param in &func.params {
+            self.check_type(&param.type_name, param.span);
+            let ty = Some(param.type_name.clone());
+            let binding = self.binding(BindingKind::Parameter, ty, param.span);
+            self.scopes.define(&param.name, binding);
+        }
+        if let Some(ty) = &func.return_type {
+            self.check_type(ty, func.span);
+        }
+        self.check_block(&func.body, ScopeKind::Block);
+        // Linear parameters must be consumed by the end of the body
+        if !self.diverged {
+            self.check_leaks(false);
+        }
+        self.type_params = &[];
+        self.bounds = &[];
+    }
+
+    // --- Traits ---
+
+    fn check_bound_names(&mut self, bounds: &[TypeBound]) {
+        for bound in bounds {
+            if !self.type_params.contains(&bound.param) {
+                let message = format!("Undefined type parameter '{}' in bound", bound.param);
+                self.error(E_UNDEFINED_TYPE, message, bound.span);
+            }
+            for trait_name in &bound.traits {
+                let defined = self.traits.contains_key(trait_name.as_str())
+                    || BUILTIN_TRAITS.contains(&trait_name.as_str());
+                if !defined {
+                    let message = format!("Undefined trait '{}'", trait_name);
+                    self.error(E_UNDEFINED_TYPE, message, bound.span);
+                }
+            }
+        }
+    }
+
+    // The traits a generic parameter of the item being checked is bound to
+    // implement, or `None` if `ty` is not a generic parameter
+    fn bound_traits(&self, ty: &TypeExpr) -> Option<Vec<&'a str>> {
+        let name = ty.as_name()?;
+        if !self.type_params.iter().any(|p| p == name) {
+            return None;
+        }
+        let bounds = self.bounds.iter().filter(|bound| bound.param == name);
+        let traits = bounds.flat_map(|bound| bound.traits.iter().map(String::as_str));
+        Some(traits.collect())
+    }
+
+    // A generic parameter implements only the traits of its bounds
+    fn implements(&self, ty: &TypeExpr, trait_name: &str) -> bool {
+        if let Some(traits) = self.bound_traits(ty) {
+            return traits.contains(&trait_name);
+        }
+        let has_impl = self.impls.iter().any(|impl_block| {
+            impl_block.trait_name == trait_name && self.same_type(&impl_block.type_name, ty)
+        });
+        if has_impl || self.traits.contains_key(trait_name) {
+            return has_impl;
+        }
+
+        let is_pointer = matches!(self.resolve_alias(ty), TypeExpr::Pointer(_));
+        let ty = self.name_of(ty);
+        let is_number = IntType::from_name(ty).is_some() || FloatType::from_name(ty).is_some();
+        let is_enum = self.enums.contains_key(ty);
+        match trait_name {
+            "Display" => is_number || is_enum || matches!(ty, "int" | "bool" | "char" | "string"),
+            "Eq" => {
+                is_number
+                    || is_enum
+                    || is_pointer
+                    || matches!(ty, "int" | "bool" | "char" | "string")
+            }
+            "Ord" => is_number || matches!(ty, "int" | "char"),
+            _ => false,
+        }
+    }
+
+    // Whether values of `ty` can be printed: as numbers or text, or through
+    // a Display impl whose `to_string` returns a string
+    fn displayable(&self, ty: &TypeExpr) -> bool {
+        if self.bound_traits(ty).is_some() {
+            return self.implements(ty, "Display");
+        }
+        let name = self.name_of(ty);
+        let is_number = IntType::from_name(name).is_some() || FloatType::from_name(name).is_some();
+        is_number
+            || self.enums.contains_key(name)
+            || matches!(name, "bool" | "char" | "string")
+            || self.impls.iter().any(|impl_block| {
+                impl_block.trait_name == "Display"
+                    && self.same_type(&impl_block.type_name, ty)
+                    && impl_block.methods.iter().any(|m| {
+                        m.name == "to_string"
+                            && m.return_type
+                                .as_ref()
+                                .is_some_and(|r| self.is_named(r, "string"))
+                    })
+            })
+    }
+
+    // A placeholder's spec suits its value's type: precision and radix
+    // only where C's printf has them, and no numeric flags on text
+    fn check_format(&mut self, ty: &TypeExpr, spec: &FormatSpec, span: Span) {
+        let name = self.name_of(ty);
+        let radix = matches!(
+            spec.style,
+            FormatStyle::LowerHex
+                | FormatStyle::UpperHex
+                | FormatStyle::Octal
+                | FormatStyle::Binary
+        );
+        let unsupported = if IntType::from_name(name).is_some() {
+            if spec.precision.is_some() {
+                Some("a precision")
+            } else {
+                matches!(spec.style, FormatStyle::LowerExp | FormatStyle::UpperExp)
+                    .then_some("an exponent")
+            }
+        } else if FloatType::from_name(name).is_some() {
+            radix.then_some("a radix")
+        } else if !self.displayable(ty) {
+            let message = format!(
+                "Type '{}' does not implement Display and cannot be formatted",
+                ty
+            );
+            self.error(E_CANNOT_FORMAT, message, span);
+            return;
+        } else if spec.style != FormatStyle::Display {
+            Some("a radix or an exponent")
+        } else if spec.sign || spec.alternate || spec.zero_pad {
+            Some("'+', '#' or '0'")
+        } else {
+            None
+        };
+        if let Some(what) = unsupported {
+            let message = format!("Values of type '{}' cannot be formatted with {}", ty, what);
+            self.error(E_CANNOT_FORMAT, message, span);
+        }
+    }
+
+    // The type arguments of a generic call or literal satisfy its bounds
+    fn check_bounds(&mut self, item: &str, bounds: &[TypeBound], bindings: &Bindings, span: Span) {
+        for bound in bounds {
+            let Some(ty) = bindings.get(&bound.param) else {
+                continue;
+            };
+            for trait_name in &bound.traits {
+                if !self.implements(ty, trait_name) {
+                    let message = format!(
+                        "Type '{}' does not implement '{}', required by the bound '{}: {}' of '{}'",
+                        ty,
+                        trait_name,
+                        bound.param,
+                        bound.traits.join(" + "),
+                        item
+                    );
+                    self.error(E_UNSATISFIED_BOUND, message, span);
+                }
+            }
+        }
+    }
+
+    // --- Types ---
+
+    fn resolve_alias<'t>(&'t self, mut ty: &'t TypeExpr) -> &'t TypeExpr {
+        // Bounded so a cyclic alias cannot hang the checker
+        for _ in 0..self.type_aliases.len() {
+            match ty.as_name().and_then(|name| self.type_aliases.get(name)) {
+                Some(target) => ty = target,
+                None => break,
+            }
+        }
+        ty
+    }
+
+    fn resolve_alias_name<'t>(&'t self, mut name: &'t str) -> &'t str {
+        for _ in 0..self.type_aliases.len() {
+            match self
+                .type_aliases
+                .get(name)
+                .and_then(|target| target.as_name())
+            {
+                Some(target) => name = target,
+                None => break,
+            }
+        }
+        name
+    }
+
+    // The name of a type without type arguments once aliases are expanded,
+    // or "" for a pointer, slice, array, function, tuple or trait object
+    fn name_of<'t>(&'t self, ty: &'t TypeExpr) -> &'t str {
+        self.resolve_alias(ty).as_name().unwrap_or_default()
+    }
+
+    // The struct or other named type a type applies, e.g. `Pair` for
+    // `Pair<int, string>`
+    fn base_name<'t>(&'t self, ty: &'t TypeExpr) -> Option<&'t str> {
+        match self.resolve_alias(ty) {
+            TypeExpr::Named { name, .. } => Some(self.resolve_alias_name(name)),
+            _ => None,
+        }
+    }
+
+    // Whether `ty` is the named type `name` or an alias of it
+    fn is_named(&self, ty: &TypeExpr, name: &str) -> bool {
+        self.resolve_alias(ty).as_name() == Some(name)
+    }
+
+    // Expands aliases anywhere in a type, so `*Bytes` with `type Bytes =
+    // []u8` becomes `*[]u8`
+    fn resolve_type(&self, ty: &TypeExpr, depth: usize) -> TypeExpr {
+        let resolve = |ty: &TypeExpr| self.resolve_type(ty, depth);
+        match ty {
+            TypeExpr::Named { name, args } if args.is_empty() => {
+                match self.type_aliases.get(name.as_str()) {
+                    // Bounded so a cyclic alias cannot hang the checker
+                    Some(target) if depth < self.type_aliases.len() => {
+                        self.resolve_type(target, depth + 1)
+                    }
+                    _ => ty.clone(),
+                }
+            }
+            TypeExpr::Named { name, args } => TypeExpr::Named {
+                name: name.clone(),
+                args: args.iter().map(resolve).collect(),
+            },
+            TypeExpr::Pointer(inner) => TypeExpr::Pointer(Box::new(resolve(inner))),
+            TypeExpr::Slice(inner) => TypeExpr::Slice(Box::new(resolve(inner))),
+            TypeExpr::Array(inner, len) => TypeExpr::Array(Box::new(resolve(inner)), *len),
+            TypeExpr::Function {
+                params,
+                return_type,
+            } => TypeExpr::Function {
+                params: params.iter().map(resolve).collect(),
+                return_type: return_type.as_ref().map(|ty| Box::new(resolve(ty))),
+            },
+            TypeExpr::Tuple(elements) => TypeExpr::Tuple(elements.iter().map(resolve).collect()),
+            TypeExpr::Dyn(_) => ty.clone(),
+        }
+    }
+
+    fn type_exists(&self, type_name: &str) -> bool {
+        PRIMITIVE_TYPES.contains(&type_name)
+            || IntType::from_name(type_name).is_some()
+            || FloatType::from_name(type_name).is_some()
+            || self.structs.contains_key(type_name)
+            || self.enums.contains_key(type_name)
+            || self.unions.contains_key(type_name)
+            || self.type_aliases.contains_key(type_name)
+            || capability::is_capability(type_name)
+            || self.type_params.iter().any(|p| p == type_name)
+    }
+
+    // Returns whether the type is valid
+    fn check_type(&mut self, ty: &TypeExpr, span: Span) -> bool {
+        let (base, args) = match ty {
+            TypeExpr::Named { name, args } => (name, args.as_slice()),
+            TypeExpr::Pointer(inner) | TypeExpr::Slice(inner) | TypeExpr::Array(inner, _) => {
+                return self.check_type(inner, span);
+            }
+            TypeExpr::Function {
+                params,
+                return_type,
+            } => {
+                return params
+                    .iter()
+                    .chain(return_type.as_deref())
+                    .all(|p| self.check_type(p, span));
+            }
+            // Every element of a tuple must be a valid type
+            TypeExpr::Tuple(elements) => return elements.iter().all(|e| self.check_type(e, span)),
+            TypeExpr::Dyn(trait_name) => {
+                if !self.traits.contains_key(trait_name.as_str()) {
+                    self.error(E_UNDEFINED_TYPE, format!("Undefined type '{}'", ty), span);
+                    return false;
+                }
+                return true;
+            }
+        };
+        if args.is_empty() {
+            if !self.type_exists(base) {
+                self.error(E_UNDEFINED_TYPE, format!("Undefined type '{}'", base), span);
+                return false;
+            }
+            return true;
+        }
+        let Some(struct_def) = self.structs.get(base.as_str()).copied() else {
+            self.error(
+                E_UNDEFINED_TYPE,
+                format!("Undefined generic type '{}'", base),
+                span,
+            );
+            return false;
+        };
+        if struct_def.type_params.len() != args.len() {
+            let message = format!(
+                "Type '{}' takes {} type argument{} but {} were given",
+                base,
+                struct_def.type_params.len(),
+                if struct_def.type_params.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                args.len()
+            );
+            self.error(E_ARITY_MISMATCH, message, span);
+            return false;
+        }
+        args.iter().all(|arg| self.check_type(arg, span))
+    }
+
+    // A generic struct type such as `Pair<int, string>` and the bindings of
+    // its type parameters
+    fn struct_instance(&self, ty: &TypeExpr) -> Option<(&'a StructDef, Bindings)> {
+        let TypeExpr::Named { name, args } = self.resolve_alias(ty) else {
+            return None;
+        };
+        let struct_def = *self.structs.get(name.as_str())?;
+        let bindings = struct_def
+            .type_params
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+        Some((struct_def, bindings))
+    }
+
+    // Types are the same when they are once aliases are expanded, however
+    // deeply they are nested
+    fn same_type(&self, a: &TypeExpr, b: &TypeExpr) -> bool {
+        a == b || self.resolve_type(a, 0) == self.resolve_type(b, 0)
+    }
+
+    fn is_integer(&self, ty: &TypeExpr) -> bool {
+        IntType::from_name(self.name_of(ty)).is_some()
+    }
+
+    fn is_numeric(&self, ty: &TypeExpr) -> bool {
+        let name = self.name_of(ty);
+        IntType::from_name(name).is_some() || FloatType::from_name(name).is_some()
+    }
+
+    // A numeric value converts implicitly to a type that holds all of its
+    // values, bools and enum values can be used where an integer is expected, a
+    // capability can be passed where one it grants is expected, an array
+    // where a slice of its elements is, and a value coerces to a trait
+    // object of any trait its type implements. Arrays are the same type
+    // only with the same element type, as C can't convert them.
+    fn compatible(&self, expected: &TypeExpr, actual: &TypeExpr) -> bool {
+        let (expected, actual) = (self.resolve_alias(expected), self.resolve_alias(actual));
+        let coerces_to_dyn = match expected.dyn_trait() {
+            Some(trait_name) => actual.dyn_trait().is_none() && self.implements(actual, trait_name),
+            None => false,
+        };
+        let array = match (expected, actual) {
+            (TypeExpr::Slice(expected), TypeExpr::Array(actual, _)) => {
+                self.same_type(expected, actual)
+            }
+            (TypeExpr::Array(expected, len), TypeExpr::Array(actual, actual_len)) => {
+                len == actual_len && self.same_type(expected, actual)
+            }
+            _ => false,
+        };
+        let (expected_name, actual_name) = (
+            expected.as_name().unwrap_or_default(),
+            actual.as_name().unwrap_or_default(),
+        );
+        self.same_type(expected, actual)
+            || self.widens(actual, expected)
+            || capability::grants(actual_name, expected_name)
+            || (self.is_integer(expected)
+                && (actual_name == "bool" || self.enums.contains_key(actual_name)))
+            || array
+            || coerces_to_dyn
+    }
+
+    // Whether every value of numeric type `from` is one of `to`: an integer
+    // in a type at least as wide with room for its sign, any integer in a
+    // float, and an `f32` in an `f64`
+    fn widens(&self, from: &TypeExpr, to: &TypeExpr) -> bool {
+        let (from, to) = (self.name_of(from), self.name_of(to));
+        match (IntType::from_name(from), IntType::from_name(to)) {
+            (Some(from), Some(to)) if from.is_signed() == to.is_signed() => {
+                to.bits() >= from.bits()
+            }
+            (Some(from), Some(to)) => !from.is_signed() && to.bits() > from.bits(),
+            (Some(_), None) => FloatType::from_name(to).is_some(),
+            (None, _) => match (FloatType::from_name(from), FloatType::from_name(to)) {
+                (Some(from), Some(to)) => from == to || to == FloatType::F64,
+                _ => false,
+            },
+        }
+    }
+
+    // The wider of two numeric types: a float over an integer, `f64` over
+    // `f32` and the integer with more bits, `a` when they tie or aren't
+    // numeric
+    fn wider(&self, a: TypeExpr, b: TypeExpr) -> TypeExpr {
+        let rank = |ty: &TypeExpr| {
+            let name = self.name_of(ty);
+            match (IntType::from_name(name), FloatType::from_name(name)) {
+                (Some(int_type), _) => Some(int_type.bits()),
+                (_, Some(FloatType::F32)) => Some(100),
+                (_, Some(FloatType::F64)) => Some(200),
+                _ => None,
+            }
+        };
+        match (rank(&a), rank(&b)) {
+            (Some(a_rank), Some(b_rank)) if b_rank > a_rank => b,
+            _ => a,
+        }
+    }
+
+    // An unsuffixed literal used as a value of numeric type `expected`
+    // takes that type; reports an integer that doesn't fit it. Returns
+    // whether `value` is such a literal.
+    fn check_literal(&mut self, value: &Expr, expected: &TypeExpr) -> bool {
+        let fits = self.literal_fits(value, expected);
+        if let (true, Some(int_type)) = (fits, IntType::from_name(self.name_of(expected))) {
+            self.check_literal_range(value, int_type);
+        }
+        fits
+    }
+
+    // Whether `value` is an unsuffixed literal that can take type `expected`
+    fn literal_fits(&self, value: &Expr, expected: &TypeExpr) -> bool {
+        match value.unsuffixed_literal() {
+            Some(Literal::Int) => self.is_numeric(expected),
+            Some(Literal::Float) => FloatType::from_name(self.name_of(expected)).is_some(),
+            None => false,
+        }
+    }
+
+    // Whether a value of type `actual` can be used as one of `expected`
+    fn fits(&self, expected: &TypeExpr, value: &Expr, actual: &TypeExpr) -> bool {
+        self.literal_fits(value, expected) || self.compatible(expected, actual)
+    }
+
+    fn check_literal_range(&mut self, value: &Expr, int_type: IntType) {
+        let (magnitude, negative) = match &value.kind {
+            ExprKind::Number { value, .. } => (*value, false),
+            ExprKind::Unary { operand, .. } => match operand.kind {
+                ExprKind::Number { value, .. } => (value, true),
+                _ => return self.check_literal_range(operand, int_type),
+            },
+            _ => {
+                for literal in value.literal_parts() {
+                    self.check_literal_range(literal, int_type);
+                }
+                return;
+            }
+        };
+        let text = magnitude.to_string();
+        if let Err(message) = lexer::check_int_range(&text, magnitude, Some(int_type), negative) {
+            self.error(E_INVALID_LITERAL, message, value.span);
+        }
+    }
+
+    // `expect_type` for `value`, which may be a literal taking the expected type
+    fn expect_value(
+        &mut self,
+        expected: &TypeExpr,
+        value: &Expr,
+        actual: Option<&TypeExpr>,
+        context: &str,
+    ) {
+        if self.check_literal(value, expected) {
+            return;
+        }
+        // The elements of an array literal convert one by one
+        if let (ExprKind::ArrayLiteral(_), TypeExpr::Array(element, len), Some(actual)) =
+            (&value.kind, self.resolve_alias(expected), actual)
+        {
+            if let TypeExpr::Array(actual_element, actual_len) = self.resolve_alias(actual) {
+                if len == actual_len && self.compatible(element, actual_element) {
+                    return;
+                }
+            }
+        }
+        self.expect_type(expected, actual, context, value.span);
+    }
+
+    fn expect_type(
+        &mut self,
+        expected: &TypeExpr,
+        actual: Option<&TypeExpr>,
+        context: &str,
+        span: Span,
+    ) {
+        if let Some(actual) = actual {
+            if !self.compatible(expected, actual) {
+                let message = format!(
+                    "Mismatched types{}: expected '{}', found '{}'",
+                    context, expected, actual
+                );
+                self.error(E_TYPE_MISMATCH, message, span);
+            }
+        }
+    }
+
+    // --- Statements ---
+
+    fn check_block(&mut self, stmts: &[Statement], kind: ScopeKind) {
+        self.scopes.push(kind);
+        self.declare_lets(stmts);
+        for stmt in stmts {
+            self.check_statement(stmt);
+        }
+        self.end_scope();
+    }
+
+    // Leave the innermost scope, warning about bindings that were never read
+    fn end_scope(&mut self) {
+        if !self.diverged {
+            self.check_leaks(false);
+        }
+        for (name, binding) in self.scopes.pop() {
+            if binding.kind != BindingKind::Parameter && !name.starts_with('_') {
+                let message = format!(
+                    "Unused variable '{}'; prefix it with an underscore if this is intended",
+                    name
+                );
+                self.diagnostics
+                    .push(Diagnostic::warning(W_UNUSED_BINDING, message, binding.span));
+            }
+        }
+    }
+
+    fn check_statement(&mut self, stmt: &Statement) {
+        match &stmt.kind {
+            StatementKind::Let {
+                name,
+                mutable,
+                type_name,
+                value,
+            } => {
+                let declared = type_name
+                    .as_ref()
+                    .filter(|ty| self.check_type(ty, stmt.span));
+                let value_type = self.check_value(value, declared);
+                // The branches of an `if` or `match` were checked against it
+                if let (Some(declared), false) = (declared, value.kind.is_block_like()) {
+                    self.expect_value(declared, value, value_type.as_ref(), "");
+                }
+                let ty = match type_name {
+                    Some(type_name) => Some(type_name.clone()),
+                    None => value_type,
+                };
+                self.consume(value);
+                let kind = if *mutable {
+                    BindingKind::LetMut
+                } else {
+                    BindingKind::Let
+                };
+                let binding = self.binding(kind, ty, stmt.span);
+                self.scopes.define(name, binding);
+            }
+            StatementKind::LetTuple {
+                elements,
+                type_name,
+                value,
+            } => {
+                let value_type = self.check_expr(value);
+                let ty = match type_name {
+                    Some(type_name) => {
+                        if self.check_type(type_name, stmt.span) {
+                            self.expect_type(type_name, value_type.as_ref(), "", value.span);
+                        }
+                        Some(type_name.clone())
+                    }
+                    None => value_type,
+                };
+                self.consume(value);
+                self.bind_let_pattern(elements, ty, value.span, stmt.span);
+            }
+            StatementKind::Assign { target, op, value } => {
+                // Overwriting a variable doesn't read it; updating it does
+                let target_type = match (&target.kind, op) {
+                    (ExprKind::Variable(name), None) => {
+                        self.check_variable(name, false, target.span)
+                    }
+                    _ => self.check_expr(target),
+                };
+                let value_type = self.check_expr(value);
+                self.consume(value);
+                match op {
+                    Some(op) => {
+                        let operands = (target_type, value_type);
+                        let operands = self.literal_operands(*op, (target, value), operands);
+                        self.check_binary(*op, operands, stmt.span);
+                    }
+                    None => {
+                        if let Some(target_type) = target_type {
+                            self.expect_value(&target_type, value, value_type.as_ref(), "");
+                        }
+                    }
+                }
+                self.check_assign(target);
+            }
+            StatementKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                // A missing `else` is an empty branch
+                let else_block = else_block.as_deref().unwrap_or_default();
+                self.check_if(condition, then_block, else_block, false, None, stmt.span);
+            }
+            StatementKind::While { condition, body } => {
+                self.check_condition(condition);
+                let before = self.flow();
+                self.check_block(body, ScopeKind::Loop);
+                self.check_loop_moves(&before);
+            }
+            StatementKind::For {
+                loop_var,
+                start,
+                end,
+                body,
+            } => {
+                let start_type = self.check_expr(start);
+                let end_type = self.check_expr(end);
+                for (ty, bound) in [(&start_type, start), (&end_type, end)] {
+                    self.expect_integer(ty.as_ref(), "Range bound", bound.span);
+                }
+                let before = self.flow();
+                self.scopes.push(ScopeKind::Loop);
+                let ty = start_type.or_else(|| Some(TypeExpr::named("int")));
+                let binding = self.binding(BindingKind::LoopVariable, ty, stmt.span);
+                self.scopes.define(loop_var, binding);
+                self.check_block(body, ScopeKind::Block);
+                self.end_scope();
+                self.check_loop_moves(&before);
+            }
+            StatementKind::Match { value, arms } => {
+                self.check_match(value, arms, false, None, stmt.span);
+            }
+            StatementKind::Return { value } => {
+                self.check_return(value.as_ref(), stmt.span);
+                // Everything still alive is dropped on return
+                self.check_leaks(true);
+                self.diverged = true;
+            }
+            StatementKind::Break | StatementKind::Continue => {
+                self.diverged = true;
+                if !self.scopes.in_loop() {
+                    let keyword = if matches!(stmt.kind, StatementKind::Break) {
+                        "break"
+                    } else {
+                        "continue"
+                    };
+                    let message = format!("'{}' outside of a loop", keyword);
+                    self.error(E_BREAK_OUTSIDE_LOOP, message, stmt.span);
+                }
+            }
+            StatementKind::Expr(expr) | StatementKind::Tail(expr) => match &expr.kind {
+                // Used as statements, their branches need no value
+                ExprKind::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.check_if(condition, then_block, else_block, false, None, expr.span);
+                }
+                ExprKind::Match { value, arms } => {
+                    self.check_match(value, arms, false, None, expr.span);
+                }
+                ExprKind::Block(stmts) => self.check_block(stmts, ScopeKind::Block),
+                _ => {
+                    self.check_expr(expr);
+                }
+            },
+        }
+    }
+
+    // Binds the names of a tuple pattern to the elements of `ty`, the type
+    // of the value at `value_span`, descending into nested patterns
+    fn bind_let_pattern(
+        &mut self,
+        elements: &[LetPattern],
+        ty: Option<TypeExpr>,
+        value_span: Span,
+        span: Span,
+    ) {
+        let types = match &ty {
+            Some(ty) => match self.resolve_alias(ty).tuple_elements() {
+                Some(types) if types.len() == elements.len() => {
+                    types.iter().cloned().map(Some).collect()
+                }
+                found => {
+                    let message = match found {
+                        Some(types) => format!(
+                            "Tuple pattern has {} names but '{}' has {} elements",
+                            elements.len(),
+                            ty,
+                            types.len()
+                        ),
+                        None => format!("Expected a tuple to destructure, found '{}'", ty),
+                    };
+                    self.error(E_TYPE_MISMATCH, message, value_span);
+                    vec![None; elements.len()]
+                }
+            },
+            None => vec![None; elements.len()],
+        };
+        for (element, ty) in elements.iter().zip(types) {
+            match element {
+                LetPattern::Name { name, mutable } => {
+                    let kind = if *mutable {
+                        BindingKind::LetMut
+                    } else {
+                        BindingKind::Let
+                    };
+                    let binding = self.binding(kind, ty, span);
+                    self.scopes.define(name, binding);
+                }
+                LetPattern::Wildcard => {}
+                LetPattern::Tuple(inner) => self.bind_let_pattern(inner, ty, value_span, span),
+            }
+        }
+    }
+
+    // Names a block defines later, for use-before-definition errors
+    fn declare_lets(&mut self, stmts: &[Statement]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StatementKind::Let { name, .. } => self.scopes.declare(name),
+                StatementKind::LetTuple { elements, .. } => {
+                    for (name, _, _) in LetPattern::bindings(elements) {
+                        self.scopes.declare(name);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // A block used as a value, returning the type of its trailing expression
+    // and the expression, or None when it has none. The trailing expression
+    // is checked against `expected`, if given.
+    fn check_value_block<'e>(
+        &mut self,
+        stmts: &'e [Statement],
+        expected: Option<&TypeExpr>,
+    ) -> Option<(Option<TypeExpr>, &'e Expr)> {
+        self.scopes.push(ScopeKind::Block);
+        self.declare_lets(stmts);
+        let mut value = None;
+        for stmt in stmts {
+            match &stmt.kind {
+                StatementKind::Tail(expr) => {
+                    let ty = self.check_value(expr, expected);
+                    self.consume(expr);
+                    value = Some((ty, expr));
+                }
+                _ => self.check_statement(stmt),
+            }
+        }
+        self.end_scope();
+        value
+    }
+
+    // Checks a branch of an `if` or `match` used as a value
+    fn check_branch<'e>(
+        &mut self,
+        stmts: &'e [Statement],
+        expected: Option<&TypeExpr>,
+    ) -> Branch<'e> {
+        match self.check_value_block(stmts, expected) {
+            _ if self.diverged => Branch::Diverged,
+            Some((ty, expr)) => Branch::Value(ty, expr),
+            None => Branch::Missing,
+        }
+    }
+
+    // The type of an `if` or `match` used as a value: the type declared for
+    // it, or else the type every branch that doesn't return or break
+    // converts to, the widest if they are numeric. Literal branches take the
+    // type of the others.
+    fn unify_branches(
+        &mut self,
+        keyword: &str,
+        branches: Vec<Branch>,
+        expected: Option<&TypeExpr>,
+        span: Span,
+    ) -> Option<TypeExpr> {
+        let (part, parts) = if keyword == "if" {
+            ("branch", "branches")
+        } else {
+            ("arm", "arms")
+        };
+        let mut values = Vec::new();
+        for branch in branches {
+            match branch {
+                Branch::Diverged => {}
+                Branch::Missing => {
+                    let message = format!(
+                        "Every {} of '{}' used as a value must end in an expression without ';'",
+                        part, keyword
+                    );
+                    self.error(E_TYPE_MISMATCH, message, span);
+                }
+                Branch::Value(ty, expr) => values.push((ty, expr)),
+            }
+        }
+        let context = format!(" in '{}' {}", keyword, parts);
+        // Each branch was checked against the declared type already
+        if let Some(expected) = expected {
+            for (ty, expr) in &values {
+                if !expr.kind.is_block_like() {
+                    self.expect_value(expected, expr, ty.as_ref(), &context);
+                }
+            }
+            return Some(expected.clone());
+        }
+
+        let typed = values
+            .iter()
+            .filter(|(_, expr)| expr.unsuffixed_literal().is_none());
+        let typed: Vec<TypeExpr> = typed.filter_map(|(ty, _)| ty.clone()).collect();
+        let candidates = if typed.is_empty() {
+            values.iter().filter_map(|(ty, _)| ty.clone()).collect()
+        } else {
+            typed
+        };
+        let mut unified = candidates.into_iter().reduce(|a, b| self.wider(a, b))?;
+        // The others are compared with the first branch when it is the one
+        // that doesn't fit, so the mismatch reads in source order
+        if let Some((Some(first_type), first)) = values.first() {
+            if !self.fits(&unified, first, first_type) {
+                unified = first_type.clone();
+            }
+        }
+        for (ty, expr) in &values {
+            self.expect_value(&unified, expr, ty.as_ref(), &context);
+        }
+        Some(unified)
+    }
+
+    fn check_if(
+        &mut self,
+        condition: &Expr,
+        then_block: &[Statement],
+        else_block: &[Statement],
+        value: bool,
+        expected: Option<&TypeExpr>,
+        span: Span,
+    ) -> Option<TypeExpr> {
+        self.check_condition(condition);
+        let before = self.flow();
+        let mut branches = Vec::new();
+        let mut flows = Vec::new();
+        for block in [then_block, else_block] {
+            self.restore(&before);
+            if value {
+                let branch = self.check_branch(block, expected);
+                branches.push(branch);
+            } else {
+                self.check_block(block, ScopeKind::Block);
+            }
+            flows.push(self.flow());
+        }
+        self.merge(&flows);
+        self.unify_branches("if", branches, expected, span)
+    }
+
+    fn check_match(
+        &mut self,
+        value: &Expr,
+        arms: &[MatchArm],
+        as_value: bool,
+        expected: Option<&TypeExpr>,
+        span: Span,
+    ) -> Option<TypeExpr> {
+        let value_type = self.check_expr(value);
+        let mut coverage = value_type.as_ref().map(|ty| self.coverage(ty));
+        let before = self.flow();
+        let mut arm_flows = Vec::new();
+        let mut arm_branches = Vec::new();
+        for arm in arms {
+            self.restore(&before);
+            // The arm's scope holds what its pattern binds
+            self.scopes.push(ScopeKind::MatchArm);
+            self.check_pattern(&arm.pattern, value_type.as_ref());
+            if let Some(guard) = &arm.guard {
+                self.check_condition(guard);
+                if guard.contains_block() {
+                    let message =
+                        "A match guard can't contain an 'if', 'match' or block".to_string();
+                    self.error(E_INVALID_PATTERN, message, guard.span);
+                }
+            }
+            if let (Some(coverage), Some(ty)) = (&mut coverage, &value_type) {
+                let covers = self.pattern_covers(&arm.pattern, ty);
+                let mut reachable = true;
+                if let Some(alternatives) = covers {
+                    // A guarded arm may not be taken, so it covers nothing
+                    reachable = false;
+                    for covers in &alternatives {
+                        reachable |= match arm.guard {
+                            Some(_) => coverage.overlaps(covers),
+                            None => coverage.cover(covers),
+                        };
+                    }
+                }
+                if !reachable {
+                    let message =
+                        "Unreachable match arm; earlier arms match every value it does".to_string();
+                    self.diagnostics.push(Diagnostic::warning(
+                        W_UNREACHABLE_ARM,
+                        message,
+                        arm.pattern.span,
+                    ));
+                }
+            }
+            if as_value {
+                let branch = self.check_branch(&arm.body, expected);
+                arm_branches.push(branch);
+            } else {
+                self.check_block(&arm.body, ScopeKind::Block);
+            }
+            self.end_scope();
+            arm_flows.push(self.flow());
+        }
+        if !arm_flows.is_empty() {
+            self.merge(&arm_flows);
+        }
+        if let (Some(coverage), Some(ty)) = (coverage, value_type) {
+            let missing = coverage.missing();
+            if !missing.is_empty() {
+                let missing: Vec<String> = missing
+                    .iter()
+                    .map(|pattern| format!("'{}'", pattern))
+                    .collect();
+                let message = format!(
+                    "Non-exhaustive match on '{}'; missing {}",
+                    ty,
+                    missing.join(", ")
+                );
+                self.error(E_NON_EXHAUSTIVE, message, span);
+            }
+        }
+        self.unify_branches("match", arm_branches, expected, span)
+    }
+
+    fn check_condition(&mut self, condition: &Expr) {
+        if let Some(ty) = self.check_expr(condition) {
+            if !self.is_named(&ty, "bool") {
+                let message = format!("Condition must be 'bool', found '{}'", ty);
+                self.error(E_TYPE_MISMATCH, message, condition.span);
+            }
+        }
+    }
+
+    fn expect_integer(&mut self, ty: Option<&TypeExpr>, what: &str, span: Span) {
+        if let Some(ty) = ty {
+            if !self.is_integer(ty) {
+                let message = format!("{} must be an integer, found '{}'", what, ty);
+                self.error(E_TYPE_MISMATCH, message, span);
+            }
+        }
+    }
+
+    fn check_return(&mut self, value: Option<&Expr>, span: Span) {
+        let expected = self
+            .return_type
+            .clone()
+            .filter(|ty| ty.as_name() != Some("void"));
+        let value_type = value.and_then(|v| self.check_value(v, expected.as_ref()));
+        if let Some(value) = value {
+            self.consume(value);
+        }
+        match (value, expected) {
+            (Some(value), None) => {
+                let message = "Cannot return a value from a function without a return type";
+                self.error(E_TYPE_MISMATCH, message.to_string(), value.span);
+            }
+            (None, Some(ty)) => {
+                let message = format!("Expected a return value of type '{}'", ty);
+                self.error(E_TYPE_MISMATCH, message, span);
+            }
+            (Some(value), Some(ty)) if !value.kind.is_block_like() => {
+                self.expect_value(&ty, value, value_type.as_ref(), " in return");
+            }
+            _ => {}
+        }
+    }
+
+    // A value whose type is declared: the elements of an array literal and
+    // the type arguments of a struct literal follow the declaration
+    fn check_value(&mut self, value: &Expr, expected: Option<&TypeExpr>) -> Option<TypeExpr> {
+        let element = expected.and_then(|ty| self.resolve_alias(ty).element().cloned());
+        match &value.kind {
+            ExprKind::ArrayLiteral(elements) if element.is_some() => {
+                self.check_array_literal(elements, element)
+            }
+            ExprKind::StructLiteral {
+                struct_name,
+                fields,
+            } => self.check_struct_literal(struct_name, fields, expected, value.span),
+            _ if value.kind.is_block_like() => self.check_block_value(value, expected),
+            _ => self.check_expr(value),
+        }
+    }
+
+    fn check_pattern(&mut self, pattern: &Pattern, value_type: Option<&TypeExpr>) {
+        let span = pattern.span;
+        let (enum_name, variant) = match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Number(_) | PatternKind::Boolean(_) => return,
+            PatternKind::String(_) => {
+                let is_string = |c: &Self, ty: &TypeExpr| c.is_named(ty, "string");
+                self.check_pattern_type(value_type, "string", is_string, span);
+                return;
+            }
+            PatternKind::Range { start, end } => {
+                if start > end {
+                    let message = format!("Range pattern '{}..={}' matches nothing", start, end);
+                    self.error(E_INVALID_PATTERN, message, span);
+                }
+                let is_integer = |c: &Self, ty: &TypeExpr| {
+                    c.is_integer(ty) || c.enums.contains_key(c.name_of(ty))
+                };
+                self.check_pattern_type(value_type, "int", is_integer, span);
+                return;
+            }
+            PatternKind::Binding(name) => {
+                let ty = value_type.cloned();
+                let binding = self.binding(BindingKind::PatternBinding, ty, span);
+                self.scopes.define(name, binding);
+                return;
+            }
+            PatternKind::UnionVariant { name, binding } => {
+                self.check_union_pattern(name, binding.as_deref(), value_type, span);
+                return;
+            }
+            PatternKind::Struct { name, fields } => {
+                self.check_struct_pattern(name, fields, value_type, span);
+                return;
+            }
+            PatternKind::Or(alternatives) => {
+                for alternative in alternatives {
+                    self.check_pattern(alternative, value_type);
+                }
+                if let Some(name) = pattern.bindings().first() {
+                    let message = format!(
+                        "Cannot bind '{}' in an or-pattern; only one of its alternatives matches",
+                        name
+                    );
+                    self.error(E_INVALID_PATTERN, message, span);
+                }
+                return;
+            }
+            PatternKind::EnumVariant { enum_name, variant } => (enum_name, variant),
+        };
+        if !self.check_variant(enum_name, variant, span) {
+            return;
+        }
+        // Integers may be matched against enum variants
+        if let Some(value_type) = value_type {
+            if !self.compatible(value_type, &TypeExpr::named(enum_name)) {
+                let message = format!(
+                    "Mismatched types in pattern: expected '{}', found '{}'",
+                    value_type, enum_name
+                );
+                self.error(E_TYPE_MISMATCH, message, pattern.span);
+            }
+        }
+    }
+
+    // Every value of `ty` a match has to cover
+    fn coverage(&self, ty: &TypeExpr) -> Coverage {
+        let resolved = self.name_of(ty);
+        if resolved == "bool" {
+            let variants = ["true", "false"].map(|b| (b.to_string(), b.to_string()));
+            return Coverage::variants(variants.to_vec());
+        }
+        if let Some(int_type) = IntType::from_name(resolved) {
+            return Coverage::integers(int_type);
+        }
+        if let Some(enum_def) = self.enums.get(resolved) {
+            let variants = enum_def
+                .variants
+                .iter()
+                .map(|v| (v.clone(), format!("{}::{}", enum_def.name, v)))
+                .collect();
+            return Coverage::variants(variants);
+        }
+        if let Some(union_type) = self.unions.get(resolved) {
+            let variants = union_type
+                .variants
+                .iter()
+                .map(|v| match v.associated_type {
+                    Some(_) => (v.name.clone(), format!("{}(_)", v.name)),
+                    None => (v.name.clone(), v.name.clone()),
+                })
+                .collect();
+            return Coverage::variants(variants);
+        }
+        Coverage::open()
+    }
+
+    // Values of `ty` that `pattern` matches, one entry per alternative, or
+    // `None` when the pattern is one already reported as invalid for it.
+    // Enum variants are numbered from zero, as in the generated C, so
+    // integers and enum values match each other.
+    fn pattern_covers(&self, pattern: &Pattern, ty: &TypeExpr) -> Option<Vec<Covers>> {
+        let resolved = self.resolve_alias(ty);
+        let enum_def = self.enums.get(resolved.as_name().unwrap_or_default());
+        let covers = match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => Covers::All,
+            PatternKind::String(_) => Covers::Part,
+            PatternKind::Range { start, end } => match enum_def {
+                Some(enum_def) => {
+                    let indices = (*start).max(0)..=*end;
+                    let variants = enum_def.variants.iter().enumerate();
+                    let covers = variants
+                        .filter(|(i, _)| indices.contains(&(*i as i64)))
+                        .map(|(_, variant)| Covers::Variant(variant.clone()))
+                        .collect();
+                    return Some(covers);
+                }
+                None => Covers::Range(*start as i128, *end as i128),
+            },
+            PatternKind::Struct { name, .. } => {
+                let name = self.resolve_alias_name(name);
+                if !self.structs.contains_key(name) || self.base_name(resolved) != Some(name) {
+                    return None;
+                }
+                // Only a struct pattern that matches every field covers the type
+                if irrefutable(pattern) {
+                    Covers::All
+                } else {
+                    Covers::Part
+                }
+            }
+            PatternKind::Or(alternatives) => {
+                let mut covers = Vec::new();
+                for alternative in alternatives {
+                    covers.extend(self.pattern_covers(alternative, ty)?);
+                }
+                return Some(covers);
+            }
+            PatternKind::Number(n) => match enum_def {
+                Some(enum_def) => {
+                    let variant = usize::try_from(*n)
+                        .ok()
+                        .and_then(|i| enum_def.variants.get(i));
+                    Covers::Variant(variant?.clone())
+                }
+                None => Covers::Range(*n as i128, *n as i128),
+            },
+            PatternKind::Boolean(b) if resolved.as_name() == Some("bool") => {
+                Covers::Variant(b.to_string())
+            }
+            PatternKind::Boolean(b) => Covers::Range(*b as i128, *b as i128),
+            PatternKind::EnumVariant { enum_name, variant } => {
+                let pattern_enum = self.enums.get(self.resolve_alias_name(enum_name))?;
+                let index = pattern_enum.variants.iter().position(|v| v == variant)?;
+                match enum_def {
+                    Some(enum_def) if enum_def.name == pattern_enum.name => {
+                        Covers::Variant(variant.clone())
+                    }
+                    Some(_) => return None,
+                    None => Covers::Range(index as i128, index as i128),
+                }
+            }
+            PatternKind::UnionVariant { name, .. } => {
+                let union_type = self.unions.get(resolved.as_name()?)?;
+                union_type.variants.iter().find(|v| v.name == *name)?;
+                Covers::Variant(name.clone())
+            }
+        };
+        Some(vec![covers])
+    }
+
+    // A literal or range pattern of type `found` against a value for which
+    // `accepts` fails
+    fn check_pattern_type(
+        &mut self,
+        value_type: Option<&TypeExpr>,
+        found: &str,
+        accepts: impl Fn(&Self, &TypeExpr) -> bool,
+        span: Span,
+    ) {
+        if let Some(value_type) = value_type.filter(|ty| !accepts(self, ty)) {
+            let message = format!(
+                "Mismatched types in pattern: expected '{}', found '{}'",
+                value_type, found
+            );
+            self.error(E_TYPE_MISMATCH, message, span);
+        }
+    }
+
+    // `Point { x, y: 0 }` matches each named field of the struct against
+    // its own pattern
+    fn check_struct_pattern(
+        &mut self,
+        name: &str,
+        fields: &[(String, Pattern)],
+        value_type: Option<&TypeExpr>,
+        span: Span,
+    ) {
+        let mut struct_type = None;
+        if !self.structs.contains_key(self.resolve_alias_name(name)) {
+            self.error(
+                E_UNDEFINED_TYPE,
+                format!("Undefined struct '{}'", name),
+                span,
+            );
+        } else if let Some(value_type) = value_type {
+            // The matched value's type gives the fields of a generic struct
+            if self.base_name(value_type) == Some(self.resolve_alias_name(name)) {
+                struct_type = Some(value_type.clone());
+            } else {
+                let message = format!(
+                    "Mismatched types in pattern: expected '{}', found '{}'",
+                    value_type, name
+                );
+                self.error(E_TYPE_MISMATCH, message, span);
+            }
+        } else {
+            struct_type = Some(TypeExpr::named(name));
+        }
+        for (field, pattern) in fields {
+            let field_type = struct_type
+                .as_ref()
+                .and_then(|ty| self.check_member(ty, field, pattern.span));
+            self.check_pattern(pattern, field_type.as_ref());
+        }
+    }
+
+    // `Some(x)` matches a variant of the union being matched on, and binds
+    // `x` to its payload in the arm
+    fn check_union_pattern(
+        &mut self,
+        name: &str,
+        binding: Option<&str>,
+        value_type: Option<&TypeExpr>,
+        span: Span,
+    ) {
+        let mut payload = None;
+        if let Some(value_type) = value_type {
+            let union_name = self.name_of(value_type);
+            let union_type = self.unions.get(union_name).copied();
+            let variant = union_type.and_then(|u| u.variants.iter().find(|v| v.name == name));
+            match variant {
+                Some(variant) => {
+                    payload = variant.associated_type.clone();
+                    let message = match (&payload, binding) {
+                        (Some(_), None) => Some(format!(
+                            "Variant '{}' of '{}' holds a value; match it with '{}(_)'",
+                            name, value_type, name
+                        )),
+                        (None, Some(_)) => Some(format!(
+                            "Variant '{}' of '{}' holds no value to bind",
+                            name, value_type
+                        )),
+                        _ => None,
+                    };
+                    if let Some(message) = message {
+                        self.error(E_ARITY_MISMATCH, message, span);
+                    }
+                }
+                None => {
+                    let message = format!("Type '{}' has no variant '{}'", value_type, name);
+                    self.error(E_UNKNOWN_MEMBER, message, span);
+                }
+            }
+        }
+        if let Some(binding) = binding.filter(|b| *b != "_") {
+            let binding_value = self.binding(BindingKind::PatternBinding, payload, span);
+            self.scopes.define(binding, binding_value);
+        }
+    }
+
+    // `Some(5)` or `None`: a variant of a declared union, with a value
+    // exactly when the variant has a payload
+    fn check_union_variant(
+        &mut self,
+        union_name: &str,
+        variant: &str,
+        value: Option<&Expr>,
+        span: Span,
+    ) -> Option<TypeExpr> {
+        let value_type = value.and_then(|value| {
+            let ty = self.check_expr(value);
+            self.consume(value);
+            ty
+        });
+        let Some(union_type) = self
+            .unions
+            .get(self.resolve_alias_name(union_name))
+            .copied()
+        else {
+            self.error(
+                E_UNDEFINED_TYPE,
+                format!("Undefined union '{}'", union_name),
+                span,
+            );
+            return None;
+        };
+        let Some(declared) = union_type.variants.iter().find(|v| v.name == variant) else {
+            let message = format!("Union '{}' has no variant '{}'", union_name, variant);
+            self.error(E_UNKNOWN_MEMBER, message, span);
+            return Some(TypeExpr::named(union_name));
+        };
+        match (&declared.associated_type, value) {
+            (Some(payload), Some(value)) => {
+                let context = format!(" in variant '{}'", variant);
+                self.expect_value(payload, value, value_type.as_ref(), &context);
+            }
+            (Some(payload), None) => {
+                let message = format!(
+                    "Variant '{}' of '{}' takes a value of type '{}'",
+                    variant, union_name, payload
+                );
+                self.error(E_ARITY_MISMATCH, message, span);
+            }
+            (None, Some(_)) => {
+                let message = format!("Variant '{}' of '{}' takes no value", variant, union_name);
+                self.error(E_ARITY_MISMATCH, message, span);
+            }
+            (None, None) => {}
+        }
+        Some(TypeExpr::named(union_name))
+    }
+
+    // Reports an unknown enum or variant; returns whether both exist
+    fn check_variant(&mut self, enum_name: &str, variant: &str, span: Span) -> bool {
+        let resolved = self.resolve_alias_name(enum_name);
+        let variants: Vec<&str> = if let Some(enum_def) = self.enums.get(resolved) {
+            enum_def.variants.iter().map(String::as_str).collect()
+        } else if let Some(union_type) = self.unions.get(resolved) {
+            union_type
+                .variants
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect()
+        } else {
+            self.error(
+                E_UNDEFINED_TYPE,
+                format!("Undefined enum '{}'", enum_name),
+                span,
+            );
+            return false;
+        };
+        if !variants.contains(&variant) {
+            let message = format!("Enum '{}' has no variant '{}'", enum_name, variant);
+            self.error(E_UNKNOWN_MEMBER, message, span);
+            return false;
+        }
+        true
+    }
+
+    // Assigning `p.x` or `xs[i]` mutates the variable they belong to
+    fn check_assign(&mut self, target: &Expr) {
+        let mut root = target;
+        loop {
+            match &root.kind {
+                ExprKind::MemberAccess { object, .. } => root = object,
+                ExprKind::ArrayIndex { array, .. } => root = array,
+                _ => break,
+            }
+        }
+        let ExprKind::Variable(name) = &root.kind else {
+            return;
+        };
+
+        let message = match self.scopes.get(name).ok().map(|b| b.kind) {
+            Some(BindingKind::Let) => format!(
+                "Cannot assign to immutable variable '{}'; declare it with 'let mut {}'",
+                name, name
+            ),
+            Some(BindingKind::Parameter) => format!(
+                "Cannot assign to parameter '{}'; copy it into a 'let mut' binding first",
+                name
+            ),
+            Some(BindingKind::LoopVariable) => {
+                format!("Cannot assign to loop variable '{}'", name)
+            }
+            Some(BindingKind::PatternBinding) => {
+                format!("Cannot assign to pattern binding '{}'", name)
+            }
+            // Undefined names were reported when the target was checked
+            Some(BindingKind::LetMut) | None => return,
+        };
+        self.error(E_ASSIGN_IMMUTABLE, message, target.span);
+    }
+
+    // --- Capabilities ---
+
+    // Built-in capabilities are affine; `linear struct` and `affine struct`
+    // declare their own
+    fn binding(&self, kind: BindingKind, ty: Option<TypeExpr>, span: Span) -> Binding {
+        let linearity = ty
+            .as_ref()
+            .map_or(Linearity::Unrestricted, |ty| self.linearity(ty));
+        Binding {
+            kind,
+            ty,
+            linearity,
+            moved: None,
+            revoked: false,
+            split_off: Vec::new(),
+            span,
+        }
+    }
+
+    // A tuple must be used as strictly as its strictest element
+    fn linearity(&self, ty: &TypeExpr) -> Linearity {
+        let ty = self.resolve_alias(ty);
+        if let Some(elements) = ty.tuple_elements() {
+            let linearities: Vec<Linearity> = elements.iter().map(|e| self.linearity(e)).collect();
+            return [Linearity::Linear, Linearity::Affine]
+                .into_iter()
+                .find(|l| linearities.contains(l))
+                .unwrap_or(Linearity::Unrestricted);
+        }
+        let base = match ty {
+            TypeExpr::Named { name, .. } => name.as_str(),
+            _ => "",
+        };
+        match self.structs.get(base) {
+            Some(struct_def) => struct_def.linearity,
+            None if capability::is_capability(base) => Linearity::Affine,
+            None => Linearity::Unrestricted,
+        }
+    }
+
+    // Passing, returning, storing or rebinding a capability moves it. A
+    // capability that had part of its authority split off can't be passed
+    // on whole.
+    fn consume(&mut self, expr: &Expr) {
+        let ExprKind::Variable(name) = &expr.kind else {
+            return;
+        };
+        let Ok(binding) = self.scopes.get(name) else {
+            return;
+        };
+        if let (None, Some((child, _))) = (binding.moved, binding.split_off.first()) {
+            let message = format!(
+                "Use of partially split capability '{}'; '{}' was split off from it",
+                name, child
+            );
+            self.error(E_USE_AFTER_MOVE, message, expr.span);
+        }
+        self.discard(expr);
+    }
+
+    // Move a value without handing it to anyone, as `drop` and `cap_revoke` do
+    fn discard(&mut self, expr: &Expr) {
+        let ExprKind::Variable(name) = &expr.kind else {
+            return;
+        };
+        if let Ok(binding) = self.scopes.get_mut(name) {
+            if binding.linearity != Linearity::Unrestricted && binding.moved.is_none() {
+                binding.moved = Some(expr.span);
+            }
+        }
+    }
+
+    // Whether a live capability binding still has the authority of `required`
+    fn holds(&self, binding: &Binding, required: &str) -> bool {
+        let Some(ty) = &binding.ty else {
+            return false;
+        };
+        binding.moved.is_none()
+            && capability::grants(self.name_of(ty), required)
+            && !binding
+                .split_off
+                .iter()
+                .any(|(child, _)| capability::overlaps(child, required))
+    }
+
+    fn flow(&self) -> Flow {
+        Flow {
+            moves: self.scopes.values().map(|(_, b)| b.moved).collect(),
+            splits: self
+                .scopes
+                .values()
+                .map(|(_, b)| b.split_off.clone())
+                .collect(),
+            diverged: self.diverged,
+        }
+    }
+
+    fn restore(&mut self, flow: &Flow) {
+        let state = flow.moves.iter().zip(&flow.splits);
+        for ((_, binding), (moved, split_off)) in self.scopes.values_mut().zip(state) {
+            binding.moved = *moved;
+            binding.split_off = split_off.clone();
+        }
+        self.diverged = flow.diverged;
+    }
+
+    // Join the branches of an `if` or `match`. Paths that returned or broke
+    // out don't reach the join. A value moved on any path is moved after
+    // it; a linear value moved on only some paths leaks on the others.
+    // Authority split off on any path is gone after it.
+    fn merge(&mut self, branches: &[Flow]) {
+        let live: Vec<&Flow> = branches.iter().filter(|b| !b.diverged).collect();
+        if live.is_empty() {
+            self.diverged = true;
+            return;
+        }
+        self.diverged = false;
+        let mut leaks = Vec::new();
+        for (i, (name, binding)) in self.scopes.values_mut().enumerate() {
+            let moves: Vec<Span> = live.iter().filter_map(|b| b.moves[i]).collect();
+            if binding.linearity == Linearity::Linear
+                && !moves.is_empty()
+                && moves.len() < live.len()
+            {
+                leaks.push((name.to_string(), moves[0]));
+            }
+            binding.moved = moves.first().copied();
+            binding.split_off.clear();
+            for (kind, span) in live.iter().flat_map(|b| &b.splits[i]) {
+                if !binding.split_off.iter().any(|(k, _)| k == kind) {
+                    binding.split_off.push((kind, *span));
+                }
+            }
+        }
+        for (name, span) in leaks {
+            let message = format!(
+                "Linear value '{}' is consumed on some paths but not others",
+                name
+            );
+            self.error(E_LINEAR_LEAK, message, span);
+        }
+    }
+
+    // A loop body can run more than once, so it must not move capabilities
+    // from outside it or split authority off them. `before` is the state on
+    // entry to the loop.
+    fn check_loop_moves(&mut self, before: &Flow) {
+        let mut in_loop = Vec::new();
+        let state = before.moves.iter().zip(&before.splits);
+        for ((name, binding), (moved_before, split_before)) in self.scopes.values().zip(state) {
+            if let (None, Some(span)) = (moved_before, binding.moved) {
+                in_loop.push(("moved", name.to_string(), span));
+            }
+            for (_, span) in &binding.split_off[split_before.len()..] {
+                in_loop.push(("split", name.to_string(), *span));
+            }
+        }
+        for (action, name, span) in in_loop {
+            let message = format!("Value '{}' is {} inside a loop", name, action);
+            self.error(E_MOVE_IN_LOOP, message, span);
+        }
+        // The loop may not run at all, and `break` only leaves the loop
+        self.diverged = before.diverged;
+    }
+
+    // Linear values still held when their scope ends, or in every scope on
+    // `return`, were never consumed
+    fn check_leaks(&mut self, all_scopes: bool) {
+        let is_leak = |b: &Binding| b.linearity == Linearity::Linear && b.moved.is_none();
+        let leaks: Vec<(String, Span)> = if all_scopes {
+            let visible = self.scopes.values();
+            visible
+                .filter(|(_, b)| is_leak(b))
+                .map(|(n, b)| (n.to_string(), b.span))
+                .collect()
+        } else {
+            let current = self.scopes.current();
+            current
+                .filter(|(_, b)| is_leak(b))
+                .map(|(n, b)| (n.to_string(), b.span))
+                .collect()
+        };
+        for (name, span) in leaks {
+            let message = format!(
+                "Linear value '{}' is never consumed; pass it on, return it or 'drop' it",
+                name
+            );
+            self.error(E_LINEAR_LEAK, message, span);
+        }
+    }
+
+    // An I/O builtin needs a capability granting its effect, received as a
+    // parameter or derived from one
+    fn check_effect(&mut self, builtin: &str, span: Span) {
+        if self.options.legacy_io {
+            return;
+        }
+        let Some(required) = capability::required_capability(builtin) else {
+            return;
+        };
+        if !self
+            .scopes
+            .values()
+            .any(|(_, binding)| self.holds(binding, required))
+        {
+            let message = format!(
+                "'{}' requires a '{}' capability, but '{}' holds none granting it",
+                builtin, required, self.function_name
+            );
+            self.error(E_MISSING_CAPABILITY, message, span);
+        }
+    }
+
+    // `cap_split(parent, CapType::Kind)` takes part of the parent's authority
+    // into a new capability, `cap_attenuate(cap, CapType::Kind)` trades a
+    // capability for a weaker one, and `cap_revoke(cap)` destroys it
+    fn check_capability_call(&mut self, name: &str, args: &[Expr], span: Span) -> Option<TypeExpr> {
+        let takes_kind = name != "cap_revoke";
+        self.check_arity(name, if takes_kind { 2 } else { 1 }, args.len(), span);
+        let cap_arg = args.first()?;
+        let cap_type = self.check_expr(cap_arg)?;
+        if !capability::is_capability(self.name_of(&cap_type)) {
+            let message = format!(
+                "Mismatched types in argument 1 of '{}': expected a capability, found '{}'",
+                name, cap_type
+            );
+            self.error(E_TYPE_MISMATCH, message, cap_arg.span);
+            return None;
+        }
+        let variable = match &cap_arg.kind {
+            ExprKind::Variable(var) => Some(var.as_str()),
+            _ => None,
+        };
+        if !takes_kind {
+            self.discard(cap_arg);
+            if let Some(binding) = variable.and_then(|var| self.scopes.get_mut(var).ok()) {
+                binding.revoked = true;
+            }
+            return None;
+        }
+
+        let kind_arg = args.get(1)?;
+        let Some(kind) = capability::named_kind(kind_arg) else {
+            let message = format!(
+                "Expected a capability kind such as '{}::FileRead'",
+                capability::CAP_KIND_ENUM
+            );
+            self.error(E_TYPE_MISMATCH, message, kind_arg.span);
+            return None;
+        };
+        // A temporary has no split state to track
+        let Some(var) = variable else {
+            return Some(TypeExpr::named(kind));
+        };
+        let binding = self.scopes.get(var).ok()?;
+        if binding.moved.is_some() {
+            // Already reported as a use after move
+            return Some(TypeExpr::named(kind));
+        }
+        if !self.holds(binding, kind) {
+            let split = binding
+                .split_off
+                .iter()
+                .find(|(child, _)| capability::overlaps(child, kind));
+            let message = match split {
+                Some((child, _)) => format!(
+                    "Capability '{}' no longer grants '{}'; '{}' was already split off from it",
+                    var, kind, child
+                ),
+                None => format!("Capability '{}' does not grant '{}'", var, kind),
+            };
+            self.error(E_MISSING_CAPABILITY, message, kind_arg.span);
+            return Some(TypeExpr::named(kind));
+        }
+        let whole = self.name_of(&cap_type) == kind;
+        if name == "cap_attenuate" || whole {
+            self.consume(cap_arg);
+        } else if let Ok(binding) = self.scopes.get_mut(var) {
+            binding.split_off.push((kind, span));
+        }
+        Some(TypeExpr::named(kind))
+    }
+
+    // --- Expressions ---
+
+    // Checks an expression and returns its type, if known
+    fn check_expr(&mut self, expr: &Expr) -> Option<TypeExpr> {
+        match &expr.kind {
+            ExprKind::Number { value, ty } => Some(TypeExpr::named(match ty {
+                Some(int_type) => int_type.name(),
+                None => match IntType::for_unsuffixed(*value) {
+                    IntType::I32 => "int",
+                    int_type => int_type.name(),
+                },
+            })),
+            ExprKind::Float { ty, .. } => {
+                Some(TypeExpr::named(ty.unwrap_or(FloatType::F64).name()))
+            }
+            ExprKind::Boolean(_) => Some(TypeExpr::named("bool")),
+            ExprKind::Char(_) => Some(TypeExpr::named("char")),
+            ExprKind::String(_) => Some(TypeExpr::named("string")),
+            ExprKind::Variable(name) => self.check_variable(name, true, expr.span),
+            ExprKind::ArrayLiteral(elements) => self.check_array_literal(elements, None),
+            ExprKind::Tuple(elements) => {
+                let types: Vec<Option<TypeExpr>> =
+                    elements.iter().map(|e| self.check_expr(e)).collect();
+                for element in elements {
+                    self.consume(element);
+                }
+                let types: Option<Vec<TypeExpr>> = types.into_iter().collect();
+                Some(TypeExpr::Tuple(types?))
+            }
+            ExprKind::ArrayIndex { array, index } => {
+                let array_type = self.check_expr(array);
+                let index_type = self.check_expr(index);
+                self.expect_integer(index_type.as_ref(), "Array index", index.span);
+                array_type.and_then(|ty| self.resolve_alias(&ty).element().cloned())
+            }
+            ExprKind::StructLiteral {
+                struct_name,
+                fields,
+            } => self.check_struct_literal(struct_name, fields, None, expr.span),
+            ExprKind::MemberAccess { object, member } => {
+                let object_type = self.check_expr(object)?;
+                self.check_member(&object_type, member, expr.span)
+            }
+            ExprKind::EnumVariant { enum_name, variant } => {
+                self.check_variant(enum_name, variant, expr.span);
+                Some(TypeExpr::named(enum_name))
+            }
+            ExprKind::Binary { left, op, right } => {
+                let operands = (self.check_expr(left), self.check_expr(right));
+                let operands = self.literal_operands(*op, (left, right), operands);
+                self.check_binary(*op, operands, expr.span)
+            }
+            ExprKind::Unary { op, operand } => {
+                let ty = self.check_expr(operand);
+                self.check_unary(*op, ty, expr.span)
+            }
+            ExprKind::Call { name, args } => self.check_call(name, args, expr.span),
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => self.check_method_call(receiver, method, args, expr.span),
+            ExprKind::UnionVariant {
+                union_name,
+                variant,
+                value,
+            } => self.check_union_variant(union_name, variant, value.as_deref(), expr.span),
+            ExprKind::Format(parts) => {
+                for part in parts {
+                    if let FormatPart::Value(value, spec) = part {
+                        if let Some(ty) = self.check_expr(value) {
+                            self.check_format(&ty, spec, value.span);
+                        }
+                    }
+                }
+                Some(TypeExpr::named("string"))
+            }
+            ExprKind::If { .. } | ExprKind::Match { .. } | ExprKind::Block(_) => {
+                self.check_block_value(expr, None)
+            }
+        }
+    }
+
+    // An `if`, `match` or block used as a value. Its branches are checked
+    // against `expected`, if given, and must otherwise agree.
+    fn check_block_value(&mut self, expr: &Expr, expected: Option<&TypeExpr>) -> Option<TypeExpr> {
+        match &expr.kind {
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => self.check_if(condition, then_block, else_block, true, expected, expr.span),
+            ExprKind::Match { value, arms } => {
+                self.check_match(value, arms, true, expected, expr.span)
+            }
+            ExprKind::Block(stmts) => match self.check_branch(stmts, expected) {
+                Branch::Value(ty, tail) => match expected {
+                    Some(expected) => {
+                        if !tail.kind.is_block_like() {
+                            self.expect_value(expected, tail, ty.as_ref(), "");
+                        }
+                        Some(expected.clone())
+                    }
+                    None => ty,
+                },
+                Branch::Missing => {
+                    let message =
+                        "A block used as a value must end in an expression without ';'".to_string();
+                    self.error(E_TYPE_MISMATCH, message, expr.span);
+                    None
+                }
+                Branch::Diverged => None,
+            },
+            _ => unreachable!("only if, match and block expressions have branches"),
+        }
+    }
+
+    // Resolves a variable; `read` marks the binding used
+    fn check_variable(&mut self, name: &str, read: bool, span: Span) -> Option<TypeExpr> {
+        let resolved = if read {
+            self.scopes.lookup(name).map(|b| &*b)
+        } else {
+            self.scopes.get(name)
+        };
+        match resolved {
+            Ok(binding) if read && binding.moved.is_some() => {
+                let ty = binding.ty.clone();
+                let message = if binding.revoked {
+                    format!("Use of revoked capability '{}'", name)
+                } else {
+                    format!("Use of moved value '{}'", name)
+                };
+                self.error(E_USE_AFTER_MOVE, message, span);
+                ty
+            }
+            Ok(binding) => binding.ty.clone(),
+            Err(ResolveError::UsedBeforeDefinition(_)) => {
+                let message = format!("Variable '{}' used before its definition", name);
+                self.error(E_USE_BEFORE_DEFINITION, message, span);
+                None
+            }
+            Err(ResolveError::Undefined(_)) if read && self.functions.contains_key(name) => {
+                self.function_value(name, span)
+            }
+            Err(ResolveError::Undefined(_)) => {
+                // Union tags are C enumerators named `Union_Tag_Variant`
+                let is_union_tag = self.unions.values().any(|u| {
+                    u.variants
+                        .iter()
+                        .any(|v| name == format!("{}_Tag_{}", u.name, v.name))
+                });
+                if !is_union_tag {
+                    self.error(
+                        E_UNDEFINED_NAME,
+                        format!("Undefined variable '{}'", name),
+                        span,
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    // A function named as a value has its `fn` type. A generic one has
+    // no single type to take.
+    fn function_value(&mut self, name: &str, span: Span) -> Option<TypeExpr> {
+        let func = self.functions[name];
+        if !func.type_params.is_empty() {
+            let message = format!("Cannot use generic function '{}' as a value", name);
+            self.error(E_CANNOT_INFER, message, span);
+            return None;
+        }
+        Some(TypeExpr::Function {
+            params: func.params.iter().map(|p| p.type_name.clone()).collect(),
+            return_type: func.return_type.clone().map(Box::new),
+        })
+    }
+
+    // Every element must convert to `element`, or without one to the type
+    // of the first element
+    fn check_array_literal(
+        &mut self,
+        elements: &[Expr],
+        element: Option<TypeExpr>,
+    ) -> Option<TypeExpr> {
+        let mut element = element;
+        for expr in elements {
+            let ty = self.check_expr(expr);
+            match &element {
+                Some(element) => self.expect_value(element, expr, ty.as_ref(), " in array literal"),
+                None => element = ty,
+            }
+        }
+        Some(TypeExpr::Array(Box::new(element?), elements.len() as u64))
+    }
+
+    // Returns the type of the literal; for a generic struct, the type
+    // arguments are inferred from the expected type and the field values
+    fn check_struct_literal(
+        &mut self,
+        struct_name: &str,
+        fields: &[(String, Expr)],
+        expected: Option<&TypeExpr>,
+        span: Span,
+    ) -> Option<TypeExpr> {
+        let field_types: Vec<Option<TypeExpr>> = fields
+            .iter()
+            .map(|(_, value)| self.check_expr(value))
+            .collect();
+        for (_, value) in fields {
+            self.consume(value);
+        }
+
+        let resolved = self.resolve_alias_name(struct_name);
+        // Unions are built field by field from their `tag` and `data`
+        if self.unions.contains_key(resolved) {
+            return Some(TypeExpr::named(struct_name));
+        }
+        let Some(struct_def) = self.structs.get(resolved).copied() else {
+            self.error(
+                E_UNDEFINED_TYPE,
+                format!("Undefined struct '{}'", struct_name),
+                span,
+            );
+            return None;
+        };
+
+        let mut bindings = Bindings::new();
+        if let Some(expected) = expected {
+            let pattern = TypeExpr::Named {
+                name: struct_def.name.clone(),
+                args: struct_def
+                    .type_params
+                    .iter()
+                    .map(|p| TypeExpr::named(p))
+                    .collect(),
+            };
+            let params = &struct_def.type_params;
+            generics::unify(
+                &pattern,
+                self.resolve_alias(expected),
+                params,
+                &mut bindings,
+            );
+        }
+        for ((name, _), value_type) in fields.iter().zip(&field_types) {
+            let field = struct_def.fields.iter().find(|f| &f.name == name);
+            if let (Some(field), Some(value_type)) = (field, value_type) {
+                let params = &struct_def.type_params;
+                generics::unify(&field.type_name, value_type, params, &mut bindings);
+            }
+        }
+        let unbound = unbound_params(&struct_def.type_params, &bindings);
+        for ((name, value), value_type) in fields.iter().zip(&field_types) {
+            match struct_def.fields.iter().find(|f| &f.name == name) {
+                Some(field) => {
+                    let expected = generics::substitute(&field.type_name, &bindings);
+                    if !generics::mentions(&expected, &unbound) {
+                        let context = format!(" in field '{}'", name);
+                        self.expect_value(&expected, value, value_type.as_ref(), &context);
+                    }
+                }
+                None => {
+                    let message = format!("Struct '{}' has no field '{}'", struct_name, name);
+                    self.error(E_UNKNOWN_MEMBER, message, value.span);
+                }
+            }
+        }
+        for field in &struct_def.fields {
+            if !fields.iter().any(|(name, _)| name == &field.name) {
+                let message = format!(
+                    "Missing field '{}' in '{}' literal",
+                    field.name, struct_name
+                );
+                self.error(E_MISSING_FIELD, message, span);
+            }
+        }
+
+        let values: Vec<&Expr> = fields.iter().map(|(_, value)| value).collect();
+        let params = &struct_def.type_params;
+        self.check_inferred(struct_name, params, &bindings, &values, &field_types, span);
+        self.check_bounds(struct_name, &struct_def.bounds, &bindings, span);
+
+        let args: Option<Vec<TypeExpr>> = struct_def
+            .type_params
+            .iter()
+            .map(|p| bindings.get(p).cloned())
+            .collect();
+        Some(TypeExpr::Named {
+            name: struct_name.to_string(),
+            args: args?,
+        })
+    }
+
+    fn check_member(
+        &mut self,
+        object_type: &TypeExpr,
+        member: &str,
+        span: Span,
+    ) -> Option<TypeExpr> {
+        if let Some(elements) = self.resolve_alias(object_type).tuple_elements() {
+            let element = member
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| elements.get(i))
+                .cloned();
+            if element.is_none() {
+                let message = format!("Tuple '{}' has no field '{}'", object_type, member);
+                self.error(E_UNKNOWN_MEMBER, message, span);
+            }
+            return element;
+        }
+        let field = self
+            .struct_instance(object_type)
+            .and_then(|(struct_def, bindings)| {
+                let field = struct_def.fields.iter().find(|f| f.name == member)?;
+                Some((field, struct_def, bindings))
+            });
+        match field {
+            Some((field, struct_def, bindings)) => {
+                let ty = generics::substitute(&field.type_name, &bindings);
+                let unbound = unbound_params(&struct_def.type_params, &bindings);
+                Some(ty).filter(|ty| !generics::mentions(ty, &unbound))
+            }
+            None => {
+                let message = format!("Type '{}' has no field '{}'", object_type, member);
+                self.error(E_UNKNOWN_MEMBER, message, span);
+                None
+            }
+        }
+    }
+
+    fn check_binary(
+        &mut self,
+        op: BinOp,
+        operands: (Option<TypeExpr>, Option<TypeExpr>),
+        span: Span,
+    ) -> Option<TypeExpr> {
+        let (left, right) = operands;
+        let allowed = |ty: &TypeExpr| match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => self.is_numeric(ty),
+            // C has no `%` on floats
+            BinOp::Mod => self.is_integer(ty),
+            BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor => {
+                self.is_integer(ty) || self.is_named(ty, "bool")
+            }
+            BinOp::Shl | BinOp::Shr => self.is_integer(ty),
+            BinOp::And | BinOp::Or => self.is_named(ty, "bool"),
+            BinOp::Lt | BinOp::LtEq | BinOp::Gt | BinOp::GtEq => self.implements(ty, "Ord"),
+            BinOp::Eq | BinOp::NotEq => self.implements(ty, "Eq"),
+        };
+        // `s + t` and `s += t` append strings
+        let is_append = matches!(op, BinOp::Add)
+            && [&left, &right]
+                .iter()
+                .all(|ty| ty.as_ref().is_some_and(|t| self.is_named(t, "string")));
+        let mismatched = match (&left, &right) {
+            _ if is_append => false,
+            (Some(l), Some(r)) => {
+                !allowed(l) || !allowed(r) || !(self.compatible(l, r) || self.compatible(r, l))
+            }
+            (Some(ty), None) | (None, Some(ty)) => !allowed(ty),
+            (None, None) => false,
+        };
+        if mismatched {
+            let describe =
+                |ty: &Option<TypeExpr>| ty.as_ref().map_or("_".to_string(), TypeExpr::to_string);
+            let message = format!(
+                "Cannot apply '{}' to '{}' and '{}'",
+                binop_symbol(op),
+                describe(&left),
+                describe(&right)
+            );
+            self.error(E_TYPE_MISMATCH, message, span);
+        }
+
+        match op {
+            BinOp::Add
+            | BinOp::Sub
+            | BinOp::Mul
+            | BinOp::Div
+            | BinOp::Mod
+            | BinOp::BitAnd
+            | BinOp::BitOr
+            | BinOp::BitXor => self.arithmetic_type(left, right),
+            // A shift has the type of the value being shifted
+            BinOp::Shl | BinOp::Shr => left,
+            _ => Some(TypeExpr::named("bool")),
+        }
+    }
+
+    // Result type of arithmetic: the wider operand type
+    fn arithmetic_type(&self, left: Option<TypeExpr>, right: Option<TypeExpr>) -> Option<TypeExpr> {
+        [left, right]
+            .into_iter()
+            .flatten()
+            .reduce(|a, b| self.wider(a, b))
+    }
+
+    // An unsuffixed literal operand takes the type of the other operand,
+    // unless it is shifted or the amount of a shift
+    fn literal_operands(
+        &mut self,
+        op: BinOp,
+        (left, right): (&Expr, &Expr),
+        types: (Option<TypeExpr>, Option<TypeExpr>),
+    ) -> (Option<TypeExpr>, Option<TypeExpr>) {
+        if matches!(op, BinOp::Shl | BinOp::Shr) {
+            return types;
+        }
+        match types {
+            (Some(_), Some(ty)) if self.check_literal(left, &ty) => (Some(ty.clone()), Some(ty)),
+            (Some(ty), Some(_)) if self.check_literal(right, &ty) => (Some(ty.clone()), Some(ty)),
+            types => types,
+        }
+    }
+
+    fn check_unary(&mut self, op: UnaryOp, ty: Option<TypeExpr>, span: Span) -> Option<TypeExpr> {
+        let (allowed, symbol) = match op {
+            UnaryOp::Not => (ty.as_ref().is_none_or(|t| self.is_named(t, "bool")), "!"),
+            UnaryOp::Neg => (ty.as_ref().is_none_or(|t| self.is_numeric(t)), "-"),
+            UnaryOp::BitNot => (ty.as_ref().is_none_or(|t| self.is_integer(t)), "~"),
+        };
+        if let (false, Some(ty)) = (allowed, &ty) {
+            let message = format!("Cannot apply '{}' to '{}'", symbol, ty);
+            self.error(E_TYPE_MISMATCH, message, span);
+        }
+        match op {
+            UnaryOp::Not => Some(TypeExpr::named("bool")),
+            UnaryOp::Neg | UnaryOp::BitNot => ty,
+        }
+    }
+
+    fn check_call(&mut self, name: &str, args: &[Expr], span: Span) -> Option<TypeExpr> {
+        if capability::CAPABILITY_BUILTINS.contains(&name) {
+            return self.check_capability_call(name, args, span);
+        }
+        let arg_types: Vec<Option<TypeExpr>> =
+            args.iter().map(|arg| self.check_expr(arg)).collect();
+        for arg in args {
+            if name == "drop" {
+                self.discard(arg);
+            } else {
+                self.consume(arg);
+            }
+        }
+
+        // A local shadows functions of the same name
+        if self.scopes.get(name).is_ok() {
+            let callee = self.check_variable(name, true, span)?;
+            return self.check_value_call(name, &callee, args, &arg_types, span);
+        }
+
+        self.check_effect(name, span);
+
+        // The parser folds print's arguments into one format string, so
+        // several are left only when the first isn't a literal
+        if name == "print" {
+            match args {
+                [arg] if !matches!(arg.kind, ExprKind::String(_) | ExprKind::Format(_)) => {
+                    if let Some(ty) = &arg_types[0] {
+                        self.check_format(ty, &FormatSpec::default(), arg.span);
+                    }
+                }
+                [first, _, ..] => {
+                    let message =
+                        "The first argument to 'print' must be a string literal".to_string();
+                    self.error(E_CANNOT_FORMAT, message, first.span);
+                }
+                _ => {}
+            }
+            return None;
+        }
+        if let Some(&(_, params)) = BUILTINS.iter().find(|(builtin, _)| *builtin == name) {
+            self.check_arity(name, params.len(), args.len(), span);
+            for (i, (param, arg)) in params.iter().zip(args).enumerate() {
+                let Some(arg_type) = &arg_types[i] else {
+                    continue;
+                };
+                let matches = match *param {
+                    "" => true,
+                    "number" => self.is_numeric(arg_type),
+                    param => {
+                        let param = TypeExpr::named(param);
+                        self.check_literal(arg, &param) || self.compatible(&param, arg_type)
+                    }
+                };
+                if !matches {
+                    let expected = if *param == "number" {
+                        "a number"
+                    } else {
+                        param
+                    };
+                    let message = format!(
+                        "Mismatched types in argument {} of '{}': expected '{}', found '{}'",
+                        i + 1,
+                        name,
+                        expected,
+                        arg_type
+                    );
+                    self.error(E_TYPE_MISMATCH, message, arg.span);
+                }
+            }
+            return builtin_return_type(name, &arg_types);
+        }
+
+        if !self.functions.contains_key(name) {
+            if let Some(method) = self.trait_method(name, &arg_types) {
+                self.check_arity(name, method.params.len(), args.len(), span);
+                let args: Vec<&Expr> = args.iter().collect();
+                return self.check_trait_call(name, method, &args, &arg_types, 0);
+            }
+        }
+        let func = match self.functions.get(name) {
+            Some(func) => *func,
+            None => match self.impl_method(name, &arg_types) {
+                Some(method) => method,
+                None => {
+                    self.undefined_function(name, args.len(), span);
+                    return None;
+                }
+            },
+        };
+        self.check_arity(name, func.params.len(), args.len(), span);
+        let args: Vec<&Expr> = args.iter().collect();
+        self.check_function_call(name, func, &args, &arg_types, 0, span)
+    }
+
+    // A call through a local holding a function, such as a callback
+    // parameter
+    fn check_value_call(
+        &mut self,
+        name: &str,
+        callee: &TypeExpr,
+        args: &[Expr],
+        arg_types: &[Option<TypeExpr>],
+        span: Span,
+    ) -> Option<TypeExpr> {
+        let TypeExpr::Function {
+            params,
+            return_type,
+        } = self.resolve_alias(callee).clone()
+        else {
+            let message = format!("Cannot call '{}', a value of type '{}'", name, callee);
+            self.error(E_TYPE_MISMATCH, message, span);
+            return None;
+        };
+        self.check_arity(name, params.len(), args.len(), span);
+        for (i, (param, arg)) in params.iter().zip(args).enumerate() {
+            let context = format!(" in argument {} of '{}'", i + 1, name);
+            self.expect_value(param, arg, arg_types[i].as_ref(), &context);
+        }
+        return_type.map(|ty| *ty)
+    }
+
+    // A call to an unknown name, which may be a union variant that could
+    // not be resolved
+    fn undefined_function(&mut self, name: &str, arg_count: usize, span: Span) {
+        let mut unions: Vec<&str> = self
+            .unions
+            .values()
+            .filter(|u| u.variants.iter().any(|v| v.name == name))
+            .map(|u| u.name.as_str())
+            .collect();
+        unions.sort_unstable();
+        match unions.as_slice() {
+            [] => {
+                self.error(
+                    E_UNDEFINED_NAME,
+                    format!("Undefined function '{}'", name),
+                    span,
+                );
+            }
+            [union_name] => {
+                let message = format!(
+                    "Variant '{}' of '{}' takes one value but {} were given",
+                    name, union_name, arg_count
+                );
+                self.error(E_ARITY_MISMATCH, message, span);
+            }
+            _ => {
+                let message = format!(
+                    "Variant '{}' is declared by several unions; write '{}::{}' or similar",
+                    name, unions[0], name
+                );
+                self.error(E_UNDEFINED_NAME, message, span);
+            }
+        }
+    }
+
+    // `receiver.method(args)`: an inherent method of the receiver's type,
+    // then a trait method implemented for it
+    fn check_method_call(
+        &mut self,
+        receiver: &Expr,
+        method: &str,
+        args: &[Expr],
+        span: Span,
+    ) -> Option<TypeExpr> {
+        let mut arg_types = vec![self.check_expr(receiver)];
+        arg_types.extend(args.iter().map(|arg| self.check_expr(arg)));
+        let args: Vec<&Expr> = std::iter::once(receiver).chain(args).collect();
+        for arg in &args {
+            self.consume(arg);
+        }
+        // Nothing to resolve the method on; the receiver's error is reported
+        let receiver_type = arg_types[0].clone()?;
+
+        if let Some(trait_method) = self.trait_method(method, &arg_types) {
+            self.check_arity(method, trait_method.params.len() - 1, args.len() - 1, span);
+            return self.check_trait_call(method, trait_method, &args, &arg_types, 1);
+        }
+        let trait_impl = self.impl_methods.get(method).and_then(|candidates| {
+            candidates
+                .iter()
+                .find(|(impl_block, _)| self.same_type(&impl_block.type_name, &receiver_type))
+        });
+        let func = match self.inherent_method(&receiver_type, method) {
+            Some(func) => func,
+            None => match trait_impl {
+                Some((_, func)) => *func,
+                None => {
+                    let message = format!("Type '{}' has no method '{}'", receiver_type, method);
+                    self.error(E_UNKNOWN_MEMBER, message, span);
+                    return None;
+                }
+            },
+        };
+        self.check_arity(
+            method,
+            func.params.len().saturating_sub(1),
+            args.len() - 1,
+            span,
+        );
+        self.check_function_call(method, func, &args, &arg_types, 1, span)
+    }
+
+    fn inherent_method(&self, self_type: &TypeExpr, method: &str) -> Option<&'a Function> {
+        let candidates = self.inherent_methods.get(method)?;
+        candidates
+            .iter()
+            .find(|(ty, _)| self.same_type(ty, self_type))
+            .map(|(_, func)| *func)
+    }
+
+    // Argument types and bounds of a call to `func`, returning its result
+    // type. The first `receivers` arguments are a method's receiver, which
+    // argument numbers in messages leave out.
+    fn check_function_call(
+        &mut self,
+        name: &str,
+        func: &Function,
+        args: &[&Expr],
+        arg_types: &[Option<TypeExpr>],
+        receivers: usize,
+        span: Span,
+    ) -> Option<TypeExpr> {
+        // Type arguments are inferred from the arguments
+        let mut bindings = Bindings::new();
+        for (param, arg_type) in func.params.iter().zip(arg_types) {
+            if let Some(arg_type) = arg_type {
+                generics::unify(&param.type_name, arg_type, &func.type_params, &mut bindings);
+            }
+        }
+        self.check_inferred(name, &func.type_params, &bindings, args, arg_types, span);
+        self.check_bounds(name, &func.bounds, &bindings, span);
+        // Bound parameters may be replaced by the caller's own, of the same name
+        let unbound = unbound_params(&func.type_params, &bindings);
+        for (i, (param, arg)) in func.params.iter().zip(args).enumerate().skip(receivers) {
+            let expected = generics::substitute(&param.type_name, &bindings);
+            if generics::mentions(&expected, &unbound) {
+                continue;
+            }
+            let context = format!(" in argument {} of '{}'", i + 1 - receivers, name);
+            self.expect_value(&expected, arg, arg_types[i].as_ref(), &context);
+        }
+        func.return_type
+            .as_ref()
+            .map(|ty| generics::substitute(ty, &bindings))
+            .filter(|ty| !generics::mentions(ty, &unbound))
+    }
+
+    // Every type parameter must be bound by the values it was inferred
+    // from. A value of unknown type has been reported already, except for
+    // an empty array literal, which has no type to infer from.
+    fn check_inferred(
+        &mut self,
+        name: &str,
+        params: &[String],
+        bindings: &Bindings,
+        values: &[&Expr],
+        types: &[Option<TypeExpr>],
+        span: Span,
+    ) {
+        let all_known = types.iter().zip(values).all(|(ty, value)| {
+            ty.is_some()
+                || matches!(&value.kind, ExprKind::ArrayLiteral(elements) if elements.is_empty())
+        });
+        let unbound = params.iter().find(|p| !bindings.contains_key(*p));
+        if let (true, Some(param)) = (all_known, unbound) {
+            let message = format!("Cannot infer type parameter '{}' of '{}'", param, name);
+            self.error(E_CANNOT_INFER, message, span);
+        }
+    }
+
+    // Trait method called on a trait object or a generic parameter bound
+    // by the trait: `area(shape)` with `shape` a `dyn Shape`, or a `T`
+    // where `T: Shape`. Only methods taking `self` first can be.
+    fn trait_method(&self, name: &str, arg_types: &[Option<TypeExpr>]) -> Option<&'a TraitMethod> {
+        let receiver = arg_types.first()?.as_ref()?;
+        let traits = match self.resolve_alias(receiver).dyn_trait() {
+            Some(trait_name) => vec![trait_name],
+            None => self.bound_traits(receiver)?,
+        };
+        traits
+            .iter()
+            .filter_map(|trait_name| self.traits.get(trait_name).copied())
+            .flat_map(|trait_def| &trait_def.methods)
+            .find(|m| m.name == name && m.params.first().is_some_and(|p| p.name == "self"))
+    }
+
+    fn check_trait_call(
+        &mut self,
+        name: &str,
+        method: &TraitMethod,
+        args: &[&Expr],
+        arg_types: &[Option<TypeExpr>],
+        receivers: usize,
+    ) -> Option<TypeExpr> {
+        // `Self` is the generic parameter the method is called on; the
+        // implementing type is unknown behind a trait object
+        let receiver = arg_types[0]
+            .clone()
+            .filter(|ty| self.resolve_alias(ty).dyn_trait().is_none());
+        let self_type = receiver.map(|ty| Bindings::from([(SELF_TYPE.to_string(), ty)]));
+        let params = method.params.iter().zip(args).enumerate().skip(1);
+        for (i, (param, arg)) in params {
+            let expected = match &self_type {
+                Some(bindings) => generics::substitute(&param.type_name, bindings),
+                None if generics::mentions(&param.type_name, &[SELF_TYPE.to_string()]) => {
+                    continue;
+                }
+                None => param.type_name.clone(),
+            };
+            let context = format!(" in argument {} of '{}'", i + 1 - receivers, name);
+            self.expect_value(&expected, arg, arg_types[i].as_ref(), &context);
+        }
+        let return_type = method.return_type.as_ref()?;
+        match &self_type {
+            Some(bindings) => Some(generics::substitute(return_type, bindings)),
+            None => Some(return_type.clone()).filter(|ty| ty.as_name() != Some(SELF_TYPE)),
+        }
+    }
+
+    // Trait impl method called as `name(value, ...)`: the impl for the type
+    // of the first argument, or any impl when that is unknown
+    fn impl_method(&self, name: &str, arg_types: &[Option<TypeExpr>]) -> Option<&'a Function> {
+        let candidates = self.impl_methods.get(name)?;
+        let by_self_type = arg_types.first().cloned().flatten().and_then(|ty| {
+            candidates
+                .iter()
+                .find(|(impl_block, _)| self.same_type(&impl_block.type_name, &ty))
+        });
+        by_self_type
+            .or(candidates.first())
+            .map(|(_, method)| *method)
+    }
+
+    fn check_arity(&mut self, name: &str, expected: usize, found: usize, span: Span) {
+        if expected != found {
+            let message = format!(
+                "Function '{}' takes {} argument{} but {} {} given",
+                name,
+                expected,
+                if expected == 1 { "" } else { "s" },
+                found,
+                if found == 1 { "was" } else { "were" }
+            );
+            self.error(E_ARITY_MISMATCH, message, span);
+        }
+    }
+}
+
+fn builtin_return_type(name: &str, arg_types: &[Option<TypeExpr>]) -> Option<TypeExpr> {
+    match name {
+        // Math builtins return the type of their first argument
+        "abs" | "min" | "max" | "sqrt" => arg_types.first().cloned().flatten(),
+        "concat" | "substring" | "file_read" => Some(TypeExpr::named("string")),
+        "drop" => None,
+        _ => Some(TypeExpr::named("int")),
+    }
+}
+
+// Type parameters of a generic item that `bindings` leaves unbound
+fn unbound_params(params: &[String], bindings: &Bindings) -> Vec<String> {
+    params
+        .iter()
+        .filter(|p| !bindings.contains_key(*p))
+        .cloned()
+        .collect()
+}
+
+fn binop_symbol(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Eq => "==",
+        BinOp::NotEq => "!=",
+        BinOp::Lt => "<",
+        BinOp::LtEq => "<=",
+        BinOp::Gt => ">",
+        BinOp::GtEq => ">=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+        BinOp::BitAnd => "&",
+        BinOp::BitOr => "|",
+        BinOp::BitXor => "^",
+        BinOp::Shl => "<<",
+        BinOp::Shr => ">>",
+    }
+}
+
+// A pattern that matches every value of its type
+fn irrefutable(pattern: &Pattern) -> bool {
+    match &pattern.kind {
+        PatternKind::Wildcard | PatternKind::Binding(_) => true,
+        PatternKind::Struct { fields, .. } => fields.iter().all(|(_, field)| irrefutable(field)),
+        PatternKind::Or(alternatives) => alternatives.iter().any(irrefutable),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        let program = Parser::new(source).parse_program().unwrap();
+        check_program(&program, CheckOptions::default())
+    }
+
+    fn errors(source: &str) -> Vec<Diagnostic> {
+        check(source).into_iter().filter(|d| d.is_error()).collect()
+    }
+
+    #[test]
+    fn test_mutable_bindings_can_be_assigned() {
+        let source = "
+            struct Point { x: int }
+            fn main() {
+                let mut total = 0;
+                let mut p = Point { x: 1 };
+                let mut xs = [1, 2];
+                for i in 0..3 { total += i; }
+                p.x = 2;
+                xs[0] = 5;
+            }
+        ";
+        assert!(errors(source).is_empty());
+    }
+
+    #[test]
+    fn test_assigning_immutable_bindings_is_an_error() {
+        let source = "
+            struct Point { x: int }
+            fn bump(n: int) -> int {
+                n = n + 1;
+                let p = Point { x: 1 };
+                p.x = 2;
+                for i in 0..3 { i = 0; }
+                if true {
+                    let mut p = Point { x: 1 };
+                    p.x = 3;
+                }
+                return n;
+            }
+        ";
+        let diagnostics = errors(source);
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics.iter().all(|d| d.code == E_ASSIGN_IMMUTABLE));
+        assert!(diagnostics[0].message.contains("parameter 'n'"));
+        assert!(diagnostics[1].message.contains("let mut p"));
+        assert!(diagnostics[2].message.contains("loop variable 'i'"));
+    }
+
+    #[test]
+    fn test_scoping_and_shadowing() {
+        let source = "
+            fn main(sys: SystemCap) {
+                print(total);
+                let total = 1;
+                let total = total + 1;
+                let _ignored = 0;
+                let unused = 2;
+                if total > 1 {
+                    let total = \"shadowed\";
+                    print(length(total));
+                }
+                for i in 0..total { if i == 1 { break; } }
+                continue;
+            }
+        ";
+        let diagnostics = check(source);
+        let codes: Vec<&str> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            [
+                E_USE_BEFORE_DEFINITION,
+                E_BREAK_OUTSIDE_LOOP,
+                W_UNUSED_BINDING
+            ]
+        );
+        assert!(diagnostics[2].message.contains("'unused'"));
+        assert!(!diagnostics[2].is_error());
+    }
+
+    #[test]
+    fn test_undefined_names_and_types() {
+        let source = "
+            enum Color { Red, Green }
+            struct Point { x: int, y: Coord }
+            fn main() {
+                let a = missing + 1;
+                nope(a);
+                let c = Color::Blue;
+                let s = Shape { r: 1 };
+            }
+        ";
+        let diagnostics = errors(source);
+        let codes: Vec<&str> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(
+            codes,
+            [
+                E_UNDEFINED_TYPE,
+                E_UNDEFINED_NAME,
+                E_UNDEFINED_NAME,
+                E_UNKNOWN_MEMBER,
+                E_UNDEFINED_TYPE
+            ]
+        );
+        assert!(diagnostics[0].message.contains("'Coord'"));
+        assert!(diagnostics[1].message.contains("variable 'missing'"));
+        assert!(diagnostics[2].message.contains("function 'nope'"));
+    }
+
+    #[test]
+    fn test_arity_and_type_mismatches() {
+        let source = "
+            struct Point { x: int, y: int }
+            fn add(a: int, b: int) -> int { return a + b; }
+            fn main(sys: SystemCap) {
+                let n: u8 = 5;
+                let total: int = add(1, 2, 3);
+                let s: string = add(n, 2);
+                let p = Point { x: \"one\" };
+                print(p.z);
+                if n { print(length(n)); }
+                let mut f = 7.5 % 2.0;
+                f %= 2.0;
+                let joined: string = \"a\" + \"b\" + 1;
+                let same = Point { x: 1, y: 2 } == Point { x: 1, y: 2 };
+                let text = \"x\" == \"y\" || joined != \"ab\";
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Function 'add' takes 2 arguments but 3 were given",
+                "Mismatched types: expected 'string', found 'int'",
+                "Mismatched types in field 'x': expected 'int', found 'string'",
+                "Missing field 'y' in 'Point' literal",
+                "Type 'Point' has no field 'z'",
+                "Condition must be 'bool', found 'u8'",
+                "Mismatched types in argument 1 of 'length': expected 'string', found 'u8'",
+                "Cannot apply '%' to 'f64' and 'f64'",
+                "Cannot apply '%' to 'f64' and 'f64'",
+                "Cannot apply '+' to 'string' and 'int'",
+                "Cannot apply '==' to 'Point' and 'Point'",
+            ]
+        );
+
+        // Spans point at the offending expression
+        let span = diagnostics[1].span;
+        assert_eq!(&source[span.start..span.end], "add(n, 2)");
+    }
+
+    #[test]
+    fn test_numeric_conversions_and_literal_ranges() {
+        let source = "
+            fn take(x: u8) -> u8 { return x; }
+            fn main() {
+                let big: i64 = 5000000000;
+                let wide: i64 = take(1) + 1;
+                let k: i32 = big;
+                let h: u8 = 300;
+                let u: u8 = -1;
+                let m: i8 = -128;
+                let f: f32 = 2.5;
+                let d: f64 = f * 2.0;
+                let g: f32 = d;
+                let n: int = d;
+                let r: u32 = m;
+                let b: u8 = if f > 1.0 { take(2) } else { 0 };
+                let c: u8 = if f > 1.0 { 1 } else { 256 };
+                let y = take(999);
+                let z = take(1) == 1000;
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types: expected 'i32', found 'i64'",
+                "integer literal '300' is out of range for u8 (max 255)",
+                "integer literal '-1' is out of range for u8 (min 0)",
+                "Mismatched types: expected 'f32', found 'f64'",
+                "Mismatched types: expected 'int', found 'f64'",
+                "Mismatched types: expected 'u32', found 'i8'",
+                "integer literal '256' is out of range for u8 (max 255)",
+                "integer literal '999' is out of range for u8 (max 255)",
+                "integer literal '1000' is out of range for u8 (max 255)",
+            ]
+        );
+        assert_eq!(diagnostics[1].code, E_INVALID_LITERAL);
+    }
+
+    #[test]
+    fn test_generic_type_arguments_are_inferred() {
+        let source = "
+            struct Pair<T, U> { first: T, second: U }
+            struct Box<T> { value: T }
+            fn first_of<T, U>(pair: Pair<T, U>) -> T { return pair.first; }
+            fn same<T>(a: T, b: T) -> T { return a; }
+            fn make<T>() -> int { return 0; }
+            fn main() {
+                let p: Pair<int, string> = Pair { first: 1, second: \"a\" };
+                let b = Box { value: p };
+                let n: int = b.value.first;
+                let s: string = first_of(b.value);
+                let x = same(1, \"a\");
+                let q: Pair<int> = p;
+                let m = make();
+                let w = same([1, 2], [3, 4]);
+                let e = same([], []);
+                let r: Box<[]u8> = Box { value: [] };
+                let l = Box { value: [] };
+                let k = same(n.size, 1);
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types: expected 'string', found 'int'",
+                "Mismatched types in argument 2 of 'same': expected 'int', found 'string'",
+                "Type 'Pair' takes 2 type arguments but 1 were given",
+                "Cannot infer type parameter 'T' of 'make'",
+                "Cannot infer type parameter 'T' of 'same'",
+                "Cannot infer type parameter 'T' of 'Box'",
+                "Type 'int' has no field 'size'",
+            ]
+        );
+        assert_eq!(diagnostics[3].code, E_CANNOT_INFER);
+    }
+
+    #[test]
+    fn test_trait_bounds_and_impl_conformance() {
+        let source = "
+            trait Shape {
+                fn area(self: Self) -> int;
+                fn name(self: Self) -> string;
+            }
+            struct Square { side: int }
+            struct Circle { r: int }
+            impl Shape for Square {
+                fn area(self: Square) -> int { return self.side * self.side; }
+                fn name(self: Square) -> string { return \"square\"; }
+            }
+            impl Shape for Circle {
+                fn area(self: Square) -> string { return \"\"; }
+                fn scale(self: Circle, by: int) -> int { return by; }
+            }
+            impl Display for Circle { fn to_string(self: Circle) -> int { return 0; } }
+            fn total<T: Shape>(shape: T) -> int { return 0; }
+            fn same<T>(a: T, b: T) -> bool where T: Eq + Ord { return true; }
+            fn bad<T: Hashable>(x: T) { }
+            fn main() {
+                let a = total(Square { side: 2 });
+                let b = total(5);
+                let c = same(1, 2);
+                let d = same(\"x\", \"y\");
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types in parameter 1 of 'area': trait 'Shape' expects 'Circle', found 'Square'",
+                "Mismatched return type of 'area': trait 'Shape' expects 'int', found 'string'",
+                "Missing method 'name' in impl of 'Shape' for 'Circle'",
+                "Method 'scale' is not a member of trait 'Shape'",
+                "Mismatched return type of 'to_string': trait 'Display' expects 'string', found 'int'",
+                "Undefined trait 'Hashable'",
+                "Type 'int' does not implement 'Shape', required by the bound 'T: Shape' of 'total'",
+                "Type 'string' does not implement 'Ord', required by the bound 'T: Eq + Ord' of 'same'",
+            ]
+        );
+        assert_eq!(diagnostics[0].code, E_IMPL_MISMATCH);
+        assert_eq!(diagnostics[6].code, E_UNSATISFIED_BOUND);
+
+        // A generic body may only use its parameters as their bounds allow
+        let source = "
+            trait Shape { fn area(self: Self) -> int; }
+            fn add<T>(a: T, b: T) -> T { return a + b; }
+            fn larger<T: Ord>(a: T, b: T) -> T { if a > b { return a; } return b; }
+            fn equal<T>(a: T, b: T) -> bool { return a == b; }
+            fn show<T>(out: StdoutCap, x: T) { print(\"{}\", x); }
+            fn total<T: Shape>(shape: T) -> int { return area(shape) + shape.area(); }
+            fn wrong<T: Shape>(shape: T) -> string { return area(shape); }
+            fn biggest<T: Ord + Display>(out: StdoutCap, a: T, b: T) -> T {
+                let big = larger(a, b);
+                print(\"{}\", big);
+                return big;
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Cannot apply '+' to 'T' and 'T'",
+                "Cannot apply '==' to 'T' and 'T'",
+                "Type 'T' does not implement Display and cannot be formatted",
+                "Mismatched types in return: expected 'string', found 'int'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trait_objects() {
+        let source = "
+            trait Shape {
+                fn area(self: Self) -> int;
+                fn scale(self: Self, by: int) -> int;
+            }
+            struct Square { side: int }
+            struct Point { x: int }
+            impl Shape for Square {
+                fn area(self: Square) -> int { return self.side * self.side; }
+                fn scale(self: Square, by: int) -> int { return self.side * by; }
+            }
+            fn total(a: dyn Shape, b: dyn Shape) -> int { return area(a) + area(b); }
+            fn main() {
+                let s: dyn Shape = Square { side: 2 };
+                let n: int = total(s, Square { side: 3 });
+                let p: dyn Shape = Point { x: 1 };
+                let q: dyn Hashable = Square { side: 1 };
+                let r: string = scale(s, \"twice\");
+                let shapes: [dyn Shape; 2] = [Square { side: 1 }, s];
+                let m: int = area(shapes[1]);
+                let mixed = [Square { side: 1 }, Point { x: 1 }];
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types: expected 'dyn Shape', found 'Point'",
+                "Undefined type 'dyn Hashable'",
+                "Mismatched types in argument 2 of 'scale': expected 'int', found 'string'",
+                "Mismatched types: expected 'string', found 'int'",
+                "Mismatched types in array literal: expected 'Square', found 'Point'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_specs_and_display() {
+        let source = "
+            struct Point { x: int }
+            struct Named { x: int }
+            impl Display for Named { fn to_string(self: Named) -> string { return \"n\"; } }
+            enum Color { Red }
+            fn main(sys: SystemCap) {
+                let (b, s, f, n) = (true, \"s\", 1.5, 7);
+                let t = (1, 2);
+                let p = Point { x: 1 };
+                let xs: [u8; 2] = [1, 2];
+                print(\"{n:#x} {f:.2e} {s:.1} {Named { x: 1 }:>4} {Color::Red:^5} {xs[0]:x}\\n\");
+                print(\"{b:x} {s:+} {p} {t} {xs} {f:x} {n:.2} {n:e}\\n\");
+                print(p);
+                print(s, n);
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Values of type 'bool' cannot be formatted with a radix or an exponent",
+                "Values of type 'string' cannot be formatted with '+', '#' or '0'",
+                "Type 'Point' does not implement Display and cannot be formatted",
+                "Type '(int, int)' does not implement Display and cannot be formatted",
+                "Type '[u8; 2]' does not implement Display and cannot be formatted",
+                "Values of type 'f64' cannot be formatted with a radix",
+                "Values of type 'int' cannot be formatted with a precision",
+                "Values of type 'int' cannot be formatted with an exponent",
+                "Type 'Point' does not implement Display and cannot be formatted",
+                "The first argument to 'print' must be a string literal",
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.code == E_CANNOT_FORMAT));
+    }
+
+    #[test]
+    fn test_method_calls() {
+        let source = "
+            struct Vec2 { x: int, y: int }
+            type Point = Vec2;
+            trait Shape { fn area(self) -> int; }
+            impl Shape for Vec2 { fn area(self) -> int { return self.x * self.y; } }
+            impl Vec2 {
+                fn norm(self) -> int { return self.x; }
+                fn norm(self) -> int { return self.y; }
+                fn origin() -> Vec2 { return Vec2 { x: 0, y: 0 }; }
+                fn scaled(self, by: int) -> Vec2 { return self; }
+            }
+            fn main() {
+                let p: Point = Vec2 { x: 1, y: 2 };
+                let a: int = p.scaled(2).norm() + p.area();
+                let b: string = p.norm();
+                let c = p.scaled(\"x\");
+                let d = p.scaled();
+                let e = p.nothing();
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Duplicate method 'norm' for type 'Vec2'",
+                "Method 'origin' of 'Vec2' must take 'self' as its first parameter",
+                "Mismatched types: expected 'string', found 'int'",
+                "Mismatched types in argument 1 of 'scaled': expected 'int', found 'string'",
+                "Function 'scaled' takes 1 argument but 0 were given",
+                "Type 'Point' has no method 'nothing'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_union_variants() {
+        let source = "
+            type Option = Some(int) | None;
+            type Other = Some(string) | Nothing;
+            type Result = Ok(int) | Err(string);
+            fn main() {
+                let a = Result::Ok(\"x\");
+                let b = Result::Nope(1);
+                let c = Err;
+                let d: Result = Ok(1, 2);
+                let e = Some(1);
+                let o = Result::Ok(1);
+                match o {
+                    Ok => drop(o),
+                    Err(_) => drop(o),
+                }
+                let mut total: int = 0;
+                match o {
+                    Ok(v) => { total = v; },
+                    Err(message) => { total = message; },
+                    Nothing(x) => drop(o),
+                }
+                match Option::None {
+                    None(x) => drop(o),
+                    Some(v) => { v = 1; },
+                }
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types in variant 'Ok': expected 'int', found 'string'",
+                "Union 'Result' has no variant 'Nope'",
+                "Variant 'Err' of 'Result' takes a value of type 'string'",
+                "Variant 'Ok' of 'Result' takes one value but 2 were given",
+                "Variant 'Some' is declared by several unions; write 'Option::Some' or similar",
+                "Variant 'Ok' of 'Result' holds a value; match it with 'Ok(_)'",
+                "Mismatched types: expected 'int', found 'string'",
+                "Type 'Result' has no variant 'Nothing'",
+                "Variant 'None' of 'Option' holds no value to bind",
+                "Cannot assign to pattern binding 'v'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        let source = "
+            enum Color { Red, Green, Blue }
+            type Option = Some(int) | None;
+            fn main() {
+                let c = Color::Red;
+                match c { Color::Red => drop(c), }
+                match c { Color::Red => drop(c), _ => drop(c), Color::Blue => drop(c), }
+                let o = Option::None;
+                match o { Some(_) => drop(o), }
+                match o { None => drop(o), Some(n) => drop(n), }
+                let b = true;
+                match b { true => drop(b), }
+                match b { false => drop(b), true => drop(b), }
+                let n: u8 = 3;
+                match n { 0 => drop(n), 10 => drop(n), }
+                match n { _ => drop(n), 1 => drop(n), }
+                let i = 0;
+                match i { Color::Red => drop(i), Color::Green => drop(i), Color::Blue => drop(i), 1 => drop(i), _ => drop(i), }
+                let s = \"x\";
+                match s { _ => drop(s), }
+            }
+        ";
+        let diagnostics = check(source);
+        let messages: Vec<(bool, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.is_error(), d.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    true,
+                    "Non-exhaustive match on 'Color'; missing 'Color::Green', 'Color::Blue'"
+                ),
+                (
+                    false,
+                    "Unreachable match arm; earlier arms match every value it does"
+                ),
+                (true, "Non-exhaustive match on 'Option'; missing 'None'"),
+                (true, "Non-exhaustive match on 'bool'; missing 'false'"),
+                (
+                    true,
+                    "Non-exhaustive match on 'u8'; missing '1..=9', '11..=255'"
+                ),
+                (
+                    false,
+                    "Unreachable match arm; earlier arms match every value it does"
+                ),
+                (
+                    false,
+                    "Unreachable match arm; earlier arms match every value it does"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_richer_patterns() {
+        let source = "
+            struct Point { x: int, y: int }
+            type Option = Some(int) | None;
+            fn main() {
+                let n = 5;
+                match n { 9..=1 => drop(n), \"a\" => drop(n), m if m => drop(m), _ => drop(n), }
+                match n { 1..=5 => drop(n), 2 | 3 => drop(n), x if x > 3 => drop(x), _ => drop(n), }
+                match n { -5..=-1 | 0 => drop(n), 1..=2147483647 => drop(n), }
+                let o = Option::None;
+                match o { Some(a) | None => drop(o), _ => drop(o), }
+                let p = Point { x: 1, y: 2 };
+                match p { Point { z: 1 } => drop(p), Pt { x } => drop(p), Point { x: \"s\", y } => drop(y), }
+                match p { Point { x, y: 0 } => drop(x), Point { x: _, y } => drop(y), _ => drop(p), }
+                let s = \"x\";
+                match s { \"a\" | \"b\" => drop(s), t if t == \"c\" => drop(t), }
+            }
+        ";
+        let diagnostics = check(source);
+        let messages: Vec<(bool, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.is_error(), d.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (true, "Range pattern '9..=1' matches nothing"),
+                (
+                    true,
+                    "Mismatched types in pattern: expected 'int', found 'string'"
+                ),
+                (true, "Condition must be 'bool', found 'int'"),
+                (
+                    false,
+                    "Unreachable match arm; earlier arms match every value it does"
+                ),
+                (
+                    true,
+                    "Non-exhaustive match on 'int'; missing '-2147483648..=-6'"
+                ),
+                (
+                    true,
+                    "Cannot bind 'a' in an or-pattern; only one of its alternatives matches"
+                ),
+                (
+                    false,
+                    "Unused variable 'a'; prefix it with an underscore if this is intended"
+                ),
+                (
+                    false,
+                    "Unreachable match arm; earlier arms match every value it does"
+                ),
+                (true, "Type 'Point' has no field 'z'"),
+                (true, "Undefined struct 'Pt'"),
+                (
+                    false,
+                    "Unused variable 'x'; prefix it with an underscore if this is intended"
+                ),
+                (
+                    true,
+                    "Mismatched types in pattern: expected 'int', found 'string'"
+                ),
+                (true, "Non-exhaustive match on 'Point'; missing '_'"),
+                (
+                    false,
+                    "Unreachable match arm; earlier arms match every value it does"
+                ),
+                (true, "Non-exhaustive match on 'string'; missing '_'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_if_match_and_block_expressions() {
+        let source = "
+            enum Color { Red, Green }
+            fn name(c: Color) -> string {
+                match c { Color::Red => \"red\", Color::Green => 2 }
+            }
+            fn pick(flag: bool) -> int {
+                let a = if flag { 1 } else { return 0; };
+                let b = if flag { 1 } else { true; };
+                let c: string = { let d = 1; d + a };
+                let e = match a { 1 if { true } => 1, _ => 2 };
+                b + e
+            }
+            fn main() {
+                let n = if 1 { 1 } else { 2 };
+                match n { 1 => 2, _ => 3 }
+                { drop(n); }
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types in 'match' arms: expected 'string', found 'int'",
+                "Every branch of 'if' used as a value must end in an expression without ';'",
+                "Mismatched types: expected 'string', found 'int'",
+                "A match guard can't contain an 'if', 'match' or block",
+                "Condition must be 'bool', found 'int'",
+            ]
+        );
+
+        // Branches are compared with the first one, or with a declared
+        // type, which each branch converts to on its own
+        let source = "
+            trait Driver { fn id(self: Self) -> int; }
+            struct Disk { n: int }
+            struct Net { m: int }
+            impl Driver for Disk { fn id(self: Disk) -> int { return self.n; } }
+            impl Driver for Net { fn id(self: Net) -> int { return self.m; } }
+            fn main() {
+                let c = true;
+                let mixed = if c { 1 } else { \"s\" };
+                let q: dyn Driver = if c { Disk { n: 1 } } else { Net { m: 2 } };
+                let r: dyn Driver = match 1 { 1 => Disk { n: 1 }, _ => { Net { m: 2 } } };
+                let small: u8 = if c { 300 } else { 2 };
+                let s: string = if c { \"a\" } else { 2 };
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types in 'if' branches: expected 'int', found 'string'",
+                "integer literal '300' is out of range for u8 (max 255)",
+                "Mismatched types in 'if' branches: expected 'string', found 'int'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuples_and_destructuring_let() {
+        let source = "
+            linear struct Handle { fd: int }
+            type Span = (int, int);
+            fn bounds() -> Span { return (1, 2); }
+            fn open() -> (Handle, int) { return (Handle { fd: 3 }, 0); }
+            fn main() {
+                let (low, high) = bounds();
+                let total: int = low + high + bounds().1;
+                let wrong: (int, string) = bounds();
+                let missing = bounds().2;
+                let (a, b, c) = bounds();
+                let (handle, _status) = open();
+                drop(handle);
+                let leaked = open();
+                let ((p, mut q), r) = ((1, \"x\"), true);
+                q = p;
+                let (mut m, n) = (5, 6);
+                m += 1;
+                n = m;
+                let ((_, _, _), _) = ((1, 2), 3);
+                drop(total + a + b + c + missing);
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types: expected '(int, string)', found 'Span'",
+                "Tuple 'Span' has no field '2'",
+                "Tuple pattern has 3 names but 'Span' has 2 elements",
+                "Mismatched types: expected 'string', found 'int'",
+                "Cannot assign to immutable variable 'n'; declare it with 'let mut n'",
+                "Tuple pattern has 3 names but '(int, int)' has 2 elements",
+                "Linear value 'leaked' is never consumed; pass it on, return it or 'drop' it",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pointer_slice_array_and_function_types() {
+        let source = "
+            struct Signal<T> { value: T, subscribers: []fn(T) }
+            type Bytes = []u8;
+            type Handler = fn(*Bytes) -> bool;
+            fn relay(h: fn(*[]u8) -> bool) -> Handler { return h; }
+            fn narrow(p: *u16) -> *u8 { return p; }
+            fn get(sig: *Signal<int, int>, cells: [Cell; 4], f: fn() -> Missing) { }
+            fn sum(bytes: []u8) -> u8 { return bytes[0]; }
+            fn widen(xs: [i64; 2]) -> [i64; 2] { return xs; }
+            fn apply(f: fn(u8) -> u8, x: u8) -> u8 { return f(x); }
+            fn id<T>(x: T) -> T { return x; }
+            fn main() {
+                let bytes: [u8; 3] = [1, 2, 3];
+                let total = sum(bytes);
+                let _short: [u8; 2] = [1, 2, 3];
+                let _flag: bool = bytes[0] + total;
+                let _copy = widen([1, 2]);
+                let _wide: [u16; 3] = bytes;
+                let twice: fn(u8) -> u8 = sum;
+                let _twice = apply(id, total(1));
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Mismatched types in return: expected '*u8', found '*u16'",
+                "Type 'Signal' takes 1 type argument but 2 were given",
+                "Undefined type 'Cell'",
+                "Undefined type 'Missing'",
+                "Mismatched types: expected '[u8; 2]', found '[u8; 3]'",
+                "Mismatched types: expected 'bool', found 'u8'",
+                "Mismatched types: expected '[u16; 3]', found '[u8; 3]'",
+                "Mismatched types: expected 'fn(u8) -> u8', found 'fn([]u8) -> u8'",
+                "Cannot use generic function 'id' as a value",
+                "Cannot call 'total', a value of type 'u8'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_linear_capabilities_across_branches() {
+        let source = "
+            linear struct Handle { fd: int }
+            fn close(cap: Handle) { drop(cap); }
+            fn ok(cap: Handle, flag: bool) {
+                if flag {
+                    close(cap);
+                } else {
+                    drop(cap);
+                }
+            }
+            fn early(cap: Handle, flag: bool) {
+                if flag {
+                    close(cap);
+                    return;
+                }
+                close(cap);
+            }
+            fn some_paths(cap: Handle, flag: bool) {
+                if flag { close(cap); }
+            }
+            fn twice(cap: Handle) {
+                close(cap);
+                close(cap);
+            }
+            fn leaks(n: int) {
+                let cap = Handle { fd: n };
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Linear value 'cap' is consumed on some paths but not others",
+                "Use of moved value 'cap'",
+                "Linear value 'cap' is never consumed; pass it on, return it or 'drop' it",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_affine_capabilities_in_loops() {
+        let source = "
+            affine struct Token { id: int }
+            fn spend(t: Token) -> int { return t.id; }
+            fn unused(t: Token) {}
+            fn in_loop(t: Token) {
+                let mut i = 0;
+                while i < 3 {
+                    i += spend(t);
+                }
+            }
+            fn fresh_each_time() {
+                for i in 0..3 {
+                    let t = Token { id: i };
+                    spend(t);
+                }
+            }
+        ";
+        let diagnostics = errors(source);
+        let codes: Vec<&str> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, [E_MOVE_IN_LOOP]);
+        let span = diagnostics[0].span;
+        assert_eq!(&source[span.start..span.end], "t");
+    }
+
+    #[test]
+    fn test_io_requires_capabilities() {
+        let source = "
+            fn greet(out: StdoutCap, name: string) { print(\"hi {name}\"); }
+            fn save(fs: FileReadCap) -> int { return file_write(\"log\", \"x\"); }
+            fn sneaky() { print(\"no capability\"); }
+            fn main(sys: SystemCap) {
+                let out = cap_split(sys, CapType::Stdout);
+                greet(out, \"world\");
+                let fs = cap_split(sys, CapType::FileRead);
+                if file_exists(\"log\") == 0 { save(fs); }
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "'file_write' requires a 'FileWriteCap' capability, but 'save' holds none granting it",
+                "'print' requires a 'StdoutCap' capability, but 'sneaky' holds none granting it",
+            ]
+        );
+
+        // Legacy programs may do I/O anywhere, but 'main' still can't take arbitrary parameters
+        let program = Parser::new(source).parse_program().unwrap();
+        let legacy = CheckOptions { legacy_io: true };
+        assert!(check_program(&program, legacy)
+            .iter()
+            .all(|d| !d.is_error()));
+        let diagnostics = errors("fn main(args: int) {}");
+        assert_eq!(diagnostics[0].code, E_INVALID_MAIN);
+    }
+
+    #[test]
+    fn test_splitting_attenuating_and_revoking_capabilities() {
+        let source = "
+            fn read_only(fs: FileReadCap) -> string { return file_read(\"a\"); }
+            fn main(sys: SystemCap) {
+                let fs = cap_split(sys, CapType::File);
+                let reader = cap_attenuate(fs, CapType::FileRead);
+                print(read_only(reader));
+                file_write(\"b\", \"x\");
+                let again = cap_split(sys, CapType::FileRead);
+                let net = cap_split(sys, CapType::Network);
+                let send = cap_attenuate(net, CapType::NetworkSend);
+                cap_revoke(send);
+                drop(send);
+                let whole: SystemCap = sys;
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "'file_write' requires a 'FileWriteCap' capability, but 'main' holds none granting it",
+                "Capability 'sys' no longer grants 'FileReadCap'; 'FileCap' was already split off from it",
+                "Use of revoked capability 'send'",
+                "Use of partially split capability 'sys'; 'FileCap' was split off from it",
+            ]
+        );
+
+        // Each branch may split the same authority off, but after the join
+        // it is gone; a loop would split it off on every iteration
+        let source = "
+            fn take(out: StdoutCap) {}
+            fn main(sys: SystemCap) {
+                if true {
+                    take(cap_split(sys, CapType::Stdout));
+                } else {
+                    let out = cap_split(sys, CapType::Stdout);
+                    take(out);
+                }
+                let again = cap_split(sys, CapType::Stdout);
+                let mut i = 0;
+                while i < 3 {
+                    let fs = cap_split(sys, CapType::File);
+                    cap_revoke(fs);
+                    i += 1;
+                }
+            }
+        ";
+        let diagnostics = errors(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Capability 'sys' no longer grants 'StdoutCap'; 'StdoutCap' was already split off from it",
+                "Value 'sys' is split inside a loop",
+            ]
+        );
+    }
+}
@@ -1,10 +1,18 @@
 // Code generation module for Ath??n bootstrap compiler
 
 use crate::ast::*;
-use std::process;
+use crate::capability;
+use crate::generics;
+use crate::mono;
+use crate::scope::{ScopeKind, ScopeStack};
+use std::collections::{HashMap, HashSet};
 
 // --- Helper Functions ---
 
+// Escape a string for a C string literal. Control characters and
+// everything outside ASCII are written as octal escapes of their UTF-8
+// bytes, so the constant is valid UTF-8 whatever the C compiler's source
+// charset. (Octal rather than \x, which would swallow following hex digits.)
 fn escape_string_for_c(s: &str) -> String {
     let mut result = String::new();
     for ch in s.chars() {
@@ -14,38 +22,150 @@ fn escape_string_for_c(s: &str) -> String {
             '\r' => result.push_str("\\r"),
             '\\' => result.push_str("\\\\"),
             '"' => result.push_str("\\\""),
-            '\0' => result.push_str("\\0"),
-            _ => result.push(ch),
+            ' '..='~' => result.push(ch),
+            _ => {
+                let mut buf = [0; 4];
+                for byte in ch.encode_utf8(&mut buf).bytes() {
+                    result.push_str(&format!("\\{:03o}", byte));
+                }
+            }
         }
     }
     result
 }
 
+// C spelling of an integer literal. Suffixed literals are cast to their
+// <stdint.h> type; values past INT_MAX get an LL/ULL suffix so the C
+// compiler does not truncate them.
+fn c_int_literal(value: u64, ty: Option<IntType>) -> String {
+    c_signed_int_literal("", value, ty)
+}
+
+// `-128i8` is negated before the cast, since `(int8_t)128` already wraps
+fn c_signed_int_literal(sign: &str, value: u64, ty: Option<IntType>) -> String {
+    let c_suffix = if value > i64::MAX as u64 {
+        "ULL"
+    } else if value > i32::MAX as u64 {
+        "LL"
+    } else {
+        ""
+    };
+    match ty {
+        Some(int_type) => format!("(({}){}{}{})", int_type.c_type(), sign, value, c_suffix),
+        None => format!("{}{}{}", sign, value, c_suffix),
+    }
+}
+
+// C spelling of a float literal. `{:?}` always keeps a `.` or exponent,
+// so C never reads the value back as an integer.
+fn c_float_literal(value: f64, ty: Option<FloatType>) -> String {
+    match ty {
+        Some(FloatType::F32) => format!("{:?}f", value),
+        _ => format!("{:?}", value),
+    }
+}
+
 // --- Codegen ---
 
+fn binop_str(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => " + ",
+        BinOp::Sub => " - ",
+        BinOp::Mul => " * ",
+        BinOp::Div => " / ",
+        BinOp::Mod => " % ",
+        BinOp::BitAnd => " & ",
+        BinOp::BitOr => " | ",
+        BinOp::BitXor => " ^ ",
+        BinOp::Shl => " << ",
+        BinOp::Shr => " >> ",
+        BinOp::Eq => " == ",
+        BinOp::NotEq => " != ",
+        BinOp::Lt => " < ",
+        BinOp::Gt => " > ",
+        BinOp::LtEq => " <= ",
+        BinOp::GtEq => " >= ",
+        BinOp::And => " && ",
+        BinOp::Or => " || ",
+    }
+}
+
+// C name of a trait impl method: `impl Display for Point { fn to_string }`
+// becomes `Point_Display_to_string`, so impls of one trait don't collide
+fn impl_method_name(impl_block: &TraitImpl, method: &Function) -> String {
+    let self_type = generics::mangle_part(&impl_block.type_name);
+    format!("{}_{}_{}", self_type, impl_block.trait_name, method.name)
+}
+
+// Function boxing a value of `ty` into a `dyn trait_name`
+fn dyn_coercion_name(ty: &TypeExpr, trait_name: &str) -> String {
+    format!("__athon_{}_as_{}", generics::mangle_part(ty), trait_name)
+}
+
+// Vtable of the impl of a declared trait for a type
+fn vtable_name(impl_block: &TraitImpl) -> String {
+    format!(
+        "{}_{}_vtable",
+        generics::mangle_part(&impl_block.type_name),
+        impl_block.trait_name
+    )
+}
+
+// A trait method taking `self` first, which can be called through a trait object
+fn has_receiver(method: &TraitMethod) -> bool {
+    method.params.first().is_some_and(|p| p.name == "self")
+}
+
+// C name of a field: tuple elements `.0`, `.1` are the fields `_0`, `_1`
+fn member_c_name(member: &str) -> String {
+    if member.bytes().all(|b| b.is_ascii_digit()) {
+        format!("_{}", member)
+    } else {
+        member.to_string()
+    }
+}
+
+fn is_void(return_type: Option<&TypeExpr>) -> bool {
+    return_type.is_none_or(|ty| ty.as_name() == Some("void"))
+}
+
+// How a value is written by printf: the conversion for it and C text
+// placed around the value expression (casts, helper calls)
+struct FormatArg<'a> {
+    conversion: String,
+    prefix: String,
+    expr: &'a Expr,
+    suffix: String,
+}
+
+// The checker rejects formats the generated C can't produce
+fn unsupported_format(spec: &str, type_name: &str) -> ! {
+    unreachable!(
+        "format {} for type '{}' passed the checker",
+        spec, type_name
+    );
+}
+
 pub fn emit_c(program: &Program) {
-    // Build a set of enum names for type checking
-    let enum_names: std::collections::HashSet<String> = program
-        .enums
-        .iter()
-        .map(|e| e.name.clone())
-        .collect();
-    
-    // Build a set of type alias names
-    let type_alias_names: std::collections::HashSet<String> = program
-        .type_aliases
-        .iter()
-        .map(|ta| ta.name.clone())
-        .collect();
-    
+    let program = &mono::monomorphize(program);
+    let mut gen = CodeGen::new(program);
+
     println!("#include <stdio.h>");
     println!("#include <string.h>");
     println!("#include <stdlib.h>");
+    println!("#include <stdint.h>");
+    println!("#include <stdarg.h>");
     println!();
-    
-    // Emit type aliases as C typedefs
+
+    // Emit type aliases as C typedefs. An alias of a tuple is replaced by
+    // the tuple, whose typedef comes later with the structs, and an alias
+    // of a pointer, slice, array or function type by what it stands for.
     for type_alias in &program.type_aliases {
-        let c_target_type = get_c_type(&type_alias.target_type, &enum_names, &type_alias_names);
+        let target_type = &type_alias.target_type;
+        if gen.is_tuple(target_type) || gen.composite_c_type(target_type).is_some() {
+            continue;
+        }
+        let c_target_type = gen.get_c_type(target_type);
         println!("typedef {} {};", c_target_type, type_alias.name);
     }
     if !program.type_aliases.is_empty() {
@@ -75,6 +195,21 @@ pub fn emit_c(program: &Program) {
     println!("    return result;");
     println!("}}");
     println!();
+    println!("double __athon_fsqrt(double x) {{");
+    println!("    if (x <= 0.0) return 0.0;");
+    println!("    double result = x > 1.0 ? x : 1.0;");
+    println!("    while (1) {{");
+    println!("        double next = 0.5 * (result + x / result);");
+    println!("        if (next >= result) break;");
+    println!("        result = next;");
+    println!("    }}");
+    println!("    return result;");
+    println!("}}");
+    println!();
+    println!("double __athon_fabs(double x) {{");
+    println!("    return x < 0.0 ? -x : x;");
+    println!("}}");
+    println!();
 
     // Emit file I/O helper functions
     println!("// File I/O helper functions");
@@ -132,31 +267,117 @@ pub fn emit_c(program: &Program) {
     println!("}}");
     println!();
 
+    // Emit string formatting helper (interpolated strings)
+    println!("char* __athon_format(const char* format, ...) {{");
+    println!("    va_list args;");
+    println!("    va_start(args, format);");
+    println!("    int length = vsnprintf(NULL, 0, format, args);");
+    println!("    va_end(args);");
+    println!("    char* result = (char*)malloc(length + 1);");
+    println!("    va_start(args, format);");
+    println!("    vsnprintf(result, length + 1, format, args);");
+    println!("    va_end(args);");
+    println!("    return result;");
+    println!("}}");
+    println!();
+    println!("char* __athon_char_str(uint32_t c) {{");
+    println!("    char* result = (char*)malloc(5);");
+    println!("    if (c < 0x80) {{");
+    println!("        result[0] = (char)c; result[1] = '\\0';");
+    println!("    }} else if (c < 0x800) {{");
+    println!("        result[0] = (char)(0xC0 | (c >> 6));");
+    println!("        result[1] = (char)(0x80 | (c & 0x3F)); result[2] = '\\0';");
+    println!("    }} else if (c < 0x10000) {{");
+    println!("        result[0] = (char)(0xE0 | (c >> 12));");
+    println!("        result[1] = (char)(0x80 | ((c >> 6) & 0x3F));");
+    println!("        result[2] = (char)(0x80 | (c & 0x3F)); result[3] = '\\0';");
+    println!("    }} else {{");
+    println!("        result[0] = (char)(0xF0 | (c >> 18));");
+    println!("        result[1] = (char)(0x80 | ((c >> 12) & 0x3F));");
+    println!("        result[2] = (char)(0x80 | ((c >> 6) & 0x3F));");
+    println!("        result[3] = (char)(0x80 | (c & 0x3F)); result[4] = '\\0';");
+    println!("    }}");
+    println!("    return result;");
+    println!("}}");
+    println!();
+    println!("char* __athon_binary(unsigned long long value, int alternate) {{");
+    println!("    char digits[64];");
+    println!("    int count = 0;");
+    println!("    do {{");
+    println!("        digits[count++] = (char)('0' + (value & 1));");
+    println!("        value >>= 1;");
+    println!("    }} while (value);");
+    println!("    char* result = (char*)malloc(count + 3);");
+    println!("    char* out = result;");
+    println!("    if (alternate) {{ *out++ = '0'; *out++ = 'b'; }}");
+    println!("    while (count > 0) *out++ = digits[--count];");
+    println!("    *out = '\\0';");
+    println!("    return result;");
+    println!("}}");
+    println!();
+    println!("// Cut `str` to `precision` characters (-1 for all of it), then pad it");
+    println!("// with `fill` to `width` characters; align is '<', '>' or '^'");
+    println!("char* __athon_pad(const char* str, int width, int precision, char align,");
+    println!("                  const char* fill) {{");
+    println!("    int bytes = 0, chars = 0;");
+    println!("    while (str[bytes] && (precision < 0 || chars < precision)) {{");
+    println!("        bytes++;");
+    println!("        while ((str[bytes] & 0xC0) == 0x80) bytes++;");
+    println!("        chars++;");
+    println!("    }}");
+    println!("    int padding = width > chars ? width - chars : 0;");
+    println!("    int before = align == '<' ? 0 : align == '^' ? padding / 2 : padding;");
+    println!("    int fill_length = strlen(fill);");
+    println!("    char* result = (char*)malloc(bytes + padding * fill_length + 1);");
+    println!("    char* out = result;");
+    println!("    for (int i = 0; i < padding; i++) {{");
+    println!("        if (i == before) {{ memcpy(out, str, bytes); out += bytes; }}");
+    println!("        memcpy(out, fill, fill_length); out += fill_length;");
+    println!("    }}");
+    println!("    if (before == padding) {{ memcpy(out, str, bytes); out += bytes; }}");
+    println!("    *out = '\\0';");
+    println!("    return result;");
+    println!("}}");
+    println!();
+
+    // Trait objects: a boxed value and the vtable of its type's impl
+    for trait_def in &program.traits {
+        println!("struct {}_Dyn {{", trait_def.name);
+        println!("    void* data;");
+        println!("    const struct {}_VTable* vtable;", trait_def.name);
+        println!("}};");
+        println!();
+    }
+
     // Emit struct definitions
+    // (generic structs are instantiated by now; tuples and arrays are
+    // anonymous structs named by a typedef)
     for struct_def in &program.structs {
-        if struct_def.type_params.is_empty() {
-            // Non-generic struct
+        let is_tuple = struct_def.name.starts_with(['(', '[']);
+        if is_tuple {
+            println!("typedef struct {{");
+        } else {
             println!("struct {} {{", struct_def.name);
-            for field in &struct_def.fields {
-                let c_type = match field.type_name.as_str() {
-                    "int" => "int",
-                    "bool" => "int",
-                    "string" => "const char*",
-                    _ => &field.type_name,
-                };
-                println!("    {} {};", c_type, field.name);
+        }
+        for field in &struct_def.fields {
+            match &field.type_name {
+                // The one field of an array's struct is a C array
+                TypeExpr::Array(element, len) if struct_def.name.starts_with('[') => {
+                    let c_type = gen.get_c_type(element);
+                    println!("    {} {}[{}];", c_type, field.name, len);
+                }
+                _ => {
+                    let c_type = gen.get_c_type(&field.type_name);
+                    println!("    {} {};", c_type, field.name);
+                }
             }
-            println!("}};");
-            println!();
+        }
+        if is_tuple {
+            println!("}} {};", mono::c_struct_name(struct_def));
         } else {
-            // Generic struct - emit as comment for now
-            // In a full implementation, we'd track instantiations and generate concrete types
-            println!("// Generic struct {} with type parameters: {}", 
-                struct_def.name, 
-                struct_def.type_params.join(", "));
-            println!("// Note: Generic structs require monomorphization at usage sites");
-            println!();
+            println!("}};");
         }
+        println!();
     }
 
     // Emit enum definitions
@@ -167,6 +388,20 @@ pub fn emit_c(program: &Program) {
         }
         println!("}};");
         println!();
+
+        // Variant names for `{}` formatting
+        println!(
+            "const char* __athon_{}_name(enum {} value) {{",
+            enum_def.name, enum_def.name
+        );
+        println!("    switch (value) {{");
+        for variant in &enum_def.variants {
+            println!("        case {}: return \"{}\";", variant, variant);
+        }
+        println!("    }}");
+        println!("    return \"?\";");
+        println!("}}");
+        println!();
     }
 
     // Emit union type definitions
@@ -178,14 +413,14 @@ pub fn emit_c(program: &Program) {
         }
         println!("}};");
         println!();
-        
+
         println!("struct {} {{", union_type.name);
         println!("    enum {}_Tag tag;", union_type.name);
         println!("    union {{");
         for variant in &union_type.variants {
             if let Some(assoc_type) = &variant.associated_type {
-                let c_type = get_c_type(assoc_type, &enum_names, &type_alias_names);
-                println!("        {} {};", c_type, variant.name.to_lowercase());
+                let c_type = gen.get_c_type(assoc_type);
+                println!("        {} {};", c_type, variant.name);
             }
         }
         println!("    }} data;");
@@ -198,15 +433,18 @@ pub fn emit_c(program: &Program) {
         println!("// Trait: {}", trait_def.name);
         println!("struct {}_VTable {{", trait_def.name);
         for method in &trait_def.methods {
-            let return_type = method.return_type.as_deref().unwrap_or("void");
-            let c_return_type = get_c_type(return_type, &enum_names, &type_alias_names);
+            let c_return_type = gen.return_c_type(method.return_type.as_ref());
             print!("    {} (*{})(", c_return_type, method.name);
             for (i, param) in method.params.iter().enumerate() {
                 if i > 0 {
                     print!(", ");
                 }
-                let c_type = get_c_type(&param.type_name, &enum_names, &type_alias_names);
-                print!("{}", c_type);
+                // The receiver is the boxed value, whatever type implements it
+                if i == 0 && has_receiver(method) {
+                    print!("void*");
+                } else {
+                    print!("{}", gen.get_c_type(&param.type_name));
+                }
             }
             println!(");");
         }
@@ -214,605 +452,2016 @@ pub fn emit_c(program: &Program) {
         println!();
     }
 
+    // Calls through a trait object: `area(shape)` on a `dyn Shape`
+    for trait_def in &program.traits {
+        for method in trait_def.methods.iter().filter(|m| has_receiver(m)) {
+            print!(
+                "{} __athon_dyn_{}_{}(struct {}_Dyn self",
+                gen.return_c_type(method.return_type.as_ref()),
+                trait_def.name,
+                method.name,
+                trait_def.name
+            );
+            for param in &method.params[1..] {
+                print!(", {} {}", gen.get_c_type(&param.type_name), param.name);
+            }
+            println!(") {{");
+            let keyword = if is_void(method.return_type.as_ref()) {
+                ""
+            } else {
+                "return "
+            };
+            print!("    {}self.vtable->{}(self.data", keyword, method.name);
+            for param in &method.params[1..] {
+                print!(", {}", param.name);
+            }
+            println!(");");
+            println!("}}");
+            println!();
+        }
+    }
+    let mut has_coercions = false;
+    for impl_block in &program.impls {
+        if gen.trait_names.contains(&impl_block.trait_name) {
+            let trait_name = &impl_block.trait_name;
+            println!(
+                "struct {}_Dyn {}({} value);",
+                trait_name,
+                dyn_coercion_name(&impl_block.type_name, trait_name),
+                gen.get_c_type(&impl_block.type_name)
+            );
+            has_coercions = true;
+        }
+    }
+    if has_coercions {
+        println!();
+    }
+
+    // Declare functions up front so they can be called in any order
+    for func in &program.functions {
+        if func.name != "main" {
+            gen.emit_signature(func, &func.name);
+            println!(";");
+        }
+    }
+    println!();
+
     // Emit trait implementations
     for impl_block in &program.impls {
-        println!("// Impl {} for {}", impl_block.trait_name, impl_block.type_name);
-        
-        // Emit each method
+        println!(
+            "// Impl {} for {}",
+            impl_block.trait_name, impl_block.type_name
+        );
+
+        // Emit each method under its mangled name
         for method in &impl_block.methods {
-            emit_function(method, &enum_names, &type_alias_names);
+            gen.emit_function(method, &impl_method_name(impl_block, method));
+            println!();
+        }
+
+        // The built-in Display trait is dispatched statically and has no vtable
+        let Some(trait_def) = program
+            .traits
+            .iter()
+            .find(|t| t.name == impl_block.trait_name)
+        else {
+            continue;
+        };
+        let self_type = gen.get_c_type(&impl_block.type_name);
+
+        // Vtable entries for receiver methods take a pointer to the boxed value
+        for method in trait_def.methods.iter().filter(|m| has_receiver(m)) {
+            let Some(func) = impl_block.methods.iter().find(|f| f.name == method.name) else {
+                continue;
+            };
+            let c_name = impl_method_name(impl_block, func);
+            print!(
+                "{} {}_dyn(void* self",
+                gen.return_c_type(func.return_type.as_ref()),
+                c_name
+            );
+            for param in &func.params[1..] {
+                print!(", {} {}", gen.get_c_type(&param.type_name), param.name);
+            }
+            println!(") {{");
+            let keyword = if is_void(func.return_type.as_ref()) {
+                ""
+            } else {
+                "return "
+            };
+            print!("    {}{}(*({}*)self", keyword, c_name, self_type);
+            for param in &func.params[1..] {
+                print!(", {}", param.name);
+            }
+            println!(");");
+            println!("}}");
             println!();
         }
-        
+
         // Emit vtable instance
-        println!("struct {}_VTable {}_{}_vtable = {{", 
-            impl_block.trait_name, 
-            impl_block.type_name,
-            impl_block.trait_name);
+        println!(
+            "struct {}_VTable {} = {{",
+            impl_block.trait_name,
+            vtable_name(impl_block)
+        );
         for method in &impl_block.methods {
-            println!("    .{} = {},", method.name, method.name);
+            let is_dispatched = trait_def
+                .methods
+                .iter()
+                .any(|m| m.name == method.name && has_receiver(m));
+            let suffix = if is_dispatched { "_dyn" } else { "" };
+            println!(
+                "    .{} = {}{},",
+                method.name,
+                impl_method_name(impl_block, method),
+                suffix
+            );
         }
         println!("}};");
         println!();
+
+        println!(
+            "struct {}_Dyn {}({} value) {{",
+            trait_def.name,
+            dyn_coercion_name(&impl_block.type_name, &trait_def.name),
+            self_type
+        );
+        println!("    {}* data = malloc(sizeof({}));", self_type, self_type);
+        println!("    *data = value;");
+        println!(
+            "    return (struct {}_Dyn) {{data, &{}}};",
+            trait_def.name,
+            vtable_name(impl_block)
+        );
+        println!("}}");
+        println!();
     }
 
     // Emit all non-main functions
     for func in &program.functions {
         if func.name != "main" {
-            if func.type_params.is_empty() {
-                // Non-generic function
-                emit_function(func, &enum_names, &type_alias_names);
-                println!();
-            } else {
-                // Generic function - emit as comment
-                println!("// Generic function {} with type parameters: {}", 
-                    func.name, 
-                    func.type_params.join(", "));
-                println!("// Note: Generic functions require monomorphization at call sites");
-                println!();
-            }
+            gen.emit_function(func, &func.name);
+            println!();
         }
     }
 
     // Emit main function last
     for func in &program.functions {
         if func.name == "main" {
-            emit_function_as_main(func);
+            gen.emit_function_as_main(func);
         }
     }
 }
 
-fn get_c_type(type_name: &str, enum_names: &std::collections::HashSet<String>, type_alias_names: &std::collections::HashSet<String>) -> String {
-    match type_name {
-        "int" => "int".to_string(),
-        "bool" => "int".to_string(),
-        "string" => "const char*".to_string(),
-        "void" => "void".to_string(),
-        _ => {
-            if enum_names.contains(type_name) {
-                format!("enum {}", type_name)
-            } else if type_alias_names.contains(type_name) {
-                // Type alias - use directly (already typedef'd)
-                type_name.to_string()
-            } else {
-                // Struct type
-                format!("struct {}", type_name)
-            }
-        }
-    }
+// Code generation context shared by every function in the program
+struct CodeGen {
+    enum_names: HashSet<String>,
+    type_aliases: HashMap<String, TypeExpr>,
+    // Fields of each struct, and of each tuple by its C name
+    struct_fields: HashMap<String, Vec<(String, TypeExpr)>>,
+    // C names of the tuple types
+    tuples: HashSet<String>,
+    function_returns: HashMap<String, TypeExpr>,
+    function_params: HashMap<String, Vec<TypeExpr>>,
+    functions: HashSet<String>,
+    // Variants of each union type
+    unions: HashMap<String, Vec<UnionVariant>>,
+    trait_names: HashSet<String>,
+    // Methods of declared traits by trait name, for calls on trait objects
+    trait_methods: HashMap<String, Vec<TraitMethod>>,
+    // Trait impl methods by method name
+    impl_methods: HashMap<String, Vec<ImplMethod>>,
+    // Locals of the function being emitted, by Athōn name
+    locals: ScopeStack<Local>,
+    // Counter for renaming shadowing `let`s in the current function
+    shadow_counter: usize,
+    // Declared return type of the function being emitted
+    return_type: Option<TypeExpr>,
+    // C temporaries holding the values of the `if`, `match` and block
+    // expressions emitted so far, with their types, by the expression
+    values: HashMap<*const Expr, (String, Option<TypeExpr>)>,
+    // Counter for naming those temporaries in the current function
+    value_counter: usize,
 }
 
-fn emit_function(func: &Function, enum_names: &std::collections::HashSet<String>, type_alias_names: &std::collections::HashSet<String>) {
-    let return_type = func.return_type.as_deref().unwrap_or("void");
-    let c_return_type = get_c_type(return_type, enum_names, type_alias_names);
+struct Local {
+    // Name in the generated C; differs from the Athōn name when it shadows
+    c_name: String,
+    ty: Option<TypeExpr>,
+}
 
-    print!("{} {}(", c_return_type, func.name);
+// The C variable an `if`, `match` or block used as a value is assigned to,
+// and its type
+type Target<'t> = Option<(&'t str, Option<&'t TypeExpr>)>;
 
-    for (i, param) in func.params.iter().enumerate() {
-        if i > 0 {
-            print!(", ");
+struct ImplMethod {
+    trait_name: String,
+    self_type: TypeExpr,
+    c_name: String,
+    param_types: Vec<TypeExpr>,
+    return_type: Option<TypeExpr>,
+}
+
+impl CodeGen {
+    fn new(program: &Program) -> Self {
+        let struct_fields = program
+            .structs
+            .iter()
+            .map(|s| {
+                let fields = s
+                    .fields
+                    .iter()
+                    .map(|f| (f.name.clone(), f.type_name.clone()));
+                (mono::c_struct_name(s), fields.collect())
+            })
+            .collect();
+        let tuples = program
+            .structs
+            .iter()
+            .filter(|s| s.name.starts_with('('))
+            .map(mono::c_struct_name)
+            .collect();
+        let function_returns = program
+            .functions
+            .iter()
+            .filter_map(|f| Some((f.name.clone(), f.return_type.clone()?)))
+            .collect();
+        let mut impl_methods: HashMap<String, Vec<ImplMethod>> = HashMap::new();
+        for impl_block in &program.impls {
+            for method in &impl_block.methods {
+                impl_methods
+                    .entry(method.name.clone())
+                    .or_default()
+                    .push(ImplMethod {
+                        trait_name: impl_block.trait_name.clone(),
+                        self_type: impl_block.type_name.clone(),
+                        c_name: impl_method_name(impl_block, method),
+                        param_types: method.params.iter().map(|p| p.type_name.clone()).collect(),
+                        return_type: method.return_type.clone(),
+                    });
+            }
+        }
+
+        Self {
+            enum_names: program.enums.iter().map(|e| e.name.clone()).collect(),
+            type_aliases: program
+                .type_aliases
+                .iter()
+                .map(|ta| (ta.name.clone(), ta.target_type.clone()))
+                .collect(),
+            struct_fields,
+            tuples,
+            function_returns,
+            function_params: program
+                .functions
+                .iter()
+                .map(|f| {
+                    (
+                        f.name.clone(),
+                        f.params.iter().map(|p| p.type_name.clone()).collect(),
+                    )
+                })
+                .collect(),
+            functions: program.functions.iter().map(|f| f.name.clone()).collect(),
+            unions: program
+                .unions
+                .iter()
+                .map(|u| (u.name.clone(), u.variants.clone()))
+                .collect(),
+            trait_names: program.traits.iter().map(|t| t.name.clone()).collect(),
+            trait_methods: program
+                .traits
+                .iter()
+                .map(|t| (t.name.clone(), t.methods.clone()))
+                .collect(),
+            impl_methods,
+            locals: ScopeStack::new(),
+            shadow_counter: 0,
+            return_type: None,
+            values: HashMap::new(),
+            value_counter: 0,
         }
-        let c_type = get_c_type(&param.type_name, enum_names, type_alias_names);
-        print!("{} {}", c_type, param.name);
     }
 
-    println!(") {{");
+    fn resolve_alias<'a>(&'a self, mut ty: &'a TypeExpr) -> &'a TypeExpr {
+        // Bounded so a cyclic alias cannot hang codegen
+        for _ in 0..self.type_aliases.len() {
+            match ty.as_name().and_then(|name| self.type_aliases.get(name)) {
+                Some(target) => ty = target,
+                None => break,
+            }
+        }
+        ty
+    }
 
-    for stmt in &func.body {
-        emit_statement(stmt, 1);
+    // Name of a type without type arguments once aliases are expanded, or
+    // "" for a pointer, slice, array, function, tuple or trait object
+    fn name_of<'a>(&'a self, ty: &'a TypeExpr) -> &'a str {
+        self.resolve_alias(ty).as_name().unwrap_or_default()
     }
 
-    println!("}}");
-}
+    // C name, parameter and return types of the local `name` when it holds
+    // a function
+    fn function_local(&self, name: &str) -> Option<(&str, &[TypeExpr], Option<&TypeExpr>)> {
+        let local = self.locals.get(name).ok()?;
+        match self.resolve_alias(local.ty.as_ref()?) {
+            TypeExpr::Function {
+                params,
+                return_type,
+            } => Some((&local.c_name, params, return_type.as_deref())),
+            _ => None,
+        }
+    }
 
-fn emit_function_as_main(func: &Function) {
-    println!("int main() {{");
+    // Whether `expr` is a fixed-size array, held in a struct
+    fn is_array(&self, expr: &Expr) -> bool {
+        self.expr_type(expr)
+            .is_some_and(|ty| matches!(self.resolve_alias(&ty), TypeExpr::Array(..)))
+    }
 
-    for stmt in &func.body {
-        emit_statement(stmt, 1);
+    // Whether `ty` is a tuple type, written out or by its C name
+    fn is_tuple(&self, ty: &TypeExpr) -> bool {
+        self.tuples
+            .contains(&generics::mangle(self.resolve_alias(ty)))
     }
 
-    println!("    return 0;");
-    println!("}}");
-}
+    fn float_type(&self, ty: &TypeExpr) -> Option<FloatType> {
+        FloatType::from_name(self.name_of(ty))
+    }
 
-fn emit_statement(stmt: &Statement, indent: usize) {
-    let ind = "    ".repeat(indent);
-    match stmt {
-        Statement::Let { name, value } => {
-            // Determine type based on value
-            match value {
-                Expr::String(_) => {
-                    // String literal - use const char*
-                    print!("{}const char* {} = ", ind, name);
-                    emit_expr(value);
-                    println!(";");
-                }
-                Expr::ArrayLiteral(_) => {
-                    // Array - use int[]
-                    print!("{}int {}[] = ", ind, name);
-                    emit_expr(value);
-                    println!(";");
-                }
-                Expr::StructLiteral { struct_name, .. } => {
-                    // Struct literal - use struct type
-                    print!("{}struct {} {} = ", ind, struct_name, name);
-                    emit_expr(value);
-                    println!(";");
+    fn is_float_expr(&self, expr: &Expr) -> bool {
+        self.expr_type(expr)
+            .is_some_and(|ty| self.float_type(&ty).is_some())
+    }
+
+    // Best-effort Athōn type of an expression, used to pick C declaration
+    // types and printf conversions. `None` means "unknown, assume int".
+    fn expr_type(&self, expr: &Expr) -> Option<TypeExpr> {
+        match &expr.kind {
+            ExprKind::Number { value, ty } => Some(TypeExpr::named(match ty {
+                Some(int_type) => int_type.name(),
+                None => match IntType::for_unsuffixed(*value) {
+                    IntType::I32 => "int",
+                    int_type => int_type.name(),
+                },
+            })),
+            ExprKind::Float { ty, .. } => {
+                Some(TypeExpr::named(ty.unwrap_or(FloatType::F64).name()))
+            }
+            ExprKind::Boolean(_) => Some(TypeExpr::named("bool")),
+            ExprKind::Char(_) => Some(TypeExpr::named("char")),
+            ExprKind::String(_) => Some(TypeExpr::named("string")),
+            ExprKind::Variable(name) => match self.locals.get(name) {
+                Ok(local) => local.ty.clone(),
+                // A function named as a value
+                Err(_) => Some(TypeExpr::Function {
+                    params: self.function_params.get(name)?.clone(),
+                    return_type: self.function_returns.get(name).cloned().map(Box::new),
+                }),
+            },
+            ExprKind::StructLiteral { struct_name, .. } => Some(TypeExpr::named(struct_name)),
+            ExprKind::EnumVariant { enum_name, .. } => Some(TypeExpr::named(enum_name)),
+            ExprKind::UnionVariant { union_name, .. } => Some(TypeExpr::named(union_name)),
+            ExprKind::MemberAccess { object, member } => {
+                let struct_name = self.expr_type(object)?;
+                self.field_type(&struct_name, member)
+            }
+            ExprKind::Binary { left, op, right } => match op {
+                BinOp::Add
+                | BinOp::Sub
+                | BinOp::Mul
+                | BinOp::Div
+                | BinOp::Mod
+                | BinOp::BitAnd
+                | BinOp::BitOr
+                | BinOp::BitXor => self.arithmetic_type(&[left.as_ref(), right.as_ref()]),
+                // A shift has the type of the value being shifted
+                BinOp::Shl | BinOp::Shr => self.expr_type(left),
+                _ => Some(TypeExpr::named("bool")),
+            },
+            ExprKind::Unary { op, operand } => match op {
+                UnaryOp::Not => Some(TypeExpr::named("bool")),
+                UnaryOp::Neg | UnaryOp::BitNot => self.expr_type(operand),
+            },
+            ExprKind::Call { name, args } => match name.as_str() {
+                "sqrt" | "abs" | "min" | "max" => {
+                    let args: Vec<&Expr> = args.iter().collect();
+                    self.arithmetic_type(&args)
                 }
-                Expr::Call { name: fn_name, .. } if fn_name == "length" => {
-                    // strlen returns size_t, but we'll use int for simplicity
-                    print!("{}int {} = ", ind, name);
-                    emit_expr(value);
-                    println!(";");
+                "length" | "compare" | "array_length" | "pow" | "mod" | "file_write"
+                | "file_append" | "file_exists" => Some(TypeExpr::named("int")),
+                "concat" | "substring" | "file_read" => Some(TypeExpr::named("string")),
+                "cap_split" | "cap_attenuate" => args
+                    .get(1)
+                    .and_then(capability::named_kind)
+                    .map(TypeExpr::named),
+                _ => {
+                    if let Some((_, _, return_type)) = self.function_local(name) {
+                        return return_type.cloned();
+                    }
+                    if let Some((_, method)) = self.dyn_method(name, args) {
+                        return method.return_type.clone();
+                    }
+                    match self.impl_method(name, args) {
+                        Some(method) => method.return_type.clone(),
+                        None => self.function_returns.get(name).cloned(),
+                    }
                 }
-                Expr::Call { name: fn_name, .. } if fn_name == "file_read" => {
-                    // file_read returns char*
-                    print!("{}char* {} = ", ind, name);
-                    emit_expr(value);
-                    println!(";");
+            },
+            ExprKind::Format(_) => Some(TypeExpr::named("string")),
+            ExprKind::ArrayLiteral(elements) => {
+                let element = self.expr_type(elements.first()?)?;
+                Some(TypeExpr::Array(Box::new(element), elements.len() as u64))
+            }
+            ExprKind::Tuple(elements) => {
+                let types: Option<Vec<TypeExpr>> =
+                    elements.iter().map(|e| self.expr_type(e)).collect();
+                Some(TypeExpr::Tuple(types?))
+            }
+            ExprKind::ArrayIndex { array, .. } => {
+                let array_type = self.expr_type(array)?;
+                self.resolve_alias(&array_type).element().cloned()
+            }
+            ExprKind::MethodCall { .. } => unreachable!("method calls are lowered by mono"),
+            ExprKind::If { .. } | ExprKind::Match { .. } | ExprKind::Block(_) => {
+                self.values.get(&(expr as *const Expr))?.1.clone()
+            }
+        }
+    }
+
+    // Result type of arithmetic: that of its widest operand
+    fn arithmetic_type(&self, operands: &[&Expr]) -> Option<TypeExpr> {
+        let values = operands.iter().map(|e| (*e, self.expr_type(e))).collect();
+        self.widest(values)
+    }
+
+    // Widest numeric type of `values`, as the checker picks it: unsuffixed
+    // literals take the type of the other values, so they only count when
+    // every value is one
+    fn widest(&self, values: Vec<(&Expr, Option<TypeExpr>)>) -> Option<TypeExpr> {
+        let typed = values
+            .iter()
+            .filter(|(e, _)| e.unsuffixed_literal().is_none());
+        let typed: Vec<TypeExpr> = typed.filter_map(|(_, ty)| ty.clone()).collect();
+        let candidates = if typed.is_empty() {
+            values.into_iter().filter_map(|(_, ty)| ty).collect()
+        } else {
+            typed
+        };
+        candidates.into_iter().reduce(|a, b| self.wider(a, b))
+    }
+
+    // The wider of two numeric types: integers by width, then f32, then
+    // f64. `a` wins ties and non-numeric types.
+    fn wider(&self, a: TypeExpr, b: TypeExpr) -> TypeExpr {
+        let rank = |ty: &TypeExpr| {
+            let name = self.name_of(ty);
+            match (IntType::from_name(name), FloatType::from_name(name)) {
+                (Some(int_type), _) => Some(int_type.bits()),
+                (_, Some(FloatType::F32)) => Some(100),
+                (_, Some(FloatType::F64)) => Some(200),
+                _ => None,
+            }
+        };
+        match (rank(&a), rank(&b)) {
+            (Some(a_rank), Some(b_rank)) if b_rank > a_rank => b,
+            _ => a,
+        }
+    }
+
+    // Trait impl method called as `name(value, ...)`: the impl for the
+    // type of the first argument, or the only impl when that is unknown.
+    // Free functions take precedence.
+    fn impl_method(&self, name: &str, args: &[Expr]) -> Option<&ImplMethod> {
+        if self.functions.contains(name) {
+            return None;
+        }
+        let candidates = self.impl_methods.get(name)?;
+        match args.first().and_then(|arg| self.expr_type(arg)) {
+            Some(ty) => {
+                let ty = self.resolve_alias(&ty);
+                candidates
+                    .iter()
+                    .find(|m| self.resolve_alias(&m.self_type) == ty)
+            }
+            None if candidates.len() == 1 => candidates.first(),
+            None => None,
+        }
+    }
+
+    // Trait method called through a trait object: `area(shape)` with
+    // `shape` a `dyn Shape`. Free functions take precedence.
+    fn dyn_method(&self, name: &str, args: &[Expr]) -> Option<(&str, &TraitMethod)> {
+        if self.functions.contains(name) {
+            return None;
+        }
+        let receiver = self.expr_type(args.first()?)?;
+        let (trait_name, methods) = self
+            .trait_methods
+            .get_key_value(self.resolve_alias(&receiver).dyn_trait()?)?;
+        let method = methods.iter().find(|m| m.name == name && has_receiver(m))?;
+        Some((trait_name, method))
+    }
+
+    // Emit `expr` where a value of type `expected` is wanted, boxing a
+    // concrete value into a trait object when a `dyn Trait` is
+    fn emit_coerced(&self, expr: &Expr, expected: Option<&TypeExpr>) {
+        if let (ExprKind::Tuple(elements), Some(ty)) = (&expr.kind, expected) {
+            if self.is_tuple(ty) {
+                return self.emit_tuple(elements, ty);
+            }
+        }
+        if let Some(ty) = expected {
+            match (&expr.kind, self.resolve_alias(ty)) {
+                (ExprKind::ArrayLiteral(elements), TypeExpr::Array(..)) => {
+                    return self.emit_array(elements, ty);
                 }
-                Expr::Call { name: fn_name, .. } if fn_name == "substring" => {
-                    // substring returns char*
-                    print!("{}char* {} = ", ind, name);
-                    emit_expr(value);
-                    println!(";");
+                // A slice of a literal points into an unnamed C array, and
+                // an empty one nowhere
+                (ExprKind::ArrayLiteral(elements), TypeExpr::Slice(element)) => {
+                    if elements.is_empty() {
+                        print!("0");
+                    } else {
+                        print!("({}[])", self.get_c_type(element));
+                        self.emit_elements(elements, Some(element));
+                    }
+                    return;
                 }
-                _ => {
-                    // Default to int for numbers, booleans, expressions
-                    print!("{}int {} = ", ind, name);
-                    emit_expr(value);
-                    println!(";");
+                (_, TypeExpr::Slice(_)) if self.is_array(expr) => {
+                    self.emit_expr(expr);
+                    print!(".items");
+                    return;
                 }
+                _ => {}
             }
         }
-        Statement::Assign { name, value } => {
-            print!("{}{} = ", ind, name);
-            emit_expr(value);
-            println!(";");
+        let trait_name = expected.and_then(|ty| self.resolve_alias(ty).dyn_trait());
+        match (trait_name, self.expr_type(expr)) {
+            (Some(trait_name), Some(ty)) if self.resolve_alias(&ty).dyn_trait().is_none() => {
+                print!(
+                    "{}(",
+                    dyn_coercion_name(self.resolve_alias(&ty), trait_name)
+                );
+                self.emit_expr(expr);
+                print!(")");
+            }
+            _ => self.emit_expr(expr),
         }
-        Statement::Return { value } => {
-            if let Some(expr) = value {
-                print!("{}return ", ind);
-                emit_expr(expr);
-                println!(";");
-            } else {
-                println!("{}return;", ind);
+    }
+
+    // `(Array_int_2){{a, b}}`, each element converted to the element type
+    // of the array type `ty`
+    fn emit_array(&self, elements: &[Expr], ty: &TypeExpr) {
+        print!("({}){{", self.get_c_type(ty));
+        self.emit_elements(elements, self.resolve_alias(ty).element());
+        print!("}}");
+    }
+
+    // `{a, b}`, each element converted to `element_type`
+    fn emit_elements(&self, elements: &[Expr], element_type: Option<&TypeExpr>) {
+        print!("{{");
+        for (i, elem) in elements.iter().enumerate() {
+            if i > 0 {
+                print!(", ");
             }
+            self.emit_coerced(elem, element_type);
+        }
+        print!("}}");
+    }
+
+    // `(Tuple_int_string){a, b}`, each element converted to its field's type
+    fn emit_tuple(&self, elements: &[Expr], ty: &TypeExpr) {
+        print!("({}){{", generics::mangle(self.resolve_alias(ty)));
+        for (i, element) in elements.iter().enumerate() {
+            if i > 0 {
+                print!(", ");
+            }
+            let field_type = self.field_type(ty, &i.to_string());
+            self.emit_coerced(element, field_type.as_ref());
+        }
+        print!("}}");
+    }
+
+    // C function that renders a value of `ty` via its Display impl
+    fn display_impl(&self, ty: &TypeExpr) -> Option<&str> {
+        let candidates = self.impl_methods.get("to_string")?;
+        candidates
+            .iter()
+            .find(|m| {
+                m.trait_name == "Display"
+                    && self.resolve_alias(&m.self_type) == self.resolve_alias(ty)
+                    && m.return_type
+                        .as_ref()
+                        .is_some_and(|t| self.name_of(t) == "string")
+            })
+            .map(|m| m.c_name.as_str())
+    }
+
+    // printf conversion for a placeholder, chosen by the static type of
+    // the value. Unknown types are formatted as int.
+    fn format_arg<'a>(&self, expr: &'a Expr, spec: &FormatSpec) -> FormatArg<'a> {
+        let ty = self
+            .expr_type(expr)
+            .unwrap_or_else(|| TypeExpr::named("int"));
+        let resolved = self.name_of(&ty);
+        if let Some(int_type) = IntType::from_name(resolved) {
+            return self.format_int(expr, int_type, spec);
         }
-        Statement::Break => {
-            println!("{}break;", ind);
+        if let Some(float_type) = FloatType::from_name(resolved) {
+            return self.format_float(expr, float_type, spec);
         }
-        Statement::Continue => {
-            println!("{}continue;", ind);
+
+        let (prefix, suffix) = match resolved {
+            "string" => (String::new(), String::new()),
+            "bool" => ("(".to_string(), ") ? \"true\" : \"false\"".to_string()),
+            "char" => ("__athon_char_str(".to_string(), ")".to_string()),
+            _ if self.enum_names.contains(resolved) => {
+                (format!("__athon_{}_name(", resolved), ")".to_string())
+            }
+            _ => match self.display_impl(&ty) {
+                Some(c_name) => (format!("{}(", c_name), ")".to_string()),
+                None => unsupported_format("without Display", &ty.to_string()),
+            },
+        };
+        if spec.style != FormatStyle::Display || spec.sign || spec.alternate || spec.zero_pad {
+            unsupported_format("flags", &ty.to_string());
         }
-        Statement::If {
-            condition,
-            then_block,
-            else_block,
-        } => {
-            print!("{}if (", ind);
-            emit_expr(condition);
-            println!(") {{");
+        let arg = FormatArg {
+            conversion: "%s".to_string(),
+            prefix,
+            expr,
+            suffix,
+        };
+        self.pad_text(arg, spec, Align::Left)
+    }
 
-            for stmt in then_block {
-                emit_statement(stmt, indent + 1);
+    fn format_int<'a>(
+        &self,
+        expr: &'a Expr,
+        int_type: IntType,
+        spec: &FormatSpec,
+    ) -> FormatArg<'a> {
+        if spec.precision.is_some() {
+            unsupported_format("precision", int_type.name());
+        }
+        // Radix forms show the two's complement bits of the value's own width
+        let unsigned_cast = match int_type {
+            IntType::I8 => "(uint8_t)",
+            IntType::I16 => "(uint16_t)",
+            IntType::I32 => "(unsigned int)",
+            IntType::I64 | IntType::U64 => "(unsigned long long)",
+            _ => "",
+        };
+
+        if spec.style == FormatStyle::Binary {
+            let mut arg = FormatArg {
+                conversion: "%s".to_string(),
+                prefix: format!("__athon_binary({}(", unsigned_cast),
+                expr,
+                suffix: format!("), {})", spec.alternate as u8),
+            };
+            if spec.zero_pad {
+                // Zeros go between the 0b prefix and the digits
+                arg.suffix = "), 0)".to_string();
+                let prefix_width = if spec.alternate { 2 } else { 0 };
+                let width = spec.width.unwrap_or(0).saturating_sub(prefix_width);
+                arg.prefix = format!("__athon_pad({}", arg.prefix);
+                arg.suffix = format!("{}, {}, -1, '>', \"0\")", arg.suffix, width);
+                if spec.alternate {
+                    arg.conversion = "0b%s".to_string();
+                }
+                return arg;
             }
+            return self.pad_text(arg, spec, Align::Right);
+        }
 
-            if let Some(else_stmts) = else_block {
-                println!("{}}} else {{", ind);
-                for stmt in else_stmts {
-                    emit_statement(stmt, indent + 1);
+        let (length, cast, conversion) = match spec.style {
+            FormatStyle::Display => match int_type {
+                IntType::I64 => ("ll", "(long long)", 'd'),
+                IntType::U64 => ("ll", "(unsigned long long)", 'u'),
+                IntType::Usize => ("z", "", 'u'),
+                _ if int_type.is_signed() => ("", "", 'd'),
+                _ => ("", "", 'u'),
+            },
+            FormatStyle::LowerHex | FormatStyle::UpperHex | FormatStyle::Octal => {
+                let conversion = match spec.style {
+                    FormatStyle::LowerHex => 'x',
+                    FormatStyle::UpperHex => 'X',
+                    _ => 'o',
+                };
+                match int_type {
+                    IntType::I64 | IntType::U64 => ("ll", unsigned_cast, conversion),
+                    IntType::Usize => ("z", "", conversion),
+                    _ => ("", unsigned_cast, conversion),
                 }
             }
+            _ => unsupported_format("{:e}", int_type.name()),
+        };
+        self.pad_number(expr, cast, &format!("{}{}", length, conversion), spec)
+    }
 
-            println!("{}}}", ind);
+    fn format_float<'a>(
+        &self,
+        expr: &'a Expr,
+        float_type: FloatType,
+        spec: &FormatSpec,
+    ) -> FormatArg<'a> {
+        let precision = spec
+            .precision
+            .map(|p| format!(".{}", p))
+            .unwrap_or_default();
+        let conversion = match spec.style {
+            FormatStyle::Display if spec.precision.is_some() => format!("{}f", precision),
+            FormatStyle::Display => "g".to_string(),
+            FormatStyle::LowerExp => format!("{}e", precision),
+            FormatStyle::UpperExp => format!("{}E", precision),
+            _ => unsupported_format("radix", float_type.name()),
+        };
+        self.pad_number(expr, "", &conversion, spec)
+    }
+
+    // Numbers use printf's own flags and width unless they need a fill
+    // character or centering, which go through __athon_pad
+    fn pad_number<'a>(
+        &self,
+        expr: &'a Expr,
+        cast: &str,
+        conversion: &str,
+        spec: &FormatSpec,
+    ) -> FormatArg<'a> {
+        let mut flags = String::new();
+        if spec.sign {
+            flags.push('+');
+        }
+        if spec.alternate {
+            flags.push('#');
+        }
+        if spec.zero_pad {
+            flags.push('0');
         }
-        Statement::While { condition, body } => {
-            print!("{}while (", ind);
-            emit_expr(condition);
-            println!(") {{");
 
-            for stmt in body {
-                emit_statement(stmt, indent + 1);
+        let custom = spec.fill.is_some_and(|c| c != ' ') || spec.align == Some(Align::Center);
+        if spec.width.is_none() || spec.zero_pad || !custom {
+            if spec.align == Some(Align::Left) && !spec.zero_pad {
+                flags.push('-');
             }
+            let width = spec.width.map(|w| w.to_string()).unwrap_or_default();
+            return FormatArg {
+                conversion: format!("%{}{}{}", flags, width, conversion),
+                prefix: cast.to_string(),
+                expr,
+                suffix: String::new(),
+            };
+        }
+
+        let arg = FormatArg {
+            conversion: "%s".to_string(),
+            prefix: format!("__athon_format(\"%{}{}\", {}", flags, conversion, cast),
+            expr,
+            suffix: ")".to_string(),
+        };
+        self.pad_text(arg, spec, Align::Right)
+    }
 
-            println!("{}}}", ind);
+    // Apply width and precision to a `%s` argument, counting characters
+    fn pad_text<'a>(
+        &self,
+        mut arg: FormatArg<'a>,
+        spec: &FormatSpec,
+        default_align: Align,
+    ) -> FormatArg<'a> {
+        if spec.width.is_none() && spec.precision.is_none() {
+            return arg;
         }
-        Statement::For {
-            loop_var,
-            start,
-            end,
-            body,
-        } => {
-            // Desugar to C for loop
-            print!("{}for (int {} = ", ind, loop_var);
-            emit_expr(start);
-            print!("; {} < ", loop_var);
-            emit_expr(end);
-            println!("; {}++) {{", loop_var);
+        let align = match spec.align.unwrap_or(default_align) {
+            Align::Left => '<',
+            Align::Right => '>',
+            Align::Center => '^',
+        };
+        let fill = escape_string_for_c(&spec.fill.unwrap_or(' ').to_string());
+        let precision = spec.precision.map_or(-1, |p| p as i64);
+        arg.prefix = format!("__athon_pad({}", arg.prefix);
+        arg.suffix = format!(
+            "{}, {}, {}, '{}', \"{}\")",
+            arg.suffix,
+            spec.width.unwrap_or(0),
+            precision,
+            align,
+            fill
+        );
+        arg
+    }
 
-            for stmt in body {
-                emit_statement(stmt, indent + 1);
+    // printf format string (already escaped for C) and arguments for the
+    // parts of an interpolated string
+    fn c_format<'a>(&self, parts: &'a [FormatPart]) -> (String, Vec<FormatArg<'a>>) {
+        let mut format = String::new();
+        let mut values = Vec::new();
+        for part in parts {
+            match part {
+                FormatPart::Literal(text) => {
+                    format.push_str(&escape_string_for_c(text).replace('%', "%%"))
+                }
+                FormatPart::Value(expr, spec) => {
+                    let arg = self.format_arg(expr, spec);
+                    format.push_str(&arg.conversion);
+                    values.push(arg);
+                }
+                FormatPart::Positional(_) => format.push_str("{}"),
             }
+        }
+        (format, values)
+    }
 
-            println!("{}}}", ind);
+    // Emit `"format", value, value...` for printf-style functions
+    fn emit_format_args(&self, parts: &[FormatPart]) {
+        let (format, values) = self.c_format(parts);
+        print!("\"{}\"", format);
+        for value in values {
+            print!(", ");
+            self.emit_format_value(&value);
         }
-        Statement::Match { value, arms } => {
-            // Generate a temporary variable to hold the match value
-            println!("{}{{", ind);
-            print!("{}    int __match_val = ", ind);
-            emit_expr(value);
-            println!(";");
+    }
 
-            // Generate if-else chain for pattern matching
-            let mut first = true;
-
-            for arm in arms {
-                match &arm.pattern {
-                    Pattern::Wildcard => {
-                        // Wildcard always matches - emit as final else
-                        if !first {
-                            print!("{}    }} else {{", ind);
-                        } else {
-                            print!("{}    {{", ind);
-                        }
-                        println!();
-                        for stmt in &arm.body {
-                            emit_statement(stmt, indent + 2);
-                        }
-                    }
-                    Pattern::Number(n) => {
-                        if first {
-                            print!("{}    if (__match_val == {}) {{", ind, n);
-                            first = false;
-                        } else {
-                            print!("{}    }} else if (__match_val == {}) {{", ind, n);
-                        }
-                        println!();
-                        for stmt in &arm.body {
-                            emit_statement(stmt, indent + 2);
-                        }
-                    }
-                    Pattern::Boolean(b) => {
-                        let val = if *b { 1 } else { 0 };
-                        if first {
-                            print!("{}    if (__match_val == {}) {{", ind, val);
-                            first = false;
-                        } else {
-                            print!("{}    }} else if (__match_val == {}) {{", ind, val);
-                        }
-                        println!();
-                        for stmt in &arm.body {
-                            emit_statement(stmt, indent + 2);
-                        }
-                    }
-                    Pattern::EnumVariant {
-                        enum_name: _,
-                        variant,
-                    } => {
-                        if first {
-                            print!("{}    if (__match_val == {}) {{", ind, variant);
-                            first = false;
-                        } else {
-                            print!("{}    }} else if (__match_val == {}) {{", ind, variant);
-                        }
-                        println!();
-                        for stmt in &arm.body {
-                            emit_statement(stmt, indent + 2);
-                        }
-                    }
+    fn emit_format_value(&self, arg: &FormatArg) {
+        print!("{}", arg.prefix);
+        self.emit_expr(arg.expr);
+        print!("{}", arg.suffix);
+    }
+}
+
+impl CodeGen {
+    fn get_c_type(&self, ty: &TypeExpr) -> String {
+        if let Some(c_type) = self.composite_c_type(ty) {
+            return c_type;
+        }
+        let type_name = match ty {
+            TypeExpr::Named { name, args } if args.is_empty() => name.as_str(),
+            TypeExpr::Dyn(trait_name) => return format!("struct {}_Dyn", trait_name),
+            // Tuples are typedef'd by their C name
+            _ if self.is_tuple(ty) => return generics::mangle(ty),
+            // Generic structs are instantiated under their C name by now
+            _ => return format!("struct {}", generics::mangle(ty)),
+        };
+        match type_name {
+            "int" => "int".to_string(),
+            "bool" => "int".to_string(),
+            "string" => "const char*".to_string(),
+            "char" => "uint32_t".to_string(),
+            "void" => "void".to_string(),
+            _ => {
+                if let Some(int_type) = IntType::from_name(type_name) {
+                    int_type.c_type().to_string()
+                } else if let Some(float_type) = FloatType::from_name(type_name) {
+                    float_type.c_type().to_string()
+                } else if self.enum_names.contains(type_name) {
+                    format!("enum {}", type_name)
+                } else if self.tuples.contains(type_name) {
+                    type_name.to_string()
+                } else if self.is_tuple(ty) {
+                    // Aliases of tuples aren't typedef'd
+                    self.get_c_type(self.resolve_alias(ty))
+                } else if self.type_aliases.contains_key(type_name) {
+                    // Type alias - use directly (already typedef'd)
+                    type_name.to_string()
+                } else if capability::is_capability(type_name)
+                    && !self.struct_fields.contains_key(type_name)
+                {
+                    // Capabilities only exist at compile time; pass a token
+                    "int".to_string()
+                } else {
+                    // Struct type
+                    format!("struct {}", type_name)
                 }
             }
+        }
+    }
 
-            println!("{}    }}", ind);
-            println!("{}}}", ind);
+    // A slice is a pointer to its first element and an array the struct
+    // holding its elements. `__typeof__` lets function pointer types be
+    // written before the declared name.
+    fn composite_c_type(&self, ty: &TypeExpr) -> Option<String> {
+        match self.resolve_alias(ty) {
+            TypeExpr::Pointer(inner) | TypeExpr::Slice(inner) => {
+                Some(format!("{}*", self.get_c_type(inner)))
+            }
+            array @ TypeExpr::Array(..) => Some(generics::mangle(array)),
+            TypeExpr::Function {
+                params,
+                return_type,
+            } => {
+                let params: Vec<String> = params.iter().map(|p| self.get_c_type(p)).collect();
+                let params = if params.is_empty() {
+                    "void".to_string()
+                } else {
+                    params.join(", ")
+                };
+                let return_type = self.return_c_type(return_type.as_deref());
+                Some(format!("__typeof__({} (*)({}))", return_type, params))
+            }
+            _ => None,
         }
-        Statement::Expr(expr) => {
-            print!("{}", ind);
-            match expr {
-                Expr::Call { name, args } if name == "print" => {
-                    if args.is_empty() {
-                        println!("printf(\"\\n\");");
-                    } else if args.len() == 1 {
-                        // Single argument - legacy behavior
-                        let arg = &args[0];
-                        match arg {
-                            Expr::String(s) => {
-                                println!("printf(\"{}\");", escape_string_for_c(s));
-                            }
-                            _ => {
-                                print!("printf(\"%d\\n\", ");
-                                emit_expr(arg);
-                                println!(");");
-                            }
-                        }
-                    } else {
-                        // Multiple arguments - format string style
-                        if let Expr::String(format_str) = &args[0] {
-                            // Convert {} to appropriate format specifiers
-                            // For now, we'll use %s for strings and %d for numbers
-                            // This is a simplification - proper implementation would need type inference
-                            let mut c_format = format_str.clone();
+    }
+
+    fn return_c_type(&self, return_type: Option<&TypeExpr>) -> String {
+        return_type.map_or("void".to_string(), |ty| self.get_c_type(ty))
+    }
 
-                            // Count placeholders
-                            let _placeholder_count = format_str.matches("{}").count();
+    fn emit_signature(&self, func: &Function, c_name: &str) {
+        let c_return_type = self.return_c_type(func.return_type.as_ref());
 
-                            // Replace with %d by default (could be improved with type checking)
-                            c_format = c_format.replace("{}", "%d");
+        print!("{} {}(", c_return_type, c_name);
+        for (i, param) in func.params.iter().enumerate() {
+            if i > 0 {
+                print!(", ");
+            }
+            let c_type = self.get_c_type(&param.type_name);
+            print!("{} {}", c_type, param.name);
+        }
+        print!(")");
+    }
 
-                            // Escape the format string for C
-                            let escaped_format = escape_string_for_c(&c_format);
-                            print!("printf(\"{}\"", escaped_format);
+    fn emit_function(&mut self, func: &Function, c_name: &str) {
+        self.emit_signature(func, c_name);
+        println!(" {{");
 
-                            // Emit remaining arguments
-                            for arg in &args[1..] {
-                                print!(", ");
-                                emit_expr(arg);
-                            }
-                            println!(");");
-                        } else {
-                            eprintln!("Error: First argument to print must be a string");
-                            process::exit(1);
-                        }
+        self.start_locals();
+        self.return_type = func.return_type.clone();
+        for param in &func.params {
+            let local = Local {
+                c_name: param.name.clone(),
+                ty: Some(param.type_name.clone()),
+            };
+            self.locals.define(&param.name, local);
+        }
+
+        for stmt in &func.body {
+            self.emit_statement(stmt, 1);
+        }
+
+        println!("}}");
+    }
+
+    fn emit_function_as_main(&mut self, func: &Function) {
+        println!("int main() {{");
+        self.start_locals();
+        // The root capability, if `main` asks for it
+        for param in &func.params {
+            let ty = param.type_name.clone();
+            println!("    {} {} = 0;", self.get_c_type(&ty), param.name);
+            let local = Local {
+                c_name: param.name.clone(),
+                ty: Some(ty),
+            };
+            self.locals.define(&param.name, local);
+        }
+
+        for stmt in &func.body {
+            self.emit_statement(stmt, 1);
+        }
+
+        println!("    return 0;");
+        println!("}}");
+    }
+
+    // Parameters and the function body share the outermost C scope
+    fn start_locals(&mut self) {
+        self.locals = ScopeStack::new();
+        self.shadow_counter = 0;
+        self.value_counter = 0;
+    }
+
+    // C name for a new local. A `let` that shadows a visible binding is
+    // renamed, because C rejects redeclaring a name in the same block and
+    // `int x = x + 1;` would read the new, uninitialized `x`.
+    // C condition under which `pattern` matches `subject`, a C expression
+    // of type `ty`, or `None` if it always does
+    fn pattern_condition(
+        &self,
+        pattern: &Pattern,
+        subject: &str,
+        ty: Option<&TypeExpr>,
+    ) -> Option<String> {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => None,
+            PatternKind::Number(n) => Some(format!("{} == {}", subject, n)),
+            PatternKind::Boolean(b) => Some(format!("{} == {}", subject, if *b { 1 } else { 0 })),
+            PatternKind::String(s) => Some(format!(
+                "strcmp({}, \"{}\") == 0",
+                subject,
+                escape_string_for_c(s)
+            )),
+            PatternKind::Range { start, end } => Some(format!(
+                "({} >= {} && {} <= {})",
+                subject, start, subject, end
+            )),
+            PatternKind::EnumVariant { variant, .. } => Some(format!("{} == {}", subject, variant)),
+            PatternKind::UnionVariant { name, .. } => {
+                let union_name = ty.map(|ty| self.name_of(ty)).unwrap_or_default();
+                Some(format!("{}.tag == {}_Tag_{}", subject, union_name, name))
+            }
+            PatternKind::Struct { fields, .. } => {
+                let conditions: Vec<String> = fields
+                    .iter()
+                    .filter_map(|(field, pattern)| {
+                        let field_type = ty.and_then(|ty| self.field_type(ty, field));
+                        let subject = format!("{}.{}", subject, field);
+                        self.pattern_condition(pattern, &subject, field_type.as_ref())
+                    })
+                    .collect();
+                (!conditions.is_empty()).then(|| conditions.join(" && "))
+            }
+            PatternKind::Or(alternatives) => {
+                let conditions: Option<Vec<String>> = alternatives
+                    .iter()
+                    .map(|alternative| self.pattern_condition(alternative, subject, ty))
+                    .collect();
+                conditions.map(|conditions| format!("({})", conditions.join(" || ")))
+            }
+        }
+    }
+
+    // Names `pattern` binds, with the C expression for each one's value
+    // within `subject` and its type
+    fn pattern_bindings(
+        &self,
+        pattern: &Pattern,
+        subject: &str,
+        ty: Option<&TypeExpr>,
+    ) -> Vec<(String, String, Option<TypeExpr>)> {
+        match &pattern.kind {
+            PatternKind::Binding(name) => {
+                vec![(name.clone(), subject.to_string(), ty.cloned())]
+            }
+            PatternKind::UnionVariant {
+                name,
+                binding: Some(binding),
+            } if binding != "_" => {
+                let payload = ty
+                    .and_then(|ty| self.unions.get(self.name_of(ty)))
+                    .and_then(|variants| variants.iter().find(|v| &v.name == name))
+                    .and_then(|v| v.associated_type.clone());
+                match payload {
+                    Some(payload) => {
+                        let c_expr = format!("{}.data.{}", subject, name);
+                        vec![(binding.clone(), c_expr, Some(payload))]
                     }
+                    None => Vec::new(),
                 }
-                _ => {
-                    emit_expr(expr);
+            }
+            PatternKind::Struct { fields, .. } => fields
+                .iter()
+                .flat_map(|(field, pattern)| {
+                    let field_type = ty.and_then(|ty| self.field_type(ty, field));
+                    let subject = format!("{}.{}", subject, field);
+                    self.pattern_bindings(pattern, &subject, field_type.as_ref())
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn field_type(&self, struct_type: &TypeExpr, field: &str) -> Option<TypeExpr> {
+        let fields = self
+            .struct_fields
+            .get(&generics::mangle(self.resolve_alias(struct_type)))?;
+        let field = member_c_name(field);
+        fields
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, ty)| ty.clone())
+    }
+
+    // The type of a nested tuple element, following `path` one field at
+    // a time
+    fn element_type(&self, tuple_type: &TypeExpr, path: &[usize]) -> Option<TypeExpr> {
+        path.iter().try_fold(tuple_type.clone(), |ty, i| {
+            self.field_type(&ty, &i.to_string())
+        })
+    }
+
+    fn local_c_name(&mut self, name: &str) -> String {
+        if self.locals.get(name).is_ok() {
+            self.shadow_counter += 1;
+            format!("{}__{}", name, self.shadow_counter)
+        } else {
+            name.to_string()
+        }
+    }
+
+    fn emit_block(&mut self, stmts: &[Statement], indent: usize, kind: ScopeKind) {
+        self.emit_block_into(stmts, indent, kind, None);
+    }
+
+    // Emit a block whose trailing expression, if any, is assigned to the C
+    // variable `target`
+    fn emit_block_into(
+        &mut self,
+        stmts: &[Statement],
+        indent: usize,
+        kind: ScopeKind,
+        target: Target,
+    ) {
+        self.locals.push(kind);
+        for stmt in stmts {
+            match (&stmt.kind, target) {
+                (StatementKind::Tail(value), Some((target, ty))) => {
+                    self.hoist_as(value, ty, indent);
+                    print!("{}{} = ", "    ".repeat(indent), target);
+                    self.emit_coerced(value, ty);
                     println!(";");
                 }
+                _ => self.emit_statement(stmt, indent),
             }
         }
+        self.locals.pop();
     }
-}
 
-fn emit_expr(expr: &Expr) {
-    match expr {
-        Expr::Number(n) => print!("{}", n),
-        Expr::Boolean(b) => print!("{}", if *b { 1 } else { 0 }),
-        Expr::Char(c) => match c {
-            '\n' => print!("'\\n'"),
-            '\r' => print!("'\\r'"),
-            '\t' => print!("'\\t'"),
-            '\\' => print!("'\\\\'"),
-            '\'' => print!("'\\''"),
-            '\0' => print!("'\\0'"),
-            _ => print!("'{}'", c),
-        },
-        Expr::String(s) => print!("\"{}\"", escape_string_for_c(s)),
-        Expr::Variable(name) => print!("{}", name),
-        Expr::ArrayLiteral(elements) => {
-            print!("{{");
-            for (i, elem) in elements.iter().enumerate() {
-                if i > 0 {
-                    print!(", ");
-                }
-                emit_expr(elem);
+    // C has no `if`, `match` or block expressions, so each one in `expr` is
+    // emitted before the statement that uses it, into a temporary the
+    // expression then reads. This evaluates them ahead of the rest of the
+    // expression, except on the right of `&&` and `||`, which only runs
+    // when the left doesn't decide the result.
+    fn hoist(&mut self, expr: &Expr, indent: usize) {
+        self.hoist_as(expr, None, indent);
+    }
+
+    // `hoist` for a value of a declared type, which the temporary of an
+    // `if`, `match` or block takes instead of that of its branches
+    fn hoist_as(&mut self, expr: &Expr, expected: Option<&TypeExpr>, indent: usize) {
+        if let ExprKind::Binary {
+            left,
+            op: op @ (BinOp::And | BinOp::Or),
+            right,
+        } = &expr.kind
+        {
+            if right.contains_block() {
+                return self.hoist_short_circuit(expr, left, *op, right, indent);
             }
-            print!("}}");
         }
-        Expr::ArrayIndex { array, index } => {
-            emit_expr(array);
-            print!("[");
-            emit_expr(index);
-            print!("]");
+        if !expr.kind.is_block_like() {
+            for child in expr.children() {
+                self.hoist(child, indent);
+            }
+            return;
         }
-        Expr::StructLiteral {
-            struct_name,
-            fields,
-        } => {
-            print!("(struct {}) {{", struct_name);
-            for (i, (field_name, value)) in fields.iter().enumerate() {
-                if i > 0 {
-                    print!(", ");
+        let ind = "    ".repeat(indent);
+        let ty = expected.cloned().or_else(|| self.value_type(expr));
+        self.value_counter += 1;
+        let name = format!("__value_{}", self.value_counter);
+        let c_type = ty
+            .as_ref()
+            .map_or("int".to_string(), |t| self.get_c_type(t));
+        println!("{}{} {};", ind, c_type, name);
+        let target = Some((name.as_str(), ty.as_ref()));
+        match &expr.kind {
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => self.emit_if(condition, then_block, Some(else_block), indent, target),
+            ExprKind::Match { value, arms } => self.emit_match(value, arms, indent, target),
+            ExprKind::Block(stmts) => {
+                println!("{}{{", ind);
+                self.emit_block_into(stmts, indent + 1, ScopeKind::Block, target);
+                println!("{}}}", ind);
+            }
+            _ => unreachable!("only block-like expressions are hoisted"),
+        }
+        self.values.insert(expr as *const Expr, (name, ty));
+    }
+
+    // `a && b` as `if (a) { t = b; } else { t = 0; }`, with `b`'s own
+    // temporaries computed inside the branch
+    fn hoist_short_circuit(
+        &mut self,
+        expr: &Expr,
+        left: &Expr,
+        op: BinOp,
+        right: &Expr,
+        indent: usize,
+    ) {
+        let ind = "    ".repeat(indent);
+        self.hoist(left, indent);
+        self.value_counter += 1;
+        let name = format!("__value_{}", self.value_counter);
+        println!("{}int {};", ind, name);
+        print!(
+            "{}if ({}(",
+            ind,
+            if matches!(op, BinOp::Or) { "!" } else { "" }
+        );
+        self.emit_expr(left);
+        println!(")) {{");
+        self.hoist(right, indent + 1);
+        print!("{}    {} = ", ind, name);
+        self.emit_expr(right);
+        println!(";");
+        println!("{}}} else {{", ind);
+        println!(
+            "{}    {} = {};",
+            ind,
+            name,
+            if matches!(op, BinOp::Or) { 1 } else { 0 }
+        );
+        println!("{}}}", ind);
+        self.values
+            .insert(expr as *const Expr, (name, Some(TypeExpr::named("bool"))));
+    }
+
+    // Type of the value an `if`, `match` or block produces: the widest of
+    // its branches ending in an expression, each typed with the branch's
+    // own bindings in scope
+    fn value_type(&mut self, expr: &Expr) -> Option<TypeExpr> {
+        match &expr.kind {
+            ExprKind::If {
+                then_block,
+                else_block,
+                ..
+            } => {
+                let mut values = Vec::new();
+                for block in [then_block, else_block] {
+                    if let Some(tail) = Statement::tail(block) {
+                        values.push((tail, self.block_value_type(block)));
+                    }
                 }
-                print!(".{} = ", field_name);
-                emit_expr(value);
-            }
-            print!("}}");
-        }
-        Expr::MemberAccess { object, member } => {
-            emit_expr(object);
-            print!(".{}", member);
-        }
-        Expr::EnumVariant {
-            enum_name: _,
-            variant,
-        } => {
-            // In C, just use the variant name
-            print!("{}", variant);
-        }
-        Expr::Binary { left, op, right } => {
-            print!("(");
-            emit_expr(left);
-            let op_str = match op {
-                BinOp::Add => " + ",
-                BinOp::Sub => " - ",
-                BinOp::Mul => " * ",
-                BinOp::Div => " / ",
-                BinOp::Eq => " == ",
-                BinOp::NotEq => " != ",
-                BinOp::Lt => " < ",
-                BinOp::Gt => " > ",
-                BinOp::LtEq => " <= ",
-                BinOp::GtEq => " >= ",
-                BinOp::And => " && ",
-                BinOp::Or => " || ",
-            };
-            print!("{}", op_str);
-            emit_expr(right);
-            print!(")");
-        }
-        Expr::Unary { op, operand } => {
-            match op {
-                UnaryOp::Not => print!("!"),
-                UnaryOp::Neg => print!("-"),
-            }
-            emit_expr(operand);
-        }
-        Expr::Call { name, args } => {
-            // Handle built-in functions
-            match name.as_str() {
-                "length" => {
-                    // C strlen function
-                    print!("strlen(");
-                    if let Some(arg) = args.first() {
-                        emit_expr(arg);
-                    }
-                    print!(")");
-                }
-                "concat" => {
-                    // String concatenation using strcat
-                    // Note: This is unsafe in real code, needs buffer management
-                    print!("strcat(");
-                    if args.len() >= 2 {
-                        emit_expr(&args[0]);
-                        print!(", ");
-                        emit_expr(&args[1]);
+                self.widest(values)
+            }
+            ExprKind::Match { value, arms } => {
+                let value_type = self.value_type(value);
+                let resolved = value_type.as_ref().map(|ty| self.resolve_alias(ty).clone());
+                let mut values = Vec::new();
+                for arm in arms {
+                    let Some(tail) = Statement::tail(&arm.body) else {
+                        continue;
+                    };
+                    self.locals.push(ScopeKind::MatchArm);
+                    let bindings =
+                        self.pattern_bindings(&arm.pattern, "__match_val", resolved.as_ref());
+                    for (name, c_name, ty) in bindings {
+                        self.locals.define(&name, Local { c_name, ty });
                     }
-                    print!(")");
+                    values.push((tail, self.block_value_type(&arm.body)));
+                    self.locals.pop();
                 }
-                "compare" => {
-                    // String comparison using strcmp
-                    print!("strcmp(");
-                    if args.len() >= 2 {
-                        emit_expr(&args[0]);
-                        print!(", ");
-                        emit_expr(&args[1]);
+                self.widest(values)
+            }
+            ExprKind::Block(stmts) => self.block_value_type(stmts),
+            _ => self.expr_type(expr),
+        }
+    }
+
+    fn block_value_type(&mut self, stmts: &[Statement]) -> Option<TypeExpr> {
+        self.locals.push(ScopeKind::Block);
+        let mut value_type = None;
+        for stmt in stmts {
+            match &stmt.kind {
+                StatementKind::Let {
+                    name,
+                    type_name,
+                    value,
+                    ..
+                } => {
+                    let ty = type_name.clone().or_else(|| self.value_type(value));
+                    let c_name = name.clone();
+                    self.locals.define(name, Local { c_name, ty });
+                }
+                StatementKind::LetTuple {
+                    elements,
+                    type_name,
+                    value,
+                } => {
+                    let ty = type_name.clone().or_else(|| self.value_type(value));
+                    for (name, _, path) in LetPattern::bindings(elements) {
+                        let ty = ty.as_ref().and_then(|ty| self.element_type(ty, &path));
+                        let c_name = name.to_string();
+                        self.locals.define(name, Local { c_name, ty });
                     }
-                    print!(")");
                 }
-                "array_length" => {
-                    // Array length - compile-time only
-                    // In C, we use sizeof(arr)/sizeof(arr[0])
-                    print!("(sizeof(");
-                    if let Some(arg) = args.first() {
-                        emit_expr(arg);
+                StatementKind::Tail(value) => value_type = self.value_type(value),
+                _ => {}
+            }
+        }
+        self.locals.pop();
+        value_type
+    }
+
+    fn emit_if(
+        &mut self,
+        condition: &Expr,
+        then_block: &[Statement],
+        else_block: Option<&[Statement]>,
+        indent: usize,
+        target: Target,
+    ) {
+        let ind = "    ".repeat(indent);
+        self.hoist(condition, indent);
+        print!("{}if (", ind);
+        self.emit_expr(condition);
+        println!(") {{");
+
+        self.emit_block_into(then_block, indent + 1, ScopeKind::Block, target);
+
+        if let Some(else_stmts) = else_block {
+            println!("{}}} else {{", ind);
+            self.emit_block_into(else_stmts, indent + 1, ScopeKind::Block, target);
+        }
+
+        println!("{}}}", ind);
+    }
+
+    fn emit_statement(&mut self, stmt: &Statement, indent: usize) {
+        let ind = "    ".repeat(indent);
+        match &stmt.kind {
+            StatementKind::Let {
+                name,
+                type_name,
+                value,
+                ..
+            } => {
+                self.hoist_as(value, type_name.as_ref(), indent);
+                let ty = type_name.clone().or_else(|| self.expr_type(value));
+                // A slice of a literal is a C array, declared by element
+                // type: `int xs[] = {...}`
+                let slice_element = match (&value.kind, ty.as_ref().map(|t| self.resolve_alias(t)))
+                {
+                    (ExprKind::ArrayLiteral(_), Some(TypeExpr::Slice(element))) => {
+                        Some(element.as_ref().clone())
                     }
-                    print!(") / sizeof((");
-                    if let Some(arg) = args.first() {
-                        emit_expr(arg);
+                    _ => None,
+                };
+                // Unknown types default to int
+                let c_type = slice_element
+                    .as_ref()
+                    .or(ty.as_ref())
+                    .map_or("int".to_string(), |t| self.get_c_type(t));
+                let c_name = self.local_c_name(name);
+                print!(
+                    "{}{} {}{} = ",
+                    ind,
+                    c_type,
+                    c_name,
+                    if slice_element.is_some() { "[]" } else { "" }
+                );
+                // Bound after the value, which may refer to a shadowed name
+                match (&value.kind, &slice_element) {
+                    (ExprKind::ArrayLiteral(elements), Some(element)) => {
+                        self.emit_elements(elements, Some(element));
                     }
-                    print!(")[0]))");
+                    _ => self.emit_coerced(value, type_name.as_ref()),
                 }
-                "substring" => {
-                    // Extract substring: substring(str, start, length)
-                    print!("__athon_substring(");
-                    if args.len() >= 3 {
-                        emit_expr(&args[0]);
-                        print!(", ");
-                        emit_expr(&args[1]);
-                        print!(", ");
-                        emit_expr(&args[2]);
-                    }
-                    print!(")");
-                }
-                // Math functions
-                "abs" => {
-                    print!("abs(");
-                    if let Some(arg) = args.first() {
-                        emit_expr(arg);
-                    }
-                    print!(")");
-                }
-                "min" => {
-                    print!("((");
-                    if args.len() >= 2 {
-                        emit_expr(&args[0]);
-                        print!(") < (");
-                        emit_expr(&args[1]);
-                        print!(") ? (");
-                        emit_expr(&args[0]);
-                        print!(") : (");
-                        emit_expr(&args[1]);
-                    }
-                    print!("))");
-                }
-                "max" => {
-                    print!("((");
-                    if args.len() >= 2 {
-                        emit_expr(&args[0]);
-                        print!(") > (");
-                        emit_expr(&args[1]);
-                        print!(") ? (");
-                        emit_expr(&args[0]);
-                        print!(") : (");
-                        emit_expr(&args[1]);
-                    }
-                    print!("))");
-                }
-                "pow" => {
-                    // Generate inline power calculation
-                    print!("__athon_pow(");
-                    if args.len() >= 2 {
-                        emit_expr(&args[0]);
+                println!(";");
+                self.locals.define(name, Local { c_name, ty });
+            }
+            StatementKind::LetTuple {
+                elements,
+                type_name,
+                value,
+            } => {
+                // The tuple is held in a temporary, then each name is bound
+                // to one of its fields
+                self.hoist(value, indent);
+                let ty = type_name.clone().or_else(|| self.expr_type(value));
+                let c_type = ty
+                    .as_ref()
+                    .map_or("int".to_string(), |t| self.get_c_type(t));
+                self.value_counter += 1;
+                let tuple = format!("__tuple_{}", self.value_counter);
+                print!("{}{} {} = ", ind, c_type, tuple);
+                self.emit_coerced(value, ty.as_ref());
+                println!(";");
+                for (name, _, path) in LetPattern::bindings(elements) {
+                    let field_type = ty.as_ref().and_then(|ty| self.element_type(ty, &path));
+                    let c_type = field_type
+                        .as_ref()
+                        .map_or("int".to_string(), |t| self.get_c_type(t));
+                    let c_name = self.local_c_name(name);
+                    let fields: String = path.iter().map(|i| format!("._{}", i)).collect();
+                    println!("{}{} {} = {}{};", ind, c_type, c_name, tuple, fields);
+                    self.locals.define(
+                        name,
+                        Local {
+                            c_name,
+                            ty: field_type,
+                        },
+                    );
+                }
+            }
+            StatementKind::Assign { target, op, value } => {
+                self.hoist(target, indent);
+                self.hoist(value, indent);
+                print!("{}", ind);
+                self.emit_expr(target);
+                let is_string = self
+                    .expr_type(target)
+                    .is_some_and(|ty| self.name_of(&ty) == "string");
+                match op {
+                    // `s += t` on strings appends into a new string
+                    Some(BinOp::Add) if is_string => {
+                        print!(" = __athon_format(\"%s%s\", ");
+                        self.emit_expr(target);
                         print!(", ");
-                        emit_expr(&args[1]);
+                        self.emit_expr(value);
+                        println!(");");
+                        return;
                     }
-                    print!(")");
-                }
-                "sqrt" => {
-                    // Integer square root
-                    print!("__athon_sqrt(");
-                    if let Some(arg) = args.first() {
-                        emit_expr(arg);
+                    Some(op) => print!(" {}= ", binop_str(*op).trim()),
+                    None => {
+                        print!(" = ");
+                        self.emit_coerced(value, self.expr_type(target).as_ref());
+                        println!(";");
+                        return;
                     }
-                    print!(")");
                 }
-                "mod" => {
-                    print!("((");
-                    if args.len() >= 2 {
-                        emit_expr(&args[0]);
-                        print!(") % (");
-                        emit_expr(&args[1]);
-                    }
-                    print!("))");
+                self.emit_expr(value);
+                println!(";");
+            }
+            StatementKind::Return { value } => {
+                if let Some(expr) = value {
+                    self.hoist_as(expr, self.return_type.clone().as_ref(), indent);
+                    print!("{}return ", ind);
+                    self.emit_coerced(expr, self.return_type.as_ref());
+                    println!(";");
+                } else {
+                    println!("{}return;", ind);
                 }
-                // File I/O functions
-                "file_read" => {
-                    print!("__athon_file_read(");
-                    if let Some(arg) = args.first() {
-                        emit_expr(arg);
+            }
+            StatementKind::Break => {
+                println!("{}break;", ind);
+            }
+            StatementKind::Continue => {
+                println!("{}continue;", ind);
+            }
+            StatementKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.emit_if(condition, then_block, else_block.as_deref(), indent, None);
+            }
+            StatementKind::While { condition, body } if condition.contains_block() => {
+                // The condition's temporaries are recomputed on every pass
+                println!("{}while (1) {{", ind);
+                self.hoist(condition, indent + 1);
+                print!("{}    if (!(", ind);
+                self.emit_expr(condition);
+                println!(")) {{");
+                println!("{}        break;", ind);
+                println!("{}    }}", ind);
+
+                self.emit_block(body, indent + 1, ScopeKind::Loop);
+
+                println!("{}}}", ind);
+            }
+            StatementKind::While { condition, body } => {
+                print!("{}while (", ind);
+                self.emit_expr(condition);
+                println!(") {{");
+
+                self.emit_block(body, indent + 1, ScopeKind::Loop);
+
+                println!("{}}}", ind);
+            }
+            StatementKind::For {
+                loop_var,
+                start,
+                end,
+                body,
+            } => {
+                // Desugar to C for loop; the bounds can't see the loop variable
+                self.hoist(start, indent);
+                self.hoist(end, indent);
+                let c_name = self.local_c_name(loop_var);
+                print!("{}for (int {} = ", ind, c_name);
+                self.emit_expr(start);
+                print!("; {} < ", c_name);
+                self.emit_expr(end);
+                println!("; {}++) {{", c_name);
+
+                self.locals.push(ScopeKind::Loop);
+                let ty = Some(TypeExpr::named("int"));
+                self.locals.define(loop_var, Local { c_name, ty });
+                self.emit_block(body, indent + 1, ScopeKind::Block);
+                self.locals.pop();
+
+                println!("{}}}", ind);
+            }
+            StatementKind::Match { value, arms } => self.emit_match(value, arms, indent, None),
+            // Used as statements, `if`, `match` and blocks need no temporary
+            StatementKind::Expr(expr) | StatementKind::Tail(expr) => match &expr.kind {
+                ExprKind::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => self.emit_if(condition, then_block, Some(else_block), indent, None),
+                ExprKind::Match { value, arms } => self.emit_match(value, arms, indent, None),
+                ExprKind::Block(stmts) => {
+                    println!("{}{{", ind);
+                    self.emit_block(stmts, indent + 1, ScopeKind::Block);
+                    println!("{}}}", ind);
+                }
+                _ => self.emit_expr_statement(expr, indent),
+            },
+        }
+    }
+
+    fn emit_expr_statement(&mut self, expr: &Expr, indent: usize) {
+        let ind = "    ".repeat(indent);
+        self.hoist(expr, indent);
+        print!("{}", ind);
+        match &expr.kind {
+            ExprKind::Call { name, args } if name == "print" => {
+                if args.is_empty() {
+                    println!("printf(\"\\n\");");
+                } else if args.len() == 1 {
+                    let arg = &args[0];
+                    match &arg.kind {
+                        ExprKind::String(s) => {
+                            let format = escape_string_for_c(s).replace('%', "%%");
+                            println!("printf(\"{}\");", format);
+                        }
+                        ExprKind::Format(parts) => {
+                            print!("printf(");
+                            self.emit_format_args(parts);
+                            println!(");");
+                        }
+                        _ => {
+                            let value = self.format_arg(arg, &FormatSpec::default());
+                            print!("printf(\"{}\", ", value.conversion);
+                            self.emit_format_value(&value);
+                            println!(");");
+                        }
                     }
-                    print!(")");
+                } else {
+                    // The parser folds `print("..{}..", args)` into one
+                    // format argument, and the checker rejects the rest
+                    unreachable!("print with a non-literal format passed the checker");
                 }
-                "file_write" => {
-                    print!("__athon_file_write(");
-                    if args.len() >= 2 {
-                        emit_expr(&args[0]);
-                        print!(", ");
-                        emit_expr(&args[1]);
+            }
+            _ => {
+                self.emit_expr(expr);
+                println!(";");
+            }
+        }
+    }
+
+    // Emit a match as an if-else chain over a temporary holding the value;
+    // each arm's trailing expression is assigned to `target`, if given
+    fn emit_match(&mut self, value: &Expr, arms: &[MatchArm], indent: usize, target: Target) {
+        let ind = "    ".repeat(indent);
+        self.hoist(value, indent);
+        // A union is matched on its tag, enums and bools as ints and
+        // anything else by value
+        let value_type = self.expr_type(value);
+        let resolved = value_type.as_ref().map(|ty| self.resolve_alias(ty).clone());
+        let c_type = match &resolved {
+            Some(ty) if !self.enum_names.contains(self.name_of(ty)) => self.get_c_type(ty),
+            _ => "int".to_string(),
+        };
+
+        // Generate a temporary variable to hold the match value
+        println!("{}{{", ind);
+        print!("{}    {} __match_val = ", ind, c_type);
+        self.emit_expr(value);
+        println!(";");
+
+        // Generate if-else chain for pattern matching
+        let mut first = true;
+
+        for arm in arms {
+            let condition = self.pattern_condition(&arm.pattern, "__match_val", resolved.as_ref());
+            let bindings = self.pattern_bindings(&arm.pattern, "__match_val", resolved.as_ref());
+            let always = condition.is_none() && arm.guard.is_none();
+            match (always, first) {
+                // Wildcard always matches - emit as final else
+                (true, false) => print!("{}    }} else {{", ind),
+                (true, true) => print!("{}    {{", ind),
+                (false, true) => print!("{}    if (", ind),
+                (false, false) => print!("{}    }} else if (", ind),
+            }
+            if !always {
+                if let Some(condition) = &condition {
+                    print!("{}", condition);
+                }
+                if let Some(guard) = &arm.guard {
+                    // The guard reads the bindings straight from the
+                    // matched value, before the arm declares them
+                    if condition.is_some() {
+                        print!(" && ");
                     }
-                    print!(")");
+                    self.locals.push(ScopeKind::MatchArm);
+                    for (name, c_expr, ty) in &bindings {
+                        let local = Local {
+                            c_name: c_expr.clone(),
+                            ty: ty.clone(),
+                        };
+                        self.locals.define(name, local);
+                    }
+                    self.emit_expr(guard);
+                    self.locals.pop();
                 }
-                "file_append" => {
-                    print!("__athon_file_append(");
-                    if args.len() >= 2 {
-                        emit_expr(&args[0]);
+                print!(") {{");
+            }
+            first = false;
+            println!();
+
+            // Bind what the pattern names for the arm
+            self.locals.push(ScopeKind::MatchArm);
+            for (name, c_expr, ty) in bindings {
+                let c_name = self.local_c_name(&name);
+                let c_type = ty
+                    .as_ref()
+                    .map_or("int".to_string(), |t| self.get_c_type(t));
+                println!("{}        {} {} = {};", ind, c_type, c_name, c_expr);
+                self.locals.define(&name, Local { c_name, ty });
+            }
+            self.emit_block_into(&arm.body, indent + 2, ScopeKind::Block, target);
+            self.locals.pop();
+
+            // Later arms can never be reached
+            if always {
+                break;
+            }
+        }
+
+        println!("{}    }}", ind);
+        println!("{}}}", ind);
+    }
+
+    fn emit_expr(&self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Number { value, ty } => print!("{}", c_int_literal(*value, *ty)),
+            ExprKind::Float { value, ty } => print!("{}", c_float_literal(*value, *ty)),
+            ExprKind::Format(parts) => {
+                print!("__athon_format(");
+                self.emit_format_args(parts);
+                print!(")");
+            }
+            ExprKind::Boolean(b) => print!("{}", if *b { 1 } else { 0 }),
+            ExprKind::Char(c) => match c {
+                '\n' => print!("'\\n'"),
+                '\r' => print!("'\\r'"),
+                '\t' => print!("'\\t'"),
+                '\\' => print!("'\\\\'"),
+                '\'' => print!("'\\''"),
+                '\0' => print!("'\\0'"),
+                ' '..='~' => print!("'{}'", c),
+                // Athōn chars are Unicode scalar values (uint32_t in C)
+                _ => print!("0x{:X}", *c as u32),
+            },
+            ExprKind::String(s) => print!("\"{}\"", escape_string_for_c(s)),
+            ExprKind::Variable(name) => match self.locals.get(name) {
+                Ok(local) => print!("{}", local.c_name),
+                // Enum variants and union tags are C globals
+                Err(_) => print!("{}", name),
+            },
+            // Elements have the type of the first, which may be a trait object
+            ExprKind::ArrayLiteral(elements) => match self.expr_type(expr) {
+                Some(ty) => self.emit_array(elements, &ty),
+                None => self.emit_elements(elements, None),
+            },
+            ExprKind::Tuple(elements) => {
+                let ty = self
+                    .expr_type(expr)
+                    .unwrap_or_else(|| TypeExpr::named("int"));
+                self.emit_tuple(elements, &ty);
+            }
+            ExprKind::ArrayIndex { array, index } => {
+                self.emit_expr(array);
+                if self.is_array(array) {
+                    print!(".items");
+                }
+                print!("[");
+                self.emit_expr(index);
+                print!("]");
+            }
+            ExprKind::StructLiteral {
+                struct_name,
+                fields,
+            } => {
+                print!("(struct {}) {{", struct_name);
+                for (i, (field_name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
                         print!(", ");
-                        emit_expr(&args[1]);
                     }
-                    print!(")");
+                    print!(".{} = ", field_name);
+                    let field_type = self
+                        .struct_fields
+                        .get(struct_name)
+                        .and_then(|fields| fields.iter().find(|(name, _)| name == field_name));
+                    self.emit_coerced(value, field_type.map(|(_, ty)| ty));
                 }
-                "file_exists" => {
-                    print!("__athon_file_exists(");
-                    if let Some(arg) = args.first() {
-                        emit_expr(arg);
-                    }
-                    print!(")");
+                print!("}}");
+            }
+            ExprKind::MemberAccess { object, member } => {
+                self.emit_expr(object);
+                print!(".{}", member_c_name(member));
+            }
+            ExprKind::EnumVariant {
+                enum_name: _,
+                variant,
+            } => {
+                // In C, just use the variant name
+                print!("{}", variant);
+            }
+            ExprKind::UnionVariant {
+                union_name,
+                variant,
+                value,
+            } => {
+                let union_type = TypeExpr::named(union_name);
+                let union_name = self.name_of(&union_type);
+                print!(
+                    "(struct {}) {{.tag = {}_Tag_{}",
+                    union_name, union_name, variant
+                );
+                if let Some(value) = value {
+                    print!(", .data.{} = ", variant);
+                    self.emit_expr(value);
                 }
-                _ => {
-                    // Regular function call
-                    print!("{}(", name);
-                    for (i, arg) in args.iter().enumerate() {
-                        if i > 0 {
+                print!("}}");
+            }
+            // `a && b` hoisted to keep `b` from running early
+            ExprKind::Binary { .. } if self.values.contains_key(&(expr as *const Expr)) => {
+                print!("{}", self.values[&(expr as *const Expr)].0);
+            }
+            // `s + t` on strings appends into a new string
+            ExprKind::Binary {
+                left,
+                op: BinOp::Add,
+                right,
+            } if self
+                .expr_type(left)
+                .is_some_and(|ty| self.name_of(&ty) == "string") =>
+            {
+                print!("__athon_format(\"%s%s\", ");
+                self.emit_expr(left);
+                print!(", ");
+                self.emit_expr(right);
+                print!(")");
+            }
+            // `s == t` on strings compares their contents
+            ExprKind::Binary {
+                left,
+                op: op @ (BinOp::Eq | BinOp::NotEq),
+                right,
+            } if [left, right].iter().any(|e| {
+                self.expr_type(e)
+                    .is_some_and(|ty| self.name_of(&ty) == "string")
+            }) =>
+            {
+                print!("(strcmp(");
+                self.emit_expr(left);
+                print!(", ");
+                self.emit_expr(right);
+                print!("){}0)", binop_str(*op));
+            }
+            ExprKind::Binary { left, op, right } => {
+                print!("(");
+                self.emit_expr(left);
+                print!("{}", binop_str(*op));
+                self.emit_expr(right);
+                print!(")");
+            }
+            ExprKind::Unary { op, operand } => {
+                if let (
+                    UnaryOp::Neg,
+                    ExprKind::Number {
+                        value,
+                        ty: Some(ty),
+                    },
+                ) = (op, &operand.kind)
+                {
+                    print!("{}", c_signed_int_literal("-", *value, Some(*ty)));
+                    return;
+                }
+                match op {
+                    UnaryOp::Not => print!("!"),
+                    UnaryOp::Neg => print!("-"),
+                    UnaryOp::BitNot => print!("~"),
+                }
+                self.emit_expr(operand);
+            }
+            ExprKind::Call { name, args } => {
+                // Handle built-in functions
+                match name.as_str() {
+                    "length" => {
+                        // C strlen function
+                        print!("strlen(");
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!(")");
+                    }
+                    "concat" => {
+                        // String concatenation using strcat
+                        // Note: This is unsafe in real code, needs buffer management
+                        print!("strcat(");
+                        if args.len() >= 2 {
+                            self.emit_expr(&args[0]);
                             print!(", ");
+                            self.emit_expr(&args[1]);
                         }
-                        emit_expr(arg);
+                        print!(")");
+                    }
+                    "compare" => {
+                        // String comparison using strcmp
+                        print!("strcmp(");
+                        if args.len() >= 2 {
+                            self.emit_expr(&args[0]);
+                            print!(", ");
+                            self.emit_expr(&args[1]);
+                        }
+                        print!(")");
+                    }
+                    "array_length" => {
+                        // Array length - compile-time only
+                        let ty = args.first().and_then(|arg| self.expr_type(arg));
+                        if let Some(TypeExpr::Array(_, len)) =
+                            ty.as_ref().map(|t| self.resolve_alias(t))
+                        {
+                            print!("{}", len);
+                            return;
+                        }
+                        // A slice of a literal is a C array: sizeof(arr)/sizeof(arr[0])
+                        print!("(sizeof(");
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!(") / sizeof((");
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!(")[0]))");
+                    }
+                    "substring" => {
+                        // Extract substring: substring(str, start, length)
+                        print!("__athon_substring(");
+                        if args.len() >= 3 {
+                            self.emit_expr(&args[0]);
+                            print!(", ");
+                            self.emit_expr(&args[1]);
+                            print!(", ");
+                            self.emit_expr(&args[2]);
+                        }
+                        print!(")");
+                    }
+                    // Math functions
+                    "abs" => {
+                        let is_float = args.first().is_some_and(|arg| self.is_float_expr(arg));
+                        print!("{}(", if is_float { "__athon_fabs" } else { "abs" });
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!(")");
+                    }
+                    "min" => {
+                        print!("((");
+                        if args.len() >= 2 {
+                            self.emit_expr(&args[0]);
+                            print!(") < (");
+                            self.emit_expr(&args[1]);
+                            print!(") ? (");
+                            self.emit_expr(&args[0]);
+                            print!(") : (");
+                            self.emit_expr(&args[1]);
+                        }
+                        print!("))");
+                    }
+                    "max" => {
+                        print!("((");
+                        if args.len() >= 2 {
+                            self.emit_expr(&args[0]);
+                            print!(") > (");
+                            self.emit_expr(&args[1]);
+                            print!(") ? (");
+                            self.emit_expr(&args[0]);
+                            print!(") : (");
+                            self.emit_expr(&args[1]);
+                        }
+                        print!("))");
+                    }
+                    "pow" => {
+                        // Generate inline power calculation
+                        print!("__athon_pow(");
+                        if args.len() >= 2 {
+                            self.emit_expr(&args[0]);
+                            print!(", ");
+                            self.emit_expr(&args[1]);
+                        }
+                        print!(")");
+                    }
+                    "sqrt" => {
+                        // Integer square root unless the argument is a float
+                        let is_float = args.first().is_some_and(|arg| self.is_float_expr(arg));
+                        print!(
+                            "{}(",
+                            if is_float {
+                                "__athon_fsqrt"
+                            } else {
+                                "__athon_sqrt"
+                            }
+                        );
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!(")");
+                    }
+                    "mod" => {
+                        print!("((");
+                        if args.len() >= 2 {
+                            self.emit_expr(&args[0]);
+                            print!(") % (");
+                            self.emit_expr(&args[1]);
+                        }
+                        print!("))");
+                    }
+                    // File I/O functions
+                    "file_read" => {
+                        print!("__athon_file_read(");
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!(")");
+                    }
+                    "file_write" => {
+                        print!("__athon_file_write(");
+                        if args.len() >= 2 {
+                            self.emit_expr(&args[0]);
+                            print!(", ");
+                            self.emit_expr(&args[1]);
+                        }
+                        print!(")");
+                    }
+                    "file_append" => {
+                        print!("__athon_file_append(");
+                        if args.len() >= 2 {
+                            self.emit_expr(&args[0]);
+                            print!(", ");
+                            self.emit_expr(&args[1]);
+                        }
+                        print!(")");
+                    }
+                    "file_exists" => {
+                        print!("__athon_file_exists(");
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!(")");
+                    }
+                    // Capabilities are checked at compile time; at runtime
+                    // they are placeholder tokens
+                    "cap_split" | "cap_attenuate" => {
+                        print!("((void)(");
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!("), 0)");
+                    }
+                    "drop" | "cap_revoke" => {
+                        // Consuming a capability is a no-op at runtime
+                        print!("(void)(");
+                        if let Some(arg) = args.first() {
+                            self.emit_expr(arg);
+                        }
+                        print!(")");
+                    }
+                    _ => {
+                        // Regular function call, a call through a local
+                        // holding a function, or a trait method called by
+                        // name, statically or through a trait object
+                        let param_types =
+                            if let Some((c_name, params, _)) = self.function_local(name) {
+                                print!("{}(", c_name);
+                                params.to_vec()
+                            } else if let Some((trait_name, method)) = self.dyn_method(name, args) {
+                                print!("__athon_dyn_{}_{}(", trait_name, method.name);
+                                method.params.iter().map(|p| p.type_name.clone()).collect()
+                            } else if let Some(method) = self.impl_method(name, args) {
+                                print!("{}(", method.c_name);
+                                method.param_types.clone()
+                            } else {
+                                print!("{}(", name);
+                                self.function_params.get(name).cloned().unwrap_or_default()
+                            };
+                        for (i, arg) in args.iter().enumerate() {
+                            if i > 0 {
+                                print!(", ");
+                            }
+                            self.emit_coerced(arg, param_types.get(i));
+                        }
+                        print!(")");
                     }
-                    print!(")");
                 }
             }
+            ExprKind::MethodCall { .. } => unreachable!("method calls are lowered by mono"),
+            ExprKind::If { .. } | ExprKind::Match { .. } | ExprKind::Block(_) => {
+                let (name, _) = self
+                    .values
+                    .get(&(expr as *const Expr))
+                    .expect("block-like expressions are hoisted before their statement");
+                print!("{}", name);
+            }
         }
     }
 }
@@ -0,0 +1,199 @@
+// Match coverage for Athōn bootstrap compiler
+// Tracks which values of a matched type the arms seen so far leave
+// uncovered. Enums, unions and bools have a finite set of variants; an
+// integer type is a set of disjoint ranges between its minimum and maximum;
+// any other type is only covered by a catch-all. An arm whose pattern
+// matches nothing still uncovered is unreachable, and whatever is left after
+// the last arm is what the match is missing.
+
+use crate::ast::IntType;
+
+/// Values matched by one pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum Covers {
+    /// `_` and other catch-alls
+    All,
+    /// A variant by name: `Red`, `Some`, `true`
+    Variant(String),
+    /// The integers `lo..=hi`
+    Range(i128, i128),
+    /// Some values of a type, such as one string, that never add up to all
+    /// of them
+    Part,
+}
+
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    remaining: Remaining,
+}
+
+#[derive(Debug, Clone)]
+enum Remaining {
+    /// Uncovered variants, with how to write each in a pattern
+    Variants(Vec<(String, String)>),
+    /// Uncovered integers as sorted, disjoint `lo..=hi` ranges
+    Integers(Vec<(i128, i128)>),
+    /// Values only a catch-all covers
+    Open { covered: bool },
+}
+
+/// Number of missing ranges listed before the rest are only counted
+const LISTED_RANGES: usize = 4;
+
+impl Coverage {
+    /// Variants given as `(name, pattern)`, e.g. `("Some", "Some(_)")`
+    pub fn variants(variants: Vec<(String, String)>) -> Self {
+        Coverage {
+            remaining: Remaining::Variants(variants),
+        }
+    }
+
+    pub fn integers(ty: IntType) -> Self {
+        let max = ty.max_value() as i128;
+        let min = if ty.is_signed() { -max - 1 } else { 0 };
+        Coverage {
+            remaining: Remaining::Integers(vec![(min, max)]),
+        }
+    }
+
+    pub fn open() -> Self {
+        Coverage {
+            remaining: Remaining::Open { covered: false },
+        }
+    }
+
+    /// Whether `covers` matches any value not yet covered
+    pub fn overlaps(&self, covers: &Covers) -> bool {
+        match (&self.remaining, covers) {
+            (Remaining::Open { covered }, _) => !covered,
+            (Remaining::Variants(left), Covers::All) => !left.is_empty(),
+            (Remaining::Variants(left), Covers::Variant(name)) => {
+                left.iter().any(|(variant, _)| variant == name)
+            }
+            (Remaining::Integers(left), Covers::All) => !left.is_empty(),
+            (Remaining::Integers(left), Covers::Range(lo, hi)) => {
+                left.iter().any(|&(start, end)| start <= *hi && *lo <= end)
+            }
+            // A pattern of the wrong kind is reported by the type checker
+            _ => true,
+        }
+    }
+
+    /// Mark the values `covers` matches as covered. Returns false when they
+    /// all already were, which makes the arm unreachable.
+    pub fn cover(&mut self, covers: &Covers) -> bool {
+        let reachable = self.overlaps(covers);
+        match (&mut self.remaining, covers) {
+            (Remaining::Open { covered }, Covers::All) => *covered = true,
+            (Remaining::Variants(left), Covers::All) => left.clear(),
+            (Remaining::Variants(left), Covers::Variant(name)) => {
+                left.retain(|(variant, _)| variant != name)
+            }
+            (Remaining::Integers(left), Covers::All) => left.clear(),
+            (Remaining::Integers(left), &Covers::Range(lo, hi)) => {
+                let mut split = Vec::new();
+                for &(start, end) in left.iter() {
+                    if end < lo || hi < start {
+                        split.push((start, end));
+                        continue;
+                    }
+                    if start < lo {
+                        split.push((start, lo - 1));
+                    }
+                    if hi < end {
+                        split.push((hi + 1, end));
+                    }
+                }
+                *left = split;
+            }
+            _ => {}
+        }
+        reachable
+    }
+
+    /// Patterns for the values no arm covers, empty when the match is
+    /// exhaustive
+    pub fn missing(&self) -> Vec<String> {
+        match &self.remaining {
+            Remaining::Open { covered: true } => Vec::new(),
+            Remaining::Open { covered: false } => vec!["_".to_string()],
+            Remaining::Variants(left) => left.iter().map(|(_, pattern)| pattern.clone()).collect(),
+            Remaining::Integers(left) => {
+                let mut missing: Vec<String> = left
+                    .iter()
+                    .take(LISTED_RANGES)
+                    .map(|&(lo, hi)| {
+                        if lo == hi {
+                            lo.to_string()
+                        } else {
+                            format!("{}..={}", lo, hi)
+                        }
+                    })
+                    .collect();
+                if left.len() > LISTED_RANGES {
+                    missing.push(format!("{} more ranges", left.len() - LISTED_RANGES));
+                }
+                missing
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(name: &str) -> Covers {
+        Covers::Variant(name.to_string())
+    }
+
+    #[test]
+    fn test_variant_coverage() {
+        let variants = ["Some", "None"]
+            .iter()
+            .map(|v| (v.to_string(), format!("Option::{}", v)))
+            .collect();
+        let mut coverage = Coverage::variants(variants);
+        assert!(coverage.cover(&variant("None")));
+        assert!(!coverage.cover(&variant("None")));
+        assert_eq!(coverage.missing(), ["Option::Some"]);
+        assert!(coverage.cover(&Covers::All));
+        assert!(coverage.missing().is_empty());
+        assert!(!coverage.cover(&Covers::All));
+    }
+
+    #[test]
+    fn test_integer_coverage() {
+        let mut coverage = Coverage::integers(IntType::U8);
+        assert!(coverage.cover(&Covers::Range(0, 0)));
+        assert!(coverage.cover(&Covers::Range(10, 255)));
+        assert!(!coverage.cover(&Covers::Range(200, 210)));
+        assert!(coverage.cover(&Covers::Range(5, 5)));
+        assert_eq!(coverage.missing(), ["1..=4", "6..=9"]);
+
+        let mut coverage = Coverage::integers(IntType::I8);
+        for n in [-100, -50, 0, 50, 100] {
+            coverage.cover(&Covers::Range(n, n));
+        }
+        assert_eq!(
+            coverage.missing(),
+            [
+                "-128..=-101",
+                "-99..=-51",
+                "-49..=-1",
+                "1..=49",
+                "2 more ranges"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_open_coverage() {
+        let mut coverage = Coverage::open();
+        assert!(coverage.cover(&Covers::Range(1, 1)));
+        assert_eq!(coverage.missing(), ["_"]);
+        assert!(coverage.cover(&Covers::All));
+        assert!(!coverage.cover(&Covers::Range(2, 2)));
+        assert!(coverage.missing().is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+// Diagnostics for Athōn bootstrap compiler
+// Every phase reports problems as `Diagnostic` values instead of exiting,
+// so the driver decides how to render them and how many to collect.
+// Locations are byte spans; `SourceMap::render` turns them into file:line:col.
+
+use crate::source_map::Span;
+use std::fmt;
+
+// Error codes
+// E01xx: lexical and syntax errors
+pub const E_EXPECTED_TOKEN: &str = "E0100";
+pub const E_EXPECTED_NAME: &str = "E0101";
+pub const E_EXPECTED_TYPE: &str = "E0102";
+pub const E_EXPECTED_EXPR: &str = "E0103";
+pub const E_EXPECTED_PATTERN: &str = "E0104";
+pub const E_EXPECTED_ITEM: &str = "E0105";
+pub const E_UNTERMINATED_LITERAL: &str = "E0106";
+pub const E_IMPORT_FAILED: &str = "E0107";
+pub const E_INVALID_LITERAL: &str = "E0108";
+pub const E_INVALID_ESCAPE: &str = "E0109";
+pub const E_FORMAT_ARGS: &str = "E0110";
+pub const E_FORMAT_SPEC: &str = "E0111";
+pub const E_INVALID_ASSIGN_TARGET: &str = "E0112";
+
+// W01xx: syntax warnings
+pub const W_UNUSED_FORMAT_ARGS: &str = "W0100";
+
+// E02xx: semantic errors
+pub const E_ASSIGN_IMMUTABLE: &str = "E0200";
+pub const E_UNDEFINED_NAME: &str = "E0201";
+pub const E_UNDEFINED_TYPE: &str = "E0202";
+pub const E_ARITY_MISMATCH: &str = "E0203";
+pub const E_TYPE_MISMATCH: &str = "E0204";
+pub const E_UNKNOWN_MEMBER: &str = "E0205";
+pub const E_MISSING_FIELD: &str = "E0206";
+pub const E_USE_BEFORE_DEFINITION: &str = "E0207";
+pub const E_BREAK_OUTSIDE_LOOP: &str = "E0208";
+pub const E_USE_AFTER_MOVE: &str = "E0209";
+pub const E_MOVE_IN_LOOP: &str = "E0210";
+pub const E_LINEAR_LEAK: &str = "E0211";
+pub const E_MISSING_CAPABILITY: &str = "E0212";
+pub const E_INVALID_MAIN: &str = "E0213";
+pub const E_CANNOT_INFER: &str = "E0214";
+pub const E_UNSATISFIED_BOUND: &str = "E0215";
+pub const E_IMPL_MISMATCH: &str = "E0216";
+pub const E_NON_EXHAUSTIVE: &str = "E0217";
+pub const E_INVALID_PATTERN: &str = "E0218";
+pub const E_CANNOT_FORMAT: &str = "E0219";
+
+// W02xx: semantic warnings
+pub const W_UNUSED_BINDING: &str = "W0200";
+pub const W_UNREACHABLE_ARM: &str = "W0201";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String, span: Span) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message,
+            span,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: String, span: Span) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code,
+            message,
+            span,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}[{}]: {}", severity, self.code, self.message)
+    }
+}
+
+/// Returns true if any diagnostic in the list is an error
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.is_error())
+}
@@ -0,0 +1,238 @@
+// Generic types for Athōn bootstrap compiler
+// Instantiating a generic struct or function binds its type parameters to
+// the types of the values it is used with. These work on the parser's
+// `TypeExpr`: `Box<Pair<int, T>>`, `(int, string)`, `dyn Shape`, and
+// pointer, slice, array and function types `*T`, `[]T`, `[T; 4]` and
+// `fn(T) -> U`.
+
+use crate::ast::TypeExpr;
+use std::collections::HashMap;
+
+/// Type parameter bindings of one instantiation, e.g. `T` -> `int`
+pub type Bindings = HashMap<String, TypeExpr>;
+
+/// `ty` with every bound type parameter replaced by its argument
+pub fn substitute(ty: &TypeExpr, bindings: &Bindings) -> TypeExpr {
+    let all = |types: &[TypeExpr]| types.iter().map(|ty| substitute(ty, bindings)).collect();
+    match ty {
+        TypeExpr::Named { name, args } if args.is_empty() => match bindings.get(name) {
+            Some(bound) => bound.clone(),
+            None => ty.clone(),
+        },
+        TypeExpr::Named { name, args } => TypeExpr::Named {
+            name: name.clone(),
+            args: all(args),
+        },
+        TypeExpr::Pointer(target) => TypeExpr::Pointer(Box::new(substitute(target, bindings))),
+        TypeExpr::Slice(element) => TypeExpr::Slice(Box::new(substitute(element, bindings))),
+        TypeExpr::Array(element, len) => {
+            TypeExpr::Array(Box::new(substitute(element, bindings)), *len)
+        }
+        TypeExpr::Function {
+            params,
+            return_type,
+        } => TypeExpr::Function {
+            params: all(params),
+            return_type: return_type
+                .as_ref()
+                .map(|ty| Box::new(substitute(ty, bindings))),
+        },
+        TypeExpr::Tuple(elements) => TypeExpr::Tuple(all(elements)),
+        TypeExpr::Dyn(_) => ty.clone(),
+    }
+}
+
+/// Match a declared type mentioning `params` against the type of a value,
+/// binding the parameters it fixes. A parameter keeps its first binding;
+/// conflicts are left for the caller's type check to report.
+pub fn unify(pattern: &TypeExpr, actual: &TypeExpr, params: &[String], bindings: &mut Bindings) {
+    let mut all = |patterns: &[TypeExpr], actuals: &[TypeExpr]| {
+        if patterns.len() == actuals.len() {
+            for (pattern, actual) in patterns.iter().zip(actuals) {
+                unify(pattern, actual, params, bindings);
+            }
+        }
+    };
+    match (pattern, actual) {
+        (TypeExpr::Named { name, args }, _) if args.is_empty() && params.contains(name) => {
+            bindings
+                .entry(name.clone())
+                .or_insert_with(|| actual.clone());
+        }
+        (
+            TypeExpr::Named { name, args },
+            TypeExpr::Named {
+                name: actual_name,
+                args: actual_args,
+            },
+        ) if name == actual_name => all(args, actual_args),
+        (TypeExpr::Tuple(elements), TypeExpr::Tuple(actual_elements)) => {
+            all(elements, actual_elements)
+        }
+        (TypeExpr::Pointer(pattern), TypeExpr::Pointer(actual))
+        | (TypeExpr::Slice(pattern), TypeExpr::Slice(actual)) => {
+            unify(pattern, actual, params, bindings)
+        }
+        // An array is passed where a slice of its elements is expected
+        (TypeExpr::Slice(pattern), TypeExpr::Array(actual, _)) => {
+            unify(pattern, actual, params, bindings)
+        }
+        (TypeExpr::Array(pattern, len), TypeExpr::Array(actual, actual_len))
+            if len == actual_len =>
+        {
+            unify(pattern, actual, params, bindings)
+        }
+        (
+            TypeExpr::Function {
+                params: pattern_params,
+                return_type,
+            },
+            TypeExpr::Function {
+                params: actual_params,
+                return_type: actual_return,
+            },
+        ) => {
+            all(pattern_params, actual_params);
+            if let (Some(pattern), Some(actual)) = (return_type, actual_return) {
+                unify(pattern, actual, params, bindings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Whether `ty` still refers to any of `params`
+pub fn mentions(ty: &TypeExpr, params: &[String]) -> bool {
+    let any = |types: &[TypeExpr]| types.iter().any(|ty| mentions(ty, params));
+    match ty {
+        TypeExpr::Named { name, args } => params.contains(name) || any(args),
+        TypeExpr::Pointer(inner) | TypeExpr::Slice(inner) | TypeExpr::Array(inner, _) => {
+            mentions(inner, params)
+        }
+        TypeExpr::Function {
+            params: types,
+            return_type,
+        } => any(types) || return_type.as_ref().is_some_and(|ty| mentions(ty, params)),
+        TypeExpr::Tuple(elements) => any(elements),
+        TypeExpr::Dyn(_) => false,
+    }
+}
+
+/// C name of a concrete type: `Pair<int, Box<string>>` is `Pair_int_Box_string`,
+/// `(int, string)` is `Tuple_int_string` and `fn(*u8) -> bool` is
+/// `Fn_Ptr_u8_to_bool`
+pub fn mangle(ty: &TypeExpr) -> String {
+    let join = |prefix: &str, types: &[TypeExpr]| {
+        let parts = types.iter().map(mangle_part);
+        std::iter::once(prefix.to_string())
+            .chain(parts)
+            .collect::<Vec<_>>()
+            .join("_")
+    };
+    match ty {
+        TypeExpr::Named { name, args } => join(name, args),
+        TypeExpr::Pointer(target) => join("Ptr", std::slice::from_ref(target)),
+        TypeExpr::Slice(element) => join("Slice", std::slice::from_ref(element)),
+        TypeExpr::Array(element, len) => {
+            format!("{}_{}", join("Array", std::slice::from_ref(element)), len)
+        }
+        TypeExpr::Function {
+            params,
+            return_type,
+        } => match return_type {
+            Some(ty) => format!("{}_to_{}", join("Fn", params), mangle_part(ty)),
+            None => join("Fn", params),
+        },
+        TypeExpr::Tuple(elements) => join("Tuple", elements),
+        TypeExpr::Dyn(trait_name) => format!("dyn {}", trait_name),
+    }
+}
+
+/// `mangle` for use inside a longer C name, where `dyn Shape` is `dyn_Shape`
+pub fn mangle_part(ty: &TypeExpr) -> String {
+    mangle(ty).replace(' ', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn ty(source: &str) -> TypeExpr {
+        let program = Parser::new(&format!("type T = {};", source))
+            .parse_program()
+            .unwrap();
+        program.type_aliases[0].target_type.clone()
+    }
+
+    #[test]
+    fn test_generic_type_names() {
+        let params = ["T".to_string(), "U".to_string()];
+        let mut bindings = Bindings::new();
+        unify(
+            &ty("Box<Pair<T, U>>"),
+            &ty("Box<Pair<int, string>>"),
+            &params,
+            &mut bindings,
+        );
+        assert_eq!(bindings["T"], ty("int"));
+        assert_eq!(bindings["U"], ty("string"));
+        assert_eq!(
+            substitute(&ty("Pair<U, Box<T>>"), &bindings),
+            ty("Pair<string, Box<int>>")
+        );
+
+        assert!(mentions(&ty("Box<Pair<int, U>>"), &params));
+        assert!(!mentions(&ty("Box<int>"), &params));
+        assert_eq!(mangle(&ty("Box<Pair<int, string>>")), "Box_Pair_int_string");
+        assert_eq!(mangle(&ty("Box<dyn Shape>")), "Box_dyn_Shape");
+
+        unify(
+            &ty("(T, Box<U>)"),
+            &ty("(int, Box<string>)"),
+            &params,
+            &mut bindings,
+        );
+        assert_eq!(
+            substitute(&ty("(U, (T, U))"), &bindings),
+            ty("(string, (int, string))")
+        );
+        assert_eq!(
+            mangle(&ty("(int, Pair<int, string>)")),
+            "Tuple_int_Pair_int_string"
+        );
+    }
+
+    #[test]
+    fn test_pointer_slice_array_and_function_types() {
+        let params = ["T".to_string(), "U".to_string()];
+        let mut bindings = Bindings::new();
+        unify(
+            &ty("*Signal<T>"),
+            &ty("*Signal<(int, bool)>"),
+            &params,
+            &mut bindings,
+        );
+        unify(
+            &ty("fn(T) -> U"),
+            &ty("fn((int, bool)) -> []u8"),
+            &params,
+            &mut bindings,
+        );
+        assert_eq!(bindings["T"], ty("(int, bool)"));
+        assert_eq!(bindings["U"], ty("[]u8"));
+        let substituted = substitute(&ty("[]fn(T) -> [U; 4]"), &bindings);
+        assert_eq!(substituted.to_string(), "[]fn((int, bool)) -> [[]u8; 4]");
+        assert!(mentions(&ty("[]fn(T)"), &params));
+        assert!(!mentions(&ty("*Signal<int>"), &params));
+        assert_eq!(
+            mangle(&ty("fn(*u8, [int; 4]) -> Slice<T>")),
+            "Fn_Ptr_u8_Array_int_4_to_Slice_T"
+        );
+
+        // An array argument fixes the element type of a slice parameter
+        let mut bindings = Bindings::new();
+        unify(&ty("[]T"), &ty("[u8; 3]"), &params, &mut bindings);
+        assert_eq!(bindings["T"], ty("u8"));
+    }
+}
@@ -1,9 +1,14 @@
 // Lexer module for Athōn bootstrap compiler
 
+use crate::ast::{FloatType, IntType};
+use crate::diagnostics::{Diagnostic, E_INVALID_ESCAPE, E_INVALID_LITERAL, E_UNTERMINATED_LITERAL};
+use crate::source_map::{FileId, Span};
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum TokenKind {
     Fn,
     Let,
+    Mut,
     If,
     Else,
     While,
@@ -24,6 +29,7 @@ pub enum TokenKind {
     Pub,
     Identifier,
     Number,
+    Float,
     LParen,
     RParen,
     LBrace,
@@ -36,6 +42,7 @@ pub enum TokenKind {
     DoubleColon,
     Dot,
     DotDot,
+    DotDotEquals,
     Equals,
     EqualsEquals,
     NotEquals,
@@ -47,6 +54,17 @@ pub enum TokenKind {
     Minus,
     Star,
     Slash,
+    Percent,
+    Ampersand,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+    PlusEquals,
+    MinusEquals,
+    StarEquals,
+    SlashEquals,
+    PercentEquals,
     And,
     Or,
     Not,
@@ -54,6 +72,13 @@ pub enum TokenKind {
     FatArrow,
     Pipe,
     StringLiteral,
+    // Pieces of an interpolated string "a{x}b{y}c": InterpStart("a"),
+    // tokens of x, InterpMiddle("b"), tokens of y, InterpEnd("c")
+    InterpStart,
+    InterpMiddle,
+    InterpEnd,
+    // `:spec` at the end of a hole, as in "{x:>8}"; the text excludes ':'
+    FormatSpec,
     CharLiteral,
     Underscore,
     EOF,
@@ -66,75 +91,107 @@ pub struct Token {
     pub text: String,
     pub line: usize,
     pub column: usize,
+    pub span: Span,
+}
+
+/// An interpolated string whose `{...}` hole is being lexed
+#[derive(Clone, Debug)]
+pub struct Interpolation {
+    // Unclosed '{' inside the hole; the hole ends at a '}' at depth 0
+    pub depth: usize,
+    // Whether the string is triple-quoted
+    pub triple: bool,
 }
 
 pub struct Lexer {
-    pub input: Vec<u8>,
+    pub input: String,
+    // Byte offset of the next character
     pub pos: usize,
     pub line: usize,
+    // 1-based and counted in characters, not bytes
     pub column: usize,
+    pub file: FileId,
+    pub diagnostics: Vec<Diagnostic>,
+    // Innermost last; holes may themselves contain interpolated strings
+    pub interpolations: Vec<Interpolation>,
+    // Where the token currently being lexed starts
+    token_line: usize,
+    token_column: usize,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
+        Self::with_file(input, FileId::default())
+    }
+
+    pub fn with_file(input: &str, file: FileId) -> Self {
         Self {
-            input: input.as_bytes().to_vec(),
+            input: input.to_string(),
             pos: 0,
             line: 1,
             column: 1,
+            file,
+            diagnostics: Vec::new(),
+            interpolations: Vec::new(),
+            token_line: 1,
+            token_column: 1,
         }
     }
 
-    fn peek(&self) -> u8 {
-        if self.pos >= self.input.len() {
-            0
-        } else {
-            self.input[self.pos]
-        }
+    fn error(&mut self, code: &'static str, message: String, start: usize) {
+        let span = self.span_from(start);
+        self.diagnostics
+            .push(Diagnostic::error(code, message, span));
     }
 
-    fn peek_ahead(&self, offset: usize) -> u8 {
-        let pos = self.pos + offset;
-        if pos >= self.input.len() {
-            0
-        } else {
-            self.input[pos]
-        }
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(self.file, start, self.pos)
+    }
+
+    // '\0' doubles as the end-of-input marker
+    fn peek(&self) -> char {
+        self.peek_ahead(0)
+    }
+
+    fn peek_ahead(&self, offset: usize) -> char {
+        self.input[self.pos..].chars().nth(offset).unwrap_or('\0')
     }
 
     fn advance(&mut self) {
-        if self.pos < self.input.len() && self.input[self.pos] == b'\n' {
-            self.line += 1;
-            self.column = 1;
-        } else {
-            self.column += 1;
+        if let Some(c) = self.input[self.pos..].chars().next() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            self.pos += c.len_utf8();
         }
-        self.pos += 1;
     }
 
     pub fn next_token(&mut self) -> Token {
         loop {
             // Skip whitespace
-            while matches!(self.peek(), b' ' | b'\n' | b'\t' | b'\r') {
+            while matches!(self.peek(), ' ' | '\n' | '\t' | '\r') {
                 self.advance();
             }
 
             // Skip comments
-            if self.peek() == b'/' {
-                if self.peek_ahead(1) == b'/' {
+            if self.peek() == '/' {
+                if self.peek_ahead(1) == '/' {
                     // Single-line comment
                     self.advance();
                     self.advance();
-                    while self.peek() != 0 && self.peek() != b'\n' {
+                    while self.peek() != '\0' && self.peek() != '\n' {
                         self.advance();
                     }
                     continue;
-                } else if self.peek_ahead(1) == b'*' {
+                } else if self.peek_ahead(1) == '*' {
                     // Multi-line comment
                     self.advance();
                     self.advance();
-                    while self.peek() != 0 {
-                        if self.peek() == b'*' && self.peek_ahead(1) == b'/' {
+                    while self.peek() != '\0' {
+                        if self.peek() == '*' && self.peek_ahead(1) == '/' {
                             self.advance();
                             self.advance();
                             break;
@@ -149,333 +206,864 @@ impl Lexer {
         }
 
         let start = self.pos;
-        let c = self.peek();
-
-        if c == 0 {
-            return Token {
-                kind: TokenKind::EOF,
-                text: String::new(),
-                line: self.line,
-                column: self.column,
-            };
-        }
+        self.token_line = self.line;
+        self.token_column = self.column;
 
-        match c {
-            b'{' => {
-                self.advance();
-                self.make_token(TokenKind::LBrace, start)
-            }
-            b'}' => {
-                self.advance();
-                self.make_token(TokenKind::RBrace, start)
-            }
-            b'[' => {
-                self.advance();
-                self.make_token(TokenKind::LBracket, start)
+        match self.peek() {
+            '\0' => self.make_token(TokenKind::EOF, start),
+            '{' => {
+                if let Some(hole) = self.interpolations.last_mut() {
+                    hole.depth += 1;
+                }
+                self.single(TokenKind::LBrace, start)
             }
-            b']' => {
+            '}' => match self.interpolations.last_mut() {
+                Some(hole) if hole.depth == 0 => {
+                    // End of a hole: carry on with the rest of the string
+                    let triple = hole.triple;
+                    self.advance();
+                    self.lex_string_part(start, triple, false)
+                }
+                Some(hole) => {
+                    hole.depth -= 1;
+                    self.single(TokenKind::RBrace, start)
+                }
+                None => self.single(TokenKind::RBrace, start),
+            },
+            '[' => self.single(TokenKind::LBracket, start),
+            ']' => self.single(TokenKind::RBracket, start),
+            '(' => self.single(TokenKind::LParen, start),
+            ')' => self.single(TokenKind::RParen, start),
+            ';' => self.single(TokenKind::Semicolon, start),
+            ',' => self.single(TokenKind::Comma, start),
+            '+' => self.one_or_two('=', TokenKind::Plus, TokenKind::PlusEquals, start),
+            '*' => self.one_or_two('=', TokenKind::Star, TokenKind::StarEquals, start),
+            '/' => self.one_or_two('=', TokenKind::Slash, TokenKind::SlashEquals, start),
+            '%' => self.one_or_two('=', TokenKind::Percent, TokenKind::PercentEquals, start),
+            '^' => self.single(TokenKind::Caret, start),
+            '~' => self.single(TokenKind::Tilde, start),
+            ':' if self.peek_ahead(1) != ':' && self.at_hole_level() => self.lex_format_spec(start),
+            ':' => self.one_or_two(':', TokenKind::Colon, TokenKind::DoubleColon, start),
+            '!' => self.one_or_two('=', TokenKind::Not, TokenKind::NotEquals, start),
+            '<' if self.peek_ahead(1) == '<' => {
                 self.advance();
-                self.make_token(TokenKind::RBracket, start)
+                self.single(TokenKind::ShiftLeft, start)
             }
-            b'(' => {
+            '<' => self.one_or_two('=', TokenKind::LessThan, TokenKind::LessEquals, start),
+            '>' if self.peek_ahead(1) == '>' => {
                 self.advance();
-                self.make_token(TokenKind::LParen, start)
+                self.single(TokenKind::ShiftRight, start)
             }
-            b')' => {
+            '>' => self.one_or_two('=', TokenKind::GreaterThan, TokenKind::GreaterEquals, start),
+            '&' => self.one_or_two('&', TokenKind::Ampersand, TokenKind::And, start),
+            '|' => self.one_or_two('|', TokenKind::Pipe, TokenKind::Or, start),
+            '.' if self.peek_ahead(1) == '.' && self.peek_ahead(2) == '=' => {
                 self.advance();
-                self.make_token(TokenKind::RParen, start)
-            }
-            b';' => {
                 self.advance();
-                self.make_token(TokenKind::Semicolon, start)
+                self.single(TokenKind::DotDotEquals, start)
             }
-            b',' => {
+            '.' => self.one_or_two('.', TokenKind::Dot, TokenKind::DotDot, start),
+            '-' if self.peek_ahead(1) == '=' => {
                 self.advance();
-                self.make_token(TokenKind::Comma, start)
+                self.single(TokenKind::MinusEquals, start)
             }
-            b':' => {
+            '-' => self.one_or_two('>', TokenKind::Minus, TokenKind::Arrow, start),
+            '=' => {
                 self.advance();
-                if self.peek() == b':' {
-                    self.advance();
-                    self.make_token(TokenKind::DoubleColon, start)
-                } else {
-                    self.make_token(TokenKind::Colon, start)
-                }
-            }
-            b'=' => {
-                self.advance();
-                if self.peek() == b'=' {
+                if self.peek() == '=' {
                     self.advance();
                     self.make_token(TokenKind::EqualsEquals, start)
-                } else if self.peek() == b'>' {
+                } else if self.peek() == '>' {
                     self.advance();
                     self.make_token(TokenKind::FatArrow, start)
                 } else {
                     self.make_token(TokenKind::Equals, start)
                 }
             }
-            b'!' => {
-                self.advance();
-                if self.peek() == b'=' {
-                    self.advance();
-                    self.make_token(TokenKind::NotEquals, start)
-                } else {
-                    self.make_token(TokenKind::Not, start)
-                }
+            '_' if !is_ident_continue(self.peek_ahead(1)) => {
+                self.single(TokenKind::Underscore, start)
             }
-            b'<' => {
-                self.advance();
-                if self.peek() == b'=' {
-                    self.advance();
-                    self.make_token(TokenKind::LessEquals, start)
-                } else {
-                    self.make_token(TokenKind::LessThan, start)
-                }
+            '\'' => self.lex_char(start),
+            '"' => self.lex_string(start),
+            'r' if self.peek_ahead(1) == '"' => self.lex_raw_string(start),
+            c if c.is_ascii_digit() => self.lex_number(start),
+            c if is_ident_start(c) => self.lex_identifier(start),
+            _ => self.single(TokenKind::Unknown, start),
+        }
+    }
+
+    fn single(&mut self, kind: TokenKind, start: usize) -> Token {
+        self.advance();
+        self.make_token(kind, start)
+    }
+
+    // A token that becomes `double` when followed by `next`, e.g. `<` / `<=`
+    fn one_or_two(
+        &mut self,
+        next: char,
+        single: TokenKind,
+        double: TokenKind,
+        start: usize,
+    ) -> Token {
+        self.advance();
+        if self.peek() == next {
+            self.advance();
+            self.make_token(double, start)
+        } else {
+            self.make_token(single, start)
+        }
+    }
+
+    fn lex_identifier(&mut self, start: usize) -> Token {
+        while is_ident_continue(self.peek()) {
+            self.advance();
+        }
+        let kind = match &self.input[start..self.pos] {
+            "fn" => TokenKind::Fn,
+            "let" => TokenKind::Let,
+            "mut" => TokenKind::Mut,
+            "if" => TokenKind::If,
+            "else" => TokenKind::Else,
+            "while" => TokenKind::While,
+            "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "return" => TokenKind::Return,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "struct" => TokenKind::Struct,
+            "enum" => TokenKind::Enum,
+            "match" => TokenKind::Match,
+            "import" => TokenKind::Import,
+            "true" => TokenKind::True,
+            "false" => TokenKind::False,
+            "type" => TokenKind::Type,
+            "trait" => TokenKind::Trait,
+            "impl" => TokenKind::Impl,
+            "pub" => TokenKind::Pub,
+            _ => TokenKind::Identifier,
+        };
+        self.make_token(kind, start)
+    }
+
+    fn lex_char(&mut self, start: usize) -> Token {
+        self.advance(); // Advance past the opening '\''
+
+        let mut text = String::new();
+        match self.peek() {
+            '\\' => {
+                // Keep going after a bad escape so the parser does not also
+                // complain about an empty literal
+                text.push(self.lex_escape().unwrap_or(char::REPLACEMENT_CHARACTER));
             }
-            b'>' => {
-                self.advance();
-                if self.peek() == b'=' {
-                    self.advance();
-                    self.make_token(TokenKind::GreaterEquals, start)
-                } else {
-                    self.make_token(TokenKind::GreaterThan, start)
-                }
+            '\'' | '\0' => {
+                // Empty char literal or EOF; reported by the parser or below
             }
-            b'&' => {
+            c => {
+                text.push(c);
                 self.advance();
-                if self.peek() == b'&' {
+            }
+        }
+
+        if self.peek() == '\'' {
+            self.advance(); // Advance past the closing '\''
+        } else {
+            self.error(
+                E_UNTERMINATED_LITERAL,
+                "Expected closing quote for char literal".to_string(),
+                start,
+            );
+        }
+        self.token_with_text(TokenKind::CharLiteral, text, start)
+    }
+
+    fn lex_string(&mut self, start: usize) -> Token {
+        let triple = self.peek_ahead(1) == '"' && self.peek_ahead(2) == '"';
+        self.skip_opening_quotes(triple);
+        self.lex_string_part(start, triple, true)
+    }
+
+    fn skip_opening_quotes(&mut self, triple: bool) {
+        for _ in 0..if triple { 3 } else { 1 } {
+            self.advance();
+        }
+        // A triple-quoted string may start on the line after its quotes
+        if triple && self.peek() == '\n' {
+            self.advance();
+        } else if triple && self.peek() == '\r' && self.peek_ahead(1) == '\n' {
+            self.advance();
+            self.advance();
+        }
+    }
+
+    fn at_closing_quote(&self, triple: bool) -> bool {
+        self.peek() == '"' && (!triple || (self.peek_ahead(1) == '"' && self.peek_ahead(2) == '"'))
+    }
+
+    // Lex string content up to the closing quote or the next `{...}` hole.
+    // `first` is true for the part right after the opening quote. A hole
+    // starts at '{' followed by a name (`{x}`, `{user.name}`), by '}' (the
+    // positional `{}` of print) or by ':' (`{:x}`); any other '{' is
+    // literal text, and `{{` / `}}` stand for single braces.
+    fn lex_string_part(&mut self, start: usize, triple: bool, first: bool) -> Token {
+        let mut content = String::new();
+        let mut hole = false;
+
+        loop {
+            match self.peek() {
+                '\0' => {
+                    self.error(
+                        E_UNTERMINATED_LITERAL,
+                        "Unterminated string literal".to_string(),
+                        start,
+                    );
+                    break;
+                }
+                '"' if self.at_closing_quote(triple) => {
+                    for _ in 0..if triple { 3 } else { 1 } {
+                        self.advance();
+                    }
+                    break;
+                }
+                '\\' => {
+                    if let Some(c) = self.lex_escape() {
+                        content.push(c);
+                    }
+                }
+                '{' | '}' if self.peek_ahead(1) == self.peek() => {
+                    content.push(self.peek());
+                    self.advance();
                     self.advance();
-                    self.make_token(TokenKind::And, start)
-                } else {
-                    self.make_token(TokenKind::Unknown, start)
                 }
-            }
-            b'|' => {
-                self.advance();
-                if self.peek() == b'|' {
+                '{' if matches!(self.peek_ahead(1), '}' | ':')
+                    || is_ident_start(self.peek_ahead(1)) =>
+                {
                     self.advance();
-                    self.make_token(TokenKind::Or, start)
-                } else {
-                    self.make_token(TokenKind::Pipe, start)
+                    hole = true;
+                    break;
                 }
-            }
-            b'.' => {
-                self.advance();
-                if self.peek() == b'.' {
+                c => {
+                    content.push(c);
                     self.advance();
-                    self.make_token(TokenKind::DotDot, start)
-                } else {
-                    self.make_token(TokenKind::Dot, start)
                 }
             }
-            b'+' => {
-                self.advance();
-                self.make_token(TokenKind::Plus, start)
-            }
-            b'*' => {
+        }
+
+        if triple && !hole {
+            trim_closing_line(&mut content);
+        }
+
+        let kind = match (first, hole) {
+            (true, false) => TokenKind::StringLiteral,
+            (true, true) => TokenKind::InterpStart,
+            (false, true) => TokenKind::InterpMiddle,
+            (false, false) => TokenKind::InterpEnd,
+        };
+        if first && hole {
+            self.interpolations.push(Interpolation { depth: 0, triple });
+        } else if !first && !hole {
+            self.interpolations.pop();
+        }
+        self.token_with_text(kind, content, start)
+    }
+
+    // Whether the next token is directly inside a `{...}` hole rather than
+    // nested in braces within it
+    fn at_hole_level(&self) -> bool {
+        self.interpolations
+            .last()
+            .is_some_and(|hole| hole.depth == 0)
+    }
+
+    // The spec runs from ':' to the '}' that closes the hole, which is left
+    // for `next_token` to resume the string with
+    fn lex_format_spec(&mut self, start: usize) -> Token {
+        self.advance(); // Advance past ':'
+        let spec_start = self.pos;
+        while !matches!(self.peek(), '}' | '"' | '\n' | '\0') {
+            self.advance();
+        }
+        let text = self.input[spec_start..self.pos].to_string();
+        self.token_with_text(TokenKind::FormatSpec, text, start)
+    }
+
+    // r"..." and r"""...""": no escapes and no interpolation
+    fn lex_raw_string(&mut self, start: usize) -> Token {
+        self.advance(); // Advance past 'r'
+        let triple = self.peek_ahead(1) == '"' && self.peek_ahead(2) == '"';
+        self.skip_opening_quotes(triple);
+
+        let content_start = self.pos;
+        while self.peek() != '\0' && !self.at_closing_quote(triple) {
+            self.advance();
+        }
+        let mut content = self.input[content_start..self.pos].to_string();
+
+        if self.peek() == '"' {
+            for _ in 0..if triple { 3 } else { 1 } {
                 self.advance();
-                self.make_token(TokenKind::Star, start)
             }
-            b'/' => {
+        } else {
+            self.error(
+                E_UNTERMINATED_LITERAL,
+                "Unterminated raw string literal".to_string(),
+                start,
+            );
+        }
+        if triple {
+            trim_closing_line(&mut content);
+        }
+        self.token_with_text(TokenKind::StringLiteral, content, start)
+    }
+
+    // Decode the escape sequence at the current '\\'. Returns `None` (after
+    // reporting it) if the escape is invalid.
+    fn lex_escape(&mut self) -> Option<char> {
+        let start = self.pos;
+        self.advance(); // Advance past '\\'
+
+        let decoded = match self.peek() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '\\' => '\\',
+            '\'' => '\'',
+            '"' => '"',
+            '0' => '\0',
+            'u' => return self.lex_unicode_escape(start),
+            '\0' => return None, // EOF, reported as an unterminated literal
+            c => {
                 self.advance();
-                self.make_token(TokenKind::Slash, start)
+                self.error(
+                    E_INVALID_ESCAPE,
+                    format!("Unknown escape sequence '\\{}'", c),
+                    start,
+                );
+                return None;
             }
-            b'-' => {
+        };
+        self.advance();
+        Some(decoded)
+    }
+
+    // `\u{XXXX}`: 1 to 6 hex digits naming a Unicode scalar value
+    fn lex_unicode_escape(&mut self, start: usize) -> Option<char> {
+        self.advance(); // Advance past 'u'
+        if self.peek() != '{' {
+            self.error(
+                E_INVALID_ESCAPE,
+                "Expected '{' after '\\u'".to_string(),
+                start,
+            );
+            return None;
+        }
+        self.advance();
+
+        let digits_start = self.pos;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = self.input[digits_start..self.pos].to_string();
+
+        if self.peek() != '}' {
+            self.error(
+                E_INVALID_ESCAPE,
+                "Unterminated unicode escape, expected '}'".to_string(),
+                start,
+            );
+            return None;
+        }
+        self.advance();
+
+        if digits.is_empty() || digits.len() > 6 {
+            self.error(
+                E_INVALID_ESCAPE,
+                "Unicode escape must have 1 to 6 hex digits".to_string(),
+                start,
+            );
+            return None;
+        }
+        let decoded = u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        if decoded.is_none() {
+            self.error(
+                E_INVALID_ESCAPE,
+                format!("'\\u{{{}}}' is not a valid Unicode scalar value", digits),
+                start,
+            );
+        }
+        decoded
+    }
+
+    fn lex_number(&mut self, start: usize) -> Token {
+        let radix_prefix = self.peek() == '0' && matches!(self.peek_ahead(1), 'x' | 'o' | 'b');
+        let mut is_float = false;
+
+        if !radix_prefix {
+            self.skip_digits();
+            // Fraction; `0..10` is a range and `x.0.1` a field chain, so a
+            // digit must follow the dot and a field index has none
+            let field_index =
+                self.input[..start].ends_with('.') && !self.input[..start].ends_with("..");
+            if !field_index && self.peek() == '.' && self.peek_ahead(1).is_ascii_digit() {
+                is_float = true;
                 self.advance();
-                if self.peek() == b'>' {
-                    self.advance();
-                    self.make_token(TokenKind::Arrow, start)
-                } else {
-                    self.make_token(TokenKind::Minus, start)
-                }
+                self.skip_digits();
             }
-            b'_' => {
-                if !is_alnum(self.peek_ahead(1)) {
+            // Exponent: e10, E-3, e+5
+            if matches!(self.peek(), 'e' | 'E') {
+                let sign = matches!(self.peek_ahead(1), '+' | '-') as usize;
+                if self.peek_ahead(1 + sign).is_ascii_digit() {
+                    is_float = true;
                     self.advance();
-                    self.make_token(TokenKind::Underscore, start)
-                } else {
-                    let token_line = self.line;
-                    let token_col = self.column;
-                    while is_alnum(self.peek()) {
+                    if sign == 1 {
                         self.advance();
                     }
-                    let text = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
-                    Token {
-                        kind: TokenKind::Identifier,
-                        text,
-                        line: token_line,
-                        column: token_col,
-                    }
+                    self.skip_digits();
                 }
             }
-            b'\'' => {
-                let token_line = self.line;
-                let token_col = self.column;
-                self.advance(); // Advance past the opening '\''
-
-                let char_start = self.pos;
-                if self.peek() == b'\\' {
-                    // Check for escape sequence
-                    self.advance(); // Advance past '\\'
-                    match self.peek() {
-                        b'n' | b'r' | b't' | b'\\' | b'\'' | b'0' => {
-                            self.advance(); // Advance past the escaped char
-                        }
-                        _ => {
-                            // Unknown escape sequence, treat as error or literal char
-                            // For now, just advance past it and let the closing quote check handle it
-                            self.advance();
-                        }
-                    }
-                } else if self.peek() != b'\'' && self.peek() != 0 {
-                    self.advance(); // Advance past the character
-                } else {
-                    // Empty char literal or EOF immediately after opening quote
-                    // This will be caught by the closing quote check
-                }
-                let char_end = self.pos;
+        }
 
-                if self.peek() != b'\'' {
-                    eprintln!(
-                        "Error: Expected closing quote for char literal at line {}, column {}",
-                        token_line, token_col
-                    );
-                    std::process::exit(1);
-                }
-                self.advance(); // Advance past the closing '\''
+        // Radix prefix, digits, `_` separators and a type suffix all belong
+        // to the literal; the parse functions sort them out
+        while is_ident_continue(self.peek()) {
+            self.advance();
+        }
 
-                Token {
-                    kind: TokenKind::CharLiteral,
-                    text: String::from_utf8_lossy(&self.input[char_start..char_end]).into_owned(),
-                    line: token_line,
-                    column: token_col,
-                }
-            }
-            b'"' => {
-                let token_line = self.line;
-                let token_col = self.column;
-                self.advance();
-                
-                // Parse string with escape sequences
-                let mut string_content = String::new();
-                while self.peek() != 0 && self.peek() != b'"' {
-                    if self.peek() == b'\\' {
-                        // Handle escape sequences
-                        self.advance();
-                        match self.peek() {
-                            b'n' => {
-                                string_content.push('\n');
-                                self.advance();
-                            }
-                            b't' => {
-                                string_content.push('\t');
-                                self.advance();
-                            }
-                            b'r' => {
-                                string_content.push('\r');
-                                self.advance();
-                            }
-                            b'\\' => {
-                                string_content.push('\\');
-                                self.advance();
-                            }
-                            b'"' => {
-                                string_content.push('"');
-                                self.advance();
-                            }
-                            b'0' => {
-                                string_content.push('\0');
-                                self.advance();
-                            }
-                            _ => {
-                                // Unknown escape sequence, just include the backslash
-                                string_content.push('\\');
-                            }
-                        }
-                    } else {
-                        string_content.push(self.peek() as char);
-                        self.advance();
-                    }
-                }
-                
-                if self.peek() == b'"' {
-                    self.advance();
-                }
-                Token {
-                    kind: TokenKind::StringLiteral,
-                    text: string_content,
-                    line: token_line,
-                    column: token_col,
-                }
-            }
-            _ if is_digit(c) => {
-                while is_digit(self.peek()) {
-                    self.advance();
-                }
-                self.make_token(TokenKind::Number, start)
-            }
-            _ if is_alpha(c) => {
-                let token_line = self.line;
-                let token_col = self.column;
-                while is_alnum(self.peek()) {
-                    self.advance();
-                }
-                let text = String::from_utf8_lossy(&self.input[start..self.pos]).into_owned();
-                let kind = match text.as_str() {
-                    "fn" => TokenKind::Fn,
-                    "let" => TokenKind::Let,
-                    "if" => TokenKind::If,
-                    "else" => TokenKind::Else,
-                    "while" => TokenKind::While,
-                    "for" => TokenKind::For,
-                    "in" => TokenKind::In,
-                    "return" => TokenKind::Return,
-                    "break" => TokenKind::Break,
-                    "continue" => TokenKind::Continue,
-                    "struct" => TokenKind::Struct,
-                    "enum" => TokenKind::Enum,
-                    "match" => TokenKind::Match,
-                    "import" => TokenKind::Import,
-                    "true" => TokenKind::True,
-                    "false" => TokenKind::False,
-                    "type" => TokenKind::Type,
-                    "trait" => TokenKind::Trait,
-                    "impl" => TokenKind::Impl,
-                    "pub" => TokenKind::Pub,
-                    _ => TokenKind::Identifier,
-                };
-                Token {
-                    kind,
-                    text,
-                    line: token_line,
-                    column: token_col,
-                }
-            }
-            _ => {
-                self.advance();
-                self.make_token(TokenKind::Unknown, start)
-            }
+        let text = &self.input[start..self.pos];
+        let float_suffix = text.ends_with("f32") || text.ends_with("f64");
+        let (kind, result) = if is_float || (!radix_prefix && float_suffix) {
+            (TokenKind::Float, parse_float_literal(text).map(|_| ()))
+        } else {
+            (TokenKind::Number, parse_int_literal(text).map(|_| ()))
+        };
+        if let Err(message) = result {
+            self.error(E_INVALID_LITERAL, message, start);
+        }
+        self.make_token(kind, start)
+    }
+
+    fn skip_digits(&mut self) {
+        while self.peek().is_ascii_digit() || self.peek() == '_' {
+            self.advance();
         }
     }
 
     fn make_token(&self, kind: TokenKind, start: usize) -> Token {
+        self.token_with_text(kind, self.input[start..self.pos].to_string(), start)
+    }
+
+    fn token_with_text(&self, kind: TokenKind, text: String, start: usize) -> Token {
         Token {
             kind,
-            text: String::from_utf8_lossy(&self.input[start..self.pos]).into_owned(),
-            line: self.line,
-            column: self.column - (self.pos - start),
+            text,
+            line: self.token_line,
+            column: self.token_column,
+            span: self.span_from(start),
+        }
+    }
+}
+
+// Drop the whitespace-only line that holds the closing `"""` of a
+// triple-quoted string, along with the newline before it
+fn trim_closing_line(content: &mut String) {
+    if let Some(last_newline) = content.rfind('\n') {
+        if content[last_newline + 1..]
+            .trim_matches([' ', '\t'])
+            .is_empty()
+        {
+            content.truncate(last_newline);
+            if content.ends_with('\r') {
+                content.pop();
+            }
         }
     }
 }
 
-fn is_alpha(c: u8) -> bool {
-    (b'a'..=b'z').contains(&c) || (b'A'..=b'Z').contains(&c) || c == b'_'
+/// Value and suffix type of an integer literal such as `0xff`, `1_000i64`
+/// or `0b1010u8`. Fails if the literal has a bad digit or suffix, or does
+/// not fit in 64 bits; whether it fits its suffix type depends on a minus
+/// sign in front, so that is left to `check_int_range`.
+pub fn parse_int_literal(text: &str) -> Result<(u64, Option<IntType>), String> {
+    let (radix, body) = match text.get(..2) {
+        Some("0x") => (16, &text[2..]),
+        Some("0o") => (8, &text[2..]),
+        Some("0b") => (2, &text[2..]),
+        _ => (10, text),
+    };
+
+    // Suffixes start with `i` or `u`, neither of which is a hex digit
+    let suffix_start = body.find(['i', 'u']).unwrap_or(body.len());
+    let (digits, suffix) = body.split_at(suffix_start);
+    let ty = if suffix.is_empty() {
+        None
+    } else {
+        match IntType::from_name(suffix) {
+            Some(ty) if suffix != "int" => Some(ty),
+            _ => return Err(format!("invalid suffix '{}' on integer literal", suffix)),
+        }
+    };
+
+    let mut value: u64 = 0;
+    let mut seen_digit = false;
+    for ch in digits.chars() {
+        if ch == '_' {
+            continue;
+        }
+        let digit = ch
+            .to_digit(radix)
+            .ok_or_else(|| format!("invalid digit '{}' in base {} literal", ch, radix))?;
+        value = value
+            .checked_mul(radix as u64)
+            .and_then(|v| v.checked_add(digit as u64))
+            .ok_or_else(|| format!("integer literal '{}' does not fit in 64 bits", text))?;
+        seen_digit = true;
+    }
+
+    if !seen_digit {
+        return Err(format!("integer literal '{}' has no digits", text));
+    }
+    Ok((value, ty))
 }
 
-fn is_digit(c: u8) -> bool {
-    (b'0'..=b'9').contains(&c)
+/// Fails if the literal `text`, with value `value`, does not fit in its
+/// suffix type. A `negative` literal follows a unary minus, so a signed
+/// type's minimum can be written: `-128i8`, and an unsigned type's `-1u8`
+/// can't.
+pub fn check_int_range(
+    text: &str,
+    value: u64,
+    ty: Option<IntType>,
+    negative: bool,
+) -> Result<(), String> {
+    let Some(ty) = ty else {
+        return Ok(());
+    };
+    if negative && !ty.is_signed() && value > 0 {
+        return Err(format!(
+            "integer literal '-{}' is out of range for {} (min 0)",
+            text,
+            ty.name()
+        ));
+    } else if negative && ty.is_signed() {
+        let min = ty.max_value() + 1;
+        if value > min {
+            return Err(format!(
+                "integer literal '-{}' is out of range for {} (min -{})",
+                text,
+                ty.name(),
+                min
+            ));
+        }
+    } else if value > ty.max_value() {
+        return Err(format!(
+            "integer literal '{}' is out of range for {} (max {})",
+            text,
+            ty.name(),
+            ty.max_value()
+        ));
+    }
+    Ok(())
 }
 
-fn is_alnum(c: u8) -> bool {
-    is_alpha(c) || is_digit(c)
+/// Value and suffix type of a float literal such as `1.5`, `2.0e-3` or
+/// `10f32`. Fails on malformed literals and on values too large for the
+/// literal's type.
+pub fn parse_float_literal(text: &str) -> Result<(f64, Option<FloatType>), String> {
+    let (number, ty) = match text.len().checked_sub(3).map(|i| text.split_at(i)) {
+        Some((number, suffix)) if FloatType::from_name(suffix).is_some() => {
+            (number, FloatType::from_name(suffix))
+        }
+        _ => (text, None),
+    };
+
+    let digits: String = number.chars().filter(|&c| c != '_').collect();
+    let value: f64 = digits
+        .parse()
+        .map_err(|_| format!("invalid float literal '{}'", text))?;
+
+    let fits = match ty {
+        Some(FloatType::F32) => (value as f32).is_finite(),
+        _ => value.is_finite(),
+    };
+    if !fits {
+        let name = ty.unwrap_or(FloatType::F64).name();
+        return Err(format!(
+            "float literal '{}' is out of range for {}",
+            text, name
+        ));
+    }
+    Ok((value, ty))
+}
+
+// Identifier rules follow Unicode UAX #31 (XID_Start / XID_Continue).
+// The bootstrap takes no dependencies, so the XID tables are approximated
+// with std's Alphabetic and Numeric properties plus the combining mark
+// blocks, which covers decomposed forms such as "o\u{304}".
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    is_ident_start(c) || c.is_alphanumeric() || is_combining_mark(c)
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex_number(input: &str) -> (Token, Vec<Diagnostic>) {
+        let mut lexer = Lexer::new(input);
+        let token = lexer.next_token();
+        assert_eq!(token.kind, TokenKind::Number);
+        (token, lexer.diagnostics)
+    }
+
+    #[test]
+    fn test_integer_literal_forms() {
+        assert_eq!(parse_int_literal("42"), Ok((42, None)));
+        assert_eq!(parse_int_literal("0xFF_ff"), Ok((0xffff, None)));
+        assert_eq!(parse_int_literal("0o755"), Ok((0o755, None)));
+        assert_eq!(
+            parse_int_literal("0b1010_0101u8"),
+            Ok((0xa5, Some(IntType::U8)))
+        );
+        assert_eq!(
+            parse_int_literal("1_000_000i64"),
+            Ok((1_000_000, Some(IntType::I64)))
+        );
+        assert_eq!(parse_int_literal("10usize"), Ok((10, Some(IntType::Usize))));
+
+        let (token, diagnostics) = lex_number("0x1fu16;");
+        assert_eq!(token.text, "0x1fu16");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_float_literals() {
+        assert_eq!(parse_float_literal("1.5"), Ok((1.5, None)));
+        assert_eq!(parse_float_literal("2.5e-3"), Ok((2.5e-3, None)));
+        assert_eq!(
+            parse_float_literal("1_000.25f32"),
+            Ok((1000.25, Some(FloatType::F32)))
+        );
+        assert_eq!(parse_float_literal("3f64"), Ok((3.0, Some(FloatType::F64))));
+        assert!(parse_float_literal("1e39f32").is_err());
+        assert!(parse_float_literal("1.5q").is_err());
+
+        let mut lexer = Lexer::new("6.02E+23 0..10 1e5 0..=9");
+        let kinds: Vec<_> = std::iter::from_fn(|| {
+            let token = lexer.next_token();
+            (token.kind != TokenKind::EOF).then_some((token.kind, token.text))
+        })
+        .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (TokenKind::Float, "6.02E+23".to_string()),
+                (TokenKind::Number, "0".to_string()),
+                (TokenKind::DotDot, "..".to_string()),
+                (TokenKind::Number, "10".to_string()),
+                (TokenKind::Float, "1e5".to_string()),
+                (TokenKind::Number, "0".to_string()),
+                (TokenKind::DotDotEquals, "..=".to_string()),
+                (TokenKind::Number, "9".to_string()),
+            ]
+        );
+        assert!(lexer.diagnostics.is_empty());
+        // A tuple field chain is two field indices, not a float
+        let (tokens, _) = lex_all("t.0.1 + 0.5");
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts[..6], ["t", ".", "0", ".", "1", "+"]);
+        assert_eq!(
+            (tokens[6].kind, tokens[6].text.as_str()),
+            (TokenKind::Float, "0.5")
+        );
+    }
+
+    #[test]
+    fn test_integer_literal_errors() {
+        let (_, diagnostics) = lex_number("0b102");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, E_INVALID_LITERAL);
+        assert!(diagnostics[0].message.contains("invalid digit '2'"));
+        assert_eq!((diagnostics[0].span.start, diagnostics[0].span.end), (0, 5));
+
+        let range = |text: &str, negative: bool| {
+            let (value, ty) = parse_int_literal(text).unwrap();
+            check_int_range(text, value, ty, negative)
+        };
+        assert!(range("256u8", false)
+            .unwrap_err()
+            .contains("out of range for u8 (max 255)"));
+        assert!(range("128i8", false).is_err());
+        assert!(range("127i8", false).is_ok());
+        assert!(range("128i8", true).is_ok());
+        assert_eq!(
+            range("129i8", true).unwrap_err(),
+            "integer literal '-129i8' is out of range for i8 (min -128)"
+        );
+        assert!(range("9223372036854775808i64", true).is_ok());
+        assert_eq!(
+            range("1u8", true).unwrap_err(),
+            "integer literal '-1u8' is out of range for u8 (min 0)"
+        );
+        assert!(range("0u8", true).is_ok());
+        assert!(parse_int_literal("18446744073709551616").is_err());
+        assert!(parse_int_literal("0b102").is_err());
+        assert!(parse_int_literal("0x").is_err());
+        assert!(parse_int_literal("10q").is_err());
+        assert!(parse_int_literal("10int").is_err());
+    }
+
+    fn lex_all(input: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+        let mut lexer = Lexer::new(input);
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if token.kind == TokenKind::EOF {
+                break;
+            }
+            tokens.push(token);
+        }
+        (tokens, lexer.diagnostics)
+    }
+
+    #[test]
+    fn test_unicode_identifiers_and_columns() {
+        let (tokens, diagnostics) = lex_all("let ōνομα = naïve + x\u{301};");
+        assert!(diagnostics.is_empty());
+        let idents: Vec<(&str, usize)> = tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::Identifier)
+            .map(|t| (t.text.as_str(), t.column))
+            .collect();
+        assert_eq!(idents[0], ("ōνομα", 5));
+        assert_eq!(idents[1], ("naïve", 13));
+        // A combining mark continues the identifier
+        assert_eq!(idents[2], ("x\u{301}", 21));
+        assert_eq!(tokens.last().unwrap().column, 23);
+    }
+
+    #[test]
+    fn test_unicode_char_and_string_literals() {
+        let (tokens, diagnostics) = lex_all(r#"'ō' '\u{14D}' '\n' "Ath\u{14d}n → ok""#);
+        assert!(diagnostics.is_empty());
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, vec!["ō", "ō", "\n", "Athōn → ok"]);
+        assert_eq!(tokens[3].kind, TokenKind::StringLiteral);
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        let (tokens, diagnostics) = lex_all(r#""\q" '\u{D800}' "\u{1234567}" "\u41""#);
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(diagnostics.len(), 4);
+        assert!(diagnostics.iter().all(|d| d.code == E_INVALID_ESCAPE));
+        assert_eq!((diagnostics[0].span.start, diagnostics[0].span.end), (1, 3));
+    }
+
+    #[test]
+    fn test_operator_tokens() {
+        let (tokens, _) = lex_all("a % b & c | d ^ e << 1 >> ~2 && f || g");
+        let kinds: Vec<TokenKind> = tokens.iter().skip(1).step_by(2).map(|t| t.kind).collect();
+        assert_eq!(
+            kinds[..6],
+            [
+                TokenKind::Percent,
+                TokenKind::Ampersand,
+                TokenKind::Pipe,
+                TokenKind::Caret,
+                TokenKind::ShiftLeft,
+                TokenKind::ShiftRight
+            ]
+        );
+        assert_eq!(tokens[12].kind, TokenKind::Tilde);
+        assert_eq!(
+            (tokens[14].kind, tokens[16].kind),
+            (TokenKind::And, TokenKind::Or)
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment_tokens() {
+        let (tokens, _) = lex_all("a += 1; b -= c->d; e *= 2; f /= 3; g %= 4;");
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds[1], TokenKind::PlusEquals);
+        assert_eq!(
+            kinds[5..8],
+            [
+                TokenKind::MinusEquals,
+                TokenKind::Identifier,
+                TokenKind::Arrow
+            ]
+        );
+        assert_eq!(kinds[11], TokenKind::StarEquals);
+        assert_eq!(kinds[15], TokenKind::SlashEquals);
+        assert_eq!(kinds[19], TokenKind::PercentEquals);
+    }
+
+    #[test]
+    fn test_interpolated_string_tokens() {
+        let (tokens, diagnostics) = lex_all(r#""a {x + f(1)} b {{c}} {} d""#);
+        assert!(diagnostics.is_empty());
+        let pieces: Vec<(TokenKind, &str)> =
+            tokens.iter().map(|t| (t.kind, t.text.as_str())).collect();
+        assert_eq!(
+            pieces,
+            vec![
+                (TokenKind::InterpStart, "a "),
+                (TokenKind::Identifier, "x"),
+                (TokenKind::Plus, "+"),
+                (TokenKind::Identifier, "f"),
+                (TokenKind::LParen, "("),
+                (TokenKind::Number, "1"),
+                (TokenKind::RParen, ")"),
+                (TokenKind::InterpMiddle, " b {c} "),
+                (TokenKind::InterpEnd, " d"),
+            ]
+        );
+        // A brace not followed by a name is ordinary text
+        let (tokens, _) = lex_all(r#""fn f() { return 1; }""#);
+        assert_eq!(tokens[0].kind, TokenKind::StringLiteral);
+
+        let (tokens, _) = lex_all(r#""{x:>8} {:#x} {Color::Red}""#);
+        let pieces: Vec<(TokenKind, &str)> =
+            tokens.iter().map(|t| (t.kind, t.text.as_str())).collect();
+        assert_eq!(
+            pieces[1..3],
+            [(TokenKind::Identifier, "x"), (TokenKind::FormatSpec, ">8")]
+        );
+        assert_eq!(pieces[4], (TokenKind::FormatSpec, "#x"));
+        assert_eq!(pieces[7], (TokenKind::DoubleColon, "::"));
+    }
+
+    #[test]
+    fn test_raw_and_triple_quoted_strings() {
+        let (tokens, diagnostics) = lex_all("r\"C:\\dir\\{x}\" r\"\"\"a \"q\" b\"\"\"");
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens[0].text, "C:\\dir\\{x}");
+        assert_eq!(tokens[1].text, "a \"q\" b");
+
+        let (tokens, diagnostics) = lex_all("\"\"\"\n    one \"two\"\n    {n}\n    \"\"\"");
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens[0].kind, TokenKind::InterpStart);
+        assert_eq!(tokens[0].text, "    one \"two\"\n    ");
+        assert_eq!(tokens[2].kind, TokenKind::InterpEnd);
+        assert_eq!(tokens[2].text, "");
+
+        let (_, diagnostics) = lex_all("r\"open");
+        assert_eq!(diagnostics[0].code, E_UNTERMINATED_LITERAL);
+    }
 }
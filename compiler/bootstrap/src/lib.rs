@@ -2,12 +2,24 @@
 // Stage 0 compiler written in Rust, compiles Athōn to C
 
 pub mod ast;
+pub mod capability;
+pub mod checker;
 pub mod codegen;
+pub mod coverage;
+pub mod diagnostics;
+pub mod generics;
 pub mod lexer;
+pub mod mono;
 pub mod parser;
+pub mod scope;
+pub mod source_map;
+pub mod variants;
 
 // Re-export main types for convenience
 pub use ast::{Expr, Function, Program, Statement};
+pub use checker::{check_program, CheckOptions};
 pub use codegen::emit_c;
+pub use diagnostics::{Diagnostic, Severity};
 pub use lexer::{Lexer, Token, TokenKind};
 pub use parser::Parser;
+pub use source_map::{FileId, SourceMap, Span};
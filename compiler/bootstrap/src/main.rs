@@ -1,20 +1,31 @@
 // Athōn Bootstrap Compiler - CLI Entry Point
 // Compiles Athōn source code to C
 
-use athon_bootstrap::{emit_c, Parser};
+use athon_bootstrap::{
+    check_program, diagnostics, emit_c, CheckOptions, Diagnostic, Parser, SourceMap,
+};
 use std::process;
 
 fn main() {
     // Read source file from command-line argument
     let args: Vec<String> = std::env::args().collect();
+    let mut options = CheckOptions::default();
+    let mut files = Vec::new();
+    for arg in &args[1..] {
+        match arg.as_str() {
+            // Programs written before capabilities may do I/O anywhere
+            "--legacy-io" => options.legacy_io = true,
+            _ => files.push(arg),
+        }
+    }
 
-    if args.len() < 2 {
-        eprintln!("Usage: {} <source.at>", args[0]);
+    if files.len() != 1 {
+        eprintln!("Usage: {} [--legacy-io] <source.at>", args[0]);
         eprintln!("Example: {} examples/hello.at", args[0]);
         process::exit(1);
     }
 
-    let filename = &args[1];
+    let filename = files[0];
 
     let source = match std::fs::read_to_string(filename) {
         Ok(content) => content,
@@ -25,9 +36,37 @@ fn main() {
     };
 
     // Parse the source code
-    let mut parser = Parser::new(&source);
-    let program = parser.parse_program();
+    let mut parser = Parser::with_file(&source, filename);
+    let program = match parser.parse_program() {
+        Ok(program) => program,
+        Err(diagnostics) => {
+            report_and_exit(parser.source_map(), &diagnostics);
+        }
+    };
+
+    let mut diagnostics = parser.warnings().to_vec();
+    diagnostics.extend(check_program(&program, options));
+    if diagnostics::has_errors(&diagnostics) {
+        report_and_exit(parser.source_map(), &diagnostics);
+    }
+    // Only warnings are left; report them and carry on
+    for diagnostic in &diagnostics {
+        eprintln!("{}\n", parser.source_map().render(diagnostic));
+    }
 
     // Generate and output C code
     emit_c(&program);
 }
+
+fn report_and_exit(source_map: &SourceMap, diagnostics: &[Diagnostic]) -> ! {
+    for diagnostic in diagnostics {
+        eprintln!("{}\n", source_map.render(diagnostic));
+    }
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    eprintln!(
+        "error: aborting due to {} previous error{}",
+        errors,
+        if errors == 1 { "" } else { "s" }
+    );
+    process::exit(1);
+}
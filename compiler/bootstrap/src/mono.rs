@@ -0,0 +1,874 @@
+// Monomorphization for Athōn bootstrap compiler
+// C has no generics, so every generic struct and function is copied once
+// for each set of type arguments it is used with. `Pair<int, string>`
+// becomes the struct `Pair_int_string`, and `swap` called on such a pair
+// becomes the function `swap_Pair_int_string`. Type arguments are never
+// written at call sites; they are inferred from the arguments, the same
+// way the checker infers them.
+//
+// Method calls are lowered here as well, since resolving them needs the
+// same types: an inherent method of `Point` becomes the function
+// `Point_norm`, and `p.norm()` becomes `Point_norm(p)`. A trait method
+// call becomes a call by the method's name, as if written `norm(p)`.
+
+use crate::ast::*;
+use crate::capability;
+use crate::generics::{self, Bindings};
+use crate::scope::{ScopeKind, ScopeStack};
+use std::collections::{HashMap, HashSet};
+
+/// `program` with every generic struct and function replaced by the
+/// instances it uses
+pub fn monomorphize(program: &Program) -> Program {
+    let program = &with_inherent_methods(program);
+    let mut mono = Mono::new(program);
+    let mut output = program.clone();
+
+    for type_alias in &mut output.type_aliases {
+        type_alias.target_type = mono.c_type(&type_alias.target_type);
+    }
+    for union_type in &mut output.unions {
+        for variant in &mut union_type.variants {
+            if let Some(ty) = &variant.associated_type {
+                variant.associated_type = Some(mono.c_type(ty));
+            }
+        }
+    }
+    for trait_def in &mut output.traits {
+        for method in &mut trait_def.methods {
+            for param in &mut method.params {
+                param.type_name = mono.c_type(&param.type_name);
+            }
+            if let Some(ty) = &method.return_type {
+                method.return_type = Some(mono.c_type(ty));
+            }
+        }
+    }
+    for impl_block in &mut output.impls {
+        impl_block.type_name = mono.c_type(&impl_block.type_name);
+        for method in &mut impl_block.methods {
+            *method = mono.instantiate(method, method.name.clone(), Bindings::new());
+        }
+    }
+
+    output.functions = Vec::new();
+    for func in &program.functions {
+        if func.type_params.is_empty() {
+            let instance = mono.instantiate(func, func.name.clone(), Bindings::new());
+            output.functions.push(instance);
+        }
+    }
+    // Instances can use further instances
+    while let Some((name, func, bindings)) = mono.pending.pop() {
+        let instance = mono.instantiate(func, name, bindings);
+        output.functions.push(instance);
+    }
+
+    let mut structs: Vec<StructDef> = Vec::new();
+    for struct_def in &program.structs {
+        if struct_def.type_params.is_empty() {
+            let mut struct_def = struct_def.clone();
+            for field in &mut struct_def.fields {
+                field.type_name = mono.c_type(&field.type_name);
+            }
+            structs.push(struct_def);
+        }
+    }
+    structs.append(&mut mono.struct_instances);
+    output.structs = mono.declaration_order(structs);
+    output.inherent_impls = Vec::new();
+    output
+}
+
+// `program` with the methods of its inherent impls also as functions
+fn with_inherent_methods(program: &Program) -> Program {
+    let mut output = program.clone();
+    for impl_block in &program.inherent_impls {
+        for method in &impl_block.methods {
+            let mut function = method.clone();
+            function.name = method_function_name(&impl_block.type_name, &method.name);
+            output.functions.push(function);
+        }
+    }
+    output
+}
+
+fn method_function_name(type_name: &TypeExpr, method: &str) -> String {
+    format!("{}_{}", generics::mangle_part(type_name), method)
+}
+
+/// C name of a struct in the output: its own name, for a tuple the name
+/// of the tuple type of its fields, and for an array that of its one field
+pub fn c_struct_name(struct_def: &StructDef) -> String {
+    if struct_def.name.starts_with('[') {
+        generics::mangle(&struct_def.fields[0].type_name)
+    } else if struct_def.name.starts_with('(') {
+        let elements = struct_def
+            .fields
+            .iter()
+            .map(|f| f.type_name.clone())
+            .collect();
+        generics::mangle(&TypeExpr::Tuple(elements))
+    } else {
+        struct_def.name.clone()
+    }
+}
+
+struct Mono<'a> {
+    structs: HashMap<&'a str, &'a StructDef>,
+    unions: HashMap<&'a str, &'a UnionType>,
+    functions: HashMap<&'a str, &'a Function>,
+    // Function names of inherent methods, by self type and method name
+    inherent_methods: HashMap<(TypeExpr, String), String>,
+    type_aliases: HashMap<&'a str, &'a TypeExpr>,
+    // Return types of trait impl methods, by method name
+    impl_returns: HashMap<&'a str, Vec<(&'a TypeExpr, Option<&'a TypeExpr>)>>,
+    // Generic struct instances made so far
+    struct_instances: Vec<StructDef>,
+    // Mangled names of every instance made or queued
+    instances: HashSet<String>,
+    // Function instances still to be made
+    pending: Vec<(String, &'a Function, Bindings)>,
+    // Type parameters of the function being rewritten
+    bindings: Bindings,
+    // Types of the locals of the function being rewritten
+    locals: ScopeStack<Option<TypeExpr>>,
+    // Return type of the function being rewritten
+    return_type: Option<TypeExpr>,
+}
+
+impl<'a> Mono<'a> {
+    fn new(program: &'a Program) -> Self {
+        let mut impl_returns: HashMap<&str, Vec<_>> = HashMap::new();
+        for impl_block in &program.impls {
+            for method in &impl_block.methods {
+                impl_returns
+                    .entry(method.name.as_str())
+                    .or_default()
+                    .push((&impl_block.type_name, method.return_type.as_ref()));
+            }
+        }
+        let mut mono = Self {
+            structs: program
+                .structs
+                .iter()
+                .map(|s| (s.name.as_str(), s))
+                .collect(),
+            unions: program
+                .unions
+                .iter()
+                .map(|u| (u.name.as_str(), u))
+                .collect(),
+            functions: program
+                .functions
+                .iter()
+                .map(|f| (f.name.as_str(), f))
+                .collect(),
+            inherent_methods: HashMap::new(),
+            type_aliases: program
+                .type_aliases
+                .iter()
+                .map(|ta| (ta.name.as_str(), &ta.target_type))
+                .collect(),
+            impl_returns,
+            struct_instances: Vec::new(),
+            instances: HashSet::new(),
+            pending: Vec::new(),
+            bindings: Bindings::new(),
+            locals: ScopeStack::new(),
+            return_type: None,
+        };
+        for impl_block in &program.inherent_impls {
+            let self_type = mono.resolve_alias(&impl_block.type_name).clone();
+            for method in &impl_block.methods {
+                let function = method_function_name(&impl_block.type_name, &method.name);
+                mono.inherent_methods
+                    .insert((self_type.clone(), method.name.clone()), function);
+            }
+        }
+        mono
+    }
+
+    fn resolve_alias<'t>(&'t self, mut ty: &'t TypeExpr) -> &'t TypeExpr {
+        // Bounded so a cyclic alias cannot hang
+        for _ in 0..self.type_aliases.len() {
+            match ty.as_name().and_then(|name| self.type_aliases.get(name)) {
+                Some(target) => ty = target,
+                None => break,
+            }
+        }
+        ty
+    }
+
+    // The type a concrete type has in the output, making the struct
+    // instances it refers to. Pointer, slice, array and function types keep
+    // their shape, with the types they are built from named as in the
+    // output; an array type also makes the struct that holds its elements.
+    fn c_type(&mut self, ty: &TypeExpr) -> TypeExpr {
+        let (base, args) = match ty {
+            TypeExpr::Named { name, args } => (name.as_str(), args.as_slice()),
+            TypeExpr::Tuple(elements) => ("", elements.as_slice()),
+            TypeExpr::Pointer(inner) => return TypeExpr::Pointer(Box::new(self.c_type(inner))),
+            TypeExpr::Slice(inner) => return TypeExpr::Slice(Box::new(self.c_type(inner))),
+            TypeExpr::Array(inner, len) => {
+                let ty = TypeExpr::Array(Box::new(self.c_type(inner)), *len);
+                if self.instances.insert(generics::mangle(&ty)) {
+                    // C arrays can't be assigned, passed or returned by
+                    // value, so each array type is a struct of one array
+                    self.struct_instances.push(StructDef {
+                        name: ty.to_string(),
+                        type_params: Vec::new(),
+                        bounds: Vec::new(),
+                        fields: vec![StructField {
+                            name: "items".to_string(),
+                            type_name: ty.clone(),
+                            span: Span::default(),
+                        }],
+                        linearity: Linearity::Unrestricted,
+                        span: Span::default(),
+                    });
+                }
+                return ty;
+            }
+            TypeExpr::Function {
+                params,
+                return_type,
+            } => {
+                return TypeExpr::Function {
+                    params: params.iter().map(|p| self.c_type(p)).collect(),
+                    return_type: return_type.as_ref().map(|r| Box::new(self.c_type(r))),
+                };
+            }
+            TypeExpr::Dyn(_) => return ty.clone(),
+        };
+        for arg in args {
+            self.c_type(arg);
+        }
+        let name = generics::mangle(ty);
+        if base.is_empty() && self.instances.insert(name.clone()) {
+            // A tuple is a struct of its elements, named by its type so
+            // that codegen can tell it from declared structs
+            let fields = args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| StructField {
+                    name: format!("_{}", i),
+                    type_name: self.c_type(arg),
+                    span: Span::default(),
+                })
+                .collect();
+            self.struct_instances.push(StructDef {
+                name: ty.to_string(),
+                type_params: Vec::new(),
+                bounds: Vec::new(),
+                fields,
+                linearity: Linearity::Unrestricted,
+                span: Span::default(),
+            });
+        }
+        if let Some(struct_def) = self.structs.get(base).copied() {
+            if !args.is_empty() && self.instances.insert(name.clone()) {
+                let bindings: Bindings = struct_def
+                    .type_params
+                    .iter()
+                    .cloned()
+                    .zip(args.iter().cloned())
+                    .collect();
+                let mut instance = struct_def.clone();
+                instance.name = name.clone();
+                instance.type_params = Vec::new();
+                for field in &mut instance.fields {
+                    let field_type = generics::substitute(&field.type_name, &bindings);
+                    field.type_name = self.c_type(&field_type);
+                }
+                self.struct_instances.push(instance);
+            }
+        }
+        TypeExpr::named(&name)
+    }
+
+    // Copy of `func` with its type parameters replaced by `bindings`
+    fn instantiate(&mut self, func: &Function, name: String, bindings: Bindings) -> Function {
+        self.bindings = bindings;
+        self.locals = ScopeStack::new();
+        let mut instance = func.clone();
+        instance.name = name;
+        instance.type_params = Vec::new();
+        for param in &mut instance.params {
+            let ty = generics::substitute(&param.type_name, &self.bindings);
+            param.type_name = self.c_type(&ty);
+            self.locals.define(&param.name, Some(ty));
+        }
+        let return_type = func
+            .return_type
+            .as_ref()
+            .map(|ty| generics::substitute(ty, &self.bindings));
+        self.return_type = return_type.clone();
+        if let Some(ty) = return_type {
+            instance.return_type = Some(self.c_type(&ty));
+        }
+        self.block(&mut instance.body, ScopeKind::Block);
+        instance
+    }
+
+    fn block(&mut self, stmts: &mut [Statement], kind: ScopeKind) {
+        self.locals.push(kind);
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+        self.locals.pop();
+    }
+
+    // A block whose trailing expression, if any, is its value of type
+    // `expected`; returns the value's type
+    fn value_block(
+        &mut self,
+        stmts: &mut [Statement],
+        expected: Option<&TypeExpr>,
+    ) -> Option<TypeExpr> {
+        self.locals.push(ScopeKind::Block);
+        let mut ty = None;
+        for stmt in stmts {
+            match &mut stmt.kind {
+                StatementKind::Tail(value) => ty = self.expr(value, expected),
+                _ => self.statement(stmt),
+            }
+        }
+        self.locals.pop();
+        ty
+    }
+
+    // The type of the first arm with a value
+    fn arms(
+        &mut self,
+        arms: &mut [MatchArm],
+        value_type: Option<&TypeExpr>,
+        expected: Option<&TypeExpr>,
+    ) -> Option<TypeExpr> {
+        let mut ty = None;
+        for arm in arms {
+            self.locals.push(ScopeKind::MatchArm);
+            for (name, ty) in self.pattern_bindings(&arm.pattern, value_type) {
+                self.locals.define(&name, ty);
+            }
+            if let Some(guard) = &mut arm.guard {
+                self.expr(guard, Some(&TypeExpr::named("bool")));
+            }
+            let arm_type = self.value_block(&mut arm.body, expected);
+            ty = ty.or(arm_type);
+            self.locals.pop();
+        }
+        ty
+    }
+
+    fn statement(&mut self, stmt: &mut Statement) {
+        match &mut stmt.kind {
+            StatementKind::Let {
+                name,
+                type_name,
+                value,
+                ..
+            } => {
+                let declared = type_name
+                    .as_ref()
+                    .map(|ty| generics::substitute(ty, &self.bindings));
+                let inferred = self.expr(value, declared.as_ref());
+                if let Some(ty) = &declared {
+                    *type_name = Some(self.c_type(ty));
+                }
+                self.locals.define(name, declared.or(inferred));
+            }
+            StatementKind::LetTuple {
+                elements,
+                type_name,
+                value,
+            } => {
+                let declared = type_name
+                    .as_ref()
+                    .map(|ty| generics::substitute(ty, &self.bindings));
+                let inferred = self.expr(value, declared.as_ref());
+                if let Some(ty) = &declared {
+                    *type_name = Some(self.c_type(ty));
+                }
+                let ty = declared.or(inferred);
+                for (name, _, path) in LetPattern::bindings(elements) {
+                    let mut element = ty.clone();
+                    for i in path {
+                        element = element.and_then(|ty| {
+                            self.resolve_alias(&ty).tuple_elements()?.get(i).cloned()
+                        });
+                    }
+                    self.locals.define(name, element);
+                }
+            }
+            StatementKind::Assign { target, value, .. } => {
+                let target_type = self.expr(target, None);
+                self.expr(value, target_type.as_ref());
+            }
+            StatementKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expr(condition, None);
+                self.block(then_block, ScopeKind::Block);
+                if let Some(else_block) = else_block {
+                    self.block(else_block, ScopeKind::Block);
+                }
+            }
+            StatementKind::While { condition, body } => {
+                self.expr(condition, None);
+                self.block(body, ScopeKind::Loop);
+            }
+            StatementKind::For {
+                loop_var,
+                start,
+                end,
+                body,
+            } => {
+                self.expr(start, None);
+                self.expr(end, None);
+                self.locals.push(ScopeKind::Loop);
+                self.locals.define(loop_var, Some(TypeExpr::named("int")));
+                self.block(body, ScopeKind::Block);
+                self.locals.pop();
+            }
+            StatementKind::Match { value, arms } => {
+                let value_type = self.expr(value, None);
+                self.arms(arms, value_type.as_ref(), None);
+            }
+            StatementKind::Return { value: Some(value) } => {
+                let return_type = self.return_type.clone();
+                self.expr(value, return_type.as_ref());
+            }
+            StatementKind::Expr(expr) | StatementKind::Tail(expr) => {
+                self.expr(expr, None);
+            }
+            StatementKind::Return { value: None }
+            | StatementKind::Break
+            | StatementKind::Continue => {}
+        }
+    }
+
+    // Rewrite generic struct literals and calls in `expr` to the instances
+    // they use, returning its type. `expected` is the type the context
+    // asks for, used when the expression alone doesn't fix its type
+    // arguments.
+    fn expr(&mut self, expr: &mut Expr, expected: Option<&TypeExpr>) -> Option<TypeExpr> {
+        match &mut expr.kind {
+            ExprKind::Number { value, ty } => Some(TypeExpr::named(match ty {
+                Some(int_type) => int_type.name(),
+                None => match IntType::for_unsuffixed(*value) {
+                    IntType::I32 => "int",
+                    int_type => int_type.name(),
+                },
+            })),
+            ExprKind::Float { ty, .. } => {
+                Some(TypeExpr::named(ty.unwrap_or(FloatType::F64).name()))
+            }
+            ExprKind::Boolean(_) => Some(TypeExpr::named("bool")),
+            ExprKind::Char(_) => Some(TypeExpr::named("char")),
+            ExprKind::String(_) => Some(TypeExpr::named("string")),
+            ExprKind::Format(parts) => {
+                for part in parts {
+                    if let FormatPart::Value(value, _) = part {
+                        self.expr(value, None);
+                    }
+                }
+                Some(TypeExpr::named("string"))
+            }
+            ExprKind::Variable(name) => match self.locals.get(name) {
+                Ok(ty) => ty.clone(),
+                // A function named as a value
+                Err(_) => {
+                    let func = self.functions.get(name.as_str())?;
+                    Some(TypeExpr::Function {
+                        params: func.params.iter().map(|p| p.type_name.clone()).collect(),
+                        return_type: func.return_type.clone().map(Box::new),
+                    })
+                }
+            },
+            ExprKind::ArrayLiteral(elements) => {
+                let expected = expected
+                    .and_then(|ty| self.resolve_alias(ty).element())
+                    .cloned();
+                let types: Vec<Option<TypeExpr>> = elements
+                    .iter_mut()
+                    .map(|e| self.expr(e, expected.as_ref()))
+                    .collect();
+                // The first element fixes the element type
+                let element = types.into_iter().next()??;
+                let ty = TypeExpr::Array(Box::new(element), elements.len() as u64);
+                self.c_type(&ty);
+                Some(ty)
+            }
+            ExprKind::Tuple(elements) => {
+                let expected = expected
+                    .and_then(|ty| self.resolve_alias(ty).tuple_elements())
+                    .map(<[TypeExpr]>::to_vec);
+                let types: Vec<Option<TypeExpr>> = elements
+                    .iter_mut()
+                    .enumerate()
+                    .map(|(i, e)| {
+                        let element = expected.as_ref().and_then(|types| types.get(i));
+                        self.expr(e, element)
+                    })
+                    .collect();
+                let types: Option<Vec<TypeExpr>> = types.into_iter().collect();
+                let ty = TypeExpr::Tuple(types?);
+                self.c_type(&ty);
+                Some(ty)
+            }
+            ExprKind::ArrayIndex { array, index } => {
+                let array_type = self.expr(array, None);
+                self.expr(index, None);
+                array_type.and_then(|ty| self.resolve_alias(&ty).element().cloned())
+            }
+            ExprKind::StructLiteral {
+                struct_name,
+                fields,
+            } => self.struct_literal(struct_name, fields, expected),
+            ExprKind::MemberAccess { object, member } => {
+                let object_type = self.expr(object, None)?;
+                self.field_type(&object_type, member)
+            }
+            ExprKind::EnumVariant { enum_name, .. } => Some(TypeExpr::named(enum_name)),
+            ExprKind::UnionVariant {
+                union_name,
+                variant,
+                value,
+            } => {
+                let union_type = TypeExpr::named(union_name);
+                if let Some(value) = value {
+                    let payload = self.payload(&union_type, variant);
+                    self.expr(value, payload.as_ref());
+                }
+                Some(union_type)
+            }
+            ExprKind::Binary { left, op, right } => {
+                let left_type = self.expr(left, None);
+                let right_type = self.expr(right, None);
+                match op {
+                    BinOp::Add
+                    | BinOp::Sub
+                    | BinOp::Mul
+                    | BinOp::Div
+                    | BinOp::Mod
+                    | BinOp::BitAnd
+                    | BinOp::BitOr
+                    | BinOp::BitXor => self.arithmetic_type(&[left_type, right_type]),
+                    BinOp::Shl | BinOp::Shr => left_type,
+                    _ => Some(TypeExpr::named("bool")),
+                }
+            }
+            ExprKind::Unary { op, operand } => {
+                let operand_type = self.expr(operand, None);
+                match op {
+                    UnaryOp::Not => Some(TypeExpr::named("bool")),
+                    UnaryOp::Neg | UnaryOp::BitNot => operand_type,
+                }
+            }
+            ExprKind::Call { name, args } => {
+                let arg_types: Vec<Option<TypeExpr>> =
+                    args.iter_mut().map(|arg| self.expr(arg, None)).collect();
+                self.call(name, args, arg_types, expected)
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let mut arg_types = vec![self.expr(receiver, None)];
+                arg_types.extend(args.iter_mut().map(|arg| self.expr(arg, None)));
+                let inherent = arg_types[0].as_ref().and_then(|ty| {
+                    let key = (self.resolve_alias(ty).clone(), method.clone());
+                    self.inherent_methods.get(&key).cloned()
+                });
+                let mut name = inherent.unwrap_or_else(|| method.clone());
+                let mut call_args = vec![(**receiver).clone()];
+                call_args.append(args);
+                let ty = self.call(&mut name, &call_args, arg_types, expected);
+                expr.kind = ExprKind::Call {
+                    name,
+                    args: call_args,
+                };
+                ty
+            }
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expr(condition, None);
+                let then_type = self.value_block(then_block, expected);
+                let else_type = self.value_block(else_block, expected);
+                then_type.or(else_type)
+            }
+            ExprKind::Match { value, arms } => {
+                let value_type = self.expr(value, None);
+                self.arms(arms, value_type.as_ref(), expected)
+            }
+            ExprKind::Block(stmts) => self.value_block(stmts, expected),
+        }
+    }
+
+    fn struct_literal(
+        &mut self,
+        struct_name: &mut String,
+        fields: &mut [(String, Expr)],
+        expected: Option<&TypeExpr>,
+    ) -> Option<TypeExpr> {
+        let Some(struct_def) = self.structs.get(struct_name.as_str()).copied() else {
+            for (_, value) in fields {
+                self.expr(value, None);
+            }
+            return Some(TypeExpr::named(struct_name));
+        };
+        let params = &struct_def.type_params;
+        let mut bindings = Bindings::new();
+        if let Some(expected) = expected {
+            let pattern = TypeExpr::Named {
+                name: struct_def.name.clone(),
+                args: params.iter().map(|p| TypeExpr::named(p)).collect(),
+            };
+            generics::unify(
+                &pattern,
+                self.resolve_alias(expected),
+                params,
+                &mut bindings,
+            );
+        }
+        for (name, value) in fields {
+            let declared = struct_def.fields.iter().find(|f| &f.name == name);
+            let field_type = declared.map(|f| generics::substitute(&f.type_name, &bindings));
+            let field_type = field_type.filter(|ty| !generics::mentions(ty, params));
+            let value_type = self.expr(value, field_type.as_ref());
+            if let (Some(declared), Some(value_type)) = (declared, value_type) {
+                generics::unify(&declared.type_name, &value_type, params, &mut bindings);
+            }
+        }
+        if params.is_empty() {
+            return Some(TypeExpr::named(struct_name));
+        }
+
+        let args: Option<Vec<TypeExpr>> = params.iter().map(|p| bindings.get(p).cloned()).collect();
+        let Some(args) = args else {
+            unreachable!(
+                "the checker reports type arguments of '{}' it cannot infer",
+                struct_name
+            );
+        };
+        let ty = TypeExpr::Named {
+            name: struct_name.clone(),
+            args,
+        };
+        *struct_name = self.c_type(&ty).to_string();
+        Some(ty)
+    }
+
+    fn call(
+        &mut self,
+        name: &mut String,
+        args: &[Expr],
+        arg_types: Vec<Option<TypeExpr>>,
+        expected: Option<&TypeExpr>,
+    ) -> Option<TypeExpr> {
+        match name.as_str() {
+            "sqrt" | "abs" | "min" | "max" => return self.arithmetic_type(&arg_types),
+            "length" | "compare" | "array_length" | "pow" | "mod" | "file_write"
+            | "file_append" | "file_exists" => return Some(TypeExpr::named("int")),
+            "concat" | "substring" | "file_read" => return Some(TypeExpr::named("string")),
+            "cap_split" | "cap_attenuate" => {
+                return args
+                    .get(1)
+                    .and_then(capability::named_kind)
+                    .map(TypeExpr::named);
+            }
+            _ => {}
+        }
+
+        // A call through a local holding a function
+        if let Ok(Some(ty)) = self.locals.get(name) {
+            if let TypeExpr::Function { return_type, .. } = self.resolve_alias(ty) {
+                return return_type.as_deref().cloned();
+            }
+        }
+        let Some(func) = self.functions.get(name.as_str()).copied() else {
+            // A trait impl method: the impl for the first argument's type
+            let candidates = self.impl_returns.get(name.as_str())?;
+            let self_type = arg_types.first().cloned().flatten();
+            let method = candidates
+                .iter()
+                .find(|(ty, _)| {
+                    self_type
+                        .as_ref()
+                        .is_some_and(|s| self.resolve_alias(s) == self.resolve_alias(ty))
+                })
+                .or(candidates.first())?;
+            return method.1.cloned();
+        };
+        if func.type_params.is_empty() {
+            return func.return_type.clone();
+        }
+
+        let mut bindings = Bindings::new();
+        for (param, arg_type) in func.params.iter().zip(&arg_types) {
+            if let Some(arg_type) = arg_type {
+                generics::unify(&param.type_name, arg_type, &func.type_params, &mut bindings);
+            }
+        }
+        if let (Some(return_type), Some(expected)) = (&func.return_type, expected) {
+            let expected = self.resolve_alias(expected);
+            generics::unify(return_type, expected, &func.type_params, &mut bindings);
+        }
+        let args: Option<Vec<TypeExpr>> = func
+            .type_params
+            .iter()
+            .map(|p| bindings.get(p).cloned())
+            .collect();
+        let Some(args) = args else {
+            unreachable!(
+                "the checker reports type arguments of '{}' it cannot infer",
+                name
+            );
+        };
+
+        // `max` on ints is `max_int`
+        let mangled: Vec<String> = args.iter().map(generics::mangle_part).collect();
+        let instance = format!("{}_{}", func.name, mangled.join("_"));
+        if self.instances.insert(instance.clone()) {
+            self.pending
+                .push((instance.clone(), func, bindings.clone()));
+        }
+        *name = instance;
+        func.return_type
+            .as_ref()
+            .map(|ty| generics::substitute(ty, &bindings))
+    }
+
+    // Type of the field `field` of a struct type such as `Pair<int, string>`
+    fn field_type(&self, struct_type: &TypeExpr, field: &str) -> Option<TypeExpr> {
+        let struct_type = self.resolve_alias(struct_type);
+        if let Some(elements) = struct_type.tuple_elements() {
+            let index: usize = field.parse().ok()?;
+            return elements.get(index).cloned();
+        }
+        let TypeExpr::Named { name, args } = struct_type else {
+            return None;
+        };
+        let struct_def = self.structs.get(name.as_str())?;
+        let bindings: Bindings = struct_def
+            .type_params
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+        let field = struct_def.fields.iter().find(|f| f.name == field)?;
+        Some(generics::substitute(&field.type_name, &bindings))
+    }
+
+    // Names `pattern` binds when matched against a value of type `ty`,
+    // with their types
+    fn pattern_bindings(
+        &self,
+        pattern: &Pattern,
+        ty: Option<&TypeExpr>,
+    ) -> Vec<(String, Option<TypeExpr>)> {
+        match &pattern.kind {
+            PatternKind::Binding(name) => vec![(name.clone(), ty.cloned())],
+            PatternKind::UnionVariant {
+                name,
+                binding: Some(binding),
+            } => {
+                let payload = ty.and_then(|ty| self.payload(ty, name));
+                vec![(binding.clone(), payload)]
+            }
+            PatternKind::Struct { fields, .. } => fields
+                .iter()
+                .flat_map(|(field, pattern)| {
+                    let field_type = ty.and_then(|ty| self.field_type(ty, field));
+                    self.pattern_bindings(pattern, field_type.as_ref())
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Payload type of the variant `variant` of the union `union_type`
+    fn payload(&self, union_type: &TypeExpr, variant: &str) -> Option<TypeExpr> {
+        let union_type = self.unions.get(self.resolve_alias(union_type).as_name()?)?;
+        let variant = union_type.variants.iter().find(|v| v.name == variant)?;
+        variant.associated_type.clone()
+    }
+
+    // Result type of arithmetic: the widest float operand if there is one,
+    // otherwise the first known type
+    fn arithmetic_type(&self, types: &[Option<TypeExpr>]) -> Option<TypeExpr> {
+        let types: Vec<&TypeExpr> = types
+            .iter()
+            .flatten()
+            .map(|t| self.resolve_alias(t))
+            .collect();
+        let floats: Vec<FloatType> = types
+            .iter()
+            .filter_map(|t| FloatType::from_name(t.as_name()?))
+            .collect();
+        if floats.contains(&FloatType::F64) {
+            Some(TypeExpr::named("f64"))
+        } else if floats.contains(&FloatType::F32) {
+            Some(TypeExpr::named("f32"))
+        } else {
+            types.into_iter().next().cloned()
+        }
+    }
+
+    // C needs a struct defined before another struct holds it by value.
+    // Declaration order is kept where it already satisfies that.
+    fn declaration_order(&self, structs: Vec<StructDef>) -> Vec<StructDef> {
+        let index: HashMap<String, usize> = structs
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (c_struct_name(s), i))
+            .collect();
+        let mut visited = vec![false; structs.len()];
+        let mut order = Vec::new();
+        for i in 0..structs.len() {
+            self.visit_struct(i, &structs, &index, &mut visited, &mut order);
+        }
+        let mut structs: Vec<Option<StructDef>> = structs.into_iter().map(Some).collect();
+        order
+            .into_iter()
+            .filter_map(|i| structs[i].take())
+            .collect()
+    }
+
+    fn visit_struct(
+        &self,
+        i: usize,
+        structs: &[StructDef],
+        index: &HashMap<String, usize>,
+        visited: &mut [bool],
+        order: &mut Vec<usize>,
+    ) {
+        if visited[i] {
+            return;
+        }
+        visited[i] = true;
+        for field in &structs[i].fields {
+            // Arrays hold their elements, pointers only refer to them. An
+            // array field needs the struct of its type, which needs the
+            // struct of its elements.
+            let mut field_type = self.resolve_alias(&field.type_name);
+            loop {
+                if let Some(&dependency) = index.get(&generics::mangle(field_type)) {
+                    self.visit_struct(dependency, structs, index, visited, order);
+                }
+                match field_type {
+                    TypeExpr::Array(element, _) => field_type = self.resolve_alias(element),
+                    _ => break,
+                }
+            }
+        }
+        order.push(i);
+    }
+}
@@ -1,24 +1,78 @@
 // Parser module for Athōn bootstrap compiler
 
 use crate::ast::*;
-use crate::lexer::{Lexer, Token, TokenKind};
-use std::process;
+use crate::diagnostics::*;
+use crate::lexer::{
+    check_int_range, parse_float_literal, parse_int_literal, Interpolation, Lexer, Token, TokenKind,
+};
+use crate::source_map::{SourceMap, Span};
+use crate::variants;
+
+type ParseResult<T> = Result<T, Diagnostic>;
 
 pub struct Parser {
     lexer: Lexer,
     current: Token,
+    // End offset of the last consumed token, used to close node spans
+    prev_end: usize,
+    diagnostics: Vec<Diagnostic>,
+    // Warnings of a program that parsed, for the driver to report
+    warnings: Vec<Diagnostic>,
+    source_map: SourceMap,
+}
+
+// Saved parser position for lookahead/backtracking
+struct Checkpoint {
+    pos: usize,
+    line: usize,
+    column: usize,
+    current: Token,
+    prev_end: usize,
+    lexer_diagnostics: usize,
+    diagnostics: usize,
+    interpolations: Vec<Interpolation>,
 }
+
 impl Parser {
     pub fn new(input: &str) -> Self {
-        let mut lexer = Lexer::new(input);
+        Self::with_file(input, "<input>")
+    }
+
+    pub fn with_file(input: &str, file: &str) -> Self {
+        let mut source_map = SourceMap::new();
+        let file_id = source_map.add_file(file, input);
+        let mut lexer = Lexer::with_file(input, file_id);
         let current = lexer.next_token();
-        Self { lexer, current }
+        Self {
+            lexer,
+            current,
+            prev_end: 0,
+            diagnostics: Vec::new(),
+            warnings: Vec::new(),
+            source_map,
+        }
+    }
+
+    /// Warnings reported while parsing a program that had no errors
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Files read during parsing, for resolving spans in diagnostics
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
     }
 
     fn advance(&mut self) {
+        self.prev_end = self.current.span.end;
         self.current = self.lexer.next_token();
     }
 
+    /// Span from `start` to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        Span::new(start.file, start.start, self.prev_end.max(start.start))
+    }
+
     fn expect(&mut self, kind: TokenKind) -> bool {
         if self.current.kind == kind {
             self.advance();
@@ -28,324 +82,504 @@ impl Parser {
         }
     }
 
-    pub fn parse_program(&mut self) -> Program {
-        let mut type_aliases = Vec::new();
-        let mut structs = Vec::new();
-        let mut enums = Vec::new();
-        let mut unions = Vec::new();
-        let mut traits = Vec::new();
-        let mut impls = Vec::new();
-        let mut functions = Vec::new();
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            pos: self.lexer.pos,
+            line: self.lexer.line,
+            column: self.lexer.column,
+            current: self.current.clone(),
+            prev_end: self.prev_end,
+            lexer_diagnostics: self.lexer.diagnostics.len(),
+            diagnostics: self.diagnostics.len(),
+            interpolations: self.lexer.interpolations.clone(),
+        }
+    }
 
-        while self.current.kind != TokenKind::EOF {
-            if self.current.kind == TokenKind::Type {
-                // Could be type alias or union type - peek ahead to determine
-                let saved_pos = self.lexer.pos;
-                let saved_line = self.lexer.line;
-                let saved_column = self.lexer.column;
-                let saved_current = self.current.clone();
-                
-                self.advance(); // consume 'type'
-                if self.current.kind == TokenKind::Identifier {
-                    self.advance(); // consume name
-                    if self.current.kind == TokenKind::Equals {
-                        self.advance(); // consume '='
-                        
-                        // Now check if first token is an identifier followed by '('
-                        // which indicates a union variant like Ok(int)
-                        let is_union = if self.current.kind == TokenKind::Identifier {
-                            let saved_pos2 = self.lexer.pos;
-                            let saved_line2 = self.lexer.line;
-                            let saved_column2 = self.lexer.column;
-                            let saved_current2 = self.current.clone();
-                            
-                            self.advance();
-                            let has_paren = self.current.kind == TokenKind::LParen;
-                            
-                            // Restore
-                            self.lexer.pos = saved_pos2;
-                            self.lexer.line = saved_line2;
-                            self.lexer.column = saved_column2;
-                            self.current = saved_current2;
-                            
-                            has_paren
-                        } else {
-                            false
-                        };
-                        
-                        // Restore to beginning and parse appropriately
-                        self.lexer.pos = saved_pos;
-                        self.lexer.line = saved_line;
-                        self.lexer.column = saved_column;
-                        self.current = saved_current;
-                        
-                        if is_union {
-                            unions.push(self.parse_union_type());
-                        } else {
-                            type_aliases.push(self.parse_type_alias());
-                        }
-                    } else {
-                        eprintln!("Error: Expected '=' after type name");
-                        process::exit(1);
+    fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.lexer.pos = checkpoint.pos;
+        self.lexer.line = checkpoint.line;
+        self.lexer.column = checkpoint.column;
+        self.lexer.interpolations = checkpoint.interpolations;
+        self.current = checkpoint.current;
+        self.prev_end = checkpoint.prev_end;
+        // Tokens will be lexed again, so drop anything reported while looking ahead
+        self.lexer
+            .diagnostics
+            .truncate(checkpoint.lexer_diagnostics);
+        self.diagnostics.truncate(checkpoint.diagnostics);
+    }
+
+    // --- Error reporting ---
+
+    fn error_here(&self, code: &'static str, message: String) -> Diagnostic {
+        Diagnostic::error(code, message, self.current.span)
+    }
+
+    // Value and type of the integer literal at the current token, which
+    // follows a unary minus when `negative`. Malformed literals were
+    // already reported by the lexer; one that doesn't fit its type is
+    // reported here, where the sign is known.
+    fn int_literal(&mut self, negative: bool) -> (u64, Option<IntType>) {
+        let text = &self.current.text;
+        let (value, ty) = parse_int_literal(text).unwrap_or((0, None));
+        if let Err(message) = check_int_range(text, value, ty, negative) {
+            self.diagnostics
+                .push(self.error_here(E_INVALID_LITERAL, message));
+        }
+        (value, ty)
+    }
+
+    fn describe_current(&self) -> String {
+        if self.current.kind == TokenKind::EOF {
+            "end of file".to_string()
+        } else {
+            format!("'{}'", self.current.text)
+        }
+    }
+
+    /// Consume a token of the given kind or fail with `message`
+    fn consume(&mut self, kind: TokenKind, message: &str) -> ParseResult<()> {
+        if self.expect(kind) {
+            Ok(())
+        } else {
+            Err(self.error_here(
+                E_EXPECTED_TOKEN,
+                format!("{}, found {}", message, self.describe_current()),
+            ))
+        }
+    }
+
+    /// Consume an identifier and return its text or fail with `message`
+    fn consume_identifier(&mut self, message: &str) -> ParseResult<String> {
+        self.consume_name(E_EXPECTED_NAME, message)
+    }
+
+    /// Parse a type or fail with `message`: a name with optional type
+    /// arguments, `*T`, `[]T`, `[T; N]`, `fn(A, B) -> R`, `(A, B)` or
+    /// `dyn Trait`
+    fn parse_type(&mut self, message: &str) -> ParseResult<TypeExpr> {
+        if self.at_dyn() {
+            self.advance();
+            let trait_name =
+                self.consume_name(E_EXPECTED_TYPE, "Expected trait name after 'dyn'")?;
+            return Ok(TypeExpr::Dyn(trait_name));
+        }
+        match self.current.kind {
+            TokenKind::Star => {
+                self.advance();
+                let target = self.parse_type("Expected type after '*'")?;
+                return Ok(TypeExpr::Pointer(Box::new(target)));
+            }
+            TokenKind::LBracket => {
+                self.advance();
+                if self.expect(TokenKind::RBracket) {
+                    let element = self.parse_type("Expected slice element type")?;
+                    return Ok(TypeExpr::Slice(Box::new(element)));
+                }
+                let element = self.parse_type("Expected array element type")?;
+                self.consume(TokenKind::Semicolon, "Expected ';' before array length")?;
+                let len = match parse_int_literal(&self.current.text) {
+                    Ok((len, _)) if self.current.kind == TokenKind::Number => len,
+                    _ => {
+                        return Err(self.error_here(
+                            E_EXPECTED_TYPE,
+                            format!("Expected array length, found {}", self.describe_current()),
+                        ))
+                    }
+                };
+                self.advance();
+                self.consume(TokenKind::RBracket, "Expected ']' after array length")?;
+                return Ok(TypeExpr::Array(Box::new(element), len));
+            }
+            TokenKind::Fn => {
+                self.advance();
+                self.consume(
+                    TokenKind::LParen,
+                    "Expected '(' after 'fn' in function type",
+                )?;
+                let mut params = Vec::new();
+                while self.current.kind != TokenKind::RParen {
+                    params.push(self.parse_type("Expected parameter type")?);
+                    if !self.expect(TokenKind::Comma) {
+                        break;
                     }
+                }
+                self.consume(TokenKind::RParen, "Expected ')' after parameter types")?;
+                let return_type = if self.expect(TokenKind::Arrow) {
+                    Some(Box::new(
+                        self.parse_type("Expected return type after '->'")?,
+                    ))
                 } else {
-                    eprintln!("Error: Expected type name");
-                    process::exit(1);
+                    None
+                };
+                return Ok(TypeExpr::Function {
+                    params,
+                    return_type,
+                });
+            }
+            TokenKind::LParen => {
+                self.advance();
+                let mut elements = vec![self.parse_type("Expected tuple element type")?];
+                while self.expect(TokenKind::Comma) && self.current.kind != TokenKind::RParen {
+                    elements.push(self.parse_type("Expected tuple element type")?);
+                }
+                if elements.len() < 2 {
+                    return Err(self.error_here(
+                        E_EXPECTED_TYPE,
+                        "A tuple type needs at least two elements".to_string(),
+                    ));
+                }
+                self.consume(TokenKind::RParen, "Expected ')' after tuple element types")?;
+                return Ok(TypeExpr::Tuple(elements));
+            }
+            _ => {}
+        }
+        let name = self.consume_name(E_EXPECTED_TYPE, message)?;
+        let mut args = Vec::new();
+        if self.expect(TokenKind::LessThan) {
+            loop {
+                args.push(self.parse_type("Expected type argument")?);
+                if !self.expect(TokenKind::Comma) {
+                    break;
                 }
-            } else if self.current.kind == TokenKind::Struct {
-                structs.push(self.parse_struct());
-            } else if self.current.kind == TokenKind::Enum {
-                enums.push(self.parse_enum());
-            } else if self.current.kind == TokenKind::Trait {
-                traits.push(self.parse_trait());
-            } else if self.current.kind == TokenKind::Impl {
-                impls.push(self.parse_impl());
-            } else if self.current.kind == TokenKind::Fn {
-                functions.push(self.parse_function());
-            } else if self.current.kind == TokenKind::Import {
-                let imported_program = self.parse_import();
-                type_aliases.extend(imported_program.type_aliases);
-                structs.extend(imported_program.structs);
-                enums.extend(imported_program.enums);
-                unions.extend(imported_program.unions);
-                traits.extend(imported_program.traits);
-                impls.extend(imported_program.impls);
-                functions.extend(imported_program.functions);
-            } else {
-                eprintln!(
-                    "Error at line {}, column {}: Expected 'type', 'import', 'struct', 'enum', 'trait', 'impl', or 'fn', got '{}'",
-                    self.current.line, self.current.column, self.current.text
-                );
-                process::exit(1);
             }
+            self.consume_closing_angle()?;
         }
+        Ok(TypeExpr::Named { name, args })
+    }
 
-        Program {
-            type_aliases,
-            structs,
-            enums,
-            unions,
-            traits,
-            impls,
-            functions,
+    // `dyn` is contextual so it stays usable as a name
+    fn at_dyn(&mut self) -> bool {
+        if self.current.kind != TokenKind::Identifier || self.current.text != "dyn" {
+            return false;
         }
+        let start = self.checkpoint();
+        self.advance();
+        let is_dyn = self.current.kind == TokenKind::Identifier;
+        self.rewind(start);
+        is_dyn
     }
 
-    fn parse_import(&mut self) -> Program {
-        if !self.expect(TokenKind::Import) {
-            eprintln!("Expected 'import'");
-            process::exit(1);
+    // The lexer reads `>>` as a shift, but in `Box<Pair<T, U>>` it closes
+    // two argument lists: consume its first half and leave the second
+    fn consume_closing_angle(&mut self) -> ParseResult<()> {
+        if self.current.kind != TokenKind::ShiftRight {
+            self.consume(TokenKind::GreaterThan, "Expected '>' after type arguments")?;
+            return Ok(());
         }
+        let span = self.current.span;
+        self.current.kind = TokenKind::GreaterThan;
+        self.current.text = ">".to_string();
+        self.current.span = Span::new(span.file, span.start + 1, span.end);
+        self.current.column += 1;
+        self.prev_end = span.start + 1;
+        Ok(())
+    }
 
-        let filename = if self.current.kind == TokenKind::StringLiteral {
-            let s = self.current.text.clone();
+    fn consume_name(&mut self, code: &'static str, message: &str) -> ParseResult<String> {
+        if self.current.kind == TokenKind::Identifier {
+            let name = self.current.text.clone();
             self.advance();
-            s
+            Ok(name)
         } else {
-            eprintln!("Expected string literal after 'import'");
-            process::exit(1);
-        };
+            Err(self.error_here(
+                code,
+                format!("{}, found {}", message, self.describe_current()),
+            ))
+        }
+    }
 
-        // Read file content
-        let content = match std::fs::read_to_string(&filename) {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!("Error reading imported file '{}': {}", filename, e);
-                process::exit(1);
-            }
-        };
+    // --- Error recovery ---
+
+    fn is_item_start(kind: TokenKind) -> bool {
+        matches!(
+            kind,
+            TokenKind::Fn
+                | TokenKind::Struct
+                | TokenKind::Enum
+                | TokenKind::Type
+                | TokenKind::Trait
+                | TokenKind::Impl
+                | TokenKind::Import
+        )
+    }
 
-        // Parse imported content
-        let mut parser = Parser::new(&content);
-        parser.parse_program()
+    fn is_statement_start(kind: TokenKind) -> bool {
+        matches!(
+            kind,
+            TokenKind::Let
+                | TokenKind::If
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::Match
+                | TokenKind::Return
+                | TokenKind::Break
+                | TokenKind::Continue
+        )
     }
 
-    fn parse_type_alias(&mut self) -> TypeAlias {
-        if !self.expect(TokenKind::Type) {
-            eprintln!(
-                "Error at line {}, column {}: Expected 'type'",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
+    /// Skip tokens until the start of the next top-level item
+    fn synchronize_item(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.current.kind {
+                TokenKind::EOF => return,
+                kind if depth == 0 && Self::is_item_start(kind) => return,
+                TokenKind::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        self.advance();
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            self.advance();
         }
+    }
 
-        let name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
+    /// Skip tokens until the end of the current statement
+    fn synchronize_statement(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.current.kind {
+                TokenKind::EOF => return,
+                TokenKind::RBrace if depth == 0 => return,
+                TokenKind::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                kind if depth == 0
+                    && (Self::is_statement_start(kind) || Self::is_item_start(kind)) =>
+                {
+                    return
+                }
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
             self.advance();
-            n
-        } else {
-            eprintln!(
-                "Error at line {}, column {}: Expected type alias name",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
-        };
-
-        if !self.expect(TokenKind::Equals) {
-            eprintln!(
-                "Error at line {}, column {}: Expected '=' after type alias name",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
         }
+    }
 
-        let target_type = if self.current.kind == TokenKind::Identifier {
-            let t = self.current.text.clone();
-            self.advance();
-            t
+    // --- Items ---
+
+    pub fn parse_program(&mut self) -> Result<Program, Vec<Diagnostic>> {
+        let mut program = Program::default();
+        self.parse_items(&mut program);
+        variants::resolve(&mut program);
+
+        let mut diagnostics = std::mem::take(&mut self.lexer.diagnostics);
+        diagnostics.append(&mut self.diagnostics);
+        if has_errors(&diagnostics) {
+            Err(diagnostics)
         } else {
-            eprintln!(
-                "Error at line {}, column {}: Expected target type",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
-        };
+            self.warnings = diagnostics;
+            Ok(program)
+        }
+    }
 
-        if !self.expect(TokenKind::Semicolon) {
-            eprintln!(
-                "Error at line {}, column {}: Expected ';' after type alias",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
+    fn parse_items(&mut self, program: &mut Program) {
+        while self.current.kind != TokenKind::EOF {
+            if let Err(diagnostic) = self.parse_item(program) {
+                self.diagnostics.push(diagnostic);
+                self.synchronize_item();
+            }
         }
+    }
+
+    fn parse_item(&mut self, program: &mut Program) -> ParseResult<()> {
+        if self.current.kind == TokenKind::Type {
+            // Could be type alias or union type - peek ahead to determine
+            let start = self.checkpoint();
+
+            self.advance(); // consume 'type'
+            self.consume_identifier("Expected type name")?;
+            self.consume(TokenKind::Equals, "Expected '=' after type name")?;
+
+            // Now check if first token is an identifier followed by '('
+            // which indicates a union variant like Ok(int)
+            let is_union = if self.current.kind == TokenKind::Identifier {
+                let variant = self.checkpoint();
+                self.advance();
+                let has_paren = self.current.kind == TokenKind::LParen;
+                self.rewind(variant);
+                has_paren
+            } else {
+                false
+            };
+
+            // Restore to beginning and parse appropriately
+            self.rewind(start);
 
-        TypeAlias { name, target_type }
+            if is_union {
+                program.unions.push(self.parse_union_type()?);
+            } else {
+                program.type_aliases.push(self.parse_type_alias()?);
+            }
+        } else if self.current.kind == TokenKind::Struct || self.at_struct_modifier() {
+            program.structs.push(self.parse_struct()?);
+        } else if self.current.kind == TokenKind::Enum {
+            program.enums.push(self.parse_enum()?);
+        } else if self.current.kind == TokenKind::Trait {
+            program.traits.push(self.parse_trait()?);
+        } else if self.current.kind == TokenKind::Impl {
+            self.parse_impl(program)?;
+        } else if self.current.kind == TokenKind::Fn {
+            program.functions.push(self.parse_function()?);
+        } else if self.current.kind == TokenKind::Import {
+            self.parse_import(program)?;
+        } else {
+            return Err(self.error_here(
+                E_EXPECTED_ITEM,
+                format!(
+                    "Expected 'type', 'import', 'struct', 'enum', 'trait', 'impl', or 'fn', found {}",
+                    self.describe_current()
+                ),
+            ));
+        }
+        Ok(())
     }
 
-    fn parse_enum(&mut self) -> EnumDef {
-        if !self.expect(TokenKind::Enum) {
-            eprintln!(
-                "Error at line {}, column {}: Expected 'enum'",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
+    fn parse_import(&mut self, program: &mut Program) -> ParseResult<()> {
+        self.consume(TokenKind::Import, "Expected 'import'")?;
+
+        if self.current.kind != TokenKind::StringLiteral {
+            return Err(self.error_here(
+                E_EXPECTED_TOKEN,
+                format!(
+                    "Expected string literal after 'import', found {}",
+                    self.describe_current()
+                ),
+            ));
         }
+        let filename = self.current.text.clone();
 
-        let name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
+        if self.source_map.find(&filename).is_some() {
+            // Already loaded through another import (or an import cycle)
             self.advance();
-            n
-        } else {
-            eprintln!(
-                "Error at line {}, column {}: Expected enum name",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
+            return Ok(());
+        }
+
+        // Read file content
+        let content = match std::fs::read_to_string(&filename) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(self.error_here(
+                    E_IMPORT_FAILED,
+                    format!("Error reading imported file '{}': {}", filename, e),
+                ));
+            }
         };
+        self.advance();
 
-        if !self.expect(TokenKind::LBrace) {
-            eprintln!(
-                "Error at line {}, column {}: Expected '{{'",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
-        }
+        // Parse the imported file with this parser so its items, spans and
+        // diagnostics all land in the same program and source map
+        let file = self.source_map.add_file(&filename, &content);
+        let mut lexer = Lexer::with_file(&content, file);
+        let current = lexer.next_token();
+        let outer_lexer = std::mem::replace(&mut self.lexer, lexer);
+        let outer_current = std::mem::replace(&mut self.current, current);
+        let outer_prev_end = self.prev_end;
+
+        self.parse_items(program);
+
+        let mut imported = std::mem::replace(&mut self.lexer, outer_lexer);
+        self.diagnostics.append(&mut imported.diagnostics);
+        self.current = outer_current;
+        self.prev_end = outer_prev_end;
+
+        Ok(())
+    }
+
+    fn parse_type_alias(&mut self) -> ParseResult<TypeAlias> {
+        let start = self.current.span;
+        self.consume(TokenKind::Type, "Expected 'type'")?;
+        let name = self.consume_identifier("Expected type alias name")?;
+        self.consume(TokenKind::Equals, "Expected '=' after type alias name")?;
+        let target_type = self.parse_type("Expected target type")?;
+        self.consume(TokenKind::Semicolon, "Expected ';' after type alias")?;
+
+        Ok(TypeAlias {
+            name,
+            target_type,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_enum(&mut self) -> ParseResult<EnumDef> {
+        let start = self.current.span;
+        self.consume(TokenKind::Enum, "Expected 'enum'")?;
+        let name = self.consume_identifier("Expected enum name")?;
+        self.consume(TokenKind::LBrace, "Expected '{'")?;
 
         let mut variants = Vec::new();
         while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-            let variant_name = if self.current.kind == TokenKind::Identifier {
-                let n = self.current.text.clone();
-                self.advance();
-                n
-            } else {
-                eprintln!(
-                    "Error at line {}, column {}: Expected variant name",
-                    self.current.line, self.current.column
-                );
-                process::exit(1);
-            };
-
-            variants.push(variant_name);
+            variants.push(self.consume_identifier("Expected variant name")?);
 
             if !self.expect(TokenKind::Comma) {
                 break;
             }
         }
 
-        if !self.expect(TokenKind::RBrace) {
-            eprintln!(
-                "Error at line {}, column {}: Expected '}}'",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
-        }
+        self.consume(TokenKind::RBrace, "Expected '}'")?;
 
-        EnumDef { name, variants }
+        Ok(EnumDef {
+            name,
+            variants,
+            span: self.span_from(start),
+        })
     }
 
-    fn parse_struct(&mut self) -> StructDef {
-        if !self.expect(TokenKind::Struct) {
-            eprintln!(
-                "Error at line {}, column {}: Expected 'struct'",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
+    // `linear` and `affine` are contextual so they stay usable as names
+    fn at_struct_modifier(&mut self) -> bool {
+        if self.current.kind != TokenKind::Identifier
+            || !matches!(self.current.text.as_str(), "linear" | "affine")
+        {
+            return false;
         }
+        let start = self.checkpoint();
+        self.advance();
+        let is_modifier = self.current.kind == TokenKind::Struct;
+        self.rewind(start);
+        is_modifier
+    }
 
-        let name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
-            self.advance();
-            n
-        } else {
-            eprintln!(
-                "Error at line {}, column {}: Expected struct name",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
+    fn parse_struct(&mut self) -> ParseResult<StructDef> {
+        let start = self.current.span;
+        let linearity = match self.current.text.as_str() {
+            "linear" if self.current.kind == TokenKind::Identifier => Linearity::Linear,
+            "affine" if self.current.kind == TokenKind::Identifier => Linearity::Affine,
+            _ => Linearity::Unrestricted,
         };
+        if linearity != Linearity::Unrestricted {
+            self.advance();
+        }
+        self.consume(TokenKind::Struct, "Expected 'struct'")?;
+        let name = self.consume_identifier("Expected struct name")?;
 
         // Parse optional type parameters: <T, U>
-        let type_params = if self.current.kind == TokenKind::LessThan {
-            self.parse_type_parameters()
-        } else {
-            Vec::new()
-        };
+        let (type_params, mut bounds) = self.parse_type_parameters()?;
+        bounds.extend(self.parse_where_clause()?);
 
-        if !self.expect(TokenKind::LBrace) {
-            eprintln!(
-                "Error at line {}, column {}: Expected '{{'",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
-        }
+        self.consume(TokenKind::LBrace, "Expected '{'")?;
 
         let mut fields = Vec::new();
         while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-            let field_name = if self.current.kind == TokenKind::Identifier {
-                let n = self.current.text.clone();
-                self.advance();
-                n
-            } else {
-                eprintln!(
-                    "Error at line {}, column {}: Expected field name",
-                    self.current.line, self.current.column
-                );
-                process::exit(1);
-            };
-
-            if !self.expect(TokenKind::Colon) {
-                eprintln!(
-                    "Error at line {}, column {}: Expected ':' after field name",
-                    self.current.line, self.current.column
-                );
-                process::exit(1);
-            }
-
-            let type_name = if self.current.kind == TokenKind::Identifier {
-                let t = self.current.text.clone();
-                self.advance();
-                t
-            } else {
-                eprintln!(
-                    "Error at line {}, column {}: Expected type",
-                    self.current.line, self.current.column
-                );
-                process::exit(1);
-            };
+            let field_start = self.current.span;
+            let field_name = self.consume_identifier("Expected field name")?;
+            self.consume(TokenKind::Colon, "Expected ':' after field name")?;
+            let type_name = self.parse_type("Expected type")?;
 
             fields.push(StructField {
                 name: field_name,
                 type_name,
+                span: self.span_from(field_start),
             });
 
             if !self.expect(TokenKind::Comma) {
@@ -353,80 +587,80 @@ impl Parser {
             }
         }
 
-        if !self.expect(TokenKind::RBrace) {
-            eprintln!(
-                "Error at line {}, column {}: Expected '}}'",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
-        }
+        self.consume(TokenKind::RBrace, "Expected '}'")?;
 
-        StructDef { name, type_params, fields }
+        Ok(StructDef {
+            name,
+            type_params,
+            bounds,
+            fields,
+            linearity,
+            span: self.span_from(start),
+        })
     }
 
-    fn parse_function(&mut self) -> Function {
-        if !self.expect(TokenKind::Fn) {
-            eprintln!(
-                "Error at line {}, column {}: Expected 'fn'",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
-        }
-
-        let name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
-            self.advance();
-            n
-        } else {
-            eprintln!(
-                "Error at line {}, column {}: Expected function name",
-                self.current.line, self.current.column
-            );
-            process::exit(1);
-        };
+    fn parse_function(&mut self) -> ParseResult<Function> {
+        let start = self.current.span;
+        self.consume(TokenKind::Fn, "Expected 'fn'")?;
+        let name = self.consume_identifier("Expected function name")?;
 
         // Parse optional type parameters: <T, U>
-        let type_params = if self.current.kind == TokenKind::LessThan {
-            self.parse_type_parameters()
+        let (type_params, mut bounds) = self.parse_type_parameters()?;
+
+        self.consume(TokenKind::LParen, "Expected '('")?;
+        let params = self.parse_parameters()?;
+        self.consume(TokenKind::RParen, "Expected ')'")?;
+
+        // Parse optional return type
+        let return_type = if self.expect(TokenKind::Arrow) {
+            Some(self.parse_type("Expected return type after '->'")?)
         } else {
-            Vec::new()
+            None
         };
-
-        if !self.expect(TokenKind::LParen) {
-            eprintln!("Expected '('");
-            process::exit(1);
+        bounds.extend(self.parse_where_clause()?);
+
+        let mut body = self.parse_block("function body")?;
+        if return_type.is_some() && yields_value(&body) {
+            // The body's value is what the function returns
+            end_in_value(&mut body);
+            if let Some(last) = body.last_mut() {
+                last.kind = match std::mem::replace(&mut last.kind, StatementKind::Break) {
+                    StatementKind::Tail(value) => StatementKind::Return { value: Some(value) },
+                    kind => kind,
+                };
+            }
         }
 
-        // Parse parameters
+        Ok(Function {
+            name,
+            type_params,
+            bounds,
+            params,
+            return_type,
+            body,
+            span: self.span_from(start),
+        })
+    }
+
+    // Parse a parameter list up to (but not including) the closing ')'
+    fn parse_parameters(&mut self) -> ParseResult<Vec<Parameter>> {
         let mut params = Vec::new();
         if self.current.kind != TokenKind::RParen {
             loop {
-                let param_name = if self.current.kind == TokenKind::Identifier {
-                    let n = self.current.text.clone();
-                    self.advance();
-                    n
-                } else {
-                    eprintln!("Expected parameter name");
-                    process::exit(1);
-                };
-
-                if !self.expect(TokenKind::Colon) {
-                    eprintln!("Expected ':' after parameter name");
-                    process::exit(1);
-                }
-
-                let type_name = if self.current.kind == TokenKind::Identifier {
-                    let t = self.current.text.clone();
-                    self.advance();
-                    t
+                let start = self.current.span;
+                let name = self.consume_identifier("Expected parameter name")?;
+                let type_name = if name == "self" && self.current.kind != TokenKind::Colon {
+                    // `self` alone is `self: Self`
+                    TypeExpr::named(SELF_TYPE)
                 } else {
-                    eprintln!("Expected type after ':'");
-                    process::exit(1);
+                    self.consume(TokenKind::Colon, "Expected ':' after parameter name")?;
+                    self.parse_type("Expected type after ':'")?
                 };
 
                 params.push(Parameter {
-                    name: param_name,
+                    name,
                     type_name,
+                    span: self.span_from(start),
                 });
 
                 if !self.expect(TokenKind::Comma) {
@@ -434,695 +668,700 @@ impl Parser {
                 }
             }
         }
+        Ok(params)
+    }
 
-        if !self.expect(TokenKind::RParen) {
-            eprintln!("Expected ')'");
-            process::exit(1);
-        }
-
-        // Parse optional return type
-        let return_type = if self.current.kind == TokenKind::Arrow {
-            self.advance();
-            if self.current.kind == TokenKind::Identifier {
-                let t = self.current.text.clone();
-                self.advance();
-                Some(t)
-            } else {
-                eprintln!("Expected return type after '->'");
-                process::exit(1);
-            }
-        } else {
-            None
-        };
+    // --- Statements ---
 
-        if !self.expect(TokenKind::LBrace) {
-            eprintln!("Expected '{{'");
-            process::exit(1);
-        }
+    /// Parse `{ statements }`, recovering from errors in individual statements
+    fn parse_block(&mut self, context: &str) -> ParseResult<Vec<Statement>> {
+        self.consume(
+            TokenKind::LBrace,
+            &format!("Expected '{{' to start {}", context),
+        )?;
 
         let mut statements = Vec::new();
-
         while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-            statements.push(self.parse_statement());
-        }
+            if Self::is_item_start(self.current.kind) {
+                // Most likely a missing '}': let the item parser pick up from here
+                self.diagnostics.push(self.error_here(
+                    E_EXPECTED_TOKEN,
+                    format!(
+                        "Expected '}}' to close {}, found {}",
+                        context,
+                        self.describe_current()
+                    ),
+                ));
+                return Ok(statements);
+            }
 
-        if !self.expect(TokenKind::RBrace) {
-            eprintln!("Expected '}}'");
-            process::exit(1);
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.synchronize_statement();
+                }
+            }
         }
 
-        Function {
-            name,
-            type_params,
-            params,
-            return_type,
-            body: statements,
-        }
+        self.consume(
+            TokenKind::RBrace,
+            &format!("Expected '}}' to close {}", context),
+        )?;
+
+        Ok(statements)
     }
 
-    fn parse_statement(&mut self) -> Statement {
-        if self.current.kind == TokenKind::Let {
-            self.advance();
+    fn parse_statement(&mut self) -> ParseResult<Statement> {
+        let start = self.current.span;
+        let kind = self.parse_statement_kind()?;
+        Ok(Statement::new(kind, self.span_from(start)))
+    }
 
-            let name = if self.current.kind == TokenKind::Identifier {
-                let n = self.current.text.clone();
-                self.advance();
-                n
+    // `let [mut] (a, mut b, (c, _))[: type_name] = value;`, after the `(`
+    fn parse_let_tuple(&mut self, mutable: bool) -> ParseResult<StatementKind> {
+        let elements = self.parse_let_patterns(mutable)?;
+        let type_name = if self.expect(TokenKind::Colon) {
+            Some(self.parse_type("Expected type after ':'")?)
+        } else {
+            None
+        };
+        self.consume(TokenKind::Equals, "Expected '=' after let binding")?;
+        let value = self.parse_expr()?;
+        self.consume(TokenKind::Semicolon, "Expected ';' after let statement")?;
+
+        Ok(StatementKind::LetTuple {
+            elements,
+            type_name,
+            value,
+        })
+    }
+
+    // The elements of a tuple pattern through its `)`; `mutable` is set
+    // under `let mut` and makes every name mutable
+    fn parse_let_patterns(&mut self, mutable: bool) -> ParseResult<Vec<LetPattern>> {
+        let mut elements = Vec::new();
+        loop {
+            let element = if self.expect(TokenKind::Underscore) {
+                LetPattern::Wildcard
+            } else if self.expect(TokenKind::LParen) {
+                LetPattern::Tuple(self.parse_let_patterns(mutable)?)
             } else {
-                eprintln!("Expected identifier after 'let'");
-                process::exit(1);
+                let mutable = self.expect(TokenKind::Mut) || mutable;
+                let name = self.consume_identifier("Expected name in tuple pattern")?;
+                LetPattern::Name { name, mutable }
             };
-
-            if !self.expect(TokenKind::Equals) {
-                eprintln!("Expected '=' after let binding");
-                process::exit(1);
+            elements.push(element);
+            if !self.expect(TokenKind::Comma) || self.current.kind == TokenKind::RParen {
+                break;
             }
+        }
+        if elements.len() < 2 {
+            return Err(self.error_here(
+                E_EXPECTED_PATTERN,
+                "A tuple pattern needs at least two names".to_string(),
+            ));
+        }
+        self.consume(TokenKind::RParen, "Expected ')' after tuple pattern")?;
+        Ok(elements)
+    }
 
-            let value = self.parse_expr();
+    fn parse_statement_kind(&mut self) -> ParseResult<StatementKind> {
+        if self.current.kind == TokenKind::Let {
+            self.advance();
 
-            if !self.expect(TokenKind::Semicolon) {
-                eprintln!("Expected ';' after let statement");
-                process::exit(1);
+            let mutable = self.expect(TokenKind::Mut);
+            if self.expect(TokenKind::LParen) {
+                return self.parse_let_tuple(mutable);
             }
+            let name = self.consume_identifier("Expected identifier after 'let'")?;
+            let type_name = if self.expect(TokenKind::Colon) {
+                Some(self.parse_type("Expected type after ':'")?)
+            } else {
+                None
+            };
+            self.consume(TokenKind::Equals, "Expected '=' after let binding")?;
+            let value = self.parse_expr()?;
+            self.consume(TokenKind::Semicolon, "Expected ';' after let statement")?;
 
-            Statement::Let { name, value }
+            Ok(StatementKind::Let {
+                name,
+                mutable,
+                type_name,
+                value,
+            })
         } else if self.current.kind == TokenKind::Return {
             self.advance();
 
             let value = if self.current.kind == TokenKind::Semicolon {
                 None
             } else {
-                Some(self.parse_expr())
+                Some(self.parse_expr()?)
             };
 
-            if !self.expect(TokenKind::Semicolon) {
-                eprintln!("Expected ';' after return statement");
-                process::exit(1);
-            }
+            self.consume(TokenKind::Semicolon, "Expected ';' after return statement")?;
 
-            Statement::Return { value }
+            Ok(StatementKind::Return { value })
         } else if self.current.kind == TokenKind::Break {
             self.advance();
+            self.consume(TokenKind::Semicolon, "Expected ';' after break")?;
 
-            if !self.expect(TokenKind::Semicolon) {
-                eprintln!("Expected ';' after break");
-                process::exit(1);
-            }
-
-            Statement::Break
+            Ok(StatementKind::Break)
         } else if self.current.kind == TokenKind::Continue {
             self.advance();
+            self.consume(TokenKind::Semicolon, "Expected ';' after continue")?;
 
-            if !self.expect(TokenKind::Semicolon) {
-                eprintln!("Expected ';' after continue");
-                process::exit(1);
-            }
-
-            Statement::Continue
+            Ok(StatementKind::Continue)
         } else if self.current.kind == TokenKind::If {
             self.advance();
 
-            let condition = self.parse_expr();
-
-            if !self.expect(TokenKind::LBrace) {
-                eprintln!("Expected '{{' after if condition");
-                process::exit(1);
-            }
-
-            let mut then_block = Vec::new();
-            while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-                then_block.push(self.parse_statement());
-            }
-
-            if !self.expect(TokenKind::RBrace) {
-                eprintln!("Expected '}}' after if block");
-                process::exit(1);
-            }
-
-            let else_block = if self.current.kind == TokenKind::Else {
-                self.advance();
-
-                if !self.expect(TokenKind::LBrace) {
-                    eprintln!("Expected '{{' after else");
-                    process::exit(1);
-                }
-
-                let mut else_stmts = Vec::new();
-                while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF
-                {
-                    else_stmts.push(self.parse_statement());
-                }
-
-                if !self.expect(TokenKind::RBrace) {
-                    eprintln!("Expected '}}' after else block");
-                    process::exit(1);
-                }
+            let condition = self.parse_expr()?;
+            let then_block = self.parse_block("if block")?;
 
-                Some(else_stmts)
-            } else {
+            let else_block = if !self.expect(TokenKind::Else) {
                 None
+            } else if self.current.kind == TokenKind::If {
+                // `else if` is an `else` holding just another `if`
+                Some(vec![self.parse_statement()?])
+            } else {
+                Some(self.parse_block("else block")?)
             };
 
-            Statement::If {
+            Ok(StatementKind::If {
                 condition,
                 then_block,
                 else_block,
-            }
+            })
         } else if self.current.kind == TokenKind::While {
             self.advance();
 
-            let condition = self.parse_expr();
+            let condition = self.parse_expr()?;
+            let body = self.parse_block("while block")?;
 
-            if !self.expect(TokenKind::LBrace) {
-                eprintln!("Expected '{{' after while condition");
-                process::exit(1);
-            }
-
-            let mut body = Vec::new();
-            while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-                body.push(self.parse_statement());
-            }
-
-            if !self.expect(TokenKind::RBrace) {
-                eprintln!("Expected '}}' after while block");
-                process::exit(1);
-            }
-
-            Statement::While { condition, body }
+            Ok(StatementKind::While { condition, body })
         } else if self.current.kind == TokenKind::For {
             // Parse for loop
             self.advance();
 
-            let loop_var = if self.current.kind == TokenKind::Identifier {
-                let v = self.current.text.clone();
-                self.advance();
-                v
-            } else {
-                eprintln!("Expected loop variable after 'for'");
-                process::exit(1);
-            };
-
-            if !self.expect(TokenKind::In) {
-                eprintln!("Expected 'in' after loop variable");
-                process::exit(1);
-            }
+            let loop_var = self.consume_identifier("Expected loop variable after 'for'")?;
+            self.consume(TokenKind::In, "Expected 'in' after loop variable")?;
 
             // Parse range start
-            let start = self.parse_additive();
-
-            if !self.expect(TokenKind::DotDot) {
-                eprintln!("Expected '..' in range");
-                process::exit(1);
-            }
+            let start = self.parse_expr()?;
+            self.consume(TokenKind::DotDot, "Expected '..' in range")?;
 
             // Parse range end
-            let end = self.parse_additive();
-
-            if !self.expect(TokenKind::LBrace) {
-                eprintln!("Expected '{{' after for range");
-                process::exit(1);
-            }
-
-            let mut body = Vec::new();
-            while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-                body.push(self.parse_statement());
-            }
+            let end = self.parse_expr()?;
+            let body = self.parse_block("for block")?;
 
-            if !self.expect(TokenKind::RBrace) {
-                eprintln!("Expected '}}' after for block");
-                process::exit(1);
-            }
-
-            Statement::For {
+            Ok(StatementKind::For {
                 loop_var,
                 start,
                 end,
                 body,
-            }
+            })
         } else if self.current.kind == TokenKind::Match {
             // Parse match statement
             self.advance();
 
-            let value = self.parse_expr();
+            let value = self.parse_expr()?;
+            let arms = self.parse_match_arms()?;
 
-            if !self.expect(TokenKind::LBrace) {
-                eprintln!(
-                    "Error at line {}, column {}: Expected '{{' after match value",
-                    self.current.line, self.current.column
-                );
-                process::exit(1);
+            Ok(StatementKind::Match { value, arms })
+        } else {
+            // Assignment or expression statement
+            let starts_with_name = self.current.kind == TokenKind::Identifier;
+            let expr = self.parse_expr()?;
+
+            if let Some(op) = assignment_op(self.current.kind) {
+                let assign = self.parse_assignment(expr, op)?;
+                self.consume(TokenKind::Semicolon, "Expected ';' after assignment")?;
+                return Ok(assign);
             }
+            // Without a `;` before the closing brace the expression is the
+            // block's value
+            if self.current.kind == TokenKind::RBrace {
+                return Ok(StatementKind::Tail(expr));
+            }
+            // A statement starting with a name must be an assignment or a call
+            let is_call = matches!(
+                expr.kind,
+                ExprKind::Call { .. } | ExprKind::MethodCall { .. }
+            );
+            if starts_with_name && !is_call {
+                return Err(self.error_here(
+                    E_EXPECTED_TOKEN,
+                    format!(
+                        "Unexpected token {} after expression",
+                        self.describe_current()
+                    ),
+                ));
+            }
+            if self.current.kind == TokenKind::Semicolon {
+                self.advance();
+            }
+            Ok(StatementKind::Expr(expr))
+        }
+    }
 
-            let mut arms = Vec::new();
-
-            while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-                let pattern = self.parse_pattern();
-
-                if !self.expect(TokenKind::FatArrow) {
-                    eprintln!(
-                        "Error at line {}, column {}: Expected '=>' after pattern",
-                        self.current.line, self.current.column
-                    );
-                    process::exit(1);
-                }
-
-                // Parse arm body - can be a single expression or a block
-                let mut body = Vec::new();
-
-                if self.current.kind == TokenKind::LBrace {
-                    // Block body
-                    self.advance();
-                    while self.current.kind != TokenKind::RBrace
-                        && self.current.kind != TokenKind::EOF
-                    {
-                        body.push(self.parse_statement());
-                    }
-                    if !self.expect(TokenKind::RBrace) {
-                        eprintln!(
-                            "Error at line {}, column {}: Expected '}}' after match arm block",
-                            self.current.line, self.current.column
-                        );
-                        process::exit(1);
-                    }
-                } else {
-                    // Single expression/statement - check if it's an assignment or expression
-                    // This allows expressions without semicolons in match arms
-                    if self.current.kind == TokenKind::Identifier {
-                        let name = self.current.text.clone();
-                        let saved_pos = self.lexer.pos;
-                        let saved_line = self.lexer.line;
-                        let saved_column = self.lexer.column;
-                        let saved_current = self.current.clone();
+    // `{ pattern [if guard] => body, ... }` after a match value
+    fn parse_match_arms(&mut self) -> ParseResult<Vec<MatchArm>> {
+        self.consume(TokenKind::LBrace, "Expected '{' after match value")?;
 
-                        self.advance();
+        let mut arms = Vec::new();
 
-                        if self.current.kind == TokenKind::Equals {
-                            // It's an assignment
-                            self.advance();
-                            let value = self.parse_expr();
-                            body.push(Statement::Assign { name, value });
-                        } else {
-                            // Not an assignment, restore and parse as expression
-                            self.lexer.pos = saved_pos;
-                            self.lexer.line = saved_line;
-                            self.lexer.column = saved_column;
-                            self.current = saved_current;
-
-                            let expr = self.parse_expr();
-                            body.push(Statement::Expr(expr));
-                        }
-                    } else {
-                        // Not an identifier, must be an expression
-                        let expr = self.parse_expr();
-                        body.push(Statement::Expr(expr));
-                    }
-                }
+        while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
+            let arm_start = self.current.span;
+            let pattern = self.parse_pattern()?;
+            let guard = if self.expect(TokenKind::If) {
+                Some(self.parse_expr()?)
+            } else {
+                None
+            };
+            self.consume(TokenKind::FatArrow, "Expected '=>' after pattern")?;
 
-                arms.push(MatchArm { pattern, body });
+            // Parse arm body - can be a single expression or a block
+            let body = if self.current.kind == TokenKind::LBrace {
+                self.parse_block("match arm block")?
+            } else {
+                vec![self.parse_match_arm_expr()?]
+            };
 
-                // Require comma after arm (unless it's the last one before })
-                if self.current.kind != TokenKind::RBrace {
-                    if !self.expect(TokenKind::Comma) {
-                        eprintln!(
-                            "Error at line {}, column {}: Expected ',' after match arm",
-                            self.current.line, self.current.column
-                        );
-                        process::exit(1);
-                    }
-                } else {
-                    // Optional comma before closing brace
-                    self.expect(TokenKind::Comma);
-                }
-            }
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+                span: self.span_from(arm_start),
+            });
 
-            if !self.expect(TokenKind::RBrace) {
-                eprintln!(
-                    "Error at line {}, column {}: Expected '}}' after match arms",
-                    self.current.line, self.current.column
-                );
-                process::exit(1);
+            // Require comma after arm (unless it's the last one before })
+            if self.current.kind != TokenKind::RBrace {
+                self.consume(TokenKind::Comma, "Expected ',' after match arm")?;
+            } else {
+                // Optional comma before closing brace
+                self.expect(TokenKind::Comma);
             }
+        }
 
-            Statement::Match { value, arms }
-        } else if self.current.kind == TokenKind::Identifier {
-            // Could be assignment or expression
-            let name = self.current.text.clone();
-            self.advance();
-
-            if self.current.kind == TokenKind::Equals {
-                // Assignment
-                self.advance();
-                let value = self.parse_expr();
+        self.consume(TokenKind::RBrace, "Expected '}' after match arms")?;
 
-                if !self.expect(TokenKind::Semicolon) {
-                    eprintln!("Expected ';' after assignment");
-                    process::exit(1);
-                }
+        Ok(arms)
+    }
 
-                Statement::Assign { name, value }
-            } else if self.current.kind == TokenKind::LParen {
-                // Function call
-                self.advance();
-                let mut args = Vec::new();
+    // Single expression/statement match arm body - check if it's an assignment or expression
+    // This allows expressions without semicolons in match arms
+    fn parse_match_arm_expr(&mut self) -> ParseResult<Statement> {
+        let start = self.current.span;
+        let expr = self.parse_expr()?;
 
-                if self.current.kind != TokenKind::RParen {
-                    args.push(self.parse_expr());
-                    while self.expect(TokenKind::Comma) {
-                        args.push(self.parse_expr());
-                    }
-                }
+        let kind = match assignment_op(self.current.kind) {
+            Some(op) => self.parse_assignment(expr, op)?,
+            None => StatementKind::Tail(expr),
+        };
+        Ok(Statement::new(kind, self.span_from(start)))
+    }
 
-                if !self.expect(TokenKind::RParen) {
-                    eprintln!("Expected ')' in function call");
-                    process::exit(1);
-                }
+    // Parse `= value` or `op= value` after an assignment target
+    fn parse_assignment(&mut self, target: Expr, op: Option<BinOp>) -> ParseResult<StatementKind> {
+        if !is_place(&target) {
+            return Err(Diagnostic::error(
+                E_INVALID_ASSIGN_TARGET,
+                "Invalid assignment target: expected a variable, field or array element"
+                    .to_string(),
+                target.span,
+            ));
+        }
+        self.advance();
+        let value = self.parse_expr()?;
+        Ok(StatementKind::Assign { target, op, value })
+    }
 
-                if self.current.kind == TokenKind::Semicolon {
-                    self.advance();
-                }
+    // A pattern and any alternatives to it: `1 | 2 | 3`
+    fn parse_pattern(&mut self) -> ParseResult<Pattern> {
+        let start = self.current.span;
+        let first = self.parse_single_pattern()?;
+        if self.current.kind != TokenKind::Pipe {
+            return Ok(first);
+        }
+        let mut alternatives = vec![first];
+        while self.expect(TokenKind::Pipe) {
+            alternatives.push(self.parse_single_pattern()?);
+        }
+        Ok(Pattern {
+            kind: PatternKind::Or(alternatives),
+            span: self.span_from(start),
+        })
+    }
 
-                Statement::Expr(Expr::Call { name, args })
-            } else {
-                eprintln!(
-                    "Error at line {}, column {}: Unexpected token '{}' after identifier",
-                    self.current.line, self.current.column, self.current.text
-                );
-                process::exit(1);
-            }
-        } else {
-            let expr = self.parse_expr();
-            if self.current.kind == TokenKind::Semicolon {
-                self.advance();
-            }
-            Statement::Expr(expr)
+    // An integer in a pattern, which may be negative
+    fn parse_pattern_number(&mut self) -> ParseResult<i64> {
+        let negative = self.expect(TokenKind::Minus);
+        if self.current.kind != TokenKind::Number {
+            return Err(self.error_here(
+                E_EXPECTED_PATTERN,
+                format!(
+                    "Expected an integer in pattern, found {}",
+                    self.describe_current()
+                ),
+            ));
         }
+        let (value, _) = self.int_literal(negative);
+        let value = if negative {
+            0i64.checked_sub_unsigned(value)
+        } else {
+            i64::try_from(value).ok()
+        };
+        let value = value.ok_or_else(|| {
+            self.error_here(
+                E_INVALID_LITERAL,
+                "Pattern literal is out of range".to_string(),
+            )
+        })?;
+        self.advance();
+        Ok(value)
     }
 
-    fn parse_pattern(&mut self) -> Pattern {
-        match self.current.kind {
+    fn parse_single_pattern(&mut self) -> ParseResult<Pattern> {
+        let start = self.current.span;
+        let kind = match self.current.kind {
             TokenKind::Underscore => {
                 self.advance();
-                Pattern::Wildcard
+                PatternKind::Wildcard
             }
-            TokenKind::Number => {
-                let num: i32 = self.current.text.parse().unwrap_or(0);
+            TokenKind::Number | TokenKind::Minus => {
+                let value = self.parse_pattern_number()?;
+                if self.expect(TokenKind::DotDotEquals) {
+                    let end = self.parse_pattern_number()?;
+                    PatternKind::Range { start: value, end }
+                } else {
+                    PatternKind::Number(value)
+                }
+            }
+            TokenKind::StringLiteral => {
+                let value = self.current.text.clone();
                 self.advance();
-                Pattern::Number(num)
+                PatternKind::String(value)
             }
             TokenKind::True => {
                 self.advance();
-                Pattern::Boolean(true)
+                PatternKind::Boolean(true)
             }
             TokenKind::False => {
                 self.advance();
-                Pattern::Boolean(false)
+                PatternKind::Boolean(false)
             }
             TokenKind::Identifier => {
                 let name = self.current.text.clone();
                 self.advance();
 
-                if self.current.kind == TokenKind::DoubleColon {
+                if self.expect(TokenKind::DoubleColon) {
                     // Enum variant pattern: Color::Red
-                    self.advance();
-
-                    let variant = if self.current.kind == TokenKind::Identifier {
-                        let v = self.current.text.clone();
-                        self.advance();
-                        v
-                    } else {
-                        eprintln!(
-                            "Error at line {}, column {}: Expected variant name after '::'",
-                            self.current.line, self.current.column
-                        );
-                        process::exit(1);
-                    };
+                    let variant = self.consume_identifier("Expected variant name after '::'")?;
 
-                    Pattern::EnumVariant {
+                    PatternKind::EnumVariant {
                         enum_name: name,
                         variant,
                     }
+                } else if self.expect(TokenKind::LParen) {
+                    // Union variant with a payload: Some(x) or Some(_)
+                    let binding = if self.expect(TokenKind::Underscore) {
+                        "_".to_string()
+                    } else {
+                        self.consume_identifier("Expected a name or '_' to bind the payload to")?
+                    };
+                    self.consume(TokenKind::RParen, "Expected ')' after pattern binding")?;
+                    PatternKind::UnionVariant {
+                        name,
+                        binding: Some(binding),
+                    }
+                } else if self.expect(TokenKind::LBrace) {
+                    // Struct pattern: Point { x, y: 0 }
+                    let mut fields = Vec::new();
+                    while self.current.kind != TokenKind::RBrace {
+                        let field_start = self.current.span;
+                        let field =
+                            self.consume_identifier("Expected field name in struct pattern")?;
+                        let pattern = if self.expect(TokenKind::Colon) {
+                            self.parse_pattern()?
+                        } else {
+                            Pattern {
+                                kind: PatternKind::Binding(field.clone()),
+                                span: self.span_from(field_start),
+                            }
+                        };
+                        fields.push((field, pattern));
+                        if !self.expect(TokenKind::Comma) {
+                            break;
+                        }
+                    }
+                    self.consume(TokenKind::RBrace, "Expected '}' after struct pattern")?;
+                    PatternKind::Struct { name, fields }
                 } else {
-                    eprintln!(
-                        "Error at line {}, column {}: Invalid pattern, expected enum variant",
-                        self.current.line, self.current.column
-                    );
-                    process::exit(1);
+                    // A name binds the value; union variants such as `None`
+                    // are told apart once every item is known
+                    PatternKind::Binding(name)
                 }
             }
             _ => {
-                eprintln!(
-                    "Error at line {}, column {}: Expected pattern",
-                    self.current.line, self.current.column
-                );
-                process::exit(1);
+                return Err(self.error_here(
+                    E_EXPECTED_PATTERN,
+                    format!("Expected pattern, found {}", self.describe_current()),
+                ))
             }
-        }
+        };
+        Ok(Pattern {
+            kind,
+            span: self.span_from(start),
+        })
     }
 
-    fn parse_expr(&mut self) -> Expr {
-        self.parse_logical_or()
-    }
+    // --- Expressions ---
 
-    fn parse_logical_or(&mut self) -> Expr {
-        let mut left = self.parse_logical_and();
+    fn parse_expr(&mut self) -> ParseResult<Expr> {
+        self.parse_binary(0)
+    }
 
-        while self.current.kind == TokenKind::Or {
-            self.advance();
-            let right = self.parse_logical_and();
-            left = Expr::Binary {
+    fn binary(left: Expr, op: BinOp, right: Expr) -> Expr {
+        let span = left.span.to(right.span);
+        Expr::new(
+            ExprKind::Binary {
                 left: Box::new(left),
-                op: BinOp::Or,
+                op,
                 right: Box::new(right),
-            };
-        }
-
-        left
+            },
+            span,
+        )
     }
 
-    fn parse_logical_and(&mut self) -> Expr {
-        let mut left = self.parse_comparison();
-
-        while self.current.kind == TokenKind::And {
-            self.advance();
-            let right = self.parse_comparison();
-            left = Expr::Binary {
-                left: Box::new(left),
-                op: BinOp::And,
-                right: Box::new(right),
-            };
-        }
+    // Precedence climbing over BINARY_OPERATORS: parse a unary operand, then
+    // fold in every operator that binds tighter than `min_precedence`
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult<Expr> {
+        let mut left = self.parse_unary()?;
 
-        left
-    }
-
-    fn parse_comparison(&mut self) -> Expr {
-        let mut left = self.parse_additive();
-
-        while matches!(
-            self.current.kind,
-            TokenKind::EqualsEquals
-                | TokenKind::NotEquals
-                | TokenKind::LessThan
-                | TokenKind::GreaterThan
-                | TokenKind::LessEquals
-                | TokenKind::GreaterEquals
-        ) {
-            let op = match self.current.kind {
-                TokenKind::EqualsEquals => BinOp::Eq,
-                TokenKind::NotEquals => BinOp::NotEq,
-                TokenKind::LessThan => BinOp::Lt,
-                TokenKind::GreaterThan => BinOp::Gt,
-                TokenKind::LessEquals => BinOp::LtEq,
-                TokenKind::GreaterEquals => BinOp::GtEq,
-                _ => unreachable!(),
-            };
+        while let Some((op, precedence)) = binary_operator(self.current.kind) {
+            if precedence <= min_precedence {
+                break;
+            }
             self.advance();
-            let right = self.parse_additive();
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
+            // Left-associative: the right operand only takes tighter operators
+            let right = self.parse_binary(precedence)?;
+            left = Self::binary(left, op, right);
         }
 
-        left
+        Ok(left)
     }
 
-    fn parse_additive(&mut self) -> Expr {
-        let mut left = self.parse_multiplicative();
+    fn parse_unary(&mut self) -> ParseResult<Expr> {
+        let start = self.current.span;
+        let op = match self.current.kind {
+            TokenKind::Not => UnaryOp::Not,
+            TokenKind::Minus => UnaryOp::Neg,
+            TokenKind::Tilde => UnaryOp::BitNot,
+            _ => return self.parse_primary(),
+        };
 
-        while matches!(self.current.kind, TokenKind::Plus | TokenKind::Minus) {
-            let op = match self.current.kind {
-                TokenKind::Plus => BinOp::Add,
-                TokenKind::Minus => BinOp::Sub,
-                _ => unreachable!(),
-            };
+        self.advance();
+        // `-128i8` is in range though `128i8` is not
+        let operand = if matches!(op, UnaryOp::Neg) && self.current.kind == TokenKind::Number {
+            let start = self.current.span;
+            let (value, ty) = self.int_literal(true);
             self.advance();
-            let right = self.parse_multiplicative();
-            left = Expr::Binary {
-                left: Box::new(left),
+            Expr::new(ExprKind::Number { value, ty }, self.span_from(start))
+        } else {
+            self.parse_unary()?
+        };
+        Ok(Expr::new(
+            ExprKind::Unary {
                 op,
-                right: Box::new(right),
-            };
-        }
-
-        left
+                operand: Box::new(operand),
+            },
+            self.span_from(start),
+        ))
     }
 
-    fn parse_multiplicative(&mut self) -> Expr {
-        let mut left = self.parse_unary();
-
-        while matches!(self.current.kind, TokenKind::Star | TokenKind::Slash) {
-            let op = match self.current.kind {
-                TokenKind::Star => BinOp::Mul,
-                TokenKind::Slash => BinOp::Div,
-                _ => unreachable!(),
+    // Parse a call to `name` starting at the '('. `print("a {} b", x)`
+    // has its placeholders filled in here, becoming `print("a {x} b")`.
+    /// Parse any `.field` and `.method(args)` suffixes of `expr`
+    fn parse_member_chain(&mut self, mut expr: Expr, start: Span) -> ParseResult<Expr> {
+        while self.expect(TokenKind::Dot) {
+            // `pair.0` reads a tuple element
+            let member = if self.current.kind == TokenKind::Number
+                && self.current.text.bytes().all(|b| b.is_ascii_digit())
+            {
+                let index = self.current.text.clone();
+                self.advance();
+                index
+            } else {
+                self.consume_identifier("Expected member name after '.'")?
             };
-            self.advance();
-            let right = self.parse_unary();
-            left = Expr::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
+            let kind = if self.current.kind == TokenKind::LParen {
+                let args = self.parse_call_args()?;
+                ExprKind::MethodCall {
+                    receiver: Box::new(expr),
+                    method: member,
+                    args,
+                }
+            } else {
+                ExprKind::MemberAccess {
+                    object: Box::new(expr),
+                    member,
+                }
             };
+            expr = Expr::new(kind, self.span_from(start));
         }
-
-        left
+        Ok(expr)
     }
 
-    fn parse_unary(&mut self) -> Expr {
-        if self.current.kind == TokenKind::Not {
-            self.advance();
-            let operand = self.parse_unary();
-            return Expr::Unary {
-                op: UnaryOp::Not,
-                operand: Box::new(operand),
+    fn parse_call(&mut self, name: String) -> ParseResult<ExprKind> {
+        let mut args = self.parse_call_args()?;
+
+        if name == "print" && args.len() > 1 {
+            let mut values = args.split_off(1).into_iter();
+            let format = &mut args[0];
+            let mut parts = match &mut format.kind {
+                ExprKind::String(text) => vec![FormatPart::Literal(std::mem::take(text))],
+                ExprKind::Format(parts) => std::mem::take(parts),
+                _ => {
+                    // Not a literal; left for codegen to reject
+                    args.extend(values);
+                    return Ok(ExprKind::Call { name, args });
+                }
             };
+
+            let placeholders = parts
+                .iter()
+                .filter(|part| matches!(part, FormatPart::Positional(_)))
+                .count();
+            let message = format!(
+                "Format string has {} placeholder(s) but {} argument(s) were given",
+                placeholders,
+                values.len()
+            );
+            if placeholders > values.len() {
+                return Err(Diagnostic::error(E_FORMAT_ARGS, message, format.span));
+            }
+            if placeholders < values.len() {
+                // The extra arguments are never printed, as with C's printf
+                let message = format!("{}; the extra arguments are ignored", message);
+                self.diagnostics.push(Diagnostic::warning(
+                    W_UNUSED_FORMAT_ARGS,
+                    message,
+                    format.span,
+                ));
+            }
+            for part in &mut parts {
+                if let FormatPart::Positional(spec) = part {
+                    *part = FormatPart::Value(values.next().unwrap(), std::mem::take(spec));
+                }
+            }
+            format.kind = ExprKind::Format(parts);
         }
 
-        if self.current.kind == TokenKind::Minus {
-            self.advance();
-            let operand = self.parse_unary();
-            return Expr::Unary {
-                op: UnaryOp::Neg,
-                operand: Box::new(operand),
-            };
+        Ok(ExprKind::Call { name, args })
+    }
+
+    // Parse `(args)` of a call, starting at the '('
+    fn parse_call_args(&mut self) -> ParseResult<Vec<Expr>> {
+        self.consume(TokenKind::LParen, "Expected '('")?;
+        let mut args = Vec::new();
+
+        if self.current.kind != TokenKind::RParen {
+            args.push(self.parse_expr()?);
+            while self.expect(TokenKind::Comma) {
+                args.push(self.parse_expr()?);
+            }
         }
 
-        self.parse_primary()
+        self.consume(TokenKind::RParen, "Expected ')' in function call")?;
+        Ok(args)
     }
 
-    fn parse_primary(&mut self) -> Expr {
-        match self.current.kind {
+    fn parse_primary(&mut self) -> ParseResult<Expr> {
+        let start = self.current.span;
+        let kind = match self.current.kind {
             TokenKind::Number => {
-                let num: i32 = self.current.text.parse().unwrap_or(0);
+                let (value, ty) = self.int_literal(false);
+                self.advance();
+                ExprKind::Number { value, ty }
+            }
+            TokenKind::Float => {
+                let (value, ty) = parse_float_literal(&self.current.text).unwrap_or((0.0, None));
                 self.advance();
-                Expr::Number(num)
+                ExprKind::Float { value, ty }
             }
             TokenKind::True => {
                 self.advance();
-                Expr::Boolean(true)
+                ExprKind::Boolean(true)
             }
             TokenKind::False => {
                 self.advance();
-                Expr::Boolean(false)
+                ExprKind::Boolean(false)
             }
             TokenKind::StringLiteral => {
                 let s = self.current.text.clone();
                 self.advance();
-                Expr::String(s)
+                ExprKind::String(s)
             }
+            TokenKind::InterpStart => self.parse_interpolated_string()?,
             TokenKind::CharLiteral => {
-                let s = self.current.text.clone();
-                self.advance();
-                if let Some(c) = s.chars().next() {
-                    Expr::Char(c)
+                if let Some(c) = self.current.text.chars().next() {
+                    self.advance();
+                    ExprKind::Char(c)
                 } else {
-                    eprintln!("Empty char literal");
-                    process::exit(1);
+                    return Err(self.error_here(E_EXPECTED_EXPR, "Empty char literal".to_string()));
                 }
             }
             TokenKind::Identifier => {
                 let name = self.current.text.clone();
                 self.advance();
 
-                if self.current.kind == TokenKind::DoubleColon {
+                if self.expect(TokenKind::DoubleColon) {
                     // Enum variant: Color::Red
-                    self.advance();
-
-                    let variant = if self.current.kind == TokenKind::Identifier {
-                        let v = self.current.text.clone();
-                        self.advance();
-                        v
+                    let variant = self.consume_identifier("Expected variant name after '::'")?;
+
+                    if self.expect(TokenKind::LParen) {
+                        // Union variant with its payload: Result::Ok(1)
+                        let value = self.parse_expr()?;
+                        self.consume(TokenKind::RParen, "Expected ')' after variant value")?;
+                        ExprKind::UnionVariant {
+                            union_name: name,
+                            variant,
+                            value: Some(Box::new(value)),
+                        }
                     } else {
-                        eprintln!("Expected variant name after '::'");
-                        process::exit(1);
-                    };
-
-                    Expr::EnumVariant {
-                        enum_name: name,
-                        variant,
-                    }
-                } else if self.current.kind == TokenKind::LParen {
-                    // Function call
-                    self.advance();
-                    let mut args = Vec::new();
-
-                    if self.current.kind != TokenKind::RParen {
-                        args.push(self.parse_expr());
-                        while self.expect(TokenKind::Comma) {
-                            args.push(self.parse_expr());
+                        ExprKind::EnumVariant {
+                            enum_name: name,
+                            variant,
                         }
                     }
-
-                    if !self.expect(TokenKind::RParen) {
-                        eprintln!("Expected ')' in function call");
-                        process::exit(1);
-                    }
-
-                    Expr::Call { name, args }
+                } else if self.current.kind == TokenKind::LParen {
+                    // Function call, possibly followed by `.field` or `.method()`
+                    let call = Expr::new(self.parse_call(name)?, self.span_from(start));
+                    return self.parse_member_chain(call, start);
                 } else if self.current.kind == TokenKind::LBracket {
                     // Array indexing
                     self.advance();
-                    let index = self.parse_expr();
+                    let index = self.parse_expr()?;
+                    self.consume(TokenKind::RBracket, "Expected ']' in array index")?;
 
-                    if !self.expect(TokenKind::RBracket) {
-                        eprintln!("Expected ']' in array index");
-                        process::exit(1);
-                    }
-
-                    Expr::ArrayIndex {
-                        array: Box::new(Expr::Variable(name)),
+                    let kind = ExprKind::ArrayIndex {
+                        array: Box::new(Expr::new(ExprKind::Variable(name), start)),
                         index: Box::new(index),
-                    }
+                    };
+                    return self.parse_member_chain(Expr::new(kind, self.span_from(start)), start);
                 } else if self.current.kind == TokenKind::LBrace {
                     // Might be struct literal: Point { x: 10, y: 20 }
                     // But only if it looks like field assignments inside
                     // Check if the next token is an identifier followed by ':'
                     // to distinguish from match arms or other block constructs
-
-                    // Peek ahead to see if this looks like a struct literal
-                    let saved_pos = self.lexer.pos;
-                    let saved_line = self.lexer.line;
-                    let saved_column = self.lexer.column;
-                    let saved_current = self.current.clone();
+                    let saved = self.checkpoint();
 
                     self.advance(); // consume '{'
                     let looks_like_struct = if self.current.kind == TokenKind::Identifier {
-                        let saved_pos2 = self.lexer.pos;
-                        let saved_line2 = self.lexer.line;
-                        let saved_column2 = self.lexer.column;
-                        let saved_current2 = self.current.clone();
-
+                        let field = self.checkpoint();
                         self.advance(); // consume identifier
                         let has_colon = self.current.kind == TokenKind::Colon;
-
-                        // Restore position
-                        self.lexer.pos = saved_pos2;
-                        self.lexer.line = saved_line2;
-                        self.lexer.column = saved_column2;
-                        self.current = saved_current2;
-
+                        self.rewind(field);
                         has_colon
                     } else {
                         false
@@ -1135,21 +1374,10 @@ impl Parser {
                         while self.current.kind != TokenKind::RBrace
                             && self.current.kind != TokenKind::EOF
                         {
-                            let field_name = if self.current.kind == TokenKind::Identifier {
-                                let n = self.current.text.clone();
-                                self.advance();
-                                n
-                            } else {
-                                eprintln!("Expected field name in struct literal");
-                                process::exit(1);
-                            };
-
-                            if !self.expect(TokenKind::Colon) {
-                                eprintln!("Expected ':' after field name");
-                                process::exit(1);
-                            }
-
-                            let value = self.parse_expr();
+                            let field_name =
+                                self.consume_identifier("Expected field name in struct literal")?;
+                            self.consume(TokenKind::Colon, "Expected ':' after field name")?;
+                            let value = self.parse_expr()?;
                             fields.push((field_name, value));
 
                             if !self.expect(TokenKind::Comma) {
@@ -1157,49 +1385,23 @@ impl Parser {
                             }
                         }
 
-                        if !self.expect(TokenKind::RBrace) {
-                            eprintln!("Expected '}}' in struct literal");
-                            process::exit(1);
-                        }
+                        self.consume(TokenKind::RBrace, "Expected '}' in struct literal")?;
 
-                        Expr::StructLiteral {
+                        ExprKind::StructLiteral {
                             struct_name: name,
                             fields,
                         }
                     } else {
                         // Not a struct literal, restore position and return just the variable
-                        self.lexer.pos = saved_pos;
-                        self.lexer.line = saved_line;
-                        self.lexer.column = saved_column;
-                        self.current = saved_current;
-
-                        Expr::Variable(name)
+                        self.rewind(saved);
+                        ExprKind::Variable(name)
                     }
                 } else if self.current.kind == TokenKind::Dot {
-                    // Member access: p.x
-                    let mut expr = Expr::Variable(name);
-
-                    while self.current.kind == TokenKind::Dot {
-                        self.advance();
-
-                        let member = if self.current.kind == TokenKind::Identifier {
-                            let m = self.current.text.clone();
-                            self.advance();
-                            m
-                        } else {
-                            eprintln!("Expected member name after '.'");
-                            process::exit(1);
-                        };
-
-                        expr = Expr::MemberAccess {
-                            object: Box::new(expr),
-                            member,
-                        };
-                    }
-
-                    expr
+                    // Member access: p.x, or a method call: p.norm()
+                    let variable = Expr::new(ExprKind::Variable(name), start);
+                    return self.parse_member_chain(variable, start);
                 } else {
-                    Expr::Variable(name)
+                    ExprKind::Variable(name)
                 }
             }
             TokenKind::LBracket => {
@@ -1208,307 +1410,1111 @@ impl Parser {
                 let mut elements = Vec::new();
 
                 if self.current.kind != TokenKind::RBracket {
-                    elements.push(self.parse_expr());
+                    elements.push(self.parse_expr()?);
                     while self.expect(TokenKind::Comma) {
-                        elements.push(self.parse_expr());
+                        elements.push(self.parse_expr()?);
                     }
                 }
 
-                if !self.expect(TokenKind::RBracket) {
-                    eprintln!("Expected ']' in array literal");
-                    process::exit(1);
-                }
+                self.consume(TokenKind::RBracket, "Expected ']' in array literal")?;
 
-                Expr::ArrayLiteral(elements)
+                ExprKind::ArrayLiteral(elements)
             }
             TokenKind::LParen => {
                 self.advance();
-                let expr = self.parse_expr();
-                if !self.expect(TokenKind::RParen) {
-                    eprintln!("Expected ')' after expression");
-                    process::exit(1);
+                let expr = self.parse_expr()?;
+                if self.current.kind != TokenKind::Comma {
+                    self.consume(TokenKind::RParen, "Expected ')' after expression")?;
+                    return Ok(expr);
+                }
+                // A comma makes it a tuple: `(a, b)`
+                let mut elements = vec![expr];
+                while self.expect(TokenKind::Comma) && self.current.kind != TokenKind::RParen {
+                    elements.push(self.parse_expr()?);
+                }
+                if elements.len() < 2 {
+                    return Err(self.error_here(
+                        E_EXPECTED_EXPR,
+                        "A tuple needs at least two elements".to_string(),
+                    ));
+                }
+                self.consume(TokenKind::RParen, "Expected ')' after tuple elements")?;
+                let tuple = Expr::new(ExprKind::Tuple(elements), self.span_from(start));
+                return self.parse_member_chain(tuple, start);
+            }
+            TokenKind::If => self.parse_if_expr()?,
+            TokenKind::Match => {
+                self.advance();
+                let value = self.parse_expr()?;
+                let mut arms = self.parse_match_arms()?;
+                for arm in &mut arms {
+                    end_in_value(&mut arm.body);
+                }
+                ExprKind::Match {
+                    value: Box::new(value),
+                    arms,
                 }
-                expr
+            }
+            TokenKind::LBrace => {
+                let mut block = self.parse_block("block")?;
+                end_in_value(&mut block);
+                ExprKind::Block(block)
             }
             _ => {
-                eprintln!("Unexpected token in expression: {:?}", self.current);
-                process::exit(1);
+                return Err(self.error_here(
+                    E_EXPECTED_EXPR,
+                    format!("Expected expression, found {}", self.describe_current()),
+                ))
+            }
+        };
+        Ok(Expr::new(kind, self.span_from(start)))
+    }
+
+    // `if` used as a value, which needs an `else`; `else if` nests another
+    // `if` as the else branch's value
+    fn parse_if_expr(&mut self) -> ParseResult<ExprKind> {
+        self.consume(TokenKind::If, "Expected 'if'")?;
+        let condition = self.parse_expr()?;
+        let mut then_block = self.parse_block("if block")?;
+        end_in_value(&mut then_block);
+
+        self.consume(
+            TokenKind::Else,
+            "Expected 'else'; an 'if' used as a value needs one",
+        )?;
+        let else_block = if self.current.kind == TokenKind::If {
+            let start = self.current.span;
+            let nested = Expr::new(self.parse_if_expr()?, self.span_from(start));
+            vec![Statement::new(
+                StatementKind::Tail(nested),
+                self.span_from(start),
+            )]
+        } else {
+            let mut else_block = self.parse_block("else block")?;
+            end_in_value(&mut else_block);
+            else_block
+        };
+
+        Ok(ExprKind::If {
+            condition: Box::new(condition),
+            then_block,
+            else_block,
+        })
+    }
+
+    // "a{x}b{:>4}c" arrives as InterpStart("a") x InterpMiddle("b")
+    // FormatSpec(">4") InterpEnd("c")
+    fn parse_interpolated_string(&mut self) -> ParseResult<ExprKind> {
+        let mut parts = Vec::new();
+        if !self.current.text.is_empty() {
+            parts.push(FormatPart::Literal(self.current.text.clone()));
+        }
+        self.advance();
+
+        loop {
+            let value = match self.current.kind {
+                TokenKind::InterpMiddle | TokenKind::InterpEnd | TokenKind::FormatSpec => None,
+                _ => Some(self.parse_expr()?),
+            };
+            let spec = if self.current.kind == TokenKind::FormatSpec {
+                let spec = FormatSpec::parse(&self.current.text).map_err(|message| {
+                    self.error_here(
+                        E_FORMAT_SPEC,
+                        format!("Invalid format spec '{}': {}", self.current.text, message),
+                    )
+                })?;
+                self.advance();
+                spec
+            } else {
+                FormatSpec::default()
+            };
+            parts.push(match value {
+                Some(expr) => FormatPart::Value(expr, spec),
+                None => FormatPart::Positional(spec),
+            });
+
+            let done = match self.current.kind {
+                TokenKind::InterpMiddle => false,
+                TokenKind::InterpEnd => true,
+                _ => {
+                    return Err(self.error_here(
+                        E_EXPECTED_TOKEN,
+                        format!(
+                            "Expected '}}' to close interpolated expression, found {}",
+                            self.describe_current()
+                        ),
+                    ))
+                }
+            };
+            if !self.current.text.is_empty() {
+                parts.push(FormatPart::Literal(self.current.text.clone()));
+            }
+            self.advance();
+            if done {
+                return Ok(ExprKind::Format(parts));
             }
         }
     }
 
-    // Parse type parameters: <T, U, V>
-    fn parse_type_parameters(&mut self) -> Vec<String> {
+    // Parse type parameters with optional bounds: <T: Display + Eq, U>
+    fn parse_type_parameters(&mut self) -> ParseResult<(Vec<String>, Vec<TypeBound>)> {
         let mut type_params = Vec::new();
-        
+        let mut bounds = Vec::new();
+
         if !self.expect(TokenKind::LessThan) {
-            return type_params;
+            return Ok((type_params, bounds));
         }
 
         loop {
-            if self.current.kind == TokenKind::Identifier {
-                type_params.push(self.current.text.clone());
-                self.advance();
-            } else {
-                eprintln!("Expected type parameter name");
-                process::exit(1);
+            let start = self.current.span;
+            let param = self.consume_identifier("Expected type parameter name")?;
+            if self.expect(TokenKind::Colon) {
+                let traits = self.parse_trait_list()?;
+                bounds.push(TypeBound {
+                    param: param.clone(),
+                    traits,
+                    span: self.span_from(start),
+                });
             }
+            type_params.push(param);
 
             if !self.expect(TokenKind::Comma) {
                 break;
             }
         }
 
-        if !self.expect(TokenKind::GreaterThan) {
-            eprintln!("Expected '>' after type parameters");
-            process::exit(1);
-        }
+        self.consume(TokenKind::GreaterThan, "Expected '>' after type parameters")?;
 
-        type_params
+        Ok((type_params, bounds))
     }
 
-    // Parse union type: type Result = Ok(int) | Err(string);
-    fn parse_union_type(&mut self) -> UnionType {
-        if !self.expect(TokenKind::Type) {
-            eprintln!("Expected 'type'");
-            process::exit(1);
+    // Parse an optional `where T: Display, U: Eq + Ord` clause. `where` is
+    // contextual so it stays usable as a name.
+    fn parse_where_clause(&mut self) -> ParseResult<Vec<TypeBound>> {
+        let mut bounds = Vec::new();
+        if self.current.kind != TokenKind::Identifier || self.current.text != "where" {
+            return Ok(bounds);
         }
+        self.advance();
 
-        let name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
-            self.advance();
-            n
-        } else {
-            eprintln!("Expected union type name");
-            process::exit(1);
-        };
+        loop {
+            let start = self.current.span;
+            let param =
+                self.consume_identifier("Expected type parameter name in 'where' clause")?;
+            self.consume(TokenKind::Colon, "Expected ':' after type parameter")?;
+            let traits = self.parse_trait_list()?;
+            bounds.push(TypeBound {
+                param,
+                traits,
+                span: self.span_from(start),
+            });
 
-        if !self.expect(TokenKind::Equals) {
-            eprintln!("Expected '=' after union type name");
-            process::exit(1);
+            if !self.expect(TokenKind::Comma) || self.current.kind == TokenKind::LBrace {
+                break;
+            }
+        }
+        Ok(bounds)
+    }
+
+    // Trait names joined by `+`
+    fn parse_trait_list(&mut self) -> ParseResult<Vec<String>> {
+        let mut traits = vec![self.consume_identifier("Expected trait name in bound")?];
+        while self.expect(TokenKind::Plus) {
+            traits.push(self.consume_identifier("Expected trait name after '+'")?);
         }
+        Ok(traits)
+    }
+
+    // Parse union type: type Result = Ok(int) | Err(string);
+    fn parse_union_type(&mut self) -> ParseResult<UnionType> {
+        let start = self.current.span;
+        self.consume(TokenKind::Type, "Expected 'type'")?;
+        let name = self.consume_identifier("Expected union type name")?;
+        self.consume(TokenKind::Equals, "Expected '=' after union type name")?;
 
         let mut variants = Vec::new();
 
         // Parse first variant
-        variants.push(self.parse_union_variant());
+        variants.push(self.parse_union_variant()?);
 
         // Parse remaining variants separated by |
-        while self.current.kind == TokenKind::Pipe {
-            self.advance();
-            variants.push(self.parse_union_variant());
+        while self.expect(TokenKind::Pipe) {
+            variants.push(self.parse_union_variant()?);
         }
 
-        if !self.expect(TokenKind::Semicolon) {
-            eprintln!("Expected ';' after union type definition");
-            process::exit(1);
-        }
+        self.consume(
+            TokenKind::Semicolon,
+            "Expected ';' after union type definition",
+        )?;
 
-        UnionType { name, variants }
+        Ok(UnionType {
+            name,
+            variants,
+            span: self.span_from(start),
+        })
     }
 
-    fn parse_union_variant(&mut self) -> UnionVariant {
-        let name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
-            self.advance();
-            n
-        } else {
-            eprintln!("Expected variant name");
-            process::exit(1);
-        };
+    fn parse_union_variant(&mut self) -> ParseResult<UnionVariant> {
+        let start = self.current.span;
+        let name = self.consume_identifier("Expected variant name")?;
 
         // Check for associated type: Ok(int)
-        let associated_type = if self.current.kind == TokenKind::LParen {
-            self.advance();
-            let type_name = if self.current.kind == TokenKind::Identifier {
-                let t = self.current.text.clone();
-                self.advance();
-                Some(t)
-            } else {
-                eprintln!("Expected type in union variant");
-                process::exit(1);
-            };
-
-            if !self.expect(TokenKind::RParen) {
-                eprintln!("Expected ')' after variant type");
-                process::exit(1);
-            }
-
-            type_name
+        let associated_type = if self.expect(TokenKind::LParen) {
+            let type_name = self.parse_type("Expected type in union variant")?;
+            self.consume(TokenKind::RParen, "Expected ')' after variant type")?;
+            Some(type_name)
         } else {
             None
         };
 
-        UnionVariant { name, associated_type }
+        Ok(UnionVariant {
+            name,
+            associated_type,
+            span: self.span_from(start),
+        })
     }
 
     // Parse trait definition
-    fn parse_trait(&mut self) -> TraitDef {
-        if !self.expect(TokenKind::Trait) {
-            eprintln!("Expected 'trait'");
-            process::exit(1);
+    fn parse_trait(&mut self) -> ParseResult<TraitDef> {
+        let start = self.current.span;
+        self.consume(TokenKind::Trait, "Expected 'trait'")?;
+        let name = self.consume_identifier("Expected trait name")?;
+        self.consume(TokenKind::LBrace, "Expected '{' after trait name")?;
+
+        let mut methods = Vec::new();
+
+        while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
+            methods.push(self.parse_trait_method()?);
         }
 
-        let name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
-            self.advance();
-            n
+        self.consume(TokenKind::RBrace, "Expected '}' after trait definition")?;
+
+        Ok(TraitDef {
+            name,
+            methods,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_trait_method(&mut self) -> ParseResult<TraitMethod> {
+        let start = self.current.span;
+        self.consume(TokenKind::Fn, "Expected 'fn' in trait method")?;
+        let name = self.consume_identifier("Expected method name")?;
+        self.consume(TokenKind::LParen, "Expected '(' after method name")?;
+        let params = self.parse_parameters()?;
+        self.consume(TokenKind::RParen, "Expected ')' after parameters")?;
+
+        let return_type = if self.expect(TokenKind::Arrow) {
+            Some(self.parse_type("Expected return type after '->'")?)
         } else {
-            eprintln!("Expected trait name");
-            process::exit(1);
+            None
         };
 
-        if !self.expect(TokenKind::LBrace) {
-            eprintln!("Expected '{{' after trait name");
-            process::exit(1);
-        }
+        self.consume(
+            TokenKind::Semicolon,
+            "Expected ';' after trait method signature",
+        )?;
+
+        Ok(TraitMethod {
+            name,
+            params,
+            return_type,
+            span: self.span_from(start),
+        })
+    }
+
+    // Parse trait implementation
+    /// Parse `impl Trait for Type { ... }` or an inherent `impl Type { ... }`
+    fn parse_impl(&mut self, program: &mut Program) -> ParseResult<()> {
+        let start = self.current.span;
+        self.consume(TokenKind::Impl, "Expected 'impl'")?;
+        let name = self.parse_type("Expected trait or type name after 'impl'")?;
+        let (trait_name, self_type) = if self.expect(TokenKind::For) {
+            (
+                Some(name.to_string()),
+                self.parse_type("Expected type name after 'for'")?,
+            )
+        } else {
+            (None, name)
+        };
+        self.consume(TokenKind::LBrace, "Expected '{' after impl declaration")?;
 
         let mut methods = Vec::new();
 
         while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-            methods.push(self.parse_trait_method());
+            let mut method = self.parse_function()?;
+            // A bare `self` parameter has the implementing type
+            for param in &mut method.params {
+                if param.type_name == TypeExpr::named(SELF_TYPE) {
+                    param.type_name = self_type.clone();
+                }
+            }
+            methods.push(method);
         }
 
-        if !self.expect(TokenKind::RBrace) {
-            eprintln!("Expected '}}' after trait definition");
-            process::exit(1);
+        self.consume(TokenKind::RBrace, "Expected '}' after impl block")?;
+
+        let span = self.span_from(start);
+        match trait_name {
+            Some(trait_name) => program.impls.push(TraitImpl {
+                trait_name,
+                type_name: self_type,
+                methods,
+                span,
+            }),
+            None => program.inherent_impls.push(InherentImpl {
+                type_name: self_type,
+                methods,
+                span,
+            }),
         }
+        Ok(())
+    }
+}
+
+// Binary operators and their precedence; a higher number binds tighter and
+// every level is left-associative. As in Rust, bitwise operators bind
+// tighter than comparisons, so `x & 1 == 0` is `(x & 1) == 0`.
+const BINARY_OPERATORS: &[(TokenKind, BinOp, u8)] = &[
+    (TokenKind::Or, BinOp::Or, 1),
+    (TokenKind::And, BinOp::And, 2),
+    (TokenKind::EqualsEquals, BinOp::Eq, 3),
+    (TokenKind::NotEquals, BinOp::NotEq, 3),
+    (TokenKind::LessThan, BinOp::Lt, 3),
+    (TokenKind::GreaterThan, BinOp::Gt, 3),
+    (TokenKind::LessEquals, BinOp::LtEq, 3),
+    (TokenKind::GreaterEquals, BinOp::GtEq, 3),
+    (TokenKind::Pipe, BinOp::BitOr, 4),
+    (TokenKind::Caret, BinOp::BitXor, 5),
+    (TokenKind::Ampersand, BinOp::BitAnd, 6),
+    (TokenKind::ShiftLeft, BinOp::Shl, 7),
+    (TokenKind::ShiftRight, BinOp::Shr, 7),
+    (TokenKind::Plus, BinOp::Add, 8),
+    (TokenKind::Minus, BinOp::Sub, 8),
+    (TokenKind::Star, BinOp::Mul, 9),
+    (TokenKind::Slash, BinOp::Div, 9),
+    (TokenKind::Percent, BinOp::Mod, 9),
+];
+
+fn binary_operator(kind: TokenKind) -> Option<(BinOp, u8)> {
+    BINARY_OPERATORS
+        .iter()
+        .find(|(token, _, _)| *token == kind)
+        .map(|&(_, op, precedence)| (op, precedence))
+}
+
+// `Some(None)` for `=`, `Some(Some(op))` for a compound `op=`
+fn assignment_op(kind: TokenKind) -> Option<Option<BinOp>> {
+    match kind {
+        TokenKind::Equals => Some(None),
+        TokenKind::PlusEquals => Some(Some(BinOp::Add)),
+        TokenKind::MinusEquals => Some(Some(BinOp::Sub)),
+        TokenKind::StarEquals => Some(Some(BinOp::Mul)),
+        TokenKind::SlashEquals => Some(Some(BinOp::Div)),
+        TokenKind::PercentEquals => Some(Some(BinOp::Mod)),
+        _ => None,
+    }
+}
 
-        TraitDef { name, methods }
+// Whether an expression denotes a storage location that can be assigned
+// Whether a block ends in a value: a trailing expression, or an `if`/`else`
+// or `match` with one in some branch
+fn yields_value(block: &[Statement]) -> bool {
+    match block.last().map(|stmt| &stmt.kind) {
+        Some(StatementKind::Tail(_)) => true,
+        Some(StatementKind::If {
+            then_block,
+            else_block: Some(else_block),
+            ..
+        }) => yields_value(then_block) || yields_value(else_block),
+        Some(StatementKind::Match { arms, .. }) => arms.iter().any(|arm| yields_value(&arm.body)),
+        _ => false,
     }
+}
 
-    fn parse_trait_method(&mut self) -> TraitMethod {
-        if !self.expect(TokenKind::Fn) {
-            eprintln!("Expected 'fn' in trait method");
-            process::exit(1);
+// Make a block used as a value end in it: a trailing `if`/`else` or `match`
+// statement becomes the expression it would be in that position
+fn end_in_value(block: &mut [Statement]) {
+    let Some(last) = block.last_mut() else {
+        return;
+    };
+    let kind = std::mem::replace(&mut last.kind, StatementKind::Break);
+    last.kind = match kind {
+        StatementKind::If {
+            condition,
+            mut then_block,
+            else_block: Some(mut else_block),
+        } => {
+            end_in_value(&mut then_block);
+            end_in_value(&mut else_block);
+            let kind = ExprKind::If {
+                condition: Box::new(condition),
+                then_block,
+                else_block,
+            };
+            StatementKind::Tail(Expr::new(kind, last.span))
         }
+        StatementKind::Match { value, mut arms } => {
+            for arm in &mut arms {
+                end_in_value(&mut arm.body);
+            }
+            let kind = ExprKind::Match {
+                value: Box::new(value),
+                arms,
+            };
+            StatementKind::Tail(Expr::new(kind, last.span))
+        }
+        kind => kind,
+    };
+}
 
-        let name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
-            self.advance();
-            n
-        } else {
-            eprintln!("Expected method name");
-            process::exit(1);
-        };
+fn is_place(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Variable(_) => true,
+        ExprKind::MemberAccess { object, .. } => is_place(object),
+        ExprKind::ArrayIndex { array, .. } => is_place(array),
+        _ => false,
+    }
+}
 
-        if !self.expect(TokenKind::LParen) {
-            eprintln!("Expected '(' after method name");
-            process::exit(1);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_errors(source: &str) -> Vec<(Diagnostic, usize, usize)> {
+        let mut parser = Parser::new(source);
+        match parser.parse_program() {
+            Ok(_) => Vec::new(),
+            Err(diagnostics) => diagnostics
+                .into_iter()
+                .map(|d| {
+                    let loc = parser.source_map().location(d.span);
+                    (d, loc.line, loc.column)
+                })
+                .collect(),
         }
+    }
 
-        let mut params = Vec::new();
-        if self.current.kind != TokenKind::RParen {
-            loop {
-                let param_name = if self.current.kind == TokenKind::Identifier {
-                    let n = self.current.text.clone();
-                    self.advance();
-                    n
-                } else {
-                    eprintln!("Expected parameter name");
-                    process::exit(1);
-                };
+    #[test]
+    fn test_valid_program() {
+        let source = "fn main() { let x = 1; print(\"{}\", x); }";
+        let program = Parser::new(source).parse_program().unwrap();
+        assert_eq!(program.functions.len(), 1);
+        assert_eq!(program.functions[0].body.len(), 2);
+    }
 
-                if !self.expect(TokenKind::Colon) {
-                    eprintln!("Expected ':' after parameter name");
-                    process::exit(1);
-                }
+    #[test]
+    fn test_missing_semicolon_reports_location() {
+        let errors = parse_errors("fn main() {\n    let x = 10\n}\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0.code, E_EXPECTED_TOKEN);
+        assert_eq!((errors[0].1, errors[0].2), (3, 1));
+    }
 
-                let type_name = if self.current.kind == TokenKind::Identifier {
-                    let t = self.current.text.clone();
-                    self.advance();
-                    t
-                } else {
-                    eprintln!("Expected type after ':'");
-                    process::exit(1);
-                };
+    #[test]
+    fn test_reports_every_syntax_error() {
+        let source = "
+            fn a() {
+                let x = ;
+                let y = 2;
+                return y
+            }
+            struct { x: int }
+            fn b() -> int { return 1; }
+        ";
+        let errors = parse_errors(source);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].0.code, E_EXPECTED_EXPR);
+        assert_eq!(errors[1].0.code, E_EXPECTED_TOKEN);
+        assert_eq!(errors[2].0.code, E_EXPECTED_NAME);
+    }
 
-                params.push(Parameter {
-                    name: param_name,
-                    type_name,
-                });
+    #[test]
+    fn test_missing_closing_brace_recovers_at_next_item() {
+        let errors = parse_errors("fn a() {\n    let x = 1;\nfn b() { }\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].1, 3);
+    }
 
-                if !self.expect(TokenKind::Comma) {
-                    break;
-                }
+    #[test]
+    fn test_expression_spans() {
+        let source = "fn main() { let total = add(1, 2) * 3; }";
+        let program = Parser::new(source).parse_program().unwrap();
+        let function = &program.functions[0];
+        assert_eq!(&source[function.span.start..function.span.end], source);
+
+        let StatementKind::Let { value, .. } = &function.body[0].kind else {
+            panic!("expected let statement");
+        };
+        assert_eq!(&source[value.span.start..value.span.end], "add(1, 2) * 3");
+        assert_eq!(
+            &source[function.body[0].span.start..function.body[0].span.end],
+            "let total = add(1, 2) * 3;"
+        );
+    }
+
+    #[test]
+    fn test_interpolated_string_and_print_placeholders() {
+        let source = "fn main() { let s = \"{a} and {b.c + 1}\"; print(\"{} {x}\", 1); }";
+        let program = Parser::new(source).parse_program().unwrap();
+        let body = &program.functions[0].body;
+        let StatementKind::Let { value, .. } = &body[0].kind else {
+            panic!("expected let statement");
+        };
+        let ExprKind::Format(parts) = &value.kind else {
+            panic!("expected format expression");
+        };
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(&parts[1], FormatPart::Literal(text) if text == " and "));
+
+        let StatementKind::Expr(call) = &body[1].kind else {
+            panic!("expected expression statement");
+        };
+        let ExprKind::Call { args, .. } = &call.kind else {
+            panic!("expected call");
+        };
+        // The positional argument is folded into the format string
+        assert_eq!(args.len(), 1);
+        let ExprKind::Format(parts) = &args[0].kind else {
+            panic!("expected format argument");
+        };
+        assert!(matches!(
+            &parts[0],
+            FormatPart::Value(e, _) if matches!(e.kind, ExprKind::Number { value: 1, .. })
+        ));
+
+        let errors = parse_errors("fn main() { print(\"{} {}\", 1); }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0.code, E_FORMAT_ARGS);
+
+        // Extra arguments are a warning; the program still parses
+        let mut parser = Parser::new("fn main() { print(\"    \", \"\"); }");
+        let program = parser.parse_program().unwrap();
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(parser.warnings()[0].code, W_UNUSED_FORMAT_ARGS);
+        let StatementKind::Expr(call) = &program.functions[0].body[0].kind else {
+            panic!("expected expression statement");
+        };
+        assert!(matches!(&call.kind, ExprKind::Call { args, .. } if args.len() == 1));
+    }
+
+    #[test]
+    fn test_format_specs() {
+        let spec = FormatSpec::parse("*^+#010.3x").unwrap();
+        assert_eq!(spec.fill, Some('*'));
+        assert_eq!(spec.align, Some(Align::Center));
+        assert!(spec.sign && spec.alternate && spec.zero_pad);
+        assert_eq!((spec.width, spec.precision), (Some(10), Some(3)));
+        assert_eq!(spec.style, FormatStyle::LowerHex);
+        assert_eq!(FormatSpec::parse("0>5").unwrap().fill, Some('0'));
+        assert_eq!(FormatSpec::parse("").unwrap(), FormatSpec::default());
+
+        let source = "fn main() { print(\"{:>8} {n:.2}\", x); }";
+        let program = Parser::new(source).parse_program().unwrap();
+        let StatementKind::Expr(call) = &program.functions[0].body[0].kind else {
+            panic!("expected expression statement");
+        };
+        let ExprKind::Call { args, .. } = &call.kind else {
+            panic!("expected call");
+        };
+        let ExprKind::Format(parts) = &args[0].kind else {
+            panic!("expected format argument");
+        };
+        assert!(matches!(&parts[0], FormatPart::Value(_, spec) if spec.width == Some(8)));
+        assert!(matches!(&parts[2], FormatPart::Value(_, spec) if spec.precision == Some(2)));
+
+        let errors = parse_errors("fn main() { print(\"{x:q}\"); }");
+        assert_eq!(errors[0].0.code, E_FORMAT_SPEC);
+        assert_eq!((errors[0].1, errors[0].2), (1, 22));
+    }
+
+    // Render an expression with explicit grouping, e.g. `(a + (b * c))`
+    fn grouped(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Variable(name) => name.clone(),
+            ExprKind::Number { value, .. } => value.to_string(),
+            ExprKind::Binary { left, op, right } => {
+                format!("({} {:?} {})", grouped(left), op, grouped(right))
             }
+            ExprKind::Unary { op, operand } => format!("({:?} {})", op, grouped(operand)),
+            other => panic!("unexpected expression {:?}", other),
         }
+    }
 
-        if !self.expect(TokenKind::RParen) {
-            eprintln!("Expected ')' after parameters");
-            process::exit(1);
-        }
+    fn parse_value(source: &str) -> String {
+        let source = format!("fn main() {{ let v = {}; }}", source);
+        let program = Parser::new(&source).parse_program().unwrap();
+        let StatementKind::Let { value, .. } = &program.functions[0].body[0].kind else {
+            panic!("expected let statement");
+        };
+        grouped(value)
+    }
 
-        let return_type = if self.current.kind == TokenKind::Arrow {
-            self.advance();
-            if self.current.kind == TokenKind::Identifier {
-                let t = self.current.text.clone();
-                self.advance();
-                Some(t)
-            } else {
-                eprintln!("Expected return type after '->'");
-                process::exit(1);
-            }
-        } else {
-            None
+    #[test]
+    fn test_operator_precedence() {
+        assert_eq!(parse_value("a - b - c"), "((a Sub b) Sub c)");
+        assert_eq!(parse_value("a + b * c % d"), "(a Add ((b Mul c) Mod d))");
+        assert_eq!(parse_value("1 << n + 1"), "(1 Shl (n Add 1))");
+        assert_eq!(parse_value("x & 1 == 0"), "((x BitAnd 1) Eq 0)");
+        assert_eq!(
+            parse_value("a | b ^ c & d"),
+            "(a BitOr (b BitXor (c BitAnd d)))"
+        );
+        assert_eq!(
+            parse_value("~a >> 2 || b && c"),
+            "(((BitNot a) Shr 2) Or (b And c))"
+        );
+    }
+
+    #[test]
+    fn test_negative_literal_range() {
+        assert!(
+            parse_errors("fn main() { let a = -128i8; let b = -9223372036854775808i64; }")
+                .is_empty()
+        );
+        assert!(parse_errors(
+            "fn f(x: i8) -> int { return match x { -128i8..=0 => 1, _ => 2, }; }"
+        )
+        .is_empty());
+
+        let errors = parse_errors("fn main() { let a = 128i8; let b = -129i8; let c = - -128i8; }");
+        let messages: Vec<&str> = errors.iter().map(|e| e.0.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "integer literal '128i8' is out of range for i8 (max 127)",
+                "integer literal '-129i8' is out of range for i8 (min -128)",
+            ]
+        );
+        assert_eq!((errors[1].1, errors[1].2), (1, 37));
+    }
+
+    #[test]
+    fn test_assignment_targets() {
+        let source = "fn main() { p.x = 1; arr[i] += 2; line.end.y -= 3; n %= 4; }";
+        let program = Parser::new(source).parse_program().unwrap();
+        let ops: Vec<Option<BinOp>> = program.functions[0]
+            .body
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                StatementKind::Assign { op, .. } => *op,
+                _ => panic!("expected assignment"),
+            })
+            .collect();
+        assert!(matches!(
+            ops[..],
+            [None, Some(BinOp::Add), Some(BinOp::Sub), Some(BinOp::Mod)]
+        ));
+        let StatementKind::Assign { target, .. } = &program.functions[0].body[2].kind else {
+            unreachable!();
         };
+        assert_eq!(&source[target.span.start..target.span.end], "line.end.y");
 
-        if !self.expect(TokenKind::Semicolon) {
-            eprintln!("Expected ';' after trait method signature");
-            process::exit(1);
-        }
+        let errors = parse_errors("fn main() {\n    f(x) = 1;\n    1 += 2;\n}\n");
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.0.code == E_INVALID_ASSIGN_TARGET));
+        assert_eq!((errors[0].1, errors[0].2), (2, 5));
+    }
 
-        TraitMethod { name, params, return_type }
+    #[test]
+    fn test_let_bindings() {
+        let source = "fn main() { let a = 1; let mut b: u8 = 2; let c: string = \"\"; }";
+        let program = Parser::new(source).parse_program().unwrap();
+        let lets: Vec<(&str, bool, Option<&str>)> = program.functions[0]
+            .body
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                StatementKind::Let {
+                    name,
+                    mutable,
+                    type_name,
+                    ..
+                } => (
+                    name.as_str(),
+                    *mutable,
+                    type_name.as_ref().and_then(TypeExpr::as_name),
+                ),
+                _ => panic!("expected let"),
+            })
+            .collect();
+        assert_eq!(
+            lets,
+            [
+                ("a", false, None),
+                ("b", true, Some("u8")),
+                ("c", false, Some("string"))
+            ]
+        );
     }
 
-    // Parse trait implementation
-    fn parse_impl(&mut self) -> TraitImpl {
-        if !self.expect(TokenKind::Impl) {
-            eprintln!("Expected 'impl'");
-            process::exit(1);
-        }
+    #[test]
+    fn test_generic_type_arguments() {
+        let source = "fn wrap<T, U>(p: Box<Pair<T, U>>) -> Pair<T,int> { let x = 8 >> 1; }";
+        let program = Parser::new(source).parse_program().unwrap();
+        let func = &program.functions[0];
+        assert_eq!(func.params[0].type_name.to_string(), "Box<Pair<T, U>>");
+        assert_eq!(
+            func.return_type.as_ref().unwrap().to_string(),
+            "Pair<T, int>"
+        );
+        // `>>` is still a shift in expressions
+        let StatementKind::Let { value, .. } = &func.body[0].kind else {
+            panic!("expected let");
+        };
+        assert!(matches!(
+            value.kind,
+            ExprKind::Binary { op: BinOp::Shr, .. }
+        ));
+    }
 
-        let trait_name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
-            self.advance();
-            n
-        } else {
-            eprintln!("Expected trait name");
-            process::exit(1);
+    #[test]
+    fn test_trait_bounds_and_where_clauses() {
+        let source = "
+            struct Sorted<T: Ord> { items: T }
+            fn show<T: Display + Eq, U>(a: T, b: U) -> int where U: Ord, T: Hash { return 0; }
+        ";
+        let program = Parser::new(source).parse_program().unwrap();
+        let bounds = |bounds: &[TypeBound]| -> Vec<(String, Vec<String>)> {
+            bounds
+                .iter()
+                .map(|b| (b.param.clone(), b.traits.clone()))
+                .collect()
+        };
+        let owned = |param: &str, traits: &[&str]| {
+            (
+                param.to_string(),
+                traits.iter().map(|t| t.to_string()).collect::<Vec<_>>(),
+            )
         };
+        assert_eq!(bounds(&program.structs[0].bounds), [owned("T", &["Ord"])]);
+        assert_eq!(program.functions[0].type_params, ["T", "U"]);
+        assert_eq!(
+            bounds(&program.functions[0].bounds),
+            [
+                owned("T", &["Display", "Eq"]),
+                owned("U", &["Ord"]),
+                owned("T", &["Hash"])
+            ]
+        );
+    }
 
-        // Expect 'for'
-        if self.current.kind != TokenKind::For {
-            eprintln!("Expected 'for' after trait name in impl");
-            process::exit(1);
-        }
-        self.advance();
+    #[test]
+    fn test_inherent_impls_and_method_calls() {
+        let source = "
+            impl Point {
+                fn scaled(self, by: int) -> Point { return self; }
+            }
+            impl Display for Point { fn to_string(self) -> string { return \"\"; } }
+            fn main() { let n = p.scaled(2).x; p.scaled(3).show(); }
+        ";
+        let program = Parser::new(source).parse_program().unwrap();
+        let methods = &program.inherent_impls[0].methods;
+        assert_eq!(
+            program.inherent_impls[0].type_name,
+            TypeExpr::named("Point")
+        );
+        assert_eq!(methods[0].params[0].type_name, TypeExpr::named("Point"));
+        assert_eq!(
+            program.impls[0].methods[0].params[0].type_name,
+            TypeExpr::named("Point")
+        );
+
+        let StatementKind::Let { value, .. } = &program.functions[0].body[0].kind else {
+            panic!("expected let statement");
+        };
+        let ExprKind::MemberAccess { object, member } = &value.kind else {
+            panic!("expected member access");
+        };
+        assert_eq!(member, "x");
+        let ExprKind::MethodCall {
+            receiver,
+            method,
+            args,
+        } = &object.kind
+        else {
+            panic!("expected method call");
+        };
+        assert!(matches!(&receiver.kind, ExprKind::Variable(name) if name == "p"));
+        assert_eq!((method.as_str(), args.len()), ("scaled", 1));
+        let StatementKind::Expr(call) = &program.functions[0].body[1].kind else {
+            panic!("expected expression statement");
+        };
+        assert!(matches!(&call.kind, ExprKind::MethodCall { method, .. } if method == "show"));
+    }
 
-        let type_name = if self.current.kind == TokenKind::Identifier {
-            let n = self.current.text.clone();
-            self.advance();
-            n
-        } else {
-            eprintln!("Expected type name after 'for'");
-            process::exit(1);
+    #[test]
+    fn test_union_variant_constructors_and_patterns() {
+        let source = "
+            type Option = Some(int) | None;
+            fn some(n: int) -> Option { return Some(n); }
+            fn main() {
+                let x = some(1);
+                match x { Some(v) => some(v), Option::None => some(0), }
+                let None = 2;
+                let y = None;
+            }
+        ";
+        let program = Parser::new(source).parse_program().unwrap();
+        let StatementKind::Return { value: Some(value) } = &program.functions[0].body[0].kind
+        else {
+            panic!("expected return statement");
+        };
+        let ExprKind::UnionVariant {
+            union_name,
+            variant,
+            value,
+        } = &value.kind
+        else {
+            panic!("expected union variant");
+        };
+        assert_eq!((union_name.as_str(), variant.as_str()), ("Option", "Some"));
+        assert!(
+            matches!(value.as_deref().map(|v| &v.kind), Some(ExprKind::Variable(n)) if n == "n")
+        );
+
+        let body = &program.functions[1].body;
+        let StatementKind::Let { value, .. } = &body[0].kind else {
+            panic!("expected let statement");
         };
+        assert!(matches!(&value.kind, ExprKind::Call { name, .. } if name == "some"));
+        let StatementKind::Match { arms, .. } = &body[1].kind else {
+            panic!("expected match statement");
+        };
+        let patterns: Vec<&PatternKind> = arms.iter().map(|arm| &arm.pattern.kind).collect();
+        assert!(
+            matches!(patterns[0], PatternKind::UnionVariant { name, binding: Some(b) } if name == "Some" && b == "v")
+        );
+        assert!(
+            matches!(patterns[1], PatternKind::UnionVariant { name, binding: None } if name == "None")
+        );
+        // A local of the same name shadows the variant
+        let StatementKind::Let { value, .. } = &body[3].kind else {
+            panic!("expected let statement");
+        };
+        assert!(matches!(&value.kind, ExprKind::Variable(name) if name == "None"));
+    }
 
-        if !self.expect(TokenKind::LBrace) {
-            eprintln!("Expected '{{' after impl declaration");
-            process::exit(1);
-        }
+    #[test]
+    fn test_patterns_and_guards() {
+        let source = "
+            fn main() {
+                match v {
+                    1 | 2 | 3 => a(),
+                    -9..=9 => a(),
+                    n if n > 10 => a(),
+                    \"stop\" => a(),
+                    Point { x, y: 0 } => a(),
+                    _ => a(),
+                }
+            }
+        ";
+        let program = Parser::new(source).parse_program().unwrap();
+        let StatementKind::Match { arms, .. } = &program.functions[0].body[0].kind else {
+            panic!("expected match statement");
+        };
+        let PatternKind::Or(alternatives) = &arms[0].pattern.kind else {
+            panic!("expected or-pattern");
+        };
+        let numbers: Vec<i64> = alternatives
+            .iter()
+            .map(|alt| match alt.kind {
+                PatternKind::Number(n) => n,
+                _ => panic!("expected number pattern"),
+            })
+            .collect();
+        assert_eq!(numbers, [1, 2, 3]);
+        assert!(matches!(
+            arms[1].pattern.kind,
+            PatternKind::Range { start: -9, end: 9 }
+        ));
+        assert!(matches!(&arms[2].pattern.kind, PatternKind::Binding(n) if n == "n"));
+        assert!(matches!(
+            &arms[2].guard,
+            Some(Expr {
+                kind: ExprKind::Binary { op: BinOp::Gt, .. },
+                ..
+            })
+        ));
+        assert!(matches!(&arms[3].pattern.kind, PatternKind::String(s) if s == "stop"));
+        let PatternKind::Struct { name, fields } = &arms[4].pattern.kind else {
+            panic!("expected struct pattern");
+        };
+        assert_eq!(name, "Point");
+        assert!(
+            matches!(&fields[0], (f, Pattern { kind: PatternKind::Binding(b), .. }) if f == "x" && b == "x")
+        );
+        assert!(
+            matches!(&fields[1], (f, Pattern { kind: PatternKind::Number(0), .. }) if f == "y")
+        );
+        assert_eq!(arms[4].pattern.bindings(), ["x"]);
+        assert!(arms[5].guard.is_none());
+    }
 
-        let mut methods = Vec::new();
+    #[test]
+    fn test_if_match_and_block_expressions() {
+        let source = "
+            fn pick(n: int) -> int {
+                if n > 0 { 1 } else if n < 0 { -1 } else { 0 }
+            }
+            fn main() {
+                let a = match n { 0 => 1, _ => { let b = 2; b } };
+                let c = { f(); 3 };
+                g()
+            }
+        ";
+        let program = Parser::new(source).parse_program().unwrap();
+        // A function's trailing value is returned
+        let StatementKind::Return { value: Some(value) } = &program.functions[0].body[0].kind
+        else {
+            panic!("expected return");
+        };
+        let ExprKind::If { else_block, .. } = &value.kind else {
+            panic!("expected if expression");
+        };
+        assert!(matches!(
+            &else_block[0].kind,
+            StatementKind::Tail(Expr {
+                kind: ExprKind::If { .. },
+                ..
+            })
+        ));
+
+        let body = &program.functions[1].body;
+        let StatementKind::Let { value, .. } = &body[0].kind else {
+            panic!("expected let");
+        };
+        let ExprKind::Match { arms, .. } = &value.kind else {
+            panic!("expected match expression");
+        };
+        assert!(matches!(arms[0].body[0].kind, StatementKind::Tail(_)));
+        assert!(matches!(arms[1].body[1].kind, StatementKind::Tail(_)));
+        let StatementKind::Let { value, .. } = &body[1].kind else {
+            panic!("expected let");
+        };
+        let ExprKind::Block(stmts) = &value.kind else {
+            panic!("expected block expression");
+        };
+        assert!(matches!(stmts[0].kind, StatementKind::Expr(_)));
+        assert!(matches!(stmts[1].kind, StatementKind::Tail(_)));
+        assert!(matches!(body[2].kind, StatementKind::Tail(_)));
+
+        let errors = parse_errors("fn main() { let x = if c { 1 }; }");
+        assert_eq!(
+            errors[0].0.message,
+            "Expected 'else'; an 'if' used as a value needs one, found ';'"
+        );
+    }
 
-        while self.current.kind != TokenKind::RBrace && self.current.kind != TokenKind::EOF {
-            methods.push(self.parse_function());
-        }
+    #[test]
+    fn test_tuples_and_destructuring_let() {
+        let source = "
+            fn split(p: (int, (string, bool))) -> (int, string,) {
+                let mut (a, _, ) = (p.0, p.1.0);
+                let ((b, mut c), _) = p.1;
+                return (a, p.1.0);
+            }
+        ";
+        let program = Parser::new(source).parse_program().unwrap();
+        let func = &program.functions[0];
+        assert_eq!(
+            func.params[0].type_name.to_string(),
+            "(int, (string, bool))"
+        );
+        assert_eq!(
+            func.return_type.as_ref().unwrap().to_string(),
+            "(int, string)"
+        );
+        let name = |name: &str, mutable| LetPattern::Name {
+            name: name.to_string(),
+            mutable,
+        };
+        let StatementKind::LetTuple {
+            elements, value, ..
+        } = &func.body[0].kind
+        else {
+            panic!("expected tuple let");
+        };
+        assert_eq!(elements, &[name("a", true), LetPattern::Wildcard]);
+        let StatementKind::LetTuple {
+            elements: nested, ..
+        } = &func.body[1].kind
+        else {
+            panic!("expected tuple let");
+        };
+        assert_eq!(
+            nested,
+            &[
+                LetPattern::Tuple(vec![name("b", false), name("c", true)]),
+                LetPattern::Wildcard
+            ]
+        );
+        assert_eq!(
+            LetPattern::bindings(nested),
+            [("b", false, vec![0, 0]), ("c", true, vec![0, 1])]
+        );
+        let ExprKind::Tuple(elements) = &value.kind else {
+            panic!("expected tuple");
+        };
+        // `p.1.0` is two field accesses, not `p` and the float `1.0`
+        let ExprKind::MemberAccess { object, member } = &elements[1].kind else {
+            panic!("expected member access");
+        };
+        assert_eq!(member, "0");
+        assert!(matches!(&object.kind, ExprKind::MemberAccess { member, .. } if member == "1"));
+
+        let errors = parse_errors("fn f(p: (int)) { let x = (1,); }");
+        assert_eq!(
+            errors[0].0.message,
+            "A tuple type needs at least two elements"
+        );
+        let errors = parse_errors("fn main() { let x = (1,); }");
+        assert_eq!(errors[0].0.message, "A tuple needs at least two elements");
+        let errors = parse_errors("fn main() { let ((a,), b) = t; }");
+        assert_eq!(
+            errors[0].0.message,
+            "A tuple pattern needs at least two names"
+        );
+    }
 
-        if !self.expect(TokenKind::RBrace) {
-            eprintln!("Expected '}}' after impl block");
-            process::exit(1);
-        }
+    #[test]
+    fn test_pointer_slice_array_and_function_types() {
+        let source = "
+            type Handler = fn(*u8, int) -> bool;
+            struct Signal<T> { value: T, subscribers: []fn(T), }
+            fn signal_get<T>(sig: *Signal<T>, grid: [[u8; 4]; 2]) -> fn() -> T { }
+        ";
+        let program = Parser::new(source).parse_program().unwrap();
+        let int = TypeExpr::named("int");
+        let byte_ptr = TypeExpr::Pointer(Box::new(TypeExpr::named("u8")));
+        assert_eq!(
+            program.type_aliases[0].target_type,
+            TypeExpr::Function {
+                params: vec![byte_ptr, int],
+                return_type: Some(Box::new(TypeExpr::named("bool"))),
+            }
+        );
+        let subscribers = &program.structs[0].fields[1].type_name;
+        assert!(matches!(subscribers, TypeExpr::Slice(f) if f.to_string() == "fn(T)"));
+        let func = &program.functions[0];
+        assert_eq!(func.params[0].type_name.to_string(), "*Signal<T>");
+        assert!(matches!(func.params[1].type_name, TypeExpr::Array(_, 2)));
+        assert_eq!(func.params[1].type_name.to_string(), "[[u8; 4]; 2]");
+        assert_eq!(func.return_type.as_ref().unwrap().to_string(), "fn() -> T");
+
+        let errors = parse_errors("fn f(a: [int; n]) { }");
+        assert_eq!(errors[0].0.message, "Expected array length, found 'n'");
+    }
 
-        TraitImpl { trait_name, type_name, methods }
+    #[test]
+    fn test_import_diagnostics_point_into_imported_file() {
+        let path = std::env::temp_dir().join("athon_parser_import_test.at");
+        std::fs::write(&path, "fn helper() {\n    let y = ;\n}\n").unwrap();
+        let source = format!("import \"{}\"\nfn main() {{ }}\n", path.display());
+
+        let mut parser = Parser::new(&source);
+        let errors = parser.parse_program().unwrap_err();
+        let loc = parser.source_map().location(errors[0].span);
+        assert_eq!(loc.file, path.display().to_string());
+        assert_eq!((loc.line, loc.column), (2, 13));
     }
 }
@@ -0,0 +1,237 @@
+// Scoped symbol table for Athōn bootstrap compiler
+// Resolves names through nested scopes (function, block, loop, match arm)
+// with shadowing, and tracks which bindings were never read. The type
+// system in compiler/type-system uses this module too, so it must not
+// depend on the rest of the crate.
+
+/// What opened a scope
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopeKind {
+    Function,
+    Block,
+    Loop,
+    MatchArm,
+}
+
+/// Why a name failed to resolve
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    Undefined(String),
+    /// The name is declared later in an enclosing scope
+    UsedBeforeDefinition(String),
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::Undefined(name) => write!(f, "Undefined variable: {}", name),
+            ResolveError::UsedBeforeDefinition(name) => {
+                write!(f, "Variable {} used before its definition", name)
+            }
+        }
+    }
+}
+
+struct Symbol<T> {
+    name: String,
+    // `None` until the declaration is reached
+    value: Option<T>,
+    used: bool,
+}
+
+struct Scope<T> {
+    kind: ScopeKind,
+    // In declaration order; a later symbol shadows an earlier one
+    symbols: Vec<Symbol<T>>,
+}
+
+/// Stack of scopes, innermost last
+pub struct ScopeStack<T> {
+    scopes: Vec<Scope<T>>,
+}
+
+impl<T> ScopeStack<T> {
+    /// A stack holding a single function scope
+    pub fn new() -> Self {
+        ScopeStack {
+            scopes: vec![Scope {
+                kind: ScopeKind::Function,
+                symbols: Vec::new(),
+            }],
+        }
+    }
+
+    pub fn push(&mut self, kind: ScopeKind) {
+        self.scopes.push(Scope {
+            kind,
+            symbols: Vec::new(),
+        });
+    }
+
+    /// Leave the innermost scope, returning the bindings that were never used
+    pub fn pop(&mut self) -> Vec<(String, T)> {
+        let scope = self.scopes.pop().expect("scope stack underflow");
+        scope
+            .symbols
+            .into_iter()
+            .filter(|s| !s.used)
+            .filter_map(|s| Some((s.name, s.value?)))
+            .collect()
+    }
+
+    /// Announce a name that the current scope defines later, so earlier
+    /// uses are reported as use-before-definition instead of undefined
+    pub fn declare(&mut self, name: &str) {
+        self.innermost().symbols.push(Symbol {
+            name: name.to_string(),
+            value: None,
+            used: false,
+        });
+    }
+
+    /// Bind a name in the current scope, shadowing any earlier binding
+    pub fn define(&mut self, name: &str, value: T) {
+        let symbols = &mut self.innermost().symbols;
+        // Complete the forward declaration made by `declare`, if any
+        match symbols
+            .iter_mut()
+            .find(|s| s.name == name && s.value.is_none())
+        {
+            Some(symbol) => symbol.value = Some(value),
+            None => symbols.push(Symbol {
+                name: name.to_string(),
+                value: Some(value),
+                used: false,
+            }),
+        }
+    }
+
+    /// Resolve a name without marking it used
+    pub fn get(&self, name: &str) -> Result<&T, ResolveError> {
+        self.find(name)
+            .map(|(scope, index)| self.scopes[scope].symbols[index].value.as_ref().unwrap())
+    }
+
+    /// Resolve a name and mark the binding used
+    pub fn lookup(&mut self, name: &str) -> Result<&mut T, ResolveError> {
+        let (scope, index) = self.find(name)?;
+        let symbol = &mut self.scopes[scope].symbols[index];
+        symbol.used = true;
+        Ok(symbol.value.as_mut().unwrap())
+    }
+
+    /// Resolve a name for updating its value without counting it as a use
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut T, ResolveError> {
+        let (scope, index) = self.find(name)?;
+        Ok(self.scopes[scope].symbols[index].value.as_mut().unwrap())
+    }
+
+    /// Every visible binding, outermost first, including shadowed ones
+    pub fn values(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.scopes
+            .iter()
+            .flat_map(|scope| &scope.symbols)
+            .filter_map(|s| Some((s.name.as_str(), s.value.as_ref()?)))
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = (&str, &mut T)> {
+        self.scopes
+            .iter_mut()
+            .flat_map(|scope| &mut scope.symbols)
+            .filter_map(|s| Some((s.name.as_str(), s.value.as_mut()?)))
+    }
+
+    /// Bindings of the innermost scope, in declaration order
+    pub fn current(&self) -> impl Iterator<Item = (&str, &T)> {
+        let scope = self.scopes.last().expect("scope stack underflow");
+        scope
+            .symbols
+            .iter()
+            .filter_map(|s| Some((s.name.as_str(), s.value.as_ref()?)))
+    }
+
+    /// Whether `break` and `continue` have a loop to refer to
+    pub fn in_loop(&self) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .take_while(|s| s.kind != ScopeKind::Function)
+            .any(|s| s.kind == ScopeKind::Loop)
+    }
+
+    fn innermost(&mut self) -> &mut Scope<T> {
+        self.scopes.last_mut().expect("scope stack underflow")
+    }
+
+    // Innermost defined binding of `name`. A pending declaration doesn't
+    // hide outer bindings: until its `let`, the name still means the outer one.
+    fn find(&self, name: &str) -> Result<(usize, usize), ResolveError> {
+        let mut pending = false;
+        for (scope_index, scope) in self.scopes.iter().enumerate().rev() {
+            for (index, symbol) in scope.symbols.iter().enumerate().rev() {
+                if symbol.name == name {
+                    if symbol.value.is_some() {
+                        return Ok((scope_index, index));
+                    }
+                    pending = true;
+                }
+            }
+        }
+        if pending {
+            Err(ResolveError::UsedBeforeDefinition(name.to_string()))
+        } else {
+            Err(ResolveError::Undefined(name.to_string()))
+        }
+    }
+}
+
+impl<T> Default for ScopeStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shadowing_and_unused() {
+        let mut scopes = ScopeStack::new();
+        scopes.define("x", 1);
+        scopes.push(ScopeKind::Block);
+        scopes.define("x", 2);
+        assert_eq!(*scopes.lookup("x").unwrap(), 2);
+        scopes.define("y", 3);
+        assert_eq!(scopes.pop(), vec![("y".to_string(), 3)]);
+
+        // The outer `x` is visible again and was never read
+        assert_eq!(*scopes.get("x").unwrap(), 1);
+        assert_eq!(scopes.pop(), vec![("x".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_use_before_definition() {
+        let mut scopes: ScopeStack<i32> = ScopeStack::new();
+        scopes.declare("total");
+        assert_eq!(
+            scopes.lookup("total"),
+            Err(ResolveError::UsedBeforeDefinition("total".to_string()))
+        );
+        assert_eq!(
+            scopes.get("other"),
+            Err(ResolveError::Undefined("other".to_string()))
+        );
+        scopes.define("total", 0);
+        assert!(scopes.lookup("total").is_ok());
+    }
+
+    #[test]
+    fn test_loop_scopes() {
+        let mut scopes: ScopeStack<i32> = ScopeStack::new();
+        assert!(!scopes.in_loop());
+        scopes.push(ScopeKind::Loop);
+        scopes.push(ScopeKind::MatchArm);
+        assert!(scopes.in_loop());
+    }
+}
@@ -0,0 +1,190 @@
+// Source map for Athōn bootstrap compiler
+// Owns the text of every file taking part in a compilation (the root file
+// and everything it imports) and turns byte-offset spans back into
+// file/line/column locations for diagnostics. Columns are 1-based and
+// counted in characters.
+
+use crate::diagnostics::Diagnostic;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct FileId(pub usize);
+
+/// Byte range `start..end` within a single source file
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        Self { file, start, end }
+    }
+
+    /// Span covering both `self` and `other` (which must be in the same file)
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    line_starts: Vec<usize>,
+}
+
+/// Resolved human-readable location of a span (1-based lines and columns)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    pub fn add_file(&mut self, name: &str, source: &str) -> FileId {
+        let mut line_starts = vec![0];
+        for (i, b) in source.bytes().enumerate() {
+            if b == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        self.files.push(SourceFile {
+            name: name.to_string(),
+            source: source.to_string(),
+            line_starts,
+        });
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    /// Find the id of an already loaded file by name
+    pub fn find(&self, name: &str) -> Option<FileId> {
+        self.files.iter().position(|f| f.name == name).map(FileId)
+    }
+
+    fn line_col(&self, file: &SourceFile, offset: usize) -> (usize, usize) {
+        let line = match file.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        // Columns count characters so they line up with what editors show
+        let line_start = file.line_starts[line];
+        let column = file
+            .source
+            .get(line_start..offset)
+            .map_or(offset - line_start, |text| text.chars().count());
+        (line + 1, column + 1)
+    }
+
+    pub fn location(&self, span: Span) -> Location {
+        let file = self.file(span.file);
+        let (line, column) = self.line_col(file, span.start);
+        let (end_line, end_column) = self.line_col(file, span.end);
+        Location {
+            file: file.name.clone(),
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
+
+    /// Text of the given 1-based line, without its newline
+    pub fn line_text(&self, id: FileId, line: usize) -> &str {
+        let file = self.file(id);
+        let start = file.line_starts[line - 1];
+        let end = file
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(file.source.len());
+        file.source[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    /// Render a diagnostic with its location and the offending source line
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let loc = self.location(diagnostic.span);
+        let text = self.line_text(diagnostic.span.file, loc.line);
+        let gutter = " ".repeat(loc.line.to_string().len());
+        let width = if loc.end_line == loc.line && loc.end_column > loc.column {
+            loc.end_column - loc.column
+        } else {
+            1
+        };
+
+        let mut out = format!("{}:{}:{}: {}\n", loc.file, loc.line, loc.column, diagnostic);
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", loc.line, text));
+        out.push_str(&format!(
+            "{} | {}{}",
+            gutter,
+            " ".repeat(loc.column - 1),
+            "^".repeat(width)
+        ));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_across_files() {
+        let mut map = SourceMap::new();
+        let root = map.add_file("main.at", "fn main() {\n    foo();\n}\n");
+        let lib = map.add_file("lib.at", "fn foo() {}\n");
+
+        let loc = map.location(Span::new(root, 16, 19));
+        assert_eq!((loc.file.as_str(), loc.line, loc.column), ("main.at", 2, 5));
+        assert_eq!((loc.end_line, loc.end_column), (2, 8));
+
+        let loc = map.location(Span::new(lib, 3, 6));
+        assert_eq!((loc.file.as_str(), loc.line, loc.column), ("lib.at", 1, 4));
+        assert_eq!(map.find("lib.at"), Some(lib));
+    }
+
+    #[test]
+    fn test_columns_count_characters() {
+        let mut map = SourceMap::new();
+        let id = map.add_file("u.at", "let pōs = \"ā\"; x");
+        let start = "let pōs = \"ā\"; ".len();
+        let loc = map.location(Span::new(id, start, start + 1));
+        assert_eq!((loc.line, loc.column, loc.end_column), (1, 16, 17));
+        assert!(map
+            .render(&Diagnostic::error(
+                "E0000",
+                "x".to_string(),
+                Span::new(id, start, start + 1)
+            ))
+            .ends_with(&format!("| {}^", " ".repeat(15))));
+    }
+
+    #[test]
+    fn test_line_text() {
+        let mut map = SourceMap::new();
+        let id = map.add_file("a.at", "let x = 1;\r\nlet y = 2;");
+        assert_eq!(map.line_text(id, 1), "let x = 1;");
+        assert_eq!(map.line_text(id, 2), "let y = 2;");
+    }
+}
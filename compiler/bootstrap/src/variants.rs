@@ -0,0 +1,267 @@
+// Union variant resolution for Athōn bootstrap compiler
+// Unqualified union variants are written like calls and variables, `Some(5)`
+// and `None`, and a qualified unit variant like an enum variant,
+// `Option::None`; in a pattern a bare `None` reads like a binding. Which
+// names are variants is only known once every item
+// is parsed, so this pass runs after parsing and turns them into union
+// variant expressions and patterns. A variant name declared by more than
+// one union must be qualified, and a function's own parameters and locals
+// shadow variants of the same name.
+
+use crate::ast::*;
+use std::collections::{HashMap, HashSet};
+
+/// Rewrite the union variants in every function body of `program`
+pub fn resolve(program: &mut Program) {
+    // Union of each variant name, or `None` when several declare it
+    let mut variants: HashMap<String, Option<String>> = HashMap::new();
+    for union_type in &program.unions {
+        for variant in &union_type.variants {
+            variants
+                .entry(variant.name.clone())
+                .and_modify(|owner| *owner = None)
+                .or_insert_with(|| Some(union_type.name.clone()));
+        }
+    }
+    if variants.is_empty() {
+        return;
+    }
+    let mut resolver = Resolver {
+        variants,
+        unions: program.unions.iter().map(|u| u.name.clone()).collect(),
+        functions: program.functions.iter().map(|f| f.name.clone()).collect(),
+        locals: HashSet::new(),
+    };
+
+    let impl_methods = program.impls.iter_mut().flat_map(|i| &mut i.methods);
+    let inherent_methods = program
+        .inherent_impls
+        .iter_mut()
+        .flat_map(|i| &mut i.methods);
+    for func in program
+        .functions
+        .iter_mut()
+        .chain(impl_methods)
+        .chain(inherent_methods)
+    {
+        resolver.locals = func.params.iter().map(|p| p.name.clone()).collect();
+        resolver.block(&mut func.body);
+    }
+}
+
+struct Resolver {
+    variants: HashMap<String, Option<String>>,
+    unions: HashSet<String>,
+    functions: HashSet<String>,
+    // Every parameter and local of the current function seen so far
+    locals: HashSet<String>,
+}
+
+impl Resolver {
+    // Union declaring the unqualified variant `name`, unless it is shadowed
+    fn union_of(&self, name: &str) -> Option<String> {
+        if self.locals.contains(name) || self.functions.contains(name) {
+            return None;
+        }
+        self.variants.get(name).cloned().flatten()
+    }
+
+    fn block(&mut self, stmts: &mut [Statement]) {
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &mut Statement) {
+        match &mut stmt.kind {
+            StatementKind::Let { name, value, .. } => {
+                self.expr(value);
+                self.locals.insert(name.clone());
+            }
+            StatementKind::LetTuple {
+                elements, value, ..
+            } => {
+                self.expr(value);
+                let names = LetPattern::bindings(elements);
+                self.locals
+                    .extend(names.into_iter().map(|(name, _, _)| name.to_string()));
+            }
+            StatementKind::Assign { target, value, .. } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            StatementKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expr(condition);
+                self.block(then_block);
+                if let Some(else_block) = else_block {
+                    self.block(else_block);
+                }
+            }
+            StatementKind::While { condition, body } => {
+                self.expr(condition);
+                self.block(body);
+            }
+            StatementKind::For {
+                loop_var,
+                start,
+                end,
+                body,
+            } => {
+                self.expr(start);
+                self.expr(end);
+                self.locals.insert(loop_var.clone());
+                self.block(body);
+            }
+            StatementKind::Match { value, arms } => {
+                self.expr(value);
+                self.arms(arms);
+            }
+            StatementKind::Return { value: Some(value) }
+            | StatementKind::Expr(value)
+            | StatementKind::Tail(value) => {
+                self.expr(value);
+            }
+            StatementKind::Return { value: None }
+            | StatementKind::Break
+            | StatementKind::Continue => {}
+        }
+    }
+
+    fn arms(&mut self, arms: &mut [MatchArm]) {
+        for arm in arms {
+            self.pattern(&mut arm.pattern);
+            self.locals
+                .extend(arm.pattern.bindings().into_iter().map(String::from));
+            if let Some(guard) = &mut arm.guard {
+                self.expr(guard);
+            }
+            self.block(&mut arm.body);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern) {
+        match &mut pattern.kind {
+            PatternKind::EnumVariant { enum_name, variant } if self.unions.contains(enum_name) => {
+                pattern.kind = PatternKind::UnionVariant {
+                    name: variant.clone(),
+                    binding: None,
+                };
+            }
+            // Patterns bind fresh names, so a variant is never shadowed here
+            PatternKind::Binding(name) if self.variants.contains_key(name) => {
+                pattern.kind = PatternKind::UnionVariant {
+                    name: name.clone(),
+                    binding: None,
+                };
+            }
+            PatternKind::Struct { fields, .. } => {
+                for (_, field) in fields {
+                    self.pattern(field);
+                }
+            }
+            PatternKind::Or(alternatives) => {
+                for alternative in alternatives {
+                    self.pattern(alternative);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &mut Expr) {
+        match &mut expr.kind {
+            ExprKind::Number { .. }
+            | ExprKind::Float { .. }
+            | ExprKind::Boolean(_)
+            | ExprKind::Char(_)
+            | ExprKind::String(_) => {}
+            ExprKind::Variable(name) => {
+                if let Some(union_name) = self.union_of(name) {
+                    expr.kind = ExprKind::UnionVariant {
+                        union_name,
+                        variant: name.clone(),
+                        value: None,
+                    };
+                }
+            }
+            ExprKind::EnumVariant { enum_name, variant } => {
+                if self.unions.contains(enum_name) {
+                    expr.kind = ExprKind::UnionVariant {
+                        union_name: enum_name.clone(),
+                        variant: variant.clone(),
+                        value: None,
+                    };
+                }
+            }
+            ExprKind::UnionVariant { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+            }
+            ExprKind::Call { name, args } => {
+                for arg in args.iter_mut() {
+                    self.expr(arg);
+                }
+                let union_name = self.union_of(name).filter(|_| args.len() <= 1);
+                if let Some(union_name) = union_name {
+                    expr.kind = ExprKind::UnionVariant {
+                        union_name,
+                        variant: name.clone(),
+                        value: args.pop().map(Box::new),
+                    };
+                }
+            }
+            ExprKind::MethodCall { receiver, args, .. } => {
+                self.expr(receiver);
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::ArrayLiteral(elements) | ExprKind::Tuple(elements) => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+            ExprKind::ArrayIndex { array, index } => {
+                self.expr(array);
+                self.expr(index);
+            }
+            ExprKind::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+            ExprKind::MemberAccess { object, .. } => self.expr(object),
+            ExprKind::Binary { left, right, .. } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Unary { operand, .. } => self.expr(operand),
+            ExprKind::Format(parts) => {
+                for part in parts {
+                    if let FormatPart::Value(value, _) = part {
+                        self.expr(value);
+                    }
+                }
+            }
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expr(condition);
+                self.block(then_block);
+                self.block(else_block);
+            }
+            ExprKind::Match { value, arms } => {
+                self.expr(value);
+                self.arms(arms);
+            }
+            ExprKind::Block(stmts) => self.block(stmts),
+        }
+    }
+}
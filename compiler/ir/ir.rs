@@ -67,6 +67,9 @@ pub enum Instruction {
     /// Constant boolean: %dest = const_bool value
     ConstBool { dest: Register, value: bool },
     
+    /// Constant float: %dest = const_float value : ty
+    ConstFloat { dest: Register, value: f64, ty: Type },
+    
     /// Constant string: %dest = const_string value
     ConstString { dest: Register, value: String },
     
@@ -152,11 +155,24 @@ impl Register {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Void,
+    /// `int` / `i32`
     Int,
+    I8,
+    I16,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+    F32,
+    F64,
     Bool,
     String,
     Array(Box<Type>, usize),
     Struct(String),
+    /// `(int, string)`; stored as a struct with fields `0`, `1`, ...
+    Tuple(Vec<Type>),
     Enum(String),
     Ptr(Box<Type>),
 }
@@ -168,6 +184,12 @@ pub enum BinOp {
     Sub,
     Mul,
     Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     Eq,
     NotEq,
     Lt,
@@ -183,6 +205,7 @@ pub enum BinOp {
 pub enum UnaryOp {
     Not,
     Neg,
+    BitNot,
 }
 
 /// Struct definition
@@ -4,7 +4,36 @@ use crate::ir::{
     Module, Function, BasicBlock, Instruction, Terminator, Register, Type, BinOp, UnaryOp,
     StructDef, EnumDef,
 };
-use std::collections::HashMap;
+use crate::ir::printer::print_type;
+use crate::type_system::{ScopeKind, ScopeStack};
+
+/// Target of an assignment: a variable, a struct field or an array element
+#[derive(Debug, Clone)]
+pub enum Place {
+    Var(String),
+    Field(Register, String),
+    Index(Register, Register),
+}
+
+/// A match pattern, tested against a value by `gen_pattern_test`
+#[derive(Debug, Clone)]
+pub enum MatchPattern {
+    Wildcard,
+    Int(i32),
+    Bool(bool),
+    /// Compared with `strcmp`
+    String(String),
+    /// `start..=end`
+    Range(i32, i32),
+    /// `Color::Red`, compared by the variant's index
+    Variant { enum_name: String, variant: String },
+    /// A name, bound to the whole value
+    Binding(String),
+    /// `Point { x, y: 0 }`
+    Struct(Vec<(String, MatchPattern)>),
+    /// `1 | 2 | 3`
+    Or(Vec<MatchPattern>),
+}
 
 /// IR Generator state
 pub struct IRGenerator {
@@ -13,7 +42,7 @@ pub struct IRGenerator {
     current_block: Option<BasicBlock>,
     register_counter: usize,
     label_counter: usize,
-    var_registers: HashMap<String, Register>,
+    var_registers: ScopeStack<Register>,
 }
 
 impl IRGenerator {
@@ -24,7 +53,7 @@ impl IRGenerator {
             current_block: None,
             register_counter: 0,
             label_counter: 0,
-            var_registers: HashMap::new(),
+            var_registers: ScopeStack::new(),
         }
     }
     
@@ -74,7 +103,17 @@ impl IRGenerator {
     /// Convert type name to IR type
     fn convert_type(&self, type_name: &str) -> Type {
         match type_name {
-            "int" => Type::Int,
+            "int" | "i32" => Type::Int,
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i64" => Type::I64,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "usize" => Type::Usize,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
             "bool" => Type::Bool,
             "string" => Type::String,
             "void" => Type::Void,
@@ -111,6 +150,17 @@ impl IRGenerator {
         reg
     }
     
+    /// Generate IR for a constant float of type `F32` or `F64`
+    pub fn gen_const_float(&mut self, value: f64, ty: Type) -> Register {
+        let reg = self.fresh_register("const");
+        self.emit(Instruction::ConstFloat {
+            dest: reg.clone(),
+            value,
+            ty,
+        });
+        reg
+    }
+    
     /// Generate IR for a constant string
     pub fn gen_const_string(&mut self, value: String) -> Register {
         let reg = self.fresh_register("str");
@@ -124,9 +174,9 @@ impl IRGenerator {
     /// Generate IR for a variable reference
     pub fn gen_variable(&mut self, name: &str) -> Result<Register, String> {
         self.var_registers
-            .get(name)
+            .lookup(name)
             .cloned()
-            .ok_or_else(|| format!("Undefined variable: {}", name))
+            .map_err(|e| e.to_string())
     }
     
     /// Generate IR for binary operation
@@ -169,9 +219,110 @@ impl IRGenerator {
         dest
     }
     
-    /// Generate IR for variable assignment
+    /// Generate IR for `let name = value`, shadowing any outer binding
+    pub fn gen_let(&mut self, name: String, value: Register) {
+        self.var_registers.define(&name, value);
+    }
+    
+    /// Generate IR for variable assignment. The innermost binding of the
+    /// name is updated, so assigning inside a block changes the outer variable.
     pub fn gen_assign(&mut self, name: String, value: Register) {
-        self.var_registers.insert(name, value);
+        match self.var_registers.get_mut(&name) {
+            Ok(reg) => *reg = value,
+            Err(_) => self.var_registers.define(&name, value),
+        }
+    }
+    
+    /// Enter a block, loop body or match arm
+    pub fn enter_scope(&mut self, kind: ScopeKind) {
+        self.var_registers.push(kind);
+    }
+    
+    /// Leave the innermost scope, returning the variables it never read
+    pub fn exit_scope(&mut self) -> Vec<String> {
+        self.var_registers.pop().into_iter().map(|(name, _)| name).collect()
+    }
+    
+    /// Generate IR for `place = value`
+    pub fn gen_store(&mut self, place: Place, value: Register) {
+        match place {
+            Place::Var(name) => self.gen_assign(name, value),
+            Place::Field(struct_reg, field) => self.gen_struct_store(struct_reg, field, value),
+            Place::Index(array, index) => self.gen_array_store(array, index, value),
+        }
+    }
+    
+    /// Generate IR for a compound assignment `place op= value`
+    pub fn gen_compound_assign(&mut self, place: Place, op: BinOp, value: Register) -> Result<(), String> {
+        let current = match &place {
+            Place::Var(name) => self.gen_variable(name)?,
+            Place::Field(struct_reg, field) => self.gen_struct_load(struct_reg.clone(), field.clone()),
+            Place::Index(array, index) => self.gen_array_load(array.clone(), index.clone()),
+        };
+        let result = self.gen_binop(op, current, value);
+        self.gen_store(place, result);
+        Ok(())
+    }
+    
+    /// Generate IR testing whether `value` matches `pattern`, returning a
+    /// bool register. The names the pattern binds are defined in the current
+    /// scope, so a match arm enters its own scope first; a guard is lowered
+    /// after the test and combined with it by `BinOp::And`.
+    pub fn gen_pattern_test(&mut self, value: Register, pattern: &MatchPattern) -> Result<Register, String> {
+        match pattern {
+            MatchPattern::Wildcard => Ok(self.gen_const_bool(true)),
+            MatchPattern::Binding(name) => {
+                self.gen_let(name.clone(), value);
+                Ok(self.gen_const_bool(true))
+            }
+            MatchPattern::Int(n) => {
+                let n = self.gen_const_int(*n);
+                Ok(self.gen_binop(BinOp::Eq, value, n))
+            }
+            MatchPattern::Bool(b) => {
+                let b = self.gen_const_bool(*b);
+                Ok(self.gen_binop(BinOp::Eq, value, b))
+            }
+            MatchPattern::String(s) => {
+                let s = self.gen_const_string(s.clone());
+                let order = self.gen_call("strcmp".to_string(), vec![value, s], true);
+                let zero = self.gen_const_int(0);
+                Ok(self.gen_binop(BinOp::Eq, order.expect("strcmp returns a value"), zero))
+            }
+            MatchPattern::Range(start, end) => {
+                let start = self.gen_const_int(*start);
+                let end = self.gen_const_int(*end);
+                let above = self.gen_binop(BinOp::GtEq, value.clone(), start);
+                let below = self.gen_binop(BinOp::LtEq, value, end);
+                Ok(self.gen_binop(BinOp::And, above, below))
+            }
+            MatchPattern::Variant { enum_name, variant } => {
+                let index = self.module.enums
+                    .iter()
+                    .find(|e| &e.name == enum_name)
+                    .and_then(|e| e.variants.iter().position(|v| v == variant))
+                    .ok_or_else(|| format!("Unknown enum variant: {}::{}", enum_name, variant))?;
+                let index = self.gen_const_int(index as i32);
+                Ok(self.gen_binop(BinOp::Eq, value, index))
+            }
+            MatchPattern::Struct(fields) => {
+                let mut test = self.gen_const_bool(true);
+                for (field, pattern) in fields {
+                    let field_value = self.gen_struct_load(value.clone(), field.clone());
+                    let field_test = self.gen_pattern_test(field_value, pattern)?;
+                    test = self.gen_binop(BinOp::And, test, field_test);
+                }
+                Ok(test)
+            }
+            MatchPattern::Or(alternatives) => {
+                let mut test = self.gen_const_bool(false);
+                for pattern in alternatives {
+                    let alternative = self.gen_pattern_test(value.clone(), pattern)?;
+                    test = self.gen_binop(BinOp::Or, test, alternative);
+                }
+                Ok(test)
+            }
+        }
     }
     
     /// Generate IR for conditional branch
@@ -253,13 +404,51 @@ impl IRGenerator {
         });
     }
     
+    /// Generate IR for a tuple literal: a struct named by the tuple type,
+    /// with one field per element
+    pub fn gen_tuple(&mut self, elements: Vec<(Register, Type)>) -> Register {
+        let types: Vec<Type> = elements.iter().map(|(_, ty)| ty.clone()).collect();
+        let struct_name = self.tuple_struct(types);
+        let tuple = self.gen_struct_alloc(struct_name);
+        for (i, (value, _)) in elements.into_iter().enumerate() {
+            self.gen_struct_store(tuple.clone(), i.to_string(), value);
+        }
+        tuple
+    }
+    
+    /// Generate IR for `tuple.index`
+    pub fn gen_tuple_load(&mut self, tuple: Register, index: usize) -> Register {
+        self.gen_struct_load(tuple, index.to_string())
+    }
+    
+    /// Generate IR for `let (a, b) = tuple`, binding each name to its
+    /// element; `_` skips one
+    pub fn gen_let_tuple(&mut self, names: &[String], tuple: Register) {
+        for (i, name) in names.iter().enumerate() {
+            if name != "_" {
+                let element = self.gen_tuple_load(tuple.clone(), i);
+                self.gen_let(name.clone(), element);
+            }
+        }
+    }
+    
+    /// Name of the struct holding tuples of `types`, declared on first use
+    fn tuple_struct(&mut self, types: Vec<Type>) -> String {
+        let name = print_type(&Type::Tuple(types.clone()));
+        if !self.module.structs.iter().any(|s| s.name == name) {
+            let fields = types.into_iter().enumerate().map(|(i, ty)| (i.to_string(), ty)).collect();
+            self.add_struct(name.clone(), fields);
+        }
+        name
+    }
+    
     /// Start a new function
     pub fn start_function(&mut self, name: String, params: Vec<(String, Type)>, return_type: Type) {
         // Finish previous function if any
         self.finish_function();
         
         self.current_function = Some(Function::new(name, params.clone(), return_type));
-        self.var_registers.clear();
+        self.var_registers = ScopeStack::new();
         self.register_counter = 0;
         self.label_counter = 0;
         
@@ -269,7 +458,7 @@ impl IRGenerator {
         // Create registers for parameters
         for (param_name, _) in params {
             let reg = self.fresh_register(&param_name);
-            self.var_registers.insert(param_name, reg);
+            self.var_registers.define(&param_name, reg);
         }
     }
     
@@ -326,4 +515,119 @@ mod tests {
         
         assert_eq!(r3.name, "binop");
     }
+    
+    #[test]
+    fn test_compound_assign_to_field() {
+        let mut gen = IRGenerator::new("test".to_string());
+        gen.start_function("main".to_string(), vec![], Type::Void);
+        
+        let point = gen.gen_struct_alloc("Point".to_string());
+        let one = gen.gen_const_int(1);
+        let place = Place::Field(point, "x".to_string());
+        gen.gen_compound_assign(place, BinOp::Add, one).unwrap();
+        
+        let module = gen.finish();
+        let insts = &module.functions[0].blocks[0].instructions;
+        assert!(matches!(insts[2], Instruction::StructLoad { .. }));
+        assert!(matches!(insts[3], Instruction::BinOp { op: BinOp::Add, .. }));
+        assert!(matches!(&insts[4], Instruction::StructStore { field, .. } if field == "x"));
+    }
+    
+    #[test]
+    fn test_block_scoped_variables() {
+        let mut gen = IRGenerator::new("test".to_string());
+        gen.start_function("main".to_string(), vec![], Type::Void);
+        
+        let outer = gen.gen_const_int(1);
+        gen.gen_let("x".to_string(), outer.clone());
+        gen.enter_scope(ScopeKind::Block);
+        let inner = gen.gen_const_int(2);
+        gen.gen_let("x".to_string(), inner.clone());
+        assert_eq!(gen.gen_variable("x").unwrap(), inner);
+        assert!(gen.exit_scope().is_empty());
+        
+        // The shadowing `let` ended with its block
+        assert_eq!(gen.gen_variable("x").unwrap(), outer);
+        
+        // Assignment inside a block updates the outer binding
+        gen.enter_scope(ScopeKind::Loop);
+        gen.gen_assign("x".to_string(), inner.clone());
+        gen.exit_scope();
+        assert_eq!(gen.gen_variable("x").unwrap(), inner);
+    }
+    
+    #[test]
+    fn test_pattern_tests() {
+        let mut gen = IRGenerator::new("test".to_string());
+        gen.add_enum("Color".to_string(), vec!["Red".to_string(), "Green".to_string()]);
+        gen.start_function("main".to_string(), vec![("s".to_string(), Type::String)], Type::Void);
+        
+        let s = gen.gen_variable("s").unwrap();
+        let command = MatchPattern::Or(vec![
+            MatchPattern::String("stop".to_string()),
+            MatchPattern::String("halt".to_string()),
+        ]);
+        let test = gen.gen_pattern_test(s, &command).unwrap();
+        assert_eq!(test.name, "binop");
+        
+        let green = MatchPattern::Variant { enum_name: "Color".to_string(), variant: "Green".to_string() };
+        let color = gen.gen_const_int(1);
+        gen.gen_pattern_test(color.clone(), &green).unwrap();
+        let blue = MatchPattern::Variant { enum_name: "Color".to_string(), variant: "Blue".to_string() };
+        assert!(gen.gen_pattern_test(color, &blue).is_err());
+        
+        let module = gen.finish();
+        let insts = &module.functions[0].blocks[0].instructions;
+        let calls = insts.iter().filter(|i| matches!(i, Instruction::Call { func, .. } if func == "strcmp"));
+        assert_eq!(calls.count(), 2);
+        assert!(insts.iter().any(|i| matches!(i, Instruction::ConstInt { value: 1, .. })));
+    }
+    
+    #[test]
+    fn test_struct_pattern_binds_fields() {
+        let mut gen = IRGenerator::new("test".to_string());
+        gen.start_function("main".to_string(), vec![], Type::Void);
+        
+        let point = gen.gen_struct_alloc("Point".to_string());
+        gen.enter_scope(ScopeKind::MatchArm);
+        let pattern = MatchPattern::Struct(vec![
+            ("x".to_string(), MatchPattern::Binding("x".to_string())),
+            ("y".to_string(), MatchPattern::Range(0, 9)),
+        ]);
+        gen.gen_pattern_test(point, &pattern).unwrap();
+        
+        // `x` is bound to the loaded field
+        let x = gen.gen_variable("x").unwrap();
+        assert_eq!(x.name, "field");
+        gen.exit_scope();
+        assert!(gen.gen_variable("x").is_err());
+    }
+    
+    #[test]
+    fn test_tuples_are_struct_aggregates() {
+        let mut gen = IRGenerator::new("test".to_string());
+        gen.start_function("main".to_string(), vec![], Type::Void);
+        
+        let one = gen.gen_const_int(1);
+        let yes = gen.gen_const_bool(true);
+        let pair = gen.gen_tuple(vec![(one.clone(), Type::Int), (yes, Type::Bool)]);
+        gen.gen_tuple(vec![(one, Type::Int), (pair.clone(), Type::Bool)]);
+        gen.gen_let_tuple(&["n".to_string(), "_".to_string()], pair);
+        assert_eq!(gen.gen_variable("n").unwrap().name, "field");
+        
+        // Each tuple type is declared once
+        let module = gen.finish();
+        assert_eq!(module.structs.len(), 1);
+        assert_eq!(module.structs[0].name, "(int, bool)");
+        assert_eq!(module.structs[0].fields[1].0, "1");
+    }
+    
+    #[test]
+    fn test_compound_assign_to_undefined_var() {
+        let mut gen = IRGenerator::new("test".to_string());
+        gen.start_function("main".to_string(), vec![], Type::Void);
+        
+        let one = gen.gen_const_int(1);
+        assert!(gen.gen_compound_assign(Place::Var("missing".to_string()), BinOp::Mul, one).is_err());
+    }
 }
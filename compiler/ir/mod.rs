@@ -5,5 +5,5 @@ pub mod ir_gen;
 pub mod printer;
 
 pub use ir::*;
-pub use ir_gen::IRGenerator;
+pub use ir_gen::{IRGenerator, MatchPattern, Place};
 pub use printer::print_module;
@@ -127,6 +127,9 @@ fn print_instruction(inst: &Instruction) -> String {
         Instruction::ConstBool { dest, value } => {
             format!("{} = const_bool {}", print_register(dest), value)
         }
+        Instruction::ConstFloat { dest, value, ty } => {
+            format!("{} = const_float {:?} : {}", print_register(dest), value, print_type(ty))
+        }
         Instruction::ConstString { dest, value } => {
             format!("{} = const_string \"{}\"", print_register(dest), value)
         }
@@ -209,14 +212,28 @@ fn print_register(reg: &Register) -> String {
 }
 
 /// Print type
-fn print_type(ty: &Type) -> String {
+pub(crate) fn print_type(ty: &Type) -> String {
     match ty {
         Type::Void => "void".to_string(),
         Type::Int => "int".to_string(),
+        Type::I8 => "i8".to_string(),
+        Type::I16 => "i16".to_string(),
+        Type::I64 => "i64".to_string(),
+        Type::U8 => "u8".to_string(),
+        Type::U16 => "u16".to_string(),
+        Type::U32 => "u32".to_string(),
+        Type::U64 => "u64".to_string(),
+        Type::Usize => "usize".to_string(),
+        Type::F32 => "f32".to_string(),
+        Type::F64 => "f64".to_string(),
         Type::Bool => "bool".to_string(),
         Type::String => "string".to_string(),
         Type::Array(elem_ty, size) => format!("[{}; {}]", print_type(elem_ty), size),
         Type::Struct(name) => format!("struct {}", name),
+        Type::Tuple(elements) => {
+            let elements: Vec<String> = elements.iter().map(print_type).collect();
+            format!("({})", elements.join(", "))
+        }
         Type::Enum(name) => format!("enum {}", name),
         Type::Ptr(ty) => format!("ptr<{}>", print_type(ty)),
     }
@@ -229,6 +246,12 @@ fn print_binop(op: &BinOp) -> &'static str {
         BinOp::Sub => "sub",
         BinOp::Mul => "mul",
         BinOp::Div => "div",
+        BinOp::Mod => "mod",
+        BinOp::BitAnd => "band",
+        BinOp::BitOr => "bor",
+        BinOp::BitXor => "bxor",
+        BinOp::Shl => "shl",
+        BinOp::Shr => "shr",
         BinOp::Eq => "eq",
         BinOp::NotEq => "neq",
         BinOp::Lt => "lt",
@@ -245,6 +268,7 @@ fn print_unaryop(op: &UnaryOp) -> &'static str {
     match op {
         UnaryOp::Not => "not",
         UnaryOp::Neg => "neg",
+        UnaryOp::BitNot => "bnot",
     }
 }
 
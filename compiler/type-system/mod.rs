@@ -1,5 +1,9 @@
 // Type system module
 
+// The scoped symbol table is the bootstrap compiler's
+#[path = "../bootstrap/src/scope.rs"]
+pub mod scope;
 pub mod type_checker;
 
-pub use type_checker::{Type, TypeChecker, TypeContext, VarState, VarInfo};
+pub use scope::{ResolveError, ScopeKind, ScopeStack};
+pub use type_checker::{FlowState, Linearity, Type, TypeChecker, TypeContext, VarState, VarInfo};
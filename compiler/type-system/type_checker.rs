@@ -1,6 +1,7 @@
 // Type Checker for Athōn
 // Validates types, checks capability safety, and ensures correctness
 
+use super::scope::{ScopeKind, ScopeStack};
 use std::collections::HashMap;
 
 /// Type information
@@ -8,6 +9,8 @@ use std::collections::HashMap;
 pub enum Type {
     Void,
     Int,
+    F32,
+    F64,
     Bool,
     String,
     Array(Box<Type>),
@@ -16,6 +19,16 @@ pub enum Type {
     Unknown,
 }
 
+impl Type {
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::F32 | Type::F64)
+    }
+    
+    pub fn is_numeric(&self) -> bool {
+        *self == Type::Int || self.is_float()
+    }
+}
+
 /// Variable state for linear/affine type checking
 #[derive(Debug, Clone, PartialEq)]
 pub enum VarState {
@@ -24,18 +37,42 @@ pub enum VarState {
     Consumed,
 }
 
+/// How many times a value must be used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Linearity {
+    /// Any number of times
+    Unrestricted,
+    /// At most once; may be dropped
+    Affine,
+    /// Exactly once
+    Linear,
+}
+
 /// Variable information in context
 #[derive(Debug, Clone)]
 pub struct VarInfo {
     pub name: String,
     pub ty: Type,
     pub state: VarState,
-    pub is_linear: bool,
+    pub linearity: Linearity,
+}
+
+impl VarInfo {
+    pub fn is_linear(&self) -> bool {
+        self.linearity == Linearity::Linear
+    }
+}
+
+/// Move states of every visible variable at one point in the program,
+/// used to merge the branches of `if` and `match` and to check loop bodies
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowState {
+    states: Vec<VarState>,
 }
 
 /// Type checking context
 pub struct TypeContext {
-    vars: HashMap<String, VarInfo>,
+    vars: ScopeStack<VarInfo>,
     functions: HashMap<String, FunctionSignature>,
     structs: HashMap<String, StructInfo>,
     enums: HashMap<String, EnumInfo>,
@@ -60,24 +97,43 @@ pub struct EnumInfo {
 impl TypeContext {
     pub fn new() -> Self {
         TypeContext {
-            vars: HashMap::new(),
+            vars: ScopeStack::new(),
             functions: HashMap::new(),
             structs: HashMap::new(),
             enums: HashMap::new(),
         }
     }
     
-    /// Add a variable to the context
+    /// Enter a nested scope
+    pub fn push_scope(&mut self, kind: ScopeKind) {
+        self.vars.push(kind);
+    }
+    
+    /// Leave the innermost scope, returning the names of variables never used
+    pub fn pop_scope(&mut self) -> Vec<String> {
+        self.vars.pop().into_iter().map(|(name, _)| name).collect()
+    }
+    
+    /// Announce a variable defined later in the current scope
+    pub fn declare_var(&mut self, name: &str) {
+        self.vars.declare(name);
+    }
+    
+    /// Add a variable to the current scope, shadowing any outer one
     pub fn add_var(&mut self, name: String, ty: Type, is_linear: bool) {
-        self.vars.insert(
-            name.clone(),
-            VarInfo {
-                name,
-                ty,
-                state: VarState::Alive,
-                is_linear,
-            },
-        );
+        let linearity = if is_linear { Linearity::Linear } else { Linearity::Unrestricted };
+        self.add_capability(name, ty, linearity);
+    }
+    
+    /// Add a variable whose uses are restricted by `linearity`
+    pub fn add_capability(&mut self, name: String, ty: Type, linearity: Linearity) {
+        let info = VarInfo {
+            name: name.clone(),
+            ty,
+            state: VarState::Alive,
+            linearity,
+        };
+        self.vars.define(&name, info);
     }
     
     /// Get variable type
@@ -85,17 +141,16 @@ impl TypeContext {
         self.vars
             .get(name)
             .map(|v| v.ty.clone())
-            .ok_or_else(|| format!("Undefined variable: {}", name))
+            .map_err(|e| e.to_string())
     }
     
     /// Check if variable can be used (not moved)
     pub fn check_var_use(&mut self, name: &str) -> Result<(), String> {
-        let var = self.vars.get_mut(name)
-            .ok_or_else(|| format!("Undefined variable: {}", name))?;
+        let var = self.vars.lookup(name).map_err(|e| e.to_string())?;
         
         match var.state {
             VarState::Alive => {
-                if var.is_linear {
+                if var.linearity != Linearity::Unrestricted {
                     var.state = VarState::Moved;
                 }
                 Ok(())
@@ -105,6 +160,80 @@ impl TypeContext {
         }
     }
     
+    /// Whether `break` and `continue` are allowed here
+    pub fn in_loop(&self) -> bool {
+        self.vars.in_loop()
+    }
+    
+    /// Record the move state of every visible variable
+    pub fn snapshot(&self) -> FlowState {
+        FlowState {
+            states: self.vars.values().map(|(_, v)| v.state.clone()).collect(),
+        }
+    }
+    
+    /// Reset move states, e.g. before checking the next branch of an `if`
+    pub fn restore(&mut self, flow: &FlowState) {
+        for ((_, var), state) in self.vars.values_mut().zip(&flow.states) {
+            var.state = state.clone();
+        }
+    }
+    
+    /// Join the states at the end of each branch of an `if` or `match`.
+    /// A value moved on any path counts as moved afterwards; a linear value
+    /// moved on only some paths is leaked on the others.
+    pub fn merge_branches(&mut self, branches: &[FlowState]) -> Result<(), String> {
+        let mut leaked = Vec::new();
+        for (i, (_, var)) in self.vars.values_mut().enumerate() {
+            let moved = branches.iter().filter(|b| b.states[i] != VarState::Alive).count();
+            if moved == 0 {
+                var.state = VarState::Alive;
+                continue;
+            }
+            if var.is_linear() && moved < branches.len() {
+                leaked.push(var.name.clone());
+            }
+            var.state = VarState::Moved;
+        }
+        if leaked.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Linear value consumed on only some paths: {}", leaked.join(", ")))
+        }
+    }
+    
+    /// A loop body may run more than once, so it must not move variables
+    /// declared outside it. `before` is the state on entry to the body.
+    pub fn check_loop_body(&self, before: &FlowState) -> Result<(), String> {
+        let moved: Vec<&str> = self
+            .vars
+            .values()
+            .zip(&before.states)
+            .filter(|((_, var), state)| **state == VarState::Alive && var.state != VarState::Alive)
+            .map(|((name, _), _)| name)
+            .collect();
+        if moved.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Value moved inside a loop: {}", moved.join(", ")))
+        }
+    }
+    
+    /// Linear variables of the innermost scope must be consumed before it ends
+    pub fn check_leaks(&self) -> Result<(), String> {
+        let leaked: Vec<&str> = self
+            .vars
+            .current()
+            .filter(|(_, var)| var.is_linear() && var.state == VarState::Alive)
+            .map(|(name, _)| name)
+            .collect();
+        if leaked.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Linear value never consumed: {}", leaked.join(", ")))
+        }
+    }
+    
     /// Add a function signature
     pub fn add_function(&mut self, name: String, params: Vec<(String, Type)>, return_type: Type) {
         self.functions.insert(
@@ -186,10 +315,34 @@ impl TypeChecker {
     pub fn check_binop(&self, op: &str, left: &Type, right: &Type) -> Result<Type, String> {
         match op {
             "+" | "-" | "*" | "/" => {
+                // No implicit conversions: both sides must be the same numeric type
+                if left == right && left.is_numeric() {
+                    Ok(left.clone())
+                } else {
+                    Err(format!("Arithmetic operation requires matching numeric operands, got {:?} and {:?}", left, right))
+                }
+            }
+            "%" => {
+                // C has no float remainder operator
+                if *left == Type::Int && *right == Type::Int {
+                    Ok(Type::Int)
+                } else {
+                    Err(format!("Remainder requires int operands, got {:?} and {:?}", left, right))
+                }
+            }
+            "&" | "|" | "^" => {
+                // Bitwise on ints, non-short-circuiting logic on bools
+                if left == right && (*left == Type::Int || *left == Type::Bool) {
+                    Ok(left.clone())
+                } else {
+                    Err(format!("Bitwise operation requires matching int or bool operands, got {:?} and {:?}", left, right))
+                }
+            }
+            "<<" | ">>" => {
                 if *left == Type::Int && *right == Type::Int {
                     Ok(Type::Int)
                 } else {
-                    Err(format!("Arithmetic operation requires int operands, got {:?} and {:?}", left, right))
+                    Err(format!("Shift requires int operands, got {:?} and {:?}", left, right))
                 }
             }
             "==" | "!=" | "<" | ">" | "<=" | ">=" => {
@@ -221,10 +374,17 @@ impl TypeChecker {
                 }
             }
             "-" => {
+                if operand.is_numeric() {
+                    Ok(operand.clone())
+                } else {
+                    Err(format!("Negation requires numeric operand, got {:?}", operand))
+                }
+            }
+            "~" => {
                 if *operand == Type::Int {
                     Ok(Type::Int)
                 } else {
-                    Err(format!("Negation requires int operand, got {:?}", operand))
+                    Err(format!("Bitwise NOT requires int operand, got {:?}", operand))
                 }
             }
             _ => Err(format!("Unknown unary operator: {}", op)),
@@ -254,6 +414,63 @@ mod tests {
         assert!(ctx.check_var_use("cap").is_err()); // Second use should fail
     }
     
+    #[test]
+    fn test_scoped_vars() {
+        let mut ctx = TypeContext::new();
+        ctx.add_var("x".to_string(), Type::Int, false);
+        ctx.push_scope(ScopeKind::Block);
+        ctx.add_var("x".to_string(), Type::Bool, false);
+        ctx.declare_var("later");
+        
+        assert_eq!(ctx.get_var_type("x").unwrap(), Type::Bool);
+        assert!(ctx.check_var_use("later").unwrap_err().contains("before its definition"));
+        assert_eq!(ctx.pop_scope(), vec!["x".to_string()]);
+        assert_eq!(ctx.get_var_type("x").unwrap(), Type::Int);
+    }
+    
+    #[test]
+    fn test_branch_merging() {
+        let mut ctx = TypeContext::new();
+        ctx.add_var("cap".to_string(), Type::Int, true);
+        ctx.add_capability("ticket".to_string(), Type::Int, Linearity::Affine);
+        let before = ctx.snapshot();
+        
+        // if ... { consume(cap); consume(ticket); } else { consume(cap); }
+        ctx.check_var_use("cap").unwrap();
+        ctx.check_var_use("ticket").unwrap();
+        let then_branch = ctx.snapshot();
+        ctx.restore(&before);
+        ctx.check_var_use("cap").unwrap();
+        let else_branch = ctx.snapshot();
+        
+        assert!(ctx.merge_branches(&[then_branch.clone(), else_branch]).is_ok());
+        assert!(ctx.check_var_use("ticket").is_err());
+        assert!(ctx.check_leaks().is_ok());
+        
+        // Consumed in the `then` branch only
+        ctx.restore(&before);
+        let err = ctx.merge_branches(&[then_branch, before]).unwrap_err();
+        assert!(err.contains("cap"));
+    }
+    
+    #[test]
+    fn test_loop_moves_and_leaks() {
+        let mut ctx = TypeContext::new();
+        ctx.add_var("cap".to_string(), Type::Int, true);
+        let before = ctx.snapshot();
+        
+        ctx.push_scope(ScopeKind::Loop);
+        ctx.add_var("local".to_string(), Type::Int, true);
+        ctx.check_var_use("local").unwrap();
+        assert!(ctx.check_leaks().is_ok());
+        ctx.check_var_use("cap").unwrap();
+        ctx.pop_scope();
+        assert!(ctx.check_loop_body(&before).unwrap_err().contains("cap"));
+        
+        ctx.restore(&before);
+        assert!(ctx.check_leaks().unwrap_err().contains("cap"));
+    }
+    
     #[test]
     fn test_binop_types() {
         let checker = TypeChecker::new();
@@ -262,4 +479,30 @@ mod tests {
         assert_eq!(checker.check_binop("==", &Type::Int, &Type::Int).unwrap(), Type::Bool);
         assert!(checker.check_binop("+", &Type::Int, &Type::Bool).is_err());
     }
+    
+    #[test]
+    fn test_float_binop_types() {
+        let checker = TypeChecker::new();
+        
+        assert_eq!(checker.check_binop("*", &Type::F64, &Type::F64).unwrap(), Type::F64);
+        assert_eq!(checker.check_binop("<", &Type::F32, &Type::F32).unwrap(), Type::Bool);
+        assert_eq!(checker.check_unary("-", &Type::F32).unwrap(), Type::F32);
+        assert!(checker.check_binop("+", &Type::F32, &Type::F64).is_err());
+        assert!(checker.check_binop("+", &Type::Int, &Type::F64).is_err());
+        assert!(checker.check_binop("<", &Type::F32, &Type::F64).is_err());
+    }
+    
+    #[test]
+    fn test_bitwise_and_remainder_types() {
+        let checker = TypeChecker::new();
+        
+        assert_eq!(checker.check_binop("%", &Type::Int, &Type::Int).unwrap(), Type::Int);
+        assert_eq!(checker.check_binop("^", &Type::Bool, &Type::Bool).unwrap(), Type::Bool);
+        assert_eq!(checker.check_binop("<<", &Type::Int, &Type::Int).unwrap(), Type::Int);
+        assert_eq!(checker.check_unary("~", &Type::Int).unwrap(), Type::Int);
+        assert!(checker.check_binop("%", &Type::F64, &Type::F64).is_err());
+        assert!(checker.check_binop("&", &Type::Int, &Type::Bool).is_err());
+        assert!(checker.check_binop(">>", &Type::F32, &Type::Int).is_err());
+        assert!(checker.check_unary("~", &Type::Bool).is_err());
+    }
 }
@@ -43,9 +43,9 @@ else
     exit 1
 fi
 
-# Check Type Checker module
+# Check Type Checker module, which needs its sibling modules from mod.rs
 echo -n "  Type Checker module... "
-if rustc --crate-type lib --edition 2021 compiler/type-system/type_checker.rs 2>/dev/null; then
+if rustc --crate-type lib --edition 2021 --crate-name type_system compiler/type-system/mod.rs 2>/dev/null; then
     echo -e "${GREEN}✓${NC}"
 else
     echo -e "${RED}✗${NC}"
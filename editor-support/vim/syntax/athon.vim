@@ -8,8 +8,8 @@ if exists("b:current_syntax")
 endif
 
 " Keywords
-syn keyword athonKeyword fn let if else while for in match return break continue loop
-syn keyword athonKeyword struct enum type trait impl pub cap region import
+syn keyword athonKeyword fn let mut if else while for in match return break continue loop
+syn keyword athonKeyword struct enum type trait impl pub cap region import linear affine
 
 " Types
 syn keyword athonType int bool string